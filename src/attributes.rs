@@ -0,0 +1,180 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    attributes.rs
+@brief   Diagnostics of vertex attributes missing from vertices or unused by shaders
+ */
+
+//a Documentation
+
+/*!
+
+When a [crate::ShaderInstantiable] is created the vertex attributes
+that its shader class requires are matched against those provided by
+each [Vertices] of the instantiable.
+
+An attribute that the shader requires but the vertices do not provide
+is left disabled in the VAO, so the shader reads the constant value of
+the attribute (which is (0, 0, 0, 1) unless set). [MissingAttributes]
+describes what to do about this: a default constant value may be given
+for a [VertexAttr] (which is set with glVertexAttrib4f before drawing,
+and reset to (0, 0, 0, 1) afterwards), and creation may be made to
+fail for missing attributes without defaults.
+
+The [AttributeReport] lists, for each vertices, the attributes that
+were missing (with or without a default) and those that were provided
+but not used by the shader.
+
+!*/
+
+//a Imports
+use gl::types::*;
+use model3d::VertexAttr;
+
+use crate::{Vertices, ShaderClass};
+
+//a MissingAttributes
+//tp MissingAttributes
+/// What to do with vertex attributes that a shader class requires but
+/// vertices do not provide
+#[derive(Debug, Clone, Default)]
+pub struct MissingAttributes {
+    /// Default constant values for attributes
+    defaults : Vec<(VertexAttr, [f32; 4])>,
+    /// True if a missing attribute without a default is an error
+    fail : bool,
+}
+
+//ip MissingAttributes
+impl MissingAttributes {
+    //fp new
+    /// Create a [MissingAttributes] that leaves missing attributes
+    /// disabled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //mp with_default
+    /// Set the default constant value for a vertex attribute, used if
+    /// the vertices do not provide it
+    pub fn with_default(mut self, vertex_attr:VertexAttr, value:[f32; 4]) -> Self {
+        self.defaults.retain(|(va, _)| *va != vertex_attr);
+        self.defaults.push((vertex_attr, value));
+        self
+    }
+
+    //mp fail_if_missing
+    /// Make creation fail if an attribute without a default is
+    /// missing
+    pub fn fail_if_missing(mut self) -> Self {
+        self.fail = true;
+        self
+    }
+
+    //ap default_of
+    /// Get the default value for a vertex attribute, if one is set
+    pub fn default_of(&self, vertex_attr:VertexAttr) -> Option<&[f32; 4]> {
+        self.defaults.iter()
+            .find(|(va, _)| *va == vertex_attr)
+            .map(|(_, value)| value)
+    }
+
+    //mp check
+    /// Check the attributes of a shader class against those of
+    /// vertices (with an index in the instantiable), adding to a
+    /// report, and returning the default values to set for the
+    /// missing attributes (by attribute location)
+    pub(crate) fn check(&self,
+                        shader_class:&dyn ShaderClass,
+                        vertices_index:usize,
+                        vertices:&Vertices,
+                        report:&mut AttributeReport) -> Result<Vec<(GLuint, [f32; 4])>, String> {
+        let (_, _, attrs) = vertices.borrow();
+        let provides = |vertex_attr:VertexAttr| {
+            vertex_attr == VertexAttr::Position || attrs.iter().any(|(va, _)| *va == vertex_attr)
+        };
+        let mut defaults = Vec::new();
+        for (index, vertex_attr) in shader_class.attributes() {
+            if provides(*vertex_attr) {
+                continue;
+            }
+            if let Some(value) = self.default_of(*vertex_attr) {
+                report.defaulted.push((vertices_index, *vertex_attr));
+                defaults.push((*index, *value));
+            } else if self.fail {
+                return Err(format!("Vertices {} do not provide the {:?} attribute required by the shader",
+                                   vertices_index, vertex_attr));
+            } else {
+                report.missing.push((vertices_index, *vertex_attr));
+            }
+        }
+        let uses = |vertex_attr:VertexAttr| {
+            shader_class.attributes().iter().any(|(_, va)| *va == vertex_attr)
+        };
+        if !uses(VertexAttr::Position) {
+            report.unused.push((vertices_index, VertexAttr::Position));
+        }
+        for (vertex_attr, _) in attrs {
+            if !uses(*vertex_attr) {
+                report.unused.push((vertices_index, *vertex_attr));
+            }
+        }
+        Ok(defaults)
+    }
+
+    //zz All done
+}
+
+//a AttributeReport
+//tp AttributeReport
+/// A report of the vertex attributes of the vertices of an
+/// instantiable that did not match those of a shader class; each is
+/// given as (vertices index, attribute)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttributeReport {
+    /// Attributes required by the shader but not provided, and left
+    /// disabled
+    pub missing : Vec<(usize, VertexAttr)>,
+    /// Attributes required by the shader but not provided, for which
+    /// a default constant value is used
+    pub defaulted : Vec<(usize, VertexAttr)>,
+    /// Attributes provided but not used by the shader
+    pub unused : Vec<(usize, VertexAttr)>,
+}
+
+//ip AttributeReport
+impl AttributeReport {
+    //ap is_clean
+    /// Return true if every attribute required by the shader was
+    /// provided (possibly by a default)
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+//ip Display for AttributeReport
+impl std::fmt::Display for AttributeReport {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        for (v, va) in &self.missing {
+            writeln!(f, "vertices {}: {:?} missing", v, va)?;
+        }
+        for (v, va) in &self.defaulted {
+            writeln!(f, "vertices {}: {:?} missing, using default", v, va)?;
+        }
+        for (v, va) in &self.unused {
+            writeln!(f, "vertices {}: {:?} unused", v, va)?;
+        }
+        Ok(())
+    }
+}
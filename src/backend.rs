@@ -0,0 +1,549 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    backend.rs
+@brief   Abstraction of the OpenGL function calls
+ */
+
+//a Documentation
+
+/*!
+
+All of the OpenGL calls made by the crate go through the [GlBackend]
+trait, which is implemented for the `gl` crate (the default, for
+desktop OpenGL) and for the `glow` crate (with the `glow` feature,
+permitting OpenGL ES 3.0 and WebGL 2 as well as desktop OpenGL).
+
+The [Backend] type is the implementation selected by the cargo
+features.
+
+OpenGL objects are always identified by a `GLuint` name (and uniform
+locations by a `GLint`), whichever backend is in use; 0 is used for
+'no object', as with OpenGL itself. The `gl` crate is used for these
+types and for the OpenGL enumerations for all backends.
+
+!*/
+
+//a Imports
+use gl::types::*;
+
+//a DebugCallback
+//tp DebugCallback
+/// The function invoked for KHR_debug messages, with the source,
+/// type, id, severity and message
+pub type DebugCallback = fn(GLenum, GLenum, GLuint, GLenum, &str);
+
+//a GlBackend
+//tt GlBackend
+/// The OpenGL operations that the crate requires
+///
+/// The methods are associated functions, as the OpenGL context is
+/// implicitly the current context for the thread
+pub trait GlBackend {
+    //fp load_with
+    /// Load the OpenGL functions using a loader (such as an EGL or
+    /// windowing library `get_proc_address`)
+    fn load_with<F:FnMut(&str) -> *const std::os::raw::c_void>(loader:F);
+
+    //fp is_gles
+    /// Return true if the context is OpenGL ES (or WebGL) rather than
+    /// desktop OpenGL
+    fn is_gles() -> bool;
+
+    //fp get_error
+    /// Get the next OpenGL error, or NO_ERROR
+    fn get_error() -> GLenum;
+
+    //fp get_string
+    /// Get a string parameter of the context (e.g. GL_VERSION)
+    fn get_string(pname:GLenum) -> String;
+
+    //fp get_integer
+    /// Get an integer parameter of the context (e.g. GL_MAX_SAMPLES)
+    fn get_integer(pname:GLenum) -> GLint;
+
+    //fp get_float
+    /// Get a floating point parameter of the context (e.g.
+    /// GL_POLYGON_OFFSET_FACTOR)
+    fn get_float(pname:GLenum) -> f32;
+
+    //fp get_integers
+    /// Get a parameter of the context with several values (e.g.
+    /// GL_COLOR_WRITEMASK)
+    fn get_integers(pname:GLenum, values:&mut [GLint]);
+
+    //fp extensions
+    /// Get the extensions supported by the context
+    fn extensions() -> Vec<String>;
+
+    //fp gen_buffer
+    /// Create a new buffer
+    fn gen_buffer() -> GLuint;
+
+    //fp bind_buffer
+    /// Bind a buffer (or 0 for none) to a target
+    fn bind_buffer(target:GLenum, buffer:GLuint);
+
+    //fp buffer_data
+    /// Copy data into the buffer bound to the target
+    fn buffer_data(target:GLenum, data:&[u8], usage:GLenum);
+
+    //fp buffer_storage
+    /// Create immutable storage (glBufferStorage) for the buffer bound
+    /// to the target, initialized with data
+    fn buffer_storage(target:GLenum, data:&[u8], flags:GLbitfield);
+
+    //fp buffer_data_size
+    /// Allocate uninitialized data of a size for the buffer bound to
+    /// the target
+    fn buffer_data_size(target:GLenum, size:usize, usage:GLenum);
+
+    //fp buffer_sub_data
+    /// Replace data of the buffer bound to the target from a byte
+    /// offset
+    fn buffer_sub_data(target:GLenum, offset:usize, data:&[u8]);
+
+    //fp bind_buffer_base
+    /// Bind a buffer (or 0 for none) to an indexed binding point of a
+    /// target (such as UNIFORM_BUFFER)
+    fn bind_buffer_base(target:GLenum, index:GLuint, buffer:GLuint);
+
+    //fp map_buffer_range
+    /// Map a range of the buffer bound to the target into client
+    /// memory, returning a null pointer if it cannot be mapped
+    fn map_buffer_range(target:GLenum, offset:usize, length:usize, access:GLbitfield) -> *mut u8;
+
+    //fp unmap_buffer
+    /// Unmap the buffer bound to the target, returning false if its
+    /// data was corrupted while it was mapped
+    ///
+    /// The `glow` backend always returns true, as glow does not
+    /// return the result of glUnmapBuffer; a corrupted buffer store is
+    /// then not reported
+    fn unmap_buffer(target:GLenum) -> bool;
+
+    //fp delete_buffer
+    /// Delete a buffer
+    fn delete_buffer(buffer:GLuint);
+
+    //fp gen_vertex_array
+    /// Create a new vertex array object
+    fn gen_vertex_array() -> GLuint;
+
+    //fp bind_vertex_array
+    /// Bind a vertex array object (or 0 for none)
+    fn bind_vertex_array(vao:GLuint);
+
+    //fp delete_vertex_array
+    /// Delete a vertex array object
+    fn delete_vertex_array(vao:GLuint);
+
+    //fp enable_vertex_attrib_array
+    /// Enable a vertex attribute in the current vertex array object
+    fn enable_vertex_attrib_array(index:GLuint);
+
+    //fp vertex_attrib_pointer
+    /// Set a vertex attribute of the current vertex array object to
+    /// use the buffer bound to ARRAY_BUFFER
+    fn vertex_attrib_pointer(index:GLuint, size:GLint, ele_type:GLenum, normalized:bool, stride:GLsizei, byte_offset:usize);
+
+    //fp vertex_attrib_4f
+    /// Set the constant value of a vertex attribute, used when its
+    /// array is not enabled
+    fn vertex_attrib_4f(index:GLuint, value:&[f32; 4]);
+
+    //fp create_shader
+    /// Create a shader of a kind (e.g. VERTEX_SHADER)
+    fn create_shader(kind:GLenum) -> GLuint;
+
+    //fp shader_source
+    /// Set the source of a shader
+    fn shader_source(shader:GLuint, source:&str);
+
+    //fp compile_shader
+    /// Compile a shader
+    fn compile_shader(shader:GLuint);
+
+    //fp shader_compile_status
+    /// Return true if the shader compiled successfully
+    fn shader_compile_status(shader:GLuint) -> bool;
+
+    //fp shader_info_log
+    /// Get the info log of a shader
+    fn shader_info_log(shader:GLuint) -> String;
+
+    //fp delete_shader
+    /// Delete a shader
+    fn delete_shader(shader:GLuint);
+
+    //fp create_program
+    /// Create a program
+    fn create_program() -> GLuint;
+
+    //fp attach_shader
+    /// Attach a shader to a program
+    fn attach_shader(program:GLuint, shader:GLuint);
+
+    //fp detach_shader
+    /// Detach a shader from a program
+    fn detach_shader(program:GLuint, shader:GLuint);
+
+    //fp link_program
+    /// Link a program
+    fn link_program(program:GLuint);
+
+    //fp bind_attrib_location
+    /// Bind an attribute name of a program to a location, for the
+    /// next link of the program
+    fn bind_attrib_location(program:GLuint, index:GLuint, name:&str);
+
+    //fp program_link_status
+    /// Return true if the program linked successfully
+    fn program_link_status(program:GLuint) -> bool;
+
+    //fp program_info_log
+    /// Get the info log of a program
+    fn program_info_log(program:GLuint) -> String;
+
+    //fp use_program
+    /// Use a program (or 0 for none)
+    fn use_program(program:GLuint);
+
+    //fp delete_program
+    /// Delete a program
+    fn delete_program(program:GLuint);
+
+    //fp attrib_location
+    /// Get the location of a named attribute of a program
+    fn attrib_location(program:GLuint, name:&str) -> Option<GLuint>;
+
+    //fp uniform_location
+    /// Get the location of a named uniform of a program
+    fn uniform_location(program:GLuint, name:&str) -> Option<GLint>;
+
+    //fp uniform_block_index
+    /// Get the index of a named uniform block of a program
+    fn uniform_block_index(program:GLuint, name:&str) -> Option<GLuint>;
+
+    //fp uniform_block_binding
+    /// Set the uniform buffer binding point of a uniform block of a
+    /// program
+    fn uniform_block_binding(program:GLuint, block_index:GLuint, binding:GLuint);
+
+    //fp uniform_matrix4
+    /// Set a mat4 (or array of mat4 if more than 16 values) uniform
+    /// of the current program
+    fn uniform_matrix4(location:GLint, data:&[f32]);
+
+    //fp uniform_1i
+    /// Set an int (or sampler) uniform of the current program
+    fn uniform_1i(location:GLint, value:GLint);
+
+    //fp uniform_4f
+    /// Set a vec4 uniform of the current program
+    fn uniform_4f(location:GLint, value:&[f32; 4]);
+
+    //fp uniform_4i
+    /// Set an ivec4 uniform of the current program
+    fn uniform_4i(location:GLint, value:&[GLint; 4]);
+
+    //fp draw_elements
+    /// Draw primitives using the current vertex array object and
+    /// its ELEMENT_ARRAY_BUFFER
+    fn draw_elements(mode:GLenum, count:GLsizei, index_type:GLenum, byte_offset:usize);
+
+    //fp draw_arrays
+    /// Draw primitives from consecutive vertices of the current
+    /// vertex array object
+    fn draw_arrays(mode:GLenum, first:GLint, count:GLsizei);
+
+    //fp gen_framebuffer
+    /// Create a framebuffer
+    fn gen_framebuffer() -> GLuint;
+
+    //fp bind_framebuffer
+    /// Bind a framebuffer (or 0 for the default framebuffer) to a target
+    fn bind_framebuffer(target:GLenum, framebuffer:GLuint);
+
+    //fp delete_framebuffer
+    /// Delete a framebuffer
+    fn delete_framebuffer(framebuffer:GLuint);
+
+    //fp check_framebuffer_status
+    /// Check the completeness of the framebuffer bound to a target
+    fn check_framebuffer_status(target:GLenum) -> GLenum;
+
+    //fp gen_renderbuffer
+    /// Create a renderbuffer
+    fn gen_renderbuffer() -> GLuint;
+
+    //fp bind_renderbuffer
+    /// Bind a renderbuffer (or 0 for none)
+    fn bind_renderbuffer(renderbuffer:GLuint);
+
+    //fp delete_renderbuffer
+    /// Delete a renderbuffer
+    fn delete_renderbuffer(renderbuffer:GLuint);
+
+    //fp renderbuffer_storage
+    /// Allocate storage for the bound renderbuffer
+    fn renderbuffer_storage(internal_format:GLenum, width:GLsizei, height:GLsizei);
+
+    //fp framebuffer_renderbuffer
+    /// Attach a renderbuffer to the framebuffer bound to a target
+    fn framebuffer_renderbuffer(target:GLenum, attachment:GLenum, renderbuffer:GLuint);
+
+    //fp framebuffer_texture_2d
+    /// Attach a level of a 2D texture (or cube map face, with its
+    /// target) to the framebuffer bound to a target
+    fn framebuffer_texture_2d(target:GLenum, attachment:GLenum, texture_target:GLenum, texture:GLuint, level:GLint);
+
+    //fp framebuffer_texture_layer
+    /// Attach a layer of a level of a 2D array texture to the
+    /// framebuffer bound to a target
+    fn framebuffer_texture_layer(target:GLenum, attachment:GLenum, texture:GLuint, level:GLint, layer:GLint);
+
+    //fp draw_buffers
+    /// Set the colour attachments drawn to of the bound draw
+    /// framebuffer (none for a depth-only framebuffer)
+    fn draw_buffers(buffers:&[GLenum]);
+
+    //fp gen_texture
+    /// Create a texture
+    fn gen_texture() -> GLuint;
+
+    //fp bind_texture
+    /// Bind a texture (or 0 for none) to a target of the active
+    /// texture unit
+    fn bind_texture(target:GLenum, texture:GLuint);
+
+    //fp delete_texture
+    /// Delete a texture
+    fn delete_texture(texture:GLuint);
+
+    //fp tex_image_2d
+    /// Allocate a level of the 2D image (or cube map face) of the
+    /// texture bound to a target, initialized with data if given;
+    /// `format` is the (format, type) of the data
+    fn tex_image_2d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei), format:(GLenum, GLenum), data:Option<&[u8]>);
+
+    //fp tex_image_3d
+    /// Allocate a level of the 3D image (or 2D array) of the texture
+    /// bound to a target, initialized with data if given; `format` is
+    /// the (format, type) of the data
+    fn tex_image_3d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei, GLsizei), format:(GLenum, GLenum), data:Option<&[u8]>);
+
+    //fp compressed_tex_image_2d
+    /// Allocate and initialize a level of the 2D image (or cube map
+    /// face) of the texture bound to a target with compressed data
+    fn compressed_tex_image_2d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei), data:&[u8]);
+
+    //fp compressed_tex_image_3d
+    /// Allocate and initialize a level of the 2D array of the texture
+    /// bound to a target with compressed data
+    fn compressed_tex_image_3d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei, GLsizei), data:&[u8]);
+
+    //fp tex_sub_image_2d
+    /// Upload data to a region of a level of the 2D image (or cube
+    /// map face) of the texture bound to a target
+    fn tex_sub_image_2d(target:GLenum, level:GLint, offset:(GLint, GLint), size:(GLsizei, GLsizei), format:(GLenum, GLenum), data:&[u8]);
+
+    //fp tex_sub_image_2d_buffer
+    /// Upload data to a region of a level of the 2D image of the
+    /// texture bound to a target from an offset in the bound
+    /// PIXEL_UNPACK_BUFFER
+    fn tex_sub_image_2d_buffer(target:GLenum, level:GLint, offset:(GLint, GLint), size:(GLsizei, GLsizei), format:(GLenum, GLenum), buffer_offset:usize);
+
+    //fp tex_sub_image_3d
+    /// Upload data to a region of a level of the 3D image (or 2D
+    /// array) of the texture bound to a target
+    fn tex_sub_image_3d(target:GLenum, level:GLint, offset:(GLint, GLint, GLint), size:(GLsizei, GLsizei, GLsizei), format:(GLenum, GLenum), data:&[u8]);
+
+    //fp tex_parameter_i
+    /// Set an integer parameter of the texture bound to a target
+    fn tex_parameter_i(target:GLenum, pname:GLenum, value:GLint);
+
+    //fp generate_mipmap
+    /// Generate the mipmaps of the texture bound to a target from
+    /// its base level
+    fn generate_mipmap(target:GLenum);
+
+    //fp active_texture
+    /// Select the active texture unit (GL_TEXTURE0 + unit)
+    fn active_texture(unit:GLuint);
+
+    //fp gen_sampler
+    /// Create a sampler
+    fn gen_sampler() -> GLuint;
+
+    //fp bind_sampler
+    /// Bind a sampler (or 0 for none) to a texture unit
+    fn bind_sampler(unit:GLuint, sampler:GLuint);
+
+    //fp delete_sampler
+    /// Delete a sampler
+    fn delete_sampler(sampler:GLuint);
+
+    //fp sampler_parameter_i
+    /// Set an integer parameter of a sampler
+    fn sampler_parameter_i(sampler:GLuint, pname:GLenum, value:GLint);
+
+    //fp sampler_parameter_f
+    /// Set a float parameter of a sampler
+    fn sampler_parameter_f(sampler:GLuint, pname:GLenum, value:f32);
+
+    //fp sampler_parameter_fv
+    /// Set a float vector parameter (such as the border colour) of a
+    /// sampler
+    fn sampler_parameter_fv(sampler:GLuint, pname:GLenum, values:&[f32]);
+
+    //fp read_buffer
+    /// Select the colour buffer for reading pixels
+    fn read_buffer(src:GLenum);
+
+    //fp pixel_store
+    /// Set a pixel storage mode
+    fn pixel_store(pname:GLenum, value:GLint);
+
+    //fp read_pixels
+    /// Read pixels from the read framebuffer
+    fn read_pixels(x:GLint, y:GLint, width:GLsizei, height:GLsizei, format:GLenum, pixel_type:GLenum, data:&mut [u8]);
+
+    //fp viewport
+    /// Set the viewport
+    fn viewport(x:GLint, y:GLint, width:GLsizei, height:GLsizei);
+
+    //fp clear_color
+    /// Set the colour to clear to
+    fn clear_color(r:f32, g:f32, b:f32, a:f32);
+
+    //fp clear
+    /// Clear buffers of the framebuffer
+    fn clear(mask:GLbitfield);
+
+    //fp enable
+    /// Enable a capability
+    fn enable(cap:GLenum);
+
+    //fp disable
+    /// Disable a capability
+    fn disable(cap:GLenum);
+
+    //fp is_enabled
+    /// Return true if a capability is enabled
+    fn is_enabled(cap:GLenum) -> bool;
+
+    //fp color_mask
+    /// Enable or disable writing of the colour components
+    fn color_mask(r:bool, g:bool, b:bool, a:bool);
+
+    //fp depth_mask
+    /// Enable or disable writing of the depth buffer
+    fn depth_mask(enabled:bool);
+
+    //fp polygon_offset
+    /// Set the scale and units of the depth offset of polygons, used
+    /// when POLYGON_OFFSET_FILL is enabled
+    fn polygon_offset(factor:f32, units:f32);
+
+    //fp finish
+    /// Wait for all OpenGL commands to complete
+    fn finish();
+
+    //fp debug_message_callback
+    /// Set the callback for debug output (KHR_debug); debug output
+    /// must be enabled with GL_DEBUG_OUTPUT for it to be invoked
+    fn debug_message_callback(callback:DebugCallback);
+
+    //fp debug_message_control
+    /// Enable or disable debug messages of a severity (from any source,
+    /// of any type)
+    fn debug_message_control(severity:GLenum, enabled:bool);
+
+    //fp object_label
+    /// Label an object (of a kind given by identifier, e.g. GL_BUFFER)
+    /// for debug messages and tools
+    fn object_label(identifier:GLenum, name:GLuint, label:&str);
+
+    //fp push_debug_group
+    /// Push a named debug group
+    fn push_debug_group(message:&str);
+
+    //fp pop_debug_group
+    /// Pop the current debug group
+    fn pop_debug_group();
+
+    //fp gen_query
+    /// Create a query object
+    fn gen_query() -> GLuint;
+
+    //fp delete_query
+    /// Delete a query object
+    fn delete_query(query:GLuint);
+
+    //fp begin_query
+    /// Begin a query of a target (e.g. GL_TIME_ELAPSED)
+    fn begin_query(target:GLenum, query:GLuint);
+
+    //fp end_query
+    /// End the active query of a target
+    fn end_query(target:GLenum);
+
+    //fp query_counter
+    /// Record the GPU time (GL_TIMESTAMP) in a query when all previous
+    /// commands have completed
+    fn query_counter(query:GLuint);
+
+    //fp query_result_available
+    /// Return true if the result of a query is available, without
+    /// waiting
+    fn query_result_available(query:GLuint) -> bool;
+
+    //fp query_result
+    /// Get the result of a query, waiting for it if required
+    fn query_result(query:GLuint) -> u64;
+
+    //fp begin_conditional_render
+    /// Begin rendering conditional on the result of an occlusion query
+    fn begin_conditional_render(query:GLuint, mode:GLenum);
+
+    //fp end_conditional_render
+    /// End conditional rendering
+    fn end_conditional_render();
+
+    //fp fence_sync
+    /// Insert a fence after the commands issued so far, returning a
+    /// handle for it (0 if it could not be created)
+    fn fence_sync() -> usize;
+
+    //fp fence_signaled
+    /// Return true if the commands before a fence have completed (or
+    /// waiting for it failed), without waiting
+    fn fence_signaled(fence:usize) -> bool;
+
+    //fp delete_fence
+    /// Delete a fence
+    fn delete_fence(fence:usize);
+}
+
+//a Backend selection
+//tp Backend
+/// The [GlBackend] selected by the cargo features - the `gl` crate
+#[cfg(not(feature="glow"))]
+pub type Backend = crate::backend_gl::GlCrate;
+
+//tp Backend
+/// The [GlBackend] selected by the cargo features - the `glow` crate
+#[cfg(feature="glow")]
+pub type Backend = crate::backend_glow::Glow;
@@ -0,0 +1,494 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    backend_gl.rs
+@brief   GlBackend implementation using the gl crate
+ */
+
+//a Imports
+use std::ffi::{CStr, CString};
+
+use gl::types::*;
+
+use crate::{GlBackend, DebugCallback};
+use crate::utils;
+
+//a GlCrate
+//tp GlCrate
+/// The [GlBackend] that uses the global function pointers of the
+/// `gl` crate; this supports desktop OpenGL
+pub struct GlCrate;
+
+//ip GlBackend for GlCrate
+impl GlBackend for GlCrate {
+    fn load_with<F:FnMut(&str) -> *const std::os::raw::c_void>(loader:F) {
+        gl::load_with(loader);
+    }
+    fn is_gles() -> bool {
+        let version = unsafe { gl::GetString(gl::VERSION) };
+        if version.is_null() {
+            false
+        } else {
+            let version = unsafe { CStr::from_ptr(version as *const GLchar) };
+            version.to_string_lossy().starts_with("OpenGL ES")
+        }
+    }
+    fn get_error() -> GLenum {
+        unsafe { gl::GetError() }
+    }
+    fn get_string(pname:GLenum) -> String {
+        let s = unsafe { gl::GetString(pname) };
+        if s.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(s as *const GLchar) }.to_string_lossy().into_owned()
+        }
+    }
+    fn get_integer(pname:GLenum) -> GLint {
+        let mut value = 0;
+        unsafe { gl::GetIntegerv(pname, &mut value) };
+        value
+    }
+    fn get_float(pname:GLenum) -> f32 {
+        let mut value = 0.;
+        unsafe { gl::GetFloatv(pname, &mut value) };
+        value
+    }
+    fn get_integers(pname:GLenum, values:&mut [GLint]) {
+        unsafe { gl::GetIntegerv(pname, values.as_mut_ptr()) };
+    }
+    fn extensions() -> Vec<String> {
+        let n = Self::get_integer(gl::NUM_EXTENSIONS);
+        let mut extensions = Vec::new();
+        for i in 0..n {
+            let s = unsafe { gl::GetStringi(gl::EXTENSIONS, i as GLuint) };
+            if !s.is_null() {
+                extensions.push(unsafe { CStr::from_ptr(s as *const GLchar) }.to_string_lossy().into_owned());
+            }
+        }
+        extensions
+    }
+
+    fn gen_buffer() -> GLuint {
+        let mut buffer = 0;
+        unsafe { gl::GenBuffers(1, &mut buffer) };
+        buffer
+    }
+    fn bind_buffer(target:GLenum, buffer:GLuint) {
+        unsafe { gl::BindBuffer(target, buffer) };
+    }
+    fn buffer_data(target:GLenum, data:&[u8], usage:GLenum) {
+        unsafe {
+            gl::BufferData(target,
+                           data.len() as GLsizeiptr,
+                           data.as_ptr() as *const GLvoid,
+                           usage);
+        }
+    }
+    fn buffer_storage(target:GLenum, data:&[u8], flags:GLbitfield) {
+        unsafe {
+            gl::BufferStorage(target,
+                              data.len() as GLsizeiptr,
+                              data.as_ptr() as *const GLvoid,
+                              flags);
+        }
+    }
+    fn buffer_data_size(target:GLenum, size:usize, usage:GLenum) {
+        unsafe { gl::BufferData(target, size as GLsizeiptr, std::ptr::null(), usage) };
+    }
+    fn buffer_sub_data(target:GLenum, offset:usize, data:&[u8]) {
+        unsafe { gl::BufferSubData(target, offset as GLintptr, data.len() as GLsizeiptr, data.as_ptr() as *const GLvoid) };
+    }
+    fn bind_buffer_base(target:GLenum, index:GLuint, buffer:GLuint) {
+        unsafe { gl::BindBufferBase(target, index, buffer) };
+    }
+    fn map_buffer_range(target:GLenum, offset:usize, length:usize, access:GLbitfield) -> *mut u8 {
+        unsafe { gl::MapBufferRange(target, offset as GLintptr, length as GLsizeiptr, access) as *mut u8 }
+    }
+    fn unmap_buffer(target:GLenum) -> bool {
+        unsafe { gl::UnmapBuffer(target) == gl::TRUE }
+    }
+    fn delete_buffer(buffer:GLuint) {
+        unsafe { gl::DeleteBuffers(1, &buffer) };
+    }
+
+    fn gen_vertex_array() -> GLuint {
+        let mut vao = 0;
+        unsafe { gl::GenVertexArrays(1, &mut vao) };
+        vao
+    }
+    fn bind_vertex_array(vao:GLuint) {
+        unsafe { gl::BindVertexArray(vao) };
+    }
+    fn delete_vertex_array(vao:GLuint) {
+        unsafe { gl::DeleteVertexArrays(1, &vao) };
+    }
+    fn enable_vertex_attrib_array(index:GLuint) {
+        unsafe { gl::EnableVertexAttribArray(index) };
+    }
+    fn vertex_attrib_pointer(index:GLuint, size:GLint, ele_type:GLenum, normalized:bool, stride:GLsizei, byte_offset:usize) {
+        let normalized = if normalized { gl::TRUE } else { gl::FALSE };
+        unsafe {
+            gl::VertexAttribPointer(index, size, ele_type, normalized, stride,
+                                    byte_offset as *const GLvoid);
+        }
+    }
+    fn vertex_attrib_4f(index:GLuint, value:&[f32; 4]) {
+        unsafe { gl::VertexAttrib4f(index, value[0], value[1], value[2], value[3]) };
+    }
+
+    fn create_shader(kind:GLenum) -> GLuint {
+        unsafe { gl::CreateShader(kind) }
+    }
+    fn shader_source(shader:GLuint, source:&str) {
+        let source = CString::new(source).unwrap();
+        unsafe { gl::ShaderSource(shader, 1, &source.as_ptr(), std::ptr::null()) };
+    }
+    fn compile_shader(shader:GLuint) {
+        unsafe { gl::CompileShader(shader) };
+    }
+    fn shader_compile_status(shader:GLuint) -> bool {
+        utils::get_shaderiv(shader, gl::COMPILE_STATUS) != 0
+    }
+    fn shader_info_log(shader:GLuint) -> String {
+        utils::get_shader_error( shader,
+                                 |id| utils::get_shaderiv(id, gl::INFO_LOG_LENGTH),
+                                 |id, len, buf| unsafe {
+                                     gl::GetShaderInfoLog( id, len, std::ptr::null_mut(), buf)
+                                 } )
+    }
+    fn delete_shader(shader:GLuint) {
+        unsafe { gl::DeleteShader(shader) };
+    }
+
+    fn create_program() -> GLuint {
+        unsafe { gl::CreateProgram() }
+    }
+    fn attach_shader(program:GLuint, shader:GLuint) {
+        unsafe { gl::AttachShader(program, shader) };
+    }
+    fn detach_shader(program:GLuint, shader:GLuint) {
+        unsafe { gl::DetachShader(program, shader) };
+    }
+    fn link_program(program:GLuint) {
+        unsafe { gl::LinkProgram(program) };
+    }
+    fn bind_attrib_location(program:GLuint, index:GLuint, name:&str) {
+        let name_c = CString::new(name).unwrap();
+        unsafe { gl::BindAttribLocation(program, index, name_c.as_ptr()) };
+    }
+    fn program_link_status(program:GLuint) -> bool {
+        utils::get_programiv(program, gl::LINK_STATUS) != 0
+    }
+    fn program_info_log(program:GLuint) -> String {
+        utils::get_shader_error( program,
+                                 |id| utils::get_programiv(id, gl::INFO_LOG_LENGTH),
+                                 |id, len, buf| unsafe {
+                                     gl::GetProgramInfoLog( id, len, std::ptr::null_mut(), buf)
+                                 } )
+    }
+    fn use_program(program:GLuint) {
+        unsafe { gl::UseProgram(program) };
+    }
+    fn delete_program(program:GLuint) {
+        unsafe { gl::DeleteProgram(program) };
+    }
+    fn attrib_location(program:GLuint, name:&str) -> Option<GLuint> {
+        let name_c = CString::new(name).unwrap();
+        let index = unsafe { gl::GetAttribLocation(program, name_c.as_ptr()) };
+        if index < 0 { None } else { Some(index as GLuint) }
+    }
+    fn uniform_location(program:GLuint, name:&str) -> Option<GLint> {
+        let name_c = CString::new(name).unwrap();
+        let index = unsafe { gl::GetUniformLocation(program, name_c.as_ptr()) };
+        if index < 0 { None } else { Some(index) }
+    }
+    fn uniform_block_index(program:GLuint, name:&str) -> Option<GLuint> {
+        let name_c = CString::new(name).unwrap();
+        let index = unsafe { gl::GetUniformBlockIndex(program, name_c.as_ptr()) };
+        if index == gl::INVALID_INDEX { None } else { Some(index) }
+    }
+    fn uniform_block_binding(program:GLuint, block_index:GLuint, binding:GLuint) {
+        unsafe { gl::UniformBlockBinding(program, block_index, binding) };
+    }
+    fn uniform_matrix4(location:GLint, data:&[f32]) {
+        unsafe { gl::UniformMatrix4fv(location, (data.len() / 16) as GLsizei, gl::FALSE, data.as_ptr()) };
+    }
+    fn uniform_1i(location:GLint, value:GLint) {
+        unsafe { gl::Uniform1i(location, value) };
+    }
+    fn uniform_4f(location:GLint, value:&[f32; 4]) {
+        unsafe { gl::Uniform4f(location, value[0], value[1], value[2], value[3]) };
+    }
+    fn uniform_4i(location:GLint, value:&[GLint; 4]) {
+        unsafe { gl::Uniform4i(location, value[0], value[1], value[2], value[3]) };
+    }
+    fn draw_elements(mode:GLenum, count:GLsizei, index_type:GLenum, byte_offset:usize) {
+        unsafe { gl::DrawElements(mode, count, index_type, byte_offset as *const GLvoid) };
+    }
+    fn draw_arrays(mode:GLenum, first:GLint, count:GLsizei) {
+        unsafe { gl::DrawArrays(mode, first, count) };
+    }
+
+    fn gen_framebuffer() -> GLuint {
+        let mut framebuffer = 0;
+        unsafe { gl::GenFramebuffers(1, &mut framebuffer) };
+        framebuffer
+    }
+    fn bind_framebuffer(target:GLenum, framebuffer:GLuint) {
+        unsafe { gl::BindFramebuffer(target, framebuffer) };
+    }
+    fn delete_framebuffer(framebuffer:GLuint) {
+        unsafe { gl::DeleteFramebuffers(1, &framebuffer) };
+    }
+    fn check_framebuffer_status(target:GLenum) -> GLenum {
+        unsafe { gl::CheckFramebufferStatus(target) }
+    }
+    fn gen_renderbuffer() -> GLuint {
+        let mut renderbuffer = 0;
+        unsafe { gl::GenRenderbuffers(1, &mut renderbuffer) };
+        renderbuffer
+    }
+    fn bind_renderbuffer(renderbuffer:GLuint) {
+        unsafe { gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer) };
+    }
+    fn delete_renderbuffer(renderbuffer:GLuint) {
+        unsafe { gl::DeleteRenderbuffers(1, &renderbuffer) };
+    }
+    fn renderbuffer_storage(internal_format:GLenum, width:GLsizei, height:GLsizei) {
+        unsafe { gl::RenderbufferStorage(gl::RENDERBUFFER, internal_format, width, height) };
+    }
+    fn framebuffer_renderbuffer(target:GLenum, attachment:GLenum, renderbuffer:GLuint) {
+        unsafe { gl::FramebufferRenderbuffer(target, attachment, gl::RENDERBUFFER, renderbuffer) };
+    }
+    fn framebuffer_texture_2d(target:GLenum, attachment:GLenum, texture_target:GLenum, texture:GLuint, level:GLint) {
+        unsafe { gl::FramebufferTexture2D(target, attachment, texture_target, texture, level) };
+    }
+    fn framebuffer_texture_layer(target:GLenum, attachment:GLenum, texture:GLuint, level:GLint, layer:GLint) {
+        unsafe { gl::FramebufferTextureLayer(target, attachment, texture, level, layer) };
+    }
+    fn draw_buffers(buffers:&[GLenum]) {
+        unsafe { gl::DrawBuffers(buffers.len() as GLsizei, buffers.as_ptr()) };
+    }
+    fn gen_texture() -> GLuint {
+        let mut texture = 0;
+        unsafe { gl::GenTextures(1, &mut texture) };
+        texture
+    }
+    fn bind_texture(target:GLenum, texture:GLuint) {
+        unsafe { gl::BindTexture(target, texture) };
+    }
+    fn delete_texture(texture:GLuint) {
+        unsafe { gl::DeleteTextures(1, &texture) };
+    }
+    fn tex_image_2d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei), format:(GLenum, GLenum), data:Option<&[u8]>) {
+        let data = data.map_or(std::ptr::null(), |d| d.as_ptr() as *const GLvoid);
+        unsafe {
+            gl::TexImage2D(target, level, internal_format as GLint, size.0, size.1, 0,
+                           format.0, format.1, data);
+        }
+    }
+    fn tex_image_3d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei, GLsizei), format:(GLenum, GLenum), data:Option<&[u8]>) {
+        let data = data.map_or(std::ptr::null(), |d| d.as_ptr() as *const GLvoid);
+        unsafe {
+            gl::TexImage3D(target, level, internal_format as GLint, size.0, size.1, size.2, 0,
+                           format.0, format.1, data);
+        }
+    }
+    fn compressed_tex_image_2d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei), data:&[u8]) {
+        unsafe {
+            gl::CompressedTexImage2D(target, level, internal_format, size.0, size.1, 0,
+                                     data.len() as GLsizei, data.as_ptr() as *const GLvoid);
+        }
+    }
+    fn compressed_tex_image_3d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei, GLsizei), data:&[u8]) {
+        unsafe {
+            gl::CompressedTexImage3D(target, level, internal_format, size.0, size.1, size.2, 0,
+                                     data.len() as GLsizei, data.as_ptr() as *const GLvoid);
+        }
+    }
+    fn tex_sub_image_2d(target:GLenum, level:GLint, offset:(GLint, GLint), size:(GLsizei, GLsizei), format:(GLenum, GLenum), data:&[u8]) {
+        unsafe {
+            gl::TexSubImage2D(target, level, offset.0, offset.1, size.0, size.1,
+                              format.0, format.1, data.as_ptr() as *const GLvoid);
+        }
+    }
+    fn tex_sub_image_2d_buffer(target:GLenum, level:GLint, offset:(GLint, GLint), size:(GLsizei, GLsizei), format:(GLenum, GLenum), buffer_offset:usize) {
+        unsafe {
+            gl::TexSubImage2D(target, level, offset.0, offset.1, size.0, size.1,
+                              format.0, format.1, buffer_offset as *const GLvoid);
+        }
+    }
+    fn tex_sub_image_3d(target:GLenum, level:GLint, offset:(GLint, GLint, GLint), size:(GLsizei, GLsizei, GLsizei), format:(GLenum, GLenum), data:&[u8]) {
+        unsafe {
+            gl::TexSubImage3D(target, level, offset.0, offset.1, offset.2, size.0, size.1, size.2,
+                              format.0, format.1, data.as_ptr() as *const GLvoid);
+        }
+    }
+    fn tex_parameter_i(target:GLenum, pname:GLenum, value:GLint) {
+        unsafe { gl::TexParameteri(target, pname, value) };
+    }
+    fn generate_mipmap(target:GLenum) {
+        unsafe { gl::GenerateMipmap(target) };
+    }
+    fn active_texture(unit:GLuint) {
+        unsafe { gl::ActiveTexture(gl::TEXTURE0 + unit) };
+    }
+    fn gen_sampler() -> GLuint {
+        let mut sampler = 0;
+        unsafe { gl::GenSamplers(1, &mut sampler) };
+        sampler
+    }
+    fn bind_sampler(unit:GLuint, sampler:GLuint) {
+        unsafe { gl::BindSampler(unit, sampler) };
+    }
+    fn delete_sampler(sampler:GLuint) {
+        unsafe { gl::DeleteSamplers(1, &sampler) };
+    }
+    fn sampler_parameter_i(sampler:GLuint, pname:GLenum, value:GLint) {
+        unsafe { gl::SamplerParameteri(sampler, pname, value) };
+    }
+    fn sampler_parameter_f(sampler:GLuint, pname:GLenum, value:f32) {
+        unsafe { gl::SamplerParameterf(sampler, pname, value) };
+    }
+    fn sampler_parameter_fv(sampler:GLuint, pname:GLenum, values:&[f32]) {
+        unsafe { gl::SamplerParameterfv(sampler, pname, values.as_ptr()) };
+    }
+    fn read_buffer(src:GLenum) {
+        unsafe { gl::ReadBuffer(src) };
+    }
+    fn pixel_store(pname:GLenum, value:GLint) {
+        unsafe { gl::PixelStorei(pname, value) };
+    }
+    fn read_pixels(x:GLint, y:GLint, width:GLsizei, height:GLsizei, format:GLenum, pixel_type:GLenum, data:&mut [u8]) {
+        unsafe {
+            gl::ReadPixels(x, y, width, height, format, pixel_type,
+                           data.as_mut_ptr() as *mut GLvoid);
+        }
+    }
+
+    fn viewport(x:GLint, y:GLint, width:GLsizei, height:GLsizei) {
+        unsafe { gl::Viewport(x, y, width, height) };
+    }
+    fn clear_color(r:f32, g:f32, b:f32, a:f32) {
+        unsafe { gl::ClearColor(r, g, b, a) };
+    }
+    fn clear(mask:GLbitfield) {
+        unsafe { gl::Clear(mask) };
+    }
+    fn enable(cap:GLenum) {
+        unsafe { gl::Enable(cap) };
+    }
+    fn disable(cap:GLenum) {
+        unsafe { gl::Disable(cap) };
+    }
+    fn is_enabled(cap:GLenum) -> bool {
+        unsafe { gl::IsEnabled(cap) != gl::FALSE }
+    }
+    fn color_mask(r:bool, g:bool, b:bool, a:bool) {
+        unsafe { gl::ColorMask(r as GLboolean, g as GLboolean, b as GLboolean, a as GLboolean) };
+    }
+    fn depth_mask(enabled:bool) {
+        unsafe { gl::DepthMask(enabled as GLboolean) };
+    }
+    fn polygon_offset(factor:f32, units:f32) {
+        unsafe { gl::PolygonOffset(factor, units) };
+    }
+    fn finish() {
+        unsafe { gl::Finish() };
+    }
+
+    fn debug_message_callback(callback:DebugCallback) {
+        unsafe { gl::DebugMessageCallback(debug_callback, callback as *const std::os::raw::c_void) };
+    }
+    fn debug_message_control(severity:GLenum, enabled:bool) {
+        let enabled = if enabled { gl::TRUE } else { gl::FALSE };
+        unsafe { gl::DebugMessageControl(gl::DONT_CARE, gl::DONT_CARE, severity, 0, std::ptr::null(), enabled) };
+    }
+    fn object_label(identifier:GLenum, name:GLuint, label:&str) {
+        unsafe { gl::ObjectLabel(identifier, name, label.len() as GLsizei, label.as_ptr() as *const GLchar) };
+    }
+    fn push_debug_group(message:&str) {
+        unsafe {
+            gl::PushDebugGroup(gl::DEBUG_SOURCE_APPLICATION, 0,
+                               message.len() as GLsizei, message.as_ptr() as *const GLchar)
+        };
+    }
+    fn pop_debug_group() {
+        unsafe { gl::PopDebugGroup() };
+    }
+
+    fn gen_query() -> GLuint {
+        let mut query = 0;
+        unsafe { gl::GenQueries(1, &mut query) };
+        query
+    }
+    fn delete_query(query:GLuint) {
+        unsafe { gl::DeleteQueries(1, &query) };
+    }
+    fn begin_query(target:GLenum, query:GLuint) {
+        unsafe { gl::BeginQuery(target, query) };
+    }
+    fn end_query(target:GLenum) {
+        unsafe { gl::EndQuery(target) };
+    }
+    fn query_counter(query:GLuint) {
+        unsafe { gl::QueryCounter(query, gl::TIMESTAMP) };
+    }
+    fn query_result_available(query:GLuint) -> bool {
+        let mut available = 0;
+        unsafe { gl::GetQueryObjectuiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available) };
+        available != 0
+    }
+    fn query_result(query:GLuint) -> u64 {
+        let mut result = 0;
+        unsafe { gl::GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut result) };
+        result
+    }
+    fn begin_conditional_render(query:GLuint, mode:GLenum) {
+        unsafe { gl::BeginConditionalRender(query, mode) };
+    }
+    fn end_conditional_render() {
+        unsafe { gl::EndConditionalRender() };
+    }
+    fn fence_sync() -> usize {
+        unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) as usize }
+    }
+    fn fence_signaled(fence:usize) -> bool {
+        unsafe { gl::ClientWaitSync(fence as GLsync, 0, 0) != gl::TIMEOUT_EXPIRED }
+    }
+    fn delete_fence(fence:usize) {
+        unsafe { gl::DeleteSync(fence as GLsync) };
+    }
+}
+
+//a Functions
+//fi debug_callback
+/// The KHR_debug callback; the user parameter is the [DebugCallback]
+extern "system" fn debug_callback(source:GLenum, gltype:GLenum, id:GLuint, severity:GLenum,
+                                  length:GLsizei, message:*const GLchar, user_param:*mut GLvoid) {
+    if user_param.is_null() || message.is_null() {
+        return;
+    }
+    let callback : DebugCallback = unsafe { std::mem::transmute(user_param) };
+    let message = {
+        if length < 0 {
+            unsafe { CStr::from_ptr(message) }.to_string_lossy()
+        } else {
+            let bytes = unsafe { std::slice::from_raw_parts(message as *const u8, length as usize) };
+            String::from_utf8_lossy(bytes)
+        }
+    };
+    callback(source, gltype, id, severity, &message);
+}
@@ -0,0 +1,778 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    backend_glow.rs
+@brief   GlBackend implementation using the glow crate
+ */
+
+//a Documentation
+
+/*!
+
+The `glow` crate has a context object, and its OpenGL objects are
+typed (and for WebGL they are Javascript objects); the crate's
+objects are all `GLuint` names, so this backend keeps a table per
+kind of object mapping the names to the `glow` objects.
+
+The `glow` context must be provided to the backend (for the thread
+on which rendering is performed) with [set_context] before any
+OpenGL objects are created.
+
+A program, shader or query name that is unknown (or deleted) is not
+passed to `glow`; the call does nothing, and GL_INVALID_VALUE (or
+GL_INVALID_OPERATION for a query) is returned by the next
+`get_error`, as with OpenGL.
+
+Object labels are not supported with this backend, as `glow` does
+not expose the native names of its objects; debug groups and the
+debug message callback are supported for native contexts (WebGL
+does not support KHR_debug).
+
+!*/
+
+//a Imports
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use gl::types::*;
+use glow::HasContext;
+
+use crate::{GlBackend, DebugCallback};
+
+//a Names
+//ti Names
+/// A table mapping `GLuint` names to `glow` objects; name 0 is
+/// reserved for 'none'
+struct Names<T> {
+    objects : Vec<Option<T>>,
+    free : Vec<usize>,
+}
+
+//ip Names
+impl <T:Clone> Names<T> {
+    //fp new
+    fn new() -> Self {
+        Self { objects:Vec::new(), free:Vec::new() }
+    }
+
+    //mp add
+    /// Add an object, returning its name
+    fn add(&mut self, object:T) -> GLuint {
+        if let Some(index) = self.free.pop() {
+            self.objects[index] = Some(object);
+            (index + 1) as GLuint
+        } else {
+            self.objects.push(Some(object));
+            self.objects.len() as GLuint
+        }
+    }
+
+    //mp get
+    /// Get the object for a name; 0 (or a deleted name) is None
+    fn get(&self, name:GLuint) -> Option<T> {
+        if name == 0 {
+            None
+        } else {
+            self.objects.get(name as usize - 1).cloned().flatten()
+        }
+    }
+
+    //mp remove
+    /// Remove the object for a name, freeing the name
+    fn remove(&mut self, name:GLuint) -> Option<T> {
+        let object = self.get(name);
+        if object.is_some() {
+            self.objects[name as usize - 1] = None;
+            self.free.push(name as usize - 1);
+        }
+        object
+    }
+}
+
+//a GlowState
+//ti GlowState
+/// The `glow` context and object tables
+struct GlowState {
+    context : glow::Context,
+    buffers : Names<glow::Buffer>,
+    vertex_arrays : Names<glow::VertexArray>,
+    shaders : Names<glow::Shader>,
+    programs : Names<glow::Program>,
+    framebuffers : Names<glow::Framebuffer>,
+    renderbuffers : Names<glow::Renderbuffer>,
+    textures : Names<glow::Texture>,
+    samplers : Names<glow::Sampler>,
+    fences : Names<glow::Fence>,
+    queries : Names<glow::Query>,
+    uniform_locations : Names<glow::UniformLocation>,
+    /// The uniform location names handed out for each program, by
+    /// program name and uniform name
+    program_uniforms : HashMap<GLuint, HashMap<String, Option<GLint>>>,
+    /// The error recorded for an unknown program or shader name, as
+    /// the `glow` context cannot be given one; returned (and
+    /// cleared) by the next `get_error`
+    error : GLenum,
+}
+
+thread_local! {
+    static STATE : RefCell<Option<GlowState>> = const { RefCell::new(None) };
+    static DEBUG_CALLBACK : Cell<Option<DebugCallback>> = const { Cell::new(None) };
+}
+
+//fp set_context
+/// Set the `glow` context to be used by the backend on this thread
+pub fn set_context(context:glow::Context) {
+    let state = GlowState {
+        context,
+        buffers : Names::new(),
+        vertex_arrays : Names::new(),
+        shaders : Names::new(),
+        programs : Names::new(),
+        framebuffers : Names::new(),
+        renderbuffers : Names::new(),
+        textures : Names::new(),
+        samplers : Names::new(),
+        fences : Names::new(),
+        queries : Names::new(),
+        uniform_locations : Names::new(),
+        program_uniforms : HashMap::new(),
+        error : gl::NO_ERROR,
+    };
+    STATE.with(|s| *s.borrow_mut() = Some(state));
+}
+
+//ip GlowState
+impl GlowState {
+    //mp record_error
+    /// Record an error for an unknown name, unless an error is
+    /// already recorded (as OpenGL keeps the first error)
+    fn record_error(&mut self, error:GLenum) {
+        if self.error == gl::NO_ERROR {
+            self.error = error;
+        }
+    }
+
+    //mp shader
+    /// Get the `glow` shader for a name, recording GL_INVALID_VALUE
+    /// if there is none
+    fn shader(&mut self, shader:GLuint) -> Option<glow::Shader> {
+        let s = self.shaders.get(shader);
+        if s.is_none() {
+            self.record_error(gl::INVALID_VALUE);
+        }
+        s
+    }
+
+    //mp program
+    /// Get the `glow` program for a name, recording GL_INVALID_VALUE
+    /// if there is none
+    fn program(&mut self, program:GLuint) -> Option<glow::Program> {
+        let p = self.programs.get(program);
+        if p.is_none() {
+            self.record_error(gl::INVALID_VALUE);
+        }
+        p
+    }
+
+    //mp query
+    /// Get the `glow` query for a name, recording
+    /// GL_INVALID_OPERATION if there is none
+    fn query(&mut self, query:GLuint) -> Option<glow::Query> {
+        let q = self.queries.get(query);
+        if q.is_none() {
+            self.record_error(gl::INVALID_OPERATION);
+        }
+        q
+    }
+
+    //mp forget_uniforms
+    /// Free the uniform location names handed out for a program, as
+    /// it is being relinked or deleted
+    fn forget_uniforms(&mut self, program:GLuint) {
+        if let Some(uniforms) = self.program_uniforms.remove(&program) {
+            for location in uniforms.values().flatten() {
+                self.uniform_locations.remove(*location as GLuint);
+            }
+        }
+    }
+}
+
+//fi with_state
+/// Invoke a function with the `glow` state for the thread
+fn with_state<T, F:FnOnce(&mut GlowState) -> T>(f:F) -> T {
+    STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        f(s.as_mut().expect("glow context must be set with backend_glow::set_context before use"))
+    })
+}
+
+//a Glow
+//tp Glow
+/// The [GlBackend] that uses a `glow` context; this supports
+/// desktop OpenGL, OpenGL ES 3.0 and WebGL 2
+pub struct Glow;
+
+//ip GlBackend for Glow
+impl GlBackend for Glow {
+    #[cfg(not(target_arch="wasm32"))]
+    fn load_with<F:FnMut(&str) -> *const std::os::raw::c_void>(loader:F) {
+        set_context(unsafe { glow::Context::from_loader_function(loader) });
+    }
+    #[cfg(target_arch="wasm32")]
+    fn load_with<F:FnMut(&str) -> *const std::os::raw::c_void>(_loader:F) {
+        panic!("WebGL contexts must be provided with backend_glow::set_context");
+    }
+    fn is_gles() -> bool {
+        with_state(|s| s.context.version().is_embedded)
+    }
+    fn get_error() -> GLenum {
+        with_state(|s| {
+            if s.error != gl::NO_ERROR {
+                std::mem::replace(&mut s.error, gl::NO_ERROR)
+            } else {
+                unsafe { s.context.get_error() }
+            }
+        })
+    }
+    fn get_string(pname:GLenum) -> String {
+        with_state(|s| unsafe { s.context.get_parameter_string(pname) })
+    }
+    fn get_integer(pname:GLenum) -> GLint {
+        with_state(|s| unsafe { s.context.get_parameter_i32(pname) })
+    }
+    fn get_float(pname:GLenum) -> f32 {
+        with_state(|s| unsafe { s.context.get_parameter_f32(pname) })
+    }
+    fn get_integers(pname:GLenum, values:&mut [GLint]) {
+        with_state(|s| unsafe { s.context.get_parameter_i32_slice(pname, values) });
+    }
+    fn extensions() -> Vec<String> {
+        with_state(|s| s.context.supported_extensions().iter().cloned().collect())
+    }
+
+    fn gen_buffer() -> GLuint {
+        with_state(|s| {
+            let buffer = unsafe { s.context.create_buffer() }.expect("Failed to create buffer");
+            s.buffers.add(buffer)
+        })
+    }
+    fn bind_buffer(target:GLenum, buffer:GLuint) {
+        with_state(|s| unsafe { s.context.bind_buffer(target, s.buffers.get(buffer)) });
+    }
+    fn buffer_data(target:GLenum, data:&[u8], usage:GLenum) {
+        with_state(|s| unsafe { s.context.buffer_data_u8_slice(target, data, usage) });
+    }
+    fn buffer_storage(target:GLenum, data:&[u8], flags:GLbitfield) {
+        with_state(|s| unsafe { s.context.buffer_storage(target, data.len() as i32, Some(data), flags) });
+    }
+    fn buffer_data_size(target:GLenum, size:usize, usage:GLenum) {
+        with_state(|s| unsafe { s.context.buffer_data_size(target, size as i32, usage) });
+    }
+    fn buffer_sub_data(target:GLenum, offset:usize, data:&[u8]) {
+        with_state(|s| unsafe { s.context.buffer_sub_data_u8_slice(target, offset as i32, data) });
+    }
+    fn bind_buffer_base(target:GLenum, index:GLuint, buffer:GLuint) {
+        with_state(|s| unsafe { s.context.bind_buffer_base(target, index, s.buffers.get(buffer)) });
+    }
+    fn map_buffer_range(target:GLenum, offset:usize, length:usize, access:GLbitfield) -> *mut u8 {
+        with_state(|s| unsafe { s.context.map_buffer_range(target, offset as i32, length as i32, access) })
+    }
+    fn unmap_buffer(target:GLenum) -> bool {
+        // glow does not return whether the data store was corrupted
+        with_state(|s| unsafe { s.context.unmap_buffer(target) });
+        true
+    }
+    fn delete_buffer(buffer:GLuint) {
+        with_state(|s| {
+            if let Some(b) = s.buffers.remove(buffer) {
+                unsafe { s.context.delete_buffer(b) };
+            }
+        });
+    }
+
+    fn gen_vertex_array() -> GLuint {
+        with_state(|s| {
+            let vao = unsafe { s.context.create_vertex_array() }.expect("Failed to create vertex array");
+            s.vertex_arrays.add(vao)
+        })
+    }
+    fn bind_vertex_array(vao:GLuint) {
+        with_state(|s| unsafe { s.context.bind_vertex_array(s.vertex_arrays.get(vao)) });
+    }
+    fn delete_vertex_array(vao:GLuint) {
+        with_state(|s| {
+            if let Some(v) = s.vertex_arrays.remove(vao) {
+                unsafe { s.context.delete_vertex_array(v) };
+            }
+        });
+    }
+    fn enable_vertex_attrib_array(index:GLuint) {
+        with_state(|s| unsafe { s.context.enable_vertex_attrib_array(index) });
+    }
+    fn vertex_attrib_pointer(index:GLuint, size:GLint, ele_type:GLenum, normalized:bool, stride:GLsizei, byte_offset:usize) {
+        with_state(|s| unsafe {
+            s.context.vertex_attrib_pointer_f32(index, size, ele_type, normalized, stride, byte_offset as i32)
+        });
+    }
+    fn vertex_attrib_4f(index:GLuint, value:&[f32; 4]) {
+        with_state(|s| unsafe { s.context.vertex_attrib_4_f32(index, value[0], value[1], value[2], value[3]) });
+    }
+
+    fn create_shader(kind:GLenum) -> GLuint {
+        with_state(|s| {
+            let shader = unsafe { s.context.create_shader(kind) }.expect("Failed to create shader");
+            s.shaders.add(shader)
+        })
+    }
+    fn shader_source(shader:GLuint, source:&str) {
+        with_state(|s| {
+            if let Some(sh) = s.shader(shader) {
+                unsafe { s.context.shader_source(sh, source) };
+            }
+        });
+    }
+    fn compile_shader(shader:GLuint) {
+        with_state(|s| {
+            if let Some(sh) = s.shader(shader) {
+                unsafe { s.context.compile_shader(sh) };
+            }
+        });
+    }
+    fn shader_compile_status(shader:GLuint) -> bool {
+        with_state(|s| s.shader(shader).is_some_and(|sh| unsafe { s.context.get_shader_compile_status(sh) }))
+    }
+    fn shader_info_log(shader:GLuint) -> String {
+        with_state(|s| s.shader(shader).map_or_else(String::new, |sh| unsafe { s.context.get_shader_info_log(sh) }))
+    }
+    fn delete_shader(shader:GLuint) {
+        with_state(|s| {
+            if let Some(sh) = s.shaders.remove(shader) {
+                unsafe { s.context.delete_shader(sh) };
+            }
+        });
+    }
+
+    fn create_program() -> GLuint {
+        with_state(|s| {
+            let program = unsafe { s.context.create_program() }.expect("Failed to create program");
+            s.programs.add(program)
+        })
+    }
+    fn attach_shader(program:GLuint, shader:GLuint) {
+        with_state(|s| {
+            if let (Some(p), Some(sh)) = (s.program(program), s.shader(shader)) {
+                unsafe { s.context.attach_shader(p, sh) };
+            }
+        });
+    }
+    fn detach_shader(program:GLuint, shader:GLuint) {
+        with_state(|s| {
+            if let (Some(p), Some(sh)) = (s.program(program), s.shader(shader)) {
+                unsafe { s.context.detach_shader(p, sh) };
+            }
+        });
+    }
+    fn link_program(program:GLuint) {
+        with_state(|s| {
+            s.forget_uniforms(program);
+            if let Some(p) = s.program(program) {
+                unsafe { s.context.link_program(p) };
+            }
+        });
+    }
+    fn bind_attrib_location(program:GLuint, index:GLuint, name:&str) {
+        with_state(|s| {
+            if let Some(p) = s.program(program) {
+                unsafe { s.context.bind_attrib_location(p, index, name) };
+            }
+        });
+    }
+    fn program_link_status(program:GLuint) -> bool {
+        with_state(|s| s.program(program).is_some_and(|p| unsafe { s.context.get_program_link_status(p) }))
+    }
+    fn program_info_log(program:GLuint) -> String {
+        with_state(|s| s.program(program).map_or_else(String::new, |p| unsafe { s.context.get_program_info_log(p) }))
+    }
+    fn use_program(program:GLuint) {
+        with_state(|s| unsafe { s.context.use_program(s.programs.get(program)) });
+    }
+    fn delete_program(program:GLuint) {
+        with_state(|s| {
+            if let Some(p) = s.programs.remove(program) {
+                unsafe { s.context.delete_program(p) };
+            }
+            s.forget_uniforms(program);
+        });
+    }
+    fn attrib_location(program:GLuint, name:&str) -> Option<GLuint> {
+        with_state(|s| {
+            let p = s.programs.get(program)?;
+            unsafe { s.context.get_attrib_location(p, name) }
+        })
+    }
+    fn uniform_location(program:GLuint, name:&str) -> Option<GLint> {
+        with_state(|s| {
+            let p = s.programs.get(program)?;
+            if let Some(location) = s.program_uniforms.get(&program).and_then(|u| u.get(name)) {
+                return *location;
+            }
+            let location = unsafe { s.context.get_uniform_location(p, name) };
+            let location = location.map(|l| s.uniform_locations.add(l) as GLint);
+            s.program_uniforms.entry(program).or_default().insert(name.to_string(), location);
+            location
+        })
+    }
+    fn uniform_block_index(program:GLuint, name:&str) -> Option<GLuint> {
+        with_state(|s| {
+            let p = s.programs.get(program)?;
+            unsafe { s.context.get_uniform_block_index(p, name) }
+        })
+    }
+    fn uniform_block_binding(program:GLuint, block_index:GLuint, binding:GLuint) {
+        with_state(|s| {
+            if let Some(p) = s.program(program) {
+                unsafe { s.context.uniform_block_binding(p, block_index, binding) };
+            }
+        });
+    }
+    fn uniform_matrix4(location:GLint, data:&[f32]) {
+        with_state(|s| {
+            let location = s.uniform_locations.get(location as GLuint);
+            unsafe { s.context.uniform_matrix_4_f32_slice(location.as_ref(), false, data) };
+        });
+    }
+    fn uniform_1i(location:GLint, value:GLint) {
+        with_state(|s| {
+            let location = s.uniform_locations.get(location as GLuint);
+            unsafe { s.context.uniform_1_i32(location.as_ref(), value) };
+        });
+    }
+    fn uniform_4f(location:GLint, value:&[f32; 4]) {
+        with_state(|s| {
+            let location = s.uniform_locations.get(location as GLuint);
+            unsafe { s.context.uniform_4_f32(location.as_ref(), value[0], value[1], value[2], value[3]) };
+        });
+    }
+    fn uniform_4i(location:GLint, value:&[GLint; 4]) {
+        with_state(|s| {
+            let location = s.uniform_locations.get(location as GLuint);
+            unsafe { s.context.uniform_4_i32(location.as_ref(), value[0], value[1], value[2], value[3]) };
+        });
+    }
+    fn draw_elements(mode:GLenum, count:GLsizei, index_type:GLenum, byte_offset:usize) {
+        with_state(|s| unsafe { s.context.draw_elements(mode, count, index_type, byte_offset as i32) });
+    }
+    fn draw_arrays(mode:GLenum, first:GLint, count:GLsizei) {
+        with_state(|s| unsafe { s.context.draw_arrays(mode, first, count) });
+    }
+
+    fn gen_framebuffer() -> GLuint {
+        with_state(|s| {
+            let framebuffer = unsafe { s.context.create_framebuffer() }.expect("Failed to create framebuffer");
+            s.framebuffers.add(framebuffer)
+        })
+    }
+    fn bind_framebuffer(target:GLenum, framebuffer:GLuint) {
+        with_state(|s| unsafe { s.context.bind_framebuffer(target, s.framebuffers.get(framebuffer)) });
+    }
+    fn delete_framebuffer(framebuffer:GLuint) {
+        with_state(|s| {
+            if let Some(f) = s.framebuffers.remove(framebuffer) {
+                unsafe { s.context.delete_framebuffer(f) };
+            }
+        });
+    }
+    fn check_framebuffer_status(target:GLenum) -> GLenum {
+        with_state(|s| unsafe { s.context.check_framebuffer_status(target) })
+    }
+    fn gen_renderbuffer() -> GLuint {
+        with_state(|s| {
+            let renderbuffer = unsafe { s.context.create_renderbuffer() }.expect("Failed to create renderbuffer");
+            s.renderbuffers.add(renderbuffer)
+        })
+    }
+    fn bind_renderbuffer(renderbuffer:GLuint) {
+        with_state(|s| unsafe { s.context.bind_renderbuffer(glow::RENDERBUFFER, s.renderbuffers.get(renderbuffer)) });
+    }
+    fn delete_renderbuffer(renderbuffer:GLuint) {
+        with_state(|s| {
+            if let Some(r) = s.renderbuffers.remove(renderbuffer) {
+                unsafe { s.context.delete_renderbuffer(r) };
+            }
+        });
+    }
+    fn renderbuffer_storage(internal_format:GLenum, width:GLsizei, height:GLsizei) {
+        with_state(|s| unsafe { s.context.renderbuffer_storage(glow::RENDERBUFFER, internal_format, width, height) });
+    }
+    fn framebuffer_renderbuffer(target:GLenum, attachment:GLenum, renderbuffer:GLuint) {
+        with_state(|s| unsafe {
+            s.context.framebuffer_renderbuffer(target, attachment, glow::RENDERBUFFER, s.renderbuffers.get(renderbuffer))
+        });
+    }
+    fn framebuffer_texture_2d(target:GLenum, attachment:GLenum, texture_target:GLenum, texture:GLuint, level:GLint) {
+        with_state(|s| unsafe {
+            s.context.framebuffer_texture_2d(target, attachment, texture_target, s.textures.get(texture), level)
+        });
+    }
+    fn framebuffer_texture_layer(target:GLenum, attachment:GLenum, texture:GLuint, level:GLint, layer:GLint) {
+        with_state(|s| unsafe {
+            s.context.framebuffer_texture_layer(target, attachment, s.textures.get(texture), level, layer)
+        });
+    }
+    fn draw_buffers(buffers:&[GLenum]) {
+        with_state(|s| unsafe { s.context.draw_buffers(buffers) });
+    }
+    fn gen_texture() -> GLuint {
+        with_state(|s| {
+            let texture = unsafe { s.context.create_texture() }.expect("Failed to create texture");
+            s.textures.add(texture)
+        })
+    }
+    fn bind_texture(target:GLenum, texture:GLuint) {
+        with_state(|s| unsafe { s.context.bind_texture(target, s.textures.get(texture)) });
+    }
+    fn delete_texture(texture:GLuint) {
+        with_state(|s| {
+            if let Some(t) = s.textures.remove(texture) {
+                unsafe { s.context.delete_texture(t) };
+            }
+        });
+    }
+    fn tex_image_2d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei), format:(GLenum, GLenum), data:Option<&[u8]>) {
+        with_state(|s| unsafe {
+            s.context.tex_image_2d(target, level, internal_format as i32, size.0, size.1, 0,
+                                   format.0, format.1, data)
+        });
+    }
+    fn tex_image_3d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei, GLsizei), format:(GLenum, GLenum), data:Option<&[u8]>) {
+        with_state(|s| unsafe {
+            s.context.tex_image_3d(target, level, internal_format as i32, size.0, size.1, size.2, 0,
+                                   format.0, format.1, data)
+        });
+    }
+    fn compressed_tex_image_2d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei), data:&[u8]) {
+        with_state(|s| unsafe {
+            s.context.compressed_tex_image_2d(target, level, internal_format as i32, size.0, size.1, 0,
+                                              data.len() as i32, data)
+        });
+    }
+    fn compressed_tex_image_3d(target:GLenum, level:GLint, internal_format:GLenum, size:(GLsizei, GLsizei, GLsizei), data:&[u8]) {
+        with_state(|s| unsafe {
+            s.context.compressed_tex_image_3d(target, level, internal_format as i32, size.0, size.1, size.2, 0,
+                                              data.len() as i32, data)
+        });
+    }
+    fn tex_sub_image_2d(target:GLenum, level:GLint, offset:(GLint, GLint), size:(GLsizei, GLsizei), format:(GLenum, GLenum), data:&[u8]) {
+        with_state(|s| unsafe {
+            s.context.tex_sub_image_2d(target, level, offset.0, offset.1, size.0, size.1,
+                                       format.0, format.1, glow::PixelUnpackData::Slice(data))
+        });
+    }
+    fn tex_sub_image_2d_buffer(target:GLenum, level:GLint, offset:(GLint, GLint), size:(GLsizei, GLsizei), format:(GLenum, GLenum), buffer_offset:usize) {
+        with_state(|s| unsafe {
+            s.context.tex_sub_image_2d(target, level, offset.0, offset.1, size.0, size.1,
+                                       format.0, format.1, glow::PixelUnpackData::BufferOffset(buffer_offset as u32))
+        });
+    }
+    fn tex_sub_image_3d(target:GLenum, level:GLint, offset:(GLint, GLint, GLint), size:(GLsizei, GLsizei, GLsizei), format:(GLenum, GLenum), data:&[u8]) {
+        with_state(|s| unsafe {
+            s.context.tex_sub_image_3d(target, level, offset.0, offset.1, offset.2, size.0, size.1, size.2,
+                                       format.0, format.1, glow::PixelUnpackData::Slice(data))
+        });
+    }
+    fn tex_parameter_i(target:GLenum, pname:GLenum, value:GLint) {
+        with_state(|s| unsafe { s.context.tex_parameter_i32(target, pname, value) });
+    }
+    fn generate_mipmap(target:GLenum) {
+        with_state(|s| unsafe { s.context.generate_mipmap(target) });
+    }
+    fn active_texture(unit:GLuint) {
+        with_state(|s| unsafe { s.context.active_texture(glow::TEXTURE0 + unit) });
+    }
+    fn gen_sampler() -> GLuint {
+        with_state(|s| {
+            let sampler = unsafe { s.context.create_sampler() }.expect("Failed to create sampler");
+            s.samplers.add(sampler)
+        })
+    }
+    fn bind_sampler(unit:GLuint, sampler:GLuint) {
+        with_state(|s| unsafe { s.context.bind_sampler(unit, s.samplers.get(sampler)) });
+    }
+    fn delete_sampler(sampler:GLuint) {
+        with_state(|s| {
+            if let Some(sampler) = s.samplers.remove(sampler) {
+                unsafe { s.context.delete_sampler(sampler) };
+            }
+        });
+    }
+    fn sampler_parameter_i(sampler:GLuint, pname:GLenum, value:GLint) {
+        with_state(|s| {
+            if let Some(sampler) = s.samplers.get(sampler) {
+                unsafe { s.context.sampler_parameter_i32(sampler, pname, value) };
+            }
+        });
+    }
+    fn sampler_parameter_f(sampler:GLuint, pname:GLenum, value:f32) {
+        with_state(|s| {
+            if let Some(sampler) = s.samplers.get(sampler) {
+                unsafe { s.context.sampler_parameter_f32(sampler, pname, value) };
+            }
+        });
+    }
+    fn sampler_parameter_fv(sampler:GLuint, pname:GLenum, values:&[f32]) {
+        with_state(|s| {
+            if let Some(sampler) = s.samplers.get(sampler) {
+                unsafe { s.context.sampler_parameter_f32_slice(sampler, pname, values) };
+            }
+        });
+    }
+    fn read_buffer(src:GLenum) {
+        with_state(|s| unsafe { s.context.read_buffer(src) });
+    }
+    fn pixel_store(pname:GLenum, value:GLint) {
+        with_state(|s| unsafe { s.context.pixel_store_i32(pname, value) });
+    }
+    fn read_pixels(x:GLint, y:GLint, width:GLsizei, height:GLsizei, format:GLenum, pixel_type:GLenum, data:&mut [u8]) {
+        with_state(|s| unsafe {
+            s.context.read_pixels(x, y, width, height, format, pixel_type, glow::PixelPackData::Slice(data))
+        });
+    }
+
+    fn viewport(x:GLint, y:GLint, width:GLsizei, height:GLsizei) {
+        with_state(|s| unsafe { s.context.viewport(x, y, width, height) });
+    }
+    fn clear_color(r:f32, g:f32, b:f32, a:f32) {
+        with_state(|s| unsafe { s.context.clear_color(r, g, b, a) });
+    }
+    fn clear(mask:GLbitfield) {
+        with_state(|s| unsafe { s.context.clear(mask) });
+    }
+    fn enable(cap:GLenum) {
+        with_state(|s| unsafe { s.context.enable(cap) });
+    }
+    fn disable(cap:GLenum) {
+        with_state(|s| unsafe { s.context.disable(cap) });
+    }
+    fn is_enabled(cap:GLenum) -> bool {
+        with_state(|s| unsafe { s.context.is_enabled(cap) })
+    }
+    fn color_mask(r:bool, g:bool, b:bool, a:bool) {
+        with_state(|s| unsafe { s.context.color_mask(r, g, b, a) });
+    }
+    fn depth_mask(enabled:bool) {
+        with_state(|s| unsafe { s.context.depth_mask(enabled) });
+    }
+    fn polygon_offset(factor:f32, units:f32) {
+        with_state(|s| unsafe { s.context.polygon_offset(factor, units) });
+    }
+    fn finish() {
+        with_state(|s| unsafe { s.context.finish() });
+    }
+
+    fn debug_message_callback(callback:DebugCallback) {
+        // glow retains a pointer to the closure it is given beyond the
+        // call, so the closure must capture nothing; the callback is
+        // kept in a thread-local, as debug output is synchronous
+        DEBUG_CALLBACK.with(|c| c.set(Some(callback)));
+        with_state(|s| unsafe {
+            s.context.debug_message_callback(|source, gltype, id, severity, message:&str| {
+                if let Some(callback) = DEBUG_CALLBACK.with(|c| c.get()) {
+                    callback(source, gltype, id, severity, message);
+                }
+            })
+        });
+    }
+    fn debug_message_control(severity:GLenum, enabled:bool) {
+        with_state(|s| unsafe {
+            s.context.debug_message_control(glow::DONT_CARE, glow::DONT_CARE, severity, &[], enabled)
+        });
+    }
+    fn object_label(_identifier:GLenum, _name:GLuint, _label:&str) {
+    }
+    fn push_debug_group(message:&str) {
+        with_state(|s| unsafe { s.context.push_debug_group(glow::DEBUG_SOURCE_APPLICATION, 0, message) });
+    }
+    fn pop_debug_group() {
+        with_state(|s| unsafe { s.context.pop_debug_group() });
+    }
+
+    fn gen_query() -> GLuint {
+        with_state(|s| {
+            let query = unsafe { s.context.create_query() }.expect("Failed to create query");
+            s.queries.add(query)
+        })
+    }
+    fn delete_query(query:GLuint) {
+        with_state(|s| {
+            if let Some(q) = s.queries.remove(query) {
+                unsafe { s.context.delete_query(q) };
+            }
+        });
+    }
+    fn begin_query(target:GLenum, query:GLuint) {
+        with_state(|s| {
+            if let Some(q) = s.query(query) {
+                unsafe { s.context.begin_query(target, q) };
+            }
+        });
+    }
+    fn end_query(target:GLenum) {
+        with_state(|s| unsafe { s.context.end_query(target) });
+    }
+    fn query_counter(_query:GLuint) {
+        panic!("glQueryCounter is not supported by the glow backend");
+    }
+    fn query_result_available(query:GLuint) -> bool {
+        with_state(|s| {
+            s.query(query).is_some_and(|q| unsafe {
+                s.context.get_query_parameter_u32(q, glow::QUERY_RESULT_AVAILABLE) != 0
+            })
+        })
+    }
+    fn query_result(query:GLuint) -> u64 {
+        // glow provides only 32-bit results; GL_TIME_ELAPSED overflows after 4 seconds
+        with_state(|s| {
+            s.query(query).map_or(0, |q| unsafe { s.context.get_query_parameter_u32(q, glow::QUERY_RESULT) as u64 })
+        })
+    }
+    fn begin_conditional_render(_query:GLuint, _mode:GLenum) {
+        panic!("glBeginConditionalRender is not supported by the glow backend");
+    }
+    fn end_conditional_render() {
+        panic!("glEndConditionalRender is not supported by the glow backend");
+    }
+    fn fence_sync() -> usize {
+        with_state(|s| {
+            match unsafe { s.context.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0) } {
+                Ok(fence) => s.fences.add(fence) as usize,
+                Err(_) => 0,
+            }
+        })
+    }
+    fn fence_signaled(fence:usize) -> bool {
+        with_state(|s| {
+            match s.fences.get(fence as GLuint) {
+                Some(f) => unsafe { s.context.client_wait_sync(f, 0, 0) != glow::TIMEOUT_EXPIRED },
+                None => true,
+            }
+        })
+    }
+    fn delete_fence(fence:usize) {
+        with_state(|s| {
+            if let Some(f) = s.fences.remove(fence as GLuint) {
+                unsafe { s.context.delete_sync(f) };
+            }
+        });
+    }
+}
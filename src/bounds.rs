@@ -0,0 +1,161 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    bounds.rs
+@brief   Axis-aligned bounding boxes of vertex positions
+ */
+
+//a Imports
+use model3d::BufferElementType;
+
+use crate::Renderable;
+
+//a BoundingBox
+//tp BoundingBox
+/// An axis-aligned bounding box, in the model space of the vertices
+/// it bounds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    /// Minimum coordinates
+    pub min : [f32; 3],
+    /// Maximum coordinates
+    pub max : [f32; 3],
+}
+
+//ip BoundingBox
+impl BoundingBox {
+    //fp of_point
+    /// Create a [BoundingBox] containing just a point
+    pub fn of_point(p:[f32; 3]) -> Self {
+        Self { min:p, max:p }
+    }
+
+    //fp of_view
+    /// Create the [BoundingBox] of the positions of a view of
+    /// Float32 vertex data, if it has at least three elements per
+    /// vertex, for the vertices used by a view of indices
+    ///
+    /// The buffer data of the positions may be shared with other
+    /// attributes and the indices, so only the vertices up to the
+    /// largest index are included
+    pub fn of_view(view:&model3d::BufferView<Renderable>, indices:&model3d::BufferView<Renderable>) -> Option<Self> {
+        if view.ele_type != BufferElementType::Float32 || view.count < 3 {
+            return None;
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(view.data.as_ptr(), view.data.byte_length as usize) };
+        let index_bytes = unsafe { std::slice::from_raw_parts(indices.data.as_ptr(), indices.data.byte_length as usize) };
+        let vertex_count = max_index(index_bytes, indices.byte_offset as usize,
+                                     indices.ele_type, indices.count as usize)? + 1;
+        let stride = if view.stride == 0 { 4 * view.count as usize } else { view.stride as usize };
+        Self::of_positions(bytes, view.byte_offset as usize, stride, vertex_count)
+    }
+
+    //fp of_positions
+    /// Create the [BoundingBox] of a number of vertices whose
+    /// positions are three native-endian f32 at a byte offset into
+    /// data with a stride; None if there are no vertices, or the data
+    /// does not contain them all
+    pub fn of_positions(bytes:&[u8], byte_offset:usize, stride:usize, vertex_count:usize) -> Option<Self> {
+        let mut bbox : Option<Self> = None;
+        for v in 0..vertex_count {
+            let offset = byte_offset.checked_add(v.checked_mul(stride)?)?;
+            let b = bytes.get(offset..offset.checked_add(12)?)?;
+            let mut p = [0.0_f32; 3];
+            for (i, c) in p.iter_mut().enumerate() {
+                *c = f32::from_ne_bytes([b[4 * i], b[4 * i + 1], b[4 * i + 2], b[4 * i + 3]]);
+            }
+            bbox = Some(match bbox {
+                Some(b) => b.including(p),
+                None => Self::of_point(p),
+            });
+        }
+        bbox
+    }
+
+    //mp including
+    /// Return the [BoundingBox] extended to include a point
+    pub fn including(mut self, p:[f32; 3]) -> Self {
+        for (i, x) in p.iter().enumerate() {
+            self.min[i] = self.min[i].min(*x);
+            self.max[i] = self.max[i].max(*x);
+        }
+        self
+    }
+
+    //mp union
+    /// Return the [BoundingBox] that contains this and another
+    pub fn union(self, other:&Self) -> Self {
+        self.including(other.min).including(other.max)
+    }
+
+    //mp corners
+    /// Get the eight corners of the box; corner `i` has the maximum
+    /// x coordinate if bit 0 of `i` is set, y if bit 1, z if bit 2
+    pub fn corners(&self) -> [[f32; 3]; 8] {
+        let mut corners = [[0.0; 3]; 8];
+        for (i, c) in corners.iter_mut().enumerate() {
+            for (j, x) in c.iter_mut().enumerate() {
+                *x = if (i >> j) & 1 == 0 { self.min[j] } else { self.max[j] };
+            }
+        }
+        corners
+    }
+
+    //mp center
+    /// Get the center of the box
+    pub fn center(&self) -> [f32; 3] {
+        [ (self.min[0] + self.max[0]) * 0.5,
+          (self.min[1] + self.max[1]) * 0.5,
+          (self.min[2] + self.max[2]) * 0.5 ]
+    }
+
+    //zz All done
+}
+
+//ip Display for BoundingBox
+impl std::fmt::Display for BoundingBox {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "[{}, {}, {}]-[{}, {}, {}]",
+               self.min[0], self.min[1], self.min[2],
+               self.max[0], self.max[1], self.max[2])
+    }
+}
+
+//a Functions
+//fp max_index
+/// Find the largest of a number of native-endian unsigned indices
+/// (of an Int8, Int16 or Int32 element type) at a byte offset into
+/// data; None if there are none, the element type is not an integer,
+/// or the data does not contain them all
+pub fn max_index(bytes:&[u8], byte_offset:usize, ele_type:BufferElementType, count:usize) -> Option<usize> {
+    let ele_size = {
+        use BufferElementType::*;
+        match ele_type {
+            Int8 => 1,
+            Int16 => 2,
+            Int32 => 4,
+            _ => { return None; }
+        }
+    };
+    let end = byte_offset.checked_add(count.checked_mul(ele_size)?)?;
+    let indices = bytes.get(byte_offset..end)?;
+    indices.chunks_exact(ele_size)
+        .map(|b| match b {
+            [i] => *i as usize,
+            [a, b] => u16::from_ne_bytes([*a, *b]) as usize,
+            [a, b, c, d] => u32::from_ne_bytes([*a, *b, *c, *d]) as usize,
+            _ => unreachable!(),
+        })
+        .max()
+}
@@ -25,6 +25,7 @@ use model3d::{ViewClient, BufferElementType, VertexAttr};
 
 use crate::GlBuffer;
 use crate::{Renderable, RenderContext};
+use crate::{Backend, GlBackend};
 
 //a VertexBuffer
 //tp VertexBuffer
@@ -71,6 +72,13 @@ impl VertexBuffer {
         self.gl_buffer = view.data.borrow_client().clone();
     }
 
+    //mp set_label
+    /// Label the OpenGL buffer (if debug output is enabled); this is
+    /// shared with other views of the same data
+    pub fn set_label(&self, label:&str) {
+        self.gl_buffer.set_label(label);
+    }
+
     //fp gl_element_type
     fn gl_element_type(&self) -> gl::types::GLuint {
         use model3d::BufferElementType::*;
@@ -86,17 +94,15 @@ impl VertexBuffer {
     //fp bind_to_vao
     /// Bind the buffer as a vertex attribute to the current VAO
     pub fn bind_to_vao(&self, attr_index:gl::types::GLuint) {
-        unsafe {
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.gl_buffer());
-            gl::EnableVertexAttribArray(attr_index);
-            gl::VertexAttribPointer(attr_index,
-                                    self.count as i32, // size
-                                    self.gl_element_type(),
-                                    gl::FALSE, // normalized
-                                    self.stride as i32, // stride
-                                    std::mem::transmute::<usize, *const std::os::raw::c_void>(self.byte_offset as usize) // ptr
-                                    );
-        }
+        Backend::bind_buffer(gl::ARRAY_BUFFER, self.gl_buffer());
+        Backend::enable_vertex_attrib_array(attr_index);
+        Backend::vertex_attrib_pointer(attr_index,
+                                       self.count as i32, // size
+                                       self.gl_element_type(),
+                                       false, // normalized
+                                       self.stride as i32, // stride
+                                       self.byte_offset as usize // offset
+                                       );
     }
 
     //zz All done
@@ -186,13 +192,16 @@ impl IndexBuffer {
         }
     }
 
+    //mp set_label
+    /// Label the OpenGL buffer (if debug output is enabled)
+    pub fn set_label(&self, label:&str) {
+        self.gl_buffer.set_label(label);
+    }
+
     //fp bind_to_vao
     /// Bind the index buffer to the current VAO
     pub fn bind_to_vao(&self) {
-        unsafe {
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER,
-                           self.gl_buffer() );
-        }
+        Backend::bind_buffer(gl::ELEMENT_ARRAY_BUFFER, self.gl_buffer());
     }
     
     //zz All done
@@ -265,9 +274,7 @@ impl ViewClient<Renderable> for BufferView {
         } else {
             match self {
                 BufferView::IndexBuffer(_) => panic!("Vertex buffer is already an index buffer"),
-                BufferView::VertexBuffer(vb) => {
-                    vb.of_view(view, render_context);
-                },
+                BufferView::VertexBuffer(vb) => vb.of_view(view, render_context),
             }
         }
     }
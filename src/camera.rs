@@ -0,0 +1,271 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    camera.rs
+@brief   Camera view and projection, with frustum culling
+ */
+
+//a Documentation
+
+/*!
+
+A [Camera] owns the view and projection matrices for a frame. These
+are uploaded to the [UniformId::ViewMatrix],
+[UniformId::ProjectionMatrix] and [UniformId::ViewProjectionMatrix]
+(the product of the projection and view matrices) uniforms of a
+program with [Camera::upload], once per frame for each program used;
+a program need only have the uniforms it uses.
+
+Instances are drawn with [Camera::draw], which first tests the
+bounding box of the [ShaderInstantiable] (transformed by the
+instance's transformation) against the view frustum, skipping the
+draw if it is entirely outside. [CullStats] record how many instances
+were culled and drawn.
+
+Matrices are column-major, as used by OpenGL.
+
+!*/
+
+//a Imports
+use geo_nd::{matrix, vector};
+
+use crate::{Renderable, ShaderClass, ShaderInstantiable, UniformId, BoundingBox, Mat4};
+use crate::{Backend, GlBackend};
+use crate::utils::mat4_mul;
+
+//a Frustum
+//tp Frustum
+/// The six planes of a view frustum, as (a, b, c, d) with points
+/// (x, y, z) inside having ax+by+cz+d >= 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far planes
+    pub planes : [[f32; 4]; 6],
+}
+
+//ip Frustum
+impl Frustum {
+    //fp of_matrix
+    /// Extract the frustum planes from a projection (or
+    /// model-view-projection) matrix; the planes are in the space
+    /// that the matrix transforms from
+    pub fn of_matrix(m:&Mat4) -> Self {
+        let row = |i:usize| [m[i], m[4 + i], m[8 + i], m[12 + i]];
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let add = |a:[f32; 4], b:[f32; 4]| vector::add(a, &b, 1.);
+        let sub = |a:[f32; 4], b:[f32; 4]| vector::sub(a, &b, 1.);
+        Self { planes : [ add(r3, r0), sub(r3, r0),
+                          add(r3, r1), sub(r3, r1),
+                          add(r3, r2), sub(r3, r2) ] }
+    }
+
+    //mp intersects_box
+    /// Return true if a bounding box is (conservatively) at least
+    /// partly inside the frustum - i.e. it is not entirely outside
+    /// any one plane
+    pub fn intersects_box(&self, bbox:&BoundingBox) -> bool {
+        let corners = bbox.corners();
+        for p in &self.planes {
+            if corners.iter().all(|c| p[0] * c[0] + p[1] * c[1] + p[2] * c[2] + p[3] < 0.) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+//a CullStats
+//tp CullStats
+/// Statistics of frustum culling
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CullStats {
+    /// Number of instances culled
+    pub culled : usize,
+    /// Number of instances drawn
+    pub drawn : usize,
+}
+
+//ip Display for CullStats
+impl std::fmt::Display for CullStats {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{} drawn, {} culled", self.drawn, self.culled)
+    }
+}
+
+//a Camera
+//tp Camera
+/// A camera with view and projection matrices, for uploading to
+/// programs and for frustum culling
+#[derive(Debug, Clone)]
+pub struct Camera {
+    /// View matrix (world to camera space)
+    view : Mat4,
+    /// Projection matrix (camera to clip space)
+    projection : Mat4,
+    /// Projection * view
+    view_projection : Mat4,
+    /// Statistics since the last [Camera::begin_frame]
+    stats : CullStats,
+}
+
+//ip Default for Camera
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(matrix::identity4(), matrix::identity4())
+    }
+}
+
+//ip Camera
+impl Camera {
+    //fp new
+    /// Create a camera from view and projection matrices
+    pub fn new(view:Mat4, projection:Mat4) -> Self {
+        let view_projection = mat4_mul(&projection, &view);
+        Self { view, projection, view_projection, stats:CullStats::default() }
+    }
+
+    //fp perspective
+    /// Create an OpenGL perspective projection matrix, with a
+    /// vertical field of view in radians
+    pub fn perspective(fov_y:f32, aspect:f32, near:f32, far:f32) -> Mat4 {
+        matrix::perspective4(fov_y, aspect, near, far)
+    }
+
+    //fp orthographic
+    /// Create an OpenGL orthographic projection matrix
+    pub fn orthographic(left:f32, right:f32, bottom:f32, top:f32, near:f32, far:f32) -> Mat4 {
+        let mut m = [0.; 16];
+        m[0] = 2. / (right - left);
+        m[5] = 2. / (top - bottom);
+        m[10] = -2. / (far - near);
+        m[12] = -(right + left) / (right - left);
+        m[13] = -(top + bottom) / (top - bottom);
+        m[14] = -(far + near) / (far - near);
+        m[15] = 1.;
+        m
+    }
+
+    //fp look_at
+    /// Create a view matrix for an eye position looking at a target,
+    /// with an up direction
+    pub fn look_at(eye:[f32; 3], target:[f32; 3], up:[f32; 3]) -> Mat4 {
+        let f = vector::normalize(vector::sub(target, &eye, 1.));
+        let s = vector::normalize(vector::cross_product3(&f, &up));
+        let u = vector::cross_product3(&s, &f);
+        [s[0], u[0], -f[0], 0.,
+         s[1], u[1], -f[1], 0.,
+         s[2], u[2], -f[2], 0.,
+         -vector::dot(&s, &eye), -vector::dot(&u, &eye), vector::dot(&f, &eye), 1.]
+    }
+
+    //fp near_far
+    /// Get the near and far plane distances of a perspective or
+    /// orthographic projection matrix
+    pub fn near_far(projection:&Mat4) -> (f32, f32) {
+        let (m10, m14) = (projection[10], projection[14]);
+        if projection[11] == 0. {
+            ((m14 + 1.) / m10, (m14 - 1.) / m10)
+        } else {
+            (m14 / (m10 - 1.), m14 / (m10 + 1.))
+        }
+    }
+
+    //mp set_view
+    /// Set the view matrix
+    pub fn set_view(&mut self, view:Mat4) {
+        self.view = view;
+        self.view_projection = mat4_mul(&self.projection, &self.view);
+    }
+
+    //mp set_projection
+    /// Set the projection matrix
+    pub fn set_projection(&mut self, projection:Mat4) {
+        self.projection = projection;
+        self.view_projection = mat4_mul(&self.projection, &self.view);
+    }
+
+    //ap view
+    /// Get the view matrix
+    pub fn view(&self) -> &Mat4 {
+        &self.view
+    }
+
+    //ap projection
+    /// Get the projection matrix
+    pub fn projection(&self) -> &Mat4 {
+        &self.projection
+    }
+
+    //ap view_projection
+    /// Get the product of the projection and view matrices
+    pub fn view_projection(&self) -> &Mat4 {
+        &self.view_projection
+    }
+
+    //ap stats
+    /// Get the culling statistics since the last [Camera::begin_frame]
+    pub fn stats(&self) -> CullStats {
+        self.stats
+    }
+
+    //mp begin_frame
+    /// Begin a frame, resetting the culling statistics
+    pub fn begin_frame(&mut self) {
+        self.stats = CullStats::default();
+    }
+
+    //mp upload
+    /// Set the view, projection and view-projection matrix uniforms
+    /// of a program (those that it has), which must be in use
+    pub fn upload(&self, shader_class:&dyn ShaderClass) {
+        if let Some(u) = shader_class.uniform(UniformId::ViewMatrix) {
+            Backend::uniform_matrix4(u, &self.view);
+        }
+        if let Some(u) = shader_class.uniform(UniformId::ProjectionMatrix) {
+            Backend::uniform_matrix4(u, &self.projection);
+        }
+        if let Some(u) = shader_class.uniform(UniformId::ViewProjectionMatrix) {
+            Backend::uniform_matrix4(u, &self.view_projection);
+        }
+    }
+
+    //mp is_visible
+    /// Return true if a model-space bounding box, transformed by a
+    /// model matrix, is at least partly inside the view frustum
+    pub fn is_visible(&self, bbox:&BoundingBox, model:&Mat4) -> bool {
+        let mvp = mat4_mul(&self.view_projection, model);
+        Frustum::of_matrix(&mvp).intersects_box(bbox)
+    }
+
+    //mp draw
+    /// Draw an instance of a [ShaderInstantiable] unless its bounding
+    /// box is outside the view frustum, returning true if it was
+    /// drawn
+    ///
+    /// Instantiables without a bounding box are always drawn
+    pub fn draw(&mut self, shader_instantiable:&ShaderInstantiable, instance:&model3d::Instance<Renderable>) -> bool {
+        if let Some(bbox) = shader_instantiable.bounds() {
+            if !self.is_visible(bbox, &instance.transformation.mat4()) {
+                self.stats.culled += 1;
+                return false;
+            }
+        }
+        shader_instantiable.gl_draw(instance);
+        self.stats.drawn += 1;
+        true
+    }
+
+    //zz All done
+}
+
@@ -0,0 +1,402 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    capabilities.rs
+@brief   OpenGL version, extension and limit detection
+ */
+
+//a Imports
+use std::collections::HashSet;
+
+use crate::{Backend, GlBackend};
+
+//a Constants
+//ci MAX_TEXTURE_MAX_ANISOTROPY
+/// GL_MAX_TEXTURE_MAX_ANISOTROPY, which is not in the `gl` crate
+const MAX_TEXTURE_MAX_ANISOTROPY : gl::types::GLenum = 0x84FF;
+
+//a GlProfile
+//tp GlProfile
+/// The profile of the OpenGL context
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlProfile {
+    /// Desktop OpenGL core profile
+    Core,
+    /// Desktop OpenGL compatibility profile (or pre-3.2 OpenGL)
+    Compatibility,
+    /// OpenGL ES (or WebGL)
+    Es,
+}
+
+//a GlFeature
+//tp GlFeature
+/// Optional OpenGL features that the crate can use if the context
+/// supports them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GlFeature {
+    /// Uniform buffer objects - OpenGL 3.1, OpenGL ES 3.0
+    UniformBuffers,
+    /// Immutable buffer storage - OpenGL 4.4 or GL_ARB_buffer_storage,
+    /// GL_EXT_buffer_storage for OpenGL ES
+    BufferStorage,
+    /// Multi-draw indirect - OpenGL 4.3 or GL_ARB_multi_draw_indirect,
+    /// GL_EXT_multi_draw_indirect for OpenGL ES
+    MultiDrawIndirect,
+    /// Direct state access - OpenGL 4.5 or GL_ARB_direct_state_access
+    DirectStateAccess,
+    /// Debug output (KHR_debug) - OpenGL 4.3 or GL_KHR_debug, OpenGL
+    /// ES 3.2; the extension is not used for OpenGL ES, whose entry
+    /// points for it have a KHR suffix
+    DebugOutput,
+    /// GL_TIME_ELAPSED queries - OpenGL 3.3 or GL_ARB_timer_query,
+    /// GL_EXT_disjoint_timer_query for OpenGL ES and WebGL
+    TimerQueries,
+    /// GL_TIMESTAMP queries (glQueryCounter) - as [GlFeature::TimerQueries],
+    /// but not with the `glow` backend, which does not provide glQueryCounter
+    Timestamps,
+    /// GL_PRIMITIVES_GENERATED queries - OpenGL 3.0, OpenGL ES 3.2
+    PrimitiveQueries,
+    /// GL_SAMPLES_PASSED queries - desktop OpenGL only
+    SampleQueries,
+    /// GL_ANY_SAMPLES_PASSED queries - OpenGL 3.3 or
+    /// GL_ARB_occlusion_query2, OpenGL ES 3.0
+    OcclusionQueries,
+    /// Conditional rendering - OpenGL 3.0, but not with the `glow`
+    /// backend, which does not provide glBeginConditionalRender
+    ConditionalRender,
+    /// Seamless cube map filtering (GL_TEXTURE_CUBE_MAP_SEAMLESS) -
+    /// OpenGL 3.2 or GL_ARB_seamless_cube_map; OpenGL ES 3.0 cube
+    /// maps are always seamless, and this is not required
+    SeamlessCubeMap,
+    /// Anisotropic texture filtering - OpenGL 4.6 or
+    /// GL_ARB_texture_filter_anisotropic, GL_EXT_texture_filter_anisotropic
+    /// (or EXT_texture_filter_anisotropic for WebGL)
+    AnisotropicFiltering,
+    /// S3TC (BC1 to BC3) compressed textures -
+    /// GL_EXT_texture_compression_s3tc (or WEBGL_compressed_texture_s3tc)
+    TextureCompressionS3tc,
+    /// sRGB S3TC compressed textures - GL_EXT_texture_sRGB with S3TC,
+    /// GL_EXT_texture_compression_s3tc_srgb (or
+    /// WEBGL_compressed_texture_s3tc_srgb)
+    TextureCompressionS3tcSrgb,
+    /// RGTC (BC4 and BC5) compressed textures - OpenGL 3.0 or
+    /// GL_EXT_texture_compression_rgtc
+    TextureCompressionRgtc,
+    /// BPTC (BC6H and BC7) compressed textures - OpenGL 4.2 or
+    /// GL_ARB_texture_compression_bptc, GL_EXT_texture_compression_bptc
+    TextureCompressionBptc,
+    /// ETC2 and EAC compressed textures - OpenGL 4.3, OpenGL ES 3.0,
+    /// GL_ARB_ES3_compatibility (or WEBGL_compressed_texture_etc)
+    TextureCompressionEtc2,
+    /// ASTC (LDR) compressed textures - GL_KHR_texture_compression_astc_ldr
+    /// (or WEBGL_compressed_texture_astc)
+    TextureCompressionAstc,
+    /// Asynchronous texture uploads through mapped pixel unpack
+    /// buffers with fences - OpenGL 3.2, OpenGL ES 3.0, but not
+    /// WebGL, which cannot map buffers
+    AsyncTextureUploads,
+    /// Rendering to half-float colour textures - OpenGL 3.0, OpenGL
+    /// ES 3.2, GL_EXT_color_buffer_float or
+    /// GL_EXT_color_buffer_half_float (or EXT_color_buffer_float for
+    /// WebGL)
+    FloatRenderTargets,
+    /// Border colours for GL_CLAMP_TO_BORDER wrapping - desktop OpenGL,
+    /// OpenGL ES 3.2, GL_EXT_texture_border_clamp or
+    /// GL_OES_texture_border_clamp; not WebGL
+    TextureBorderClamp,
+}
+
+//ip Display for GlFeature
+impl std::fmt::Display for GlFeature {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let s = match self {
+            Self::UniformBuffers => "uniform buffers",
+            Self::BufferStorage => "buffer storage",
+            Self::MultiDrawIndirect => "multi-draw indirect",
+            Self::DirectStateAccess => "direct state access",
+            Self::DebugOutput => "debug output",
+            Self::TimerQueries => "timer queries",
+            Self::Timestamps => "timestamp queries",
+            Self::PrimitiveQueries => "primitives generated queries",
+            Self::SampleQueries => "samples passed queries",
+            Self::OcclusionQueries => "occlusion queries",
+            Self::ConditionalRender => "conditional rendering",
+            Self::SeamlessCubeMap => "seamless cube maps",
+            Self::AnisotropicFiltering => "anisotropic filtering",
+            Self::TextureCompressionS3tc => "S3TC texture compression",
+            Self::TextureCompressionS3tcSrgb => "sRGB S3TC texture compression",
+            Self::TextureCompressionRgtc => "RGTC texture compression",
+            Self::TextureCompressionBptc => "BPTC texture compression",
+            Self::TextureCompressionEtc2 => "ETC2 texture compression",
+            Self::TextureCompressionAstc => "ASTC texture compression",
+            Self::AsyncTextureUploads => "asynchronous texture uploads",
+            Self::FloatRenderTargets => "float render targets",
+            Self::TextureBorderClamp => "texture border clamping",
+        };
+        f.write_str(s)
+    }
+}
+
+//a GlLimits
+//tp GlLimits
+/// Implementation limits of the OpenGL context
+#[derive(Debug, Clone, Default)]
+pub struct GlLimits {
+    /// GL_MAX_VERTEX_ATTRIBS
+    pub max_vertex_attribs : usize,
+    /// GL_MAX_COMBINED_TEXTURE_IMAGE_UNITS
+    pub max_texture_units : usize,
+    /// GL_MAX_UNIFORM_BLOCK_SIZE in bytes
+    pub max_uniform_block_size : usize,
+    /// GL_MAX_UNIFORM_BUFFER_BINDINGS
+    pub max_uniform_buffer_bindings : usize,
+    /// GL_MAX_SAMPLES
+    pub max_samples : usize,
+    /// GL_MAX_TEXTURE_MAX_ANISOTROPY (0 if anisotropic filtering is
+    /// not supported)
+    pub max_anisotropy : f32,
+}
+
+//a Capabilities
+//tp Capabilities
+/// The capabilities of an OpenGL context, queried once when the
+/// [crate::RenderContext] is created
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// OpenGL (or OpenGL ES) version as (major, minor)
+    pub version : (usize, usize),
+    /// GLSL (or GLSL ES) version as (major, minor*10) - e.g. (3, 30)
+    pub glsl_version : (usize, usize),
+    /// Profile of the context
+    pub profile : GlProfile,
+    /// GL_VENDOR
+    pub vendor : String,
+    /// GL_RENDERER
+    pub renderer : String,
+    /// Extensions supported
+    pub extensions : HashSet<String>,
+    /// Implementation limits
+    pub limits : GlLimits,
+}
+
+//ip Capabilities
+impl Capabilities {
+    //fp query
+    /// Query the capabilities of the current OpenGL context
+    pub fn query() -> Self {
+        let is_gles = Backend::is_gles();
+        let version = parse_version(&Backend::get_string(gl::VERSION));
+        let glsl_version = parse_version(&Backend::get_string(gl::SHADING_LANGUAGE_VERSION));
+        let vendor = Backend::get_string(gl::VENDOR);
+        let renderer = Backend::get_string(gl::RENDERER);
+        let profile = {
+            if is_gles {
+                GlProfile::Es
+            } else if version < (3, 2) {
+                GlProfile::Compatibility
+            } else if (Backend::get_integer(gl::CONTEXT_PROFILE_MASK) as u32 & gl::CONTEXT_CORE_PROFILE_BIT) != 0 {
+                GlProfile::Core
+            } else {
+                GlProfile::Compatibility
+            }
+        };
+        let extensions = Backend::extensions().into_iter().collect();
+        let limits = GlLimits {
+            max_vertex_attribs : Backend::get_integer(gl::MAX_VERTEX_ATTRIBS) as usize,
+            max_texture_units : Backend::get_integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS) as usize,
+            max_uniform_block_size : Backend::get_integer(gl::MAX_UNIFORM_BLOCK_SIZE) as usize,
+            max_uniform_buffer_bindings : Backend::get_integer(gl::MAX_UNIFORM_BUFFER_BINDINGS) as usize,
+            max_samples : Backend::get_integer(gl::MAX_SAMPLES) as usize,
+            max_anisotropy : 0.,
+        };
+        let mut capabilities = Self { version, glsl_version, profile, vendor, renderer, extensions, limits };
+        if capabilities.supports(GlFeature::AnisotropicFiltering) {
+            capabilities.limits.max_anisotropy = Backend::get_float(MAX_TEXTURE_MAX_ANISOTROPY);
+        }
+        capabilities
+    }
+
+    //mp is_gles
+    /// Return true if the context is OpenGL ES (or WebGL)
+    pub fn is_gles(&self) -> bool {
+        self.profile == GlProfile::Es
+    }
+
+    //mp has_extension
+    /// Return true if the context supports an extension
+    pub fn has_extension(&self, extension:&str) -> bool {
+        self.extensions.contains(extension)
+    }
+
+    //mp at_least
+    /// Return true if the context version is at least the desktop
+    /// version (for desktop OpenGL) or the ES version (for OpenGL
+    /// ES); None indicates no version of that kind suffices
+    fn at_least(&self, desktop:Option<(usize, usize)>, es:Option<(usize, usize)>) -> bool {
+        let required = if self.is_gles() { es } else { desktop };
+        matches!(required, Some(r) if self.version >= r)
+    }
+
+    //mp supports
+    /// Return true if the context supports a [GlFeature]
+    pub fn supports(&self, feature:GlFeature) -> bool {
+        match feature {
+            GlFeature::UniformBuffers => {
+                self.at_least(Some((3, 1)), Some((3, 0))) ||
+                    self.has_extension("GL_ARB_uniform_buffer_object")
+            }
+            GlFeature::BufferStorage => {
+                self.at_least(Some((4, 4)), None) ||
+                    self.has_extension("GL_ARB_buffer_storage") ||
+                    self.has_extension("GL_EXT_buffer_storage")
+            }
+            GlFeature::MultiDrawIndirect => {
+                self.at_least(Some((4, 3)), None) ||
+                    self.has_extension("GL_ARB_multi_draw_indirect") ||
+                    self.has_extension("GL_EXT_multi_draw_indirect")
+            }
+            GlFeature::DirectStateAccess => {
+                self.at_least(Some((4, 5)), None) ||
+                    self.has_extension("GL_ARB_direct_state_access")
+            }
+            GlFeature::DebugOutput => {
+                self.at_least(Some((4, 3)), Some((3, 2))) ||
+                    (!self.is_gles() && self.has_extension("GL_KHR_debug"))
+            }
+            GlFeature::TimerQueries => {
+                self.at_least(Some((3, 3)), None) ||
+                    self.has_extension("GL_ARB_timer_query") ||
+                    self.has_extension("GL_EXT_disjoint_timer_query") ||
+                    self.has_extension("EXT_disjoint_timer_query_webgl2")
+            }
+            GlFeature::Timestamps => {
+                !cfg!(feature="glow") && self.supports(GlFeature::TimerQueries)
+            }
+            GlFeature::PrimitiveQueries => {
+                self.at_least(Some((3, 0)), Some((3, 2)))
+            }
+            GlFeature::SampleQueries => {
+                self.at_least(Some((1, 5)), None)
+            }
+            GlFeature::OcclusionQueries => {
+                self.at_least(Some((3, 3)), Some((3, 0))) ||
+                    self.has_extension("GL_ARB_occlusion_query2")
+            }
+            GlFeature::ConditionalRender => {
+                !cfg!(feature="glow") && self.at_least(Some((3, 0)), None)
+            }
+            GlFeature::SeamlessCubeMap => {
+                self.at_least(Some((3, 2)), None) ||
+                    (!self.is_gles() && self.has_extension("GL_ARB_seamless_cube_map"))
+            }
+            GlFeature::AnisotropicFiltering => {
+                self.at_least(Some((4, 6)), None) ||
+                    self.has_extension("GL_ARB_texture_filter_anisotropic") ||
+                    self.has_extension("GL_EXT_texture_filter_anisotropic") ||
+                    self.has_extension("EXT_texture_filter_anisotropic")
+            }
+            GlFeature::TextureCompressionS3tc => {
+                self.has_extension("GL_EXT_texture_compression_s3tc") ||
+                    self.has_extension("WEBGL_compressed_texture_s3tc")
+            }
+            GlFeature::TextureCompressionS3tcSrgb => {
+                (self.supports(GlFeature::TextureCompressionS3tc) &&
+                 self.has_extension("GL_EXT_texture_sRGB")) ||
+                    self.has_extension("GL_EXT_texture_compression_s3tc_srgb") ||
+                    self.has_extension("WEBGL_compressed_texture_s3tc_srgb")
+            }
+            GlFeature::TextureCompressionRgtc => {
+                self.at_least(Some((3, 0)), None) ||
+                    self.has_extension("GL_EXT_texture_compression_rgtc") ||
+                    self.has_extension("EXT_texture_compression_rgtc")
+            }
+            GlFeature::TextureCompressionBptc => {
+                self.at_least(Some((4, 2)), None) ||
+                    self.has_extension("GL_ARB_texture_compression_bptc") ||
+                    self.has_extension("GL_EXT_texture_compression_bptc") ||
+                    self.has_extension("EXT_texture_compression_bptc")
+            }
+            GlFeature::TextureCompressionEtc2 => {
+                self.at_least(Some((4, 3)), Some((3, 0))) ||
+                    self.has_extension("GL_ARB_ES3_compatibility") ||
+                    self.has_extension("WEBGL_compressed_texture_etc")
+            }
+            GlFeature::TextureCompressionAstc => {
+                self.has_extension("GL_KHR_texture_compression_astc_ldr") ||
+                    self.has_extension("WEBGL_compressed_texture_astc")
+            }
+            GlFeature::AsyncTextureUploads => {
+                !cfg!(target_arch="wasm32") && self.at_least(Some((3, 2)), Some((3, 0)))
+            }
+            GlFeature::FloatRenderTargets => {
+                self.at_least(Some((3, 0)), Some((3, 2))) ||
+                    self.has_extension("GL_EXT_color_buffer_float") ||
+                    self.has_extension("GL_EXT_color_buffer_half_float") ||
+                    self.has_extension("EXT_color_buffer_float")
+            }
+            GlFeature::TextureBorderClamp => {
+                self.at_least(Some((1, 3)), Some((3, 2))) ||
+                    self.has_extension("GL_EXT_texture_border_clamp") ||
+                    self.has_extension("GL_OES_texture_border_clamp")
+            }
+        }
+    }
+
+    //mp require
+    /// Return an error if the context does not support a [GlFeature]
+    pub fn require(&self, feature:GlFeature) -> Result<(), String> {
+        if self.supports(feature) {
+            Ok(())
+        } else {
+            Err(format!("OpenGL feature '{}' is unsupported by {} ({})", feature, self.renderer, self))
+        }
+    }
+
+    //zz All done
+}
+
+//ip Display for Capabilities
+impl std::fmt::Display for Capabilities {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let api = if self.is_gles() { "OpenGL ES" } else { "OpenGL" };
+        write!(f, "{} {}.{} {:?}, GLSL {}.{:02}",
+               api, self.version.0, self.version.1, self.profile,
+               self.glsl_version.0, self.glsl_version.1)
+    }
+}
+
+//a Functions
+//fi parse_version
+/// Parse the first 'major.minor' in a GL_VERSION or
+/// GL_SHADING_LANGUAGE_VERSION string
+///
+/// These are of the form "4.6.0 NVIDIA 470.82" or "OpenGL ES 3.0 Mesa
+/// 21.0" or "OpenGL ES GLSL ES 3.00"; WebGL reports "WebGL 2.0 (...)",
+/// which is OpenGL ES 3.0
+fn parse_version(s:&str) -> (usize, usize) {
+    let mut webgl = false;
+    for word in s.split_whitespace() {
+        let mut parts = word.split('.');
+        let major : Option<usize> = parts.next().and_then(|m| m.parse().ok());
+        let minor = parts.next().and_then(|m| m.parse().ok());
+        if let (Some(major), Some(minor)) = (major, minor) {
+            if webgl {
+                return (major + 1, minor);
+            }
+            return (major, minor);
+        }
+        webgl = word == "WebGL";
+    }
+    (0, 0)
+}
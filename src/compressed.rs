@@ -0,0 +1,671 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    compressed.rs
+@brief   GPU-compressed textures from KTX2 and DDS containers
+ */
+
+//a Documentation
+
+/*!
+
+A [CompressedImage] holds the mip levels of a GPU-compressed 2D
+texture, cube map or 2D array in a [CompressedFormat] - BC1 to BC7,
+ETC2/EAC or ASTC - parsed from a KTX2 or DDS container (supercompressed
+KTX2 files are not supported).
+
+A [crate::Texture] is created from it with
+[crate::Texture::of_compressed] (or directly from the container bytes
+with [crate::Texture::of_ktx2] and [crate::Texture::of_dds]). If the
+context does not support the format then the texture may instead be
+decompressed on the CPU to RGBA8 - this is supported for BC1 to BC5
+and the unsigned ETC2/EAC formats
+([CompressedFormat::can_decompress]). There is no CPU decompression
+of BC6H, BC7, ASTC or the signed formats, so on a context without
+support for these creating a texture returns an 'unsupported' error;
+assets in these formats should be provided in another format too.
+
+The images are uploaded as they are stored in the container - DDS
+files, and most KTX2 files, store the top row first.
+
+!*/
+
+//a Imports
+use gl::types::*;
+
+use crate::{Backend, GlBackend};
+use crate::{Capabilities, GlFeature};
+use crate::{Texture, TextureKind, TextureFormat, CubeFace};
+use crate::decompress;
+
+//a Constants
+/// S3TC internal formats, which are not in the `gl` crate
+const COMPRESSED_RGB_S3TC_DXT1 : GLenum = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1 : GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3 : GLenum = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5 : GLenum = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1 : GLenum = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1 : GLenum = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3 : GLenum = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5 : GLenum = 0x8C4F;
+
+/// ASTC internal formats, which are not in the `gl` crate; the
+/// sRGB formats are at the same offsets from
+/// COMPRESSED_SRGB8_ALPHA8_ASTC_4x4
+const COMPRESSED_RGBA_ASTC_4X4 : GLenum = 0x93B0;
+const COMPRESSED_SRGB8_ALPHA8_ASTC_4X4 : GLenum = 0x93D0;
+
+/// ASTC block sizes, in the order of their OpenGL enumerations and
+/// Vulkan formats
+const ASTC_BLOCKS : [(u8, u8); 14] = [(4, 4), (5, 4), (5, 5), (6, 5), (6, 6), (8, 5), (8, 6),
+                                      (8, 8), (10, 5), (10, 6), (10, 8), (10, 10), (12, 10), (12, 12)];
+
+/// The identifier at the start of a KTX2 file
+const KTX2_IDENTIFIER : [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+
+//a CompressedFormat
+//tp CompressedFormat
+/// A GPU block-compressed texture format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressedFormat {
+    /// BC1 (DXT1) - RGB, or RGB with 1-bit alpha
+    Bc1 {
+        /// Whether the block may have transparent texels
+        alpha:bool,
+        /// Whether the colour is sRGB encoded
+        srgb:bool,
+    },
+    /// BC2 (DXT3) - RGB with explicit 4-bit alpha
+    Bc2 {
+        /// Whether the colour is sRGB encoded
+        srgb:bool,
+    },
+    /// BC3 (DXT5) - RGB with interpolated alpha
+    Bc3 {
+        /// Whether the colour is sRGB encoded
+        srgb:bool,
+    },
+    /// BC4 (RGTC1) - red
+    Bc4 {
+        /// Whether the values are signed normalized
+        signed:bool,
+    },
+    /// BC5 (RGTC2) - red and green
+    Bc5 {
+        /// Whether the values are signed normalized
+        signed:bool,
+    },
+    /// BC6H (BPTC) - HDR RGB
+    Bc6h {
+        /// Whether the values are signed normalized
+        signed:bool,
+    },
+    /// BC7 (BPTC) - RGBA
+    Bc7 {
+        /// Whether the colour is sRGB encoded
+        srgb:bool,
+    },
+    /// ETC2 - RGB
+    Etc2Rgb {
+        /// Whether the colour is sRGB encoded
+        srgb:bool,
+    },
+    /// ETC2 - RGB with punch-through (1-bit) alpha
+    Etc2RgbA1 {
+        /// Whether the colour is sRGB encoded
+        srgb:bool,
+    },
+    /// ETC2 with EAC alpha - RGBA
+    Etc2Rgba {
+        /// Whether the colour is sRGB encoded
+        srgb:bool,
+    },
+    /// EAC - 11-bit red
+    EacR11 {
+        /// Whether the values are signed normalized
+        signed:bool,
+    },
+    /// EAC - 11-bit red and green
+    EacRg11 {
+        /// Whether the values are signed normalized
+        signed:bool,
+    },
+    /// ASTC (LDR) with a block size in texels
+    Astc {
+        /// The width and height of a block in texels
+        block:(u8, u8),
+        /// Whether the colour is sRGB encoded
+        srgb:bool,
+    },
+}
+
+//ip CompressedFormat
+impl CompressedFormat {
+    //ap internal_format
+    /// Get the OpenGL internal format
+    pub fn internal_format(&self) -> GLenum {
+        match *self {
+            Self::Bc1 { alpha:false, srgb:false } => COMPRESSED_RGB_S3TC_DXT1,
+            Self::Bc1 { alpha:true, srgb:false } => COMPRESSED_RGBA_S3TC_DXT1,
+            Self::Bc1 { alpha:false, srgb:true } => COMPRESSED_SRGB_S3TC_DXT1,
+            Self::Bc1 { alpha:true, srgb:true } => COMPRESSED_SRGB_ALPHA_S3TC_DXT1,
+            Self::Bc2 { srgb } => if srgb { COMPRESSED_SRGB_ALPHA_S3TC_DXT3 } else { COMPRESSED_RGBA_S3TC_DXT3 },
+            Self::Bc3 { srgb } => if srgb { COMPRESSED_SRGB_ALPHA_S3TC_DXT5 } else { COMPRESSED_RGBA_S3TC_DXT5 },
+            Self::Bc4 { signed } => if signed { gl::COMPRESSED_SIGNED_RED_RGTC1 } else { gl::COMPRESSED_RED_RGTC1 },
+            Self::Bc5 { signed } => if signed { gl::COMPRESSED_SIGNED_RG_RGTC2 } else { gl::COMPRESSED_RG_RGTC2 },
+            Self::Bc6h { signed } => if signed { gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT } else { gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT },
+            Self::Bc7 { srgb } => if srgb { gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM } else { gl::COMPRESSED_RGBA_BPTC_UNORM },
+            Self::Etc2Rgb { srgb } => if srgb { gl::COMPRESSED_SRGB8_ETC2 } else { gl::COMPRESSED_RGB8_ETC2 },
+            Self::Etc2RgbA1 { srgb } => {
+                if srgb { gl::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2 } else { gl::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2 }
+            }
+            Self::Etc2Rgba { srgb } => if srgb { gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC } else { gl::COMPRESSED_RGBA8_ETC2_EAC },
+            Self::EacR11 { signed } => if signed { gl::COMPRESSED_SIGNED_R11_EAC } else { gl::COMPRESSED_R11_EAC },
+            Self::EacRg11 { signed } => if signed { gl::COMPRESSED_SIGNED_RG11_EAC } else { gl::COMPRESSED_RG11_EAC },
+            Self::Astc { block, srgb } => {
+                let index = ASTC_BLOCKS.iter().position(|b| *b == block).unwrap_or(0) as GLenum;
+                if srgb { COMPRESSED_SRGB8_ALPHA8_ASTC_4X4 + index } else { COMPRESSED_RGBA_ASTC_4X4 + index }
+            }
+        }
+    }
+
+    //ap block_size
+    /// Get the width and height of a block in texels
+    pub fn block_size(&self) -> (usize, usize) {
+        match self {
+            Self::Astc { block, .. } => (block.0 as usize, block.1 as usize),
+            _ => (4, 4),
+        }
+    }
+
+    //ap block_bytes
+    /// Get the number of bytes in a block
+    pub fn block_bytes(&self) -> usize {
+        match self {
+            Self::Bc1 { .. } | Self::Bc4 { .. } | Self::Etc2Rgb { .. } |
+            Self::Etc2RgbA1 { .. } | Self::EacR11 { .. } => 8,
+            _ => 16,
+        }
+    }
+
+    //ap image_bytes
+    /// Get the number of bytes of an image of a size, or an error if
+    /// it is too large to be represented
+    pub fn image_bytes(&self, width:usize, height:usize) -> Result<usize, String> {
+        let (bw, bh) = self.block_size();
+        width.div_ceil(bw).checked_mul(height.div_ceil(bh))
+            .and_then(|blocks| blocks.checked_mul(self.block_bytes()))
+            .ok_or_else(|| format!("{}x{} {} image is too large", width, height, self))
+    }
+
+    //ap is_srgb
+    /// Return true if the format has sRGB colour
+    pub fn is_srgb(&self) -> bool {
+        match *self {
+            Self::Bc1 { srgb, .. } | Self::Bc2 { srgb } | Self::Bc3 { srgb } |
+            Self::Bc7 { srgb } | Self::Etc2Rgb { srgb } | Self::Etc2RgbA1 { srgb } |
+            Self::Etc2Rgba { srgb } | Self::Astc { srgb, .. } => srgb,
+            _ => false,
+        }
+    }
+
+    //ap can_decompress
+    /// Return true if the format can be decompressed on the CPU (BC1
+    /// to BC5 and the unsigned ETC2 and EAC formats)
+    pub fn can_decompress(&self) -> bool {
+        decompress::can_decompress(*self)
+    }
+
+    //ap feature
+    /// Get the [GlFeature] required to use the format
+    pub fn feature(&self) -> GlFeature {
+        match self {
+            Self::Bc1 { srgb:true, .. } | Self::Bc2 { srgb:true } | Self::Bc3 { srgb:true } => {
+                GlFeature::TextureCompressionS3tcSrgb
+            }
+            Self::Bc1 { .. } | Self::Bc2 { .. } | Self::Bc3 { .. } => GlFeature::TextureCompressionS3tc,
+            Self::Bc4 { .. } | Self::Bc5 { .. } => GlFeature::TextureCompressionRgtc,
+            Self::Bc6h { .. } | Self::Bc7 { .. } => GlFeature::TextureCompressionBptc,
+            Self::Etc2Rgb { .. } | Self::Etc2RgbA1 { .. } | Self::Etc2Rgba { .. } |
+            Self::EacR11 { .. } | Self::EacRg11 { .. } => GlFeature::TextureCompressionEtc2,
+            Self::Astc { .. } => GlFeature::TextureCompressionAstc,
+        }
+    }
+
+    //fp of_vk_format
+    /// Get the format for a Vulkan format (as used by KTX2), if it is
+    /// a supported compressed format
+    pub fn of_vk_format(vk_format:u32) -> Option<Self> {
+        let srgb = (vk_format & 1) == 0;
+        let signed = srgb;
+        match vk_format {
+            131..=134 => Some(Self::Bc1 { alpha:vk_format >= 133, srgb }),
+            135 | 136 => Some(Self::Bc2 { srgb }),
+            137 | 138 => Some(Self::Bc3 { srgb }),
+            139 | 140 => Some(Self::Bc4 { signed }),
+            141 | 142 => Some(Self::Bc5 { signed }),
+            143 | 144 => Some(Self::Bc6h { signed }),
+            145 | 146 => Some(Self::Bc7 { srgb }),
+            147 | 148 => Some(Self::Etc2Rgb { srgb }),
+            149 | 150 => Some(Self::Etc2RgbA1 { srgb }),
+            151 | 152 => Some(Self::Etc2Rgba { srgb }),
+            153 | 154 => Some(Self::EacR11 { signed }),
+            155 | 156 => Some(Self::EacRg11 { signed }),
+            157..=184 => Some(Self::Astc { block:ASTC_BLOCKS[(vk_format as usize - 157) / 2], srgb }),
+            _ => None,
+        }
+    }
+
+    //fp of_dxgi_format
+    /// Get the format for a DXGI format (as used by DDS DX10
+    /// headers), if it is a supported compressed format
+    pub fn of_dxgi_format(dxgi_format:u32) -> Option<Self> {
+        match dxgi_format {
+            70 | 71 => Some(Self::Bc1 { alpha:true, srgb:false }),
+            72 => Some(Self::Bc1 { alpha:true, srgb:true }),
+            73 | 74 => Some(Self::Bc2 { srgb:false }),
+            75 => Some(Self::Bc2 { srgb:true }),
+            76 | 77 => Some(Self::Bc3 { srgb:false }),
+            78 => Some(Self::Bc3 { srgb:true }),
+            79 | 80 => Some(Self::Bc4 { signed:false }),
+            81 => Some(Self::Bc4 { signed:true }),
+            82 | 83 => Some(Self::Bc5 { signed:false }),
+            84 => Some(Self::Bc5 { signed:true }),
+            94 | 95 => Some(Self::Bc6h { signed:false }),
+            96 => Some(Self::Bc6h { signed:true }),
+            97 | 98 => Some(Self::Bc7 { srgb:false }),
+            99 => Some(Self::Bc7 { srgb:true }),
+            _ => None,
+        }
+    }
+
+    //fp of_four_cc
+    /// Get the format for a DDS FourCC code, if it is a supported
+    /// compressed format
+    pub fn of_four_cc(four_cc:&[u8]) -> Option<Self> {
+        match four_cc {
+            b"DXT1" => Some(Self::Bc1 { alpha:true, srgb:false }),
+            b"DXT2" | b"DXT3" => Some(Self::Bc2 { srgb:false }),
+            b"DXT4" | b"DXT5" => Some(Self::Bc3 { srgb:false }),
+            b"ATI1" | b"BC4U" => Some(Self::Bc4 { signed:false }),
+            b"BC4S" => Some(Self::Bc4 { signed:true }),
+            b"ATI2" | b"BC5U" => Some(Self::Bc5 { signed:false }),
+            b"BC5S" => Some(Self::Bc5 { signed:true }),
+            _ => None,
+        }
+    }
+}
+
+//ip Display for CompressedFormat
+impl std::fmt::Display for CompressedFormat {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Astc { block, srgb } => {
+                write!(f, "ASTC {}x{}{}", block.0, block.1, if *srgb { " sRGB" } else { "" })
+            }
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+//a CompressedImage
+//tp CompressedImage
+/// The mip levels of a compressed 2D texture, cube map or 2D array
+#[derive(Debug, Clone)]
+pub struct CompressedImage {
+    /// Format of the data
+    format : CompressedFormat,
+    /// Kind of texture
+    kind : TextureKind,
+    /// Width of the base level
+    width : usize,
+    /// Height of the base level
+    height : usize,
+    /// Number of layers (6 faces for a cube map)
+    layers : usize,
+    /// Data for each mip level (largest first), with the layers in
+    /// order
+    levels : Vec<Vec<u8>>,
+}
+
+//ip CompressedImage
+impl CompressedImage {
+    //fp new
+    /// Create a compressed image from the data for its mip levels,
+    /// each of which contains all the layers (or cube map faces in
+    /// [CubeFace] order)
+    pub fn new(format:CompressedFormat, kind:TextureKind, width:usize, height:usize, layers:usize, levels:Vec<Vec<u8>>) -> Result<Self, String> {
+        let layers = if kind == TextureKind::CubeMap { 6 } else { layers };
+        if width == 0 || height == 0 || layers == 0 || levels.is_empty() {
+            return Err(format!("Empty compressed {} texture", kind));
+        }
+        check_level_count("Compressed texture", levels.len(), width, height)?;
+        if kind == TextureKind::Texture2D && layers != 1 {
+            return Err(format!("Compressed 2D texture cannot have {} layers", layers));
+        }
+        if kind == TextureKind::CubeMap && width != height {
+            return Err(format!("Compressed cube map faces must be square, not {}x{}", width, height));
+        }
+        let image = Self { format, kind, width, height, layers, levels };
+        for (level, data) in image.levels.iter().enumerate() {
+            let required = image.level_bytes(level)?;
+            if data.len() < required {
+                return Err(format!("Mip level {} of compressed texture has {} bytes, requires {}",
+                                   level, data.len(), required));
+            }
+        }
+        Ok(image)
+    }
+
+    //fp of_ktx2
+    /// Parse a KTX2 container
+    pub fn of_ktx2(bytes:&[u8]) -> Result<Self, String> {
+        if bytes.len() < 80 || bytes[0..12] != KTX2_IDENTIFIER {
+            return Err("Data is not a KTX2 file".into());
+        }
+        let vk_format = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 20)? as usize;
+        let height = read_u32(bytes, 24)?.max(1) as usize;
+        let depth = read_u32(bytes, 28)?;
+        let layer_count = read_u32(bytes, 32)?;
+        let face_count = read_u32(bytes, 36)?;
+        let level_count = read_u32(bytes, 40)?.max(1) as usize;
+        check_level_count("KTX2", level_count, width, height)?;
+        let supercompression = read_u32(bytes, 44)?;
+        if supercompression != 0 {
+            return Err(format!("KTX2 supercompression scheme {} is unsupported", supercompression));
+        }
+        if depth > 1 {
+            return Err("KTX2 3D textures are unsupported".into());
+        }
+        let format = CompressedFormat::of_vk_format(vk_format)
+            .ok_or_else(|| format!("KTX2 format (Vulkan format {}) is not a supported compressed format", vk_format))?;
+        let kind = {
+            match (face_count, layer_count) {
+                (6, 0) => TextureKind::CubeMap,
+                (6, _) => { return Err("KTX2 cube map arrays are unsupported".into()); }
+                (1, 0) => TextureKind::Texture2D,
+                (1, _) => TextureKind::Array2D,
+                _ => { return Err(format!("KTX2 face count {} is invalid", face_count)); }
+            }
+        };
+        let layers = (layer_count.max(1) * face_count) as usize;
+        let mut levels = Vec::new();
+        for level in 0..level_count {
+            let index = 80 + level * 24;
+            let offset = read_u64(bytes, index)? as usize;
+            let length = read_u64(bytes, index + 8)? as usize;
+            let data = offset.checked_add(length)
+                .and_then(|end| bytes.get(offset..end))
+                .ok_or_else(|| format!("KTX2 mip level {} is outside the file", level))?;
+            levels.push(data.to_vec());
+        }
+        Self::new(format, kind, width, height, layers, levels)
+    }
+
+    //fp of_dds
+    /// Parse a DDS container
+    pub fn of_dds(bytes:&[u8]) -> Result<Self, String> {
+        if bytes.len() < 128 || &bytes[0..4] != b"DDS " || read_u32(bytes, 4)? != 124 {
+            return Err("Data is not a DDS file".into());
+        }
+        let flags = read_u32(bytes, 8)?;
+        let height = read_u32(bytes, 12)? as usize;
+        let width = read_u32(bytes, 16)? as usize;
+        let level_count = if flags & 0x20000 != 0 { read_u32(bytes, 28)?.max(1) as usize } else { 1 };
+        check_level_count("DDS", level_count, width, height)?;
+        let pf_flags = read_u32(bytes, 80)?;
+        let four_cc = &bytes[84..88];
+        let caps2 = read_u32(bytes, 112)?;
+        if pf_flags & 0x4 == 0 {
+            return Err("Uncompressed DDS files are unsupported".into());
+        }
+        let (format, kind, layers, data_start) = {
+            if four_cc == b"DX10" {
+                let dxgi_format = read_u32(bytes, 128)?;
+                let dimension = read_u32(bytes, 132)?;
+                let misc_flags = read_u32(bytes, 136)?;
+                let array_size = read_u32(bytes, 140)?.max(1) as usize;
+                if dimension != 3 {
+                    return Err(format!("DDS resource dimension {} is unsupported (only 2D textures)", dimension));
+                }
+                let format = CompressedFormat::of_dxgi_format(dxgi_format)
+                    .ok_or_else(|| format!("DDS DXGI format {} is not a supported compressed format", dxgi_format))?;
+                if misc_flags & 0x4 != 0 {
+                    if array_size > 1 {
+                        return Err("DDS cube map arrays are unsupported".into());
+                    }
+                    (format, TextureKind::CubeMap, 6, 148)
+                } else if array_size > 1 {
+                    (format, TextureKind::Array2D, array_size, 148)
+                } else {
+                    (format, TextureKind::Texture2D, 1, 148)
+                }
+            } else {
+                let format = CompressedFormat::of_four_cc(four_cc)
+                    .ok_or_else(|| format!("DDS FourCC '{}' is not a supported compressed format",
+                                           String::from_utf8_lossy(four_cc)))?;
+                if caps2 & 0x200 != 0 {
+                    if caps2 & 0xfc00 != 0xfc00 {
+                        return Err("DDS cube maps must have all six faces".into());
+                    }
+                    (format, TextureKind::CubeMap, 6, 128)
+                } else {
+                    (format, TextureKind::Texture2D, 1, 128)
+                }
+            }
+        };
+        // DDS stores all the mip levels of each layer in turn
+        let mut levels = vec![Vec::new(); level_count];
+        let mut offset : usize = data_start;
+        for _ in 0..layers {
+            for (level, data) in levels.iter_mut().enumerate() {
+                let length = format.image_bytes((width >> level).max(1), (height >> level).max(1))?;
+                let end = offset.checked_add(length)
+                    .ok_or_else(|| format!("DDS mip level {} is outside the file", level))?;
+                let image = bytes.get(offset..end)
+                    .ok_or_else(|| format!("DDS mip level {} is outside the file", level))?;
+                data.extend_from_slice(image);
+                offset = end;
+            }
+        }
+        Self::new(format, kind, width, height, layers, levels)
+    }
+
+    //fp of_bytes
+    /// Parse a KTX2 or DDS container, determined by its contents
+    pub fn of_bytes(bytes:&[u8]) -> Result<Self, String> {
+        if bytes.starts_with(&KTX2_IDENTIFIER) {
+            Self::of_ktx2(bytes)
+        } else if bytes.starts_with(b"DDS ") {
+            Self::of_dds(bytes)
+        } else {
+            Err("Data is not a KTX2 or DDS file".into())
+        }
+    }
+
+    //ap format
+    /// Get the format of the image
+    pub fn format(&self) -> CompressedFormat {
+        self.format
+    }
+
+    //ap kind
+    /// Get the kind of texture
+    pub fn kind(&self) -> TextureKind {
+        self.kind
+    }
+
+    //ap width
+    /// Get the width of the base level
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    //ap height
+    /// Get the height of the base level
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    //ap layers
+    /// Get the number of layers (6 for a cube map)
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    //ap levels
+    /// Get the number of mip levels
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    //mi level_size
+    /// Get the width and height of a mip level
+    fn level_size(&self, level:usize) -> (usize, usize) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    //mi level_bytes
+    /// Get the number of bytes of a mip level, with all its layers,
+    /// or an error if it is too large to be represented
+    fn level_bytes(&self, level:usize) -> Result<usize, String> {
+        let (w, h) = self.level_size(level);
+        self.format.image_bytes(w, h)?.checked_mul(self.layers)
+            .ok_or_else(|| format!("Mip level {} of {}x{} {} texture with {} layers is too large",
+                                   level, self.width, self.height, self.format, self.layers))
+    }
+
+    //mp decompress
+    /// Decompress a layer (or cube map face) of a mip level to
+    /// tightly packed RGBA8 texels, top row first, if the format can
+    /// be decompressed on the CPU
+    pub fn decompress(&self, level:usize, layer:usize) -> Result<Vec<u8>, String> {
+        let data = self.levels.get(level)
+            .ok_or_else(|| format!("Compressed texture has no mip level {}", level))?;
+        if layer >= self.layers {
+            return Err(format!("Compressed texture has no layer {}", layer));
+        }
+        let (w, h) = self.level_size(level);
+        let image_bytes = self.format.image_bytes(w, h)?;
+        decompress::decompress(self.format, w, h, &data[layer * image_bytes..(layer + 1) * image_bytes])
+    }
+
+    //mp create_texture
+    /// Create a texture from the image; if the context does not
+    /// support the format then decompress it to RGBA8 if permitted
+    /// (and possible for the format)
+    pub(crate) fn create_texture(&self, capabilities:&Capabilities, decompress:bool) -> Result<Texture, String> {
+        match capabilities.require(self.format.feature()) {
+            Ok(()) => self.upload_compressed(),
+            Err(e) if decompress && !self.format.can_decompress() => {
+                Err(format!("{}, and {} textures cannot be decompressed on the CPU", e, self.format))
+            }
+            Err(_) if decompress => self.upload_decompressed(),
+            Err(e) => Err(e),
+        }
+    }
+
+    //mi upload_compressed
+    /// Create a texture with the compressed data
+    fn upload_compressed(&self) -> Result<Texture, String> {
+        let format = TextureFormat::Compressed(self.format);
+        let texture = Texture::create(self.kind, format, self.width, self.height, self.layers)?;
+        let internal_format = self.format.internal_format();
+        for (level, data) in self.levels.iter().enumerate() {
+            let (w, h) = self.level_size(level);
+            let size = (w as GLsizei, h as GLsizei);
+            let image_bytes = self.format.image_bytes(w, h)?;
+            match self.kind {
+                TextureKind::Texture2D => {
+                    Backend::compressed_tex_image_2d(gl::TEXTURE_2D, level as GLint, internal_format, size,
+                                                     &data[0..image_bytes]);
+                }
+                TextureKind::CubeMap => {
+                    for (i, face) in CubeFace::ALL.iter().enumerate() {
+                        Backend::compressed_tex_image_2d(face.target(), level as GLint, internal_format, size,
+                                                         &data[i * image_bytes..(i + 1) * image_bytes]);
+                    }
+                }
+                TextureKind::Array2D => {
+                    Backend::compressed_tex_image_3d(gl::TEXTURE_2D_ARRAY, level as GLint, internal_format,
+                                                     (size.0, size.1, self.layers as GLsizei),
+                                                     &data[0..self.level_bytes(level)?]);
+                }
+            }
+        }
+        texture.with_levels(self.levels.len())
+    }
+
+    //mi upload_decompressed
+    /// Create an RGBA8 texture by decompressing the data
+    fn upload_decompressed(&self) -> Result<Texture, String> {
+        let format = if self.format.is_srgb() { TextureFormat::Srgb8Alpha8 } else { TextureFormat::Rgba8 };
+        let texture = Texture::create(self.kind, format, self.width, self.height, self.layers)?;
+        for level in 0..self.levels.len() {
+            let (w, h) = self.level_size(level);
+            let size = (w as GLsizei, h as GLsizei);
+            let mut rgba = Vec::with_capacity(w * h * 4 * self.layers);
+            for layer in 0..self.layers {
+                rgba.extend(self.decompress(level, layer)?);
+            }
+            match self.kind {
+                TextureKind::Texture2D => {
+                    Backend::tex_image_2d(gl::TEXTURE_2D, level as GLint, format.internal_format(), size,
+                                          format.pixel_format(), Some(&rgba));
+                }
+                TextureKind::CubeMap => {
+                    let face_bytes = w * h * 4;
+                    for (i, face) in CubeFace::ALL.iter().enumerate() {
+                        Backend::tex_image_2d(face.target(), level as GLint, format.internal_format(), size,
+                                              format.pixel_format(), Some(&rgba[i * face_bytes..(i + 1) * face_bytes]));
+                    }
+                }
+                TextureKind::Array2D => {
+                    Backend::tex_image_3d(gl::TEXTURE_2D_ARRAY, level as GLint, format.internal_format(),
+                                          (size.0, size.1, self.layers as GLsizei),
+                                          format.pixel_format(), Some(&rgba));
+                }
+            }
+        }
+        texture.with_levels(self.levels.len())
+    }
+}
+
+//a Functions
+//fi check_level_count
+/// Check that a number of mip levels is at most that of a full mip
+/// chain for a base level size, before anything is allocated for them
+fn check_level_count(container:&str, level_count:usize, width:usize, height:usize) -> Result<(), String> {
+    let max_levels = (usize::BITS - width.max(height).leading_zeros()) as usize;
+    if level_count > max_levels {
+        return Err(format!("{} has {} mip levels, but a {}x{} texture has at most {}",
+                           container, level_count, width, height, max_levels));
+    }
+    Ok(())
+}
+
+//fi read_u32
+/// Read a little-endian u32 from a byte offset
+fn read_u32(bytes:&[u8], offset:usize) -> Result<u32, String> {
+    bytes.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "Texture container is truncated".into())
+}
+
+//fi read_u64
+/// Read a little-endian u64 from a byte offset
+fn read_u64(bytes:&[u8], offset:usize) -> Result<u64, String> {
+    Ok(read_u32(bytes, offset)? as u64 | ((read_u32(bytes, offset + 4)? as u64) << 32))
+}
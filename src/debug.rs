@@ -0,0 +1,306 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    debug.rs
+@brief   OpenGL debug output (KHR_debug), object labels and debug groups
+ */
+
+//a Documentation
+
+/*!
+
+Debug output is opt-in, enabled with
+[crate::RenderContext::enable_debug_output] if the context supports
+[crate::GlFeature::DebugOutput]. Messages from OpenGL at or above a
+minimum [DebugSeverity] are then passed to a [DebugHandler] - either
+the `log` crate or a closure. On OpenGL ES this requires version 3.2,
+as the backends call the core entry points rather than the
+KHR-suffixed ones of the GL_KHR_debug extension.
+
+Debug output is synchronous, so messages are delivered on the
+rendering thread during the OpenGL call that caused them.
+
+While debug output is enabled the crate labels the OpenGL objects it
+creates (buffers, VAOs and programs), and wraps the drawing of a
+[crate::ShaderInstantiable] in a debug group, so that captures in
+tools such as RenderDoc are readable.
+
+!*/
+
+//a Imports
+use std::cell::RefCell;
+
+use gl::types::*;
+
+use crate::{Backend, GlBackend};
+
+//a DebugSeverity
+//tp DebugSeverity
+/// The severity of an OpenGL debug message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DebugSeverity {
+    /// GL_DEBUG_SEVERITY_NOTIFICATION
+    Notification,
+    /// GL_DEBUG_SEVERITY_LOW
+    Low,
+    /// GL_DEBUG_SEVERITY_MEDIUM
+    Medium,
+    /// GL_DEBUG_SEVERITY_HIGH
+    High,
+}
+
+//ip DebugSeverity
+impl DebugSeverity {
+    //fp of_gl
+    /// Get the severity from its OpenGL enumeration; unknown values
+    /// are treated as high severity
+    pub fn of_gl(severity:GLenum) -> Self {
+        match severity {
+            gl::DEBUG_SEVERITY_NOTIFICATION => Self::Notification,
+            gl::DEBUG_SEVERITY_LOW => Self::Low,
+            gl::DEBUG_SEVERITY_MEDIUM => Self::Medium,
+            _ => Self::High,
+        }
+    }
+
+    //mp as_gl
+    /// Get the OpenGL enumeration for the severity
+    pub fn as_gl(self) -> GLenum {
+        match self {
+            Self::Notification => gl::DEBUG_SEVERITY_NOTIFICATION,
+            Self::Low => gl::DEBUG_SEVERITY_LOW,
+            Self::Medium => gl::DEBUG_SEVERITY_MEDIUM,
+            Self::High => gl::DEBUG_SEVERITY_HIGH,
+        }
+    }
+
+    //mp log_level
+    /// The `log` crate level used for the severity
+    pub fn log_level(self) -> log::Level {
+        match self {
+            Self::Notification => log::Level::Debug,
+            Self::Low => log::Level::Info,
+            Self::Medium => log::Level::Warn,
+            Self::High => log::Level::Error,
+        }
+    }
+}
+
+//a DebugMessage
+//tp DebugMessage
+/// A debug message from OpenGL
+#[derive(Debug, Clone)]
+pub struct DebugMessage<'a> {
+    /// Source of the message (e.g. GL_DEBUG_SOURCE_API)
+    pub source : GLenum,
+    /// Type of the message (e.g. GL_DEBUG_TYPE_ERROR)
+    pub msg_type : GLenum,
+    /// Implementation-specific id of the message
+    pub id : GLuint,
+    /// Severity of the message
+    pub severity : DebugSeverity,
+    /// Message text
+    pub message : &'a str,
+}
+
+//ip DebugMessage
+impl <'a> DebugMessage<'a> {
+    //mp source_str
+    /// Get a short string for the source of the message
+    pub fn source_str(&self) -> &'static str {
+        match self.source {
+            gl::DEBUG_SOURCE_API => "api",
+            gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+            gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+            gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+            gl::DEBUG_SOURCE_APPLICATION => "application",
+            _ => "other",
+        }
+    }
+
+    //mp type_str
+    /// Get a short string for the type of the message
+    pub fn type_str(&self) -> &'static str {
+        match self.msg_type {
+            gl::DEBUG_TYPE_ERROR => "error",
+            gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated",
+            gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+            gl::DEBUG_TYPE_PORTABILITY => "portability",
+            gl::DEBUG_TYPE_PERFORMANCE => "performance",
+            gl::DEBUG_TYPE_MARKER => "marker",
+            gl::DEBUG_TYPE_PUSH_GROUP => "push group",
+            gl::DEBUG_TYPE_POP_GROUP => "pop group",
+            _ => "other",
+        }
+    }
+}
+
+//ip Display for DebugMessage
+impl <'a> std::fmt::Display for DebugMessage<'a> {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "GL {} {} ({}) {:?}: {}",
+               self.source_str(), self.type_str(), self.id, self.severity, self.message)
+    }
+}
+
+//a DebugHandler
+//tp DebugHandler
+/// Where OpenGL debug messages are sent
+pub enum DebugHandler {
+    /// Log the messages with the `log` crate, at a level depending on
+    /// the severity
+    Log,
+    /// Invoke a closure for each message; this must not make OpenGL
+    /// calls
+    Callback(Box<dyn FnMut(&DebugMessage)>),
+}
+
+//ip DebugHandler
+impl DebugHandler {
+    //mp handle
+    /// Handle a message
+    fn handle(&mut self, message:&DebugMessage) {
+        match self {
+            Self::Log => {
+                log::log!(target:"gl_model::gl", message.severity.log_level(), "{}", message);
+            }
+            Self::Callback(f) => {
+                f(message);
+            }
+        }
+    }
+}
+
+//a DebugState
+//ti DebugState
+/// The debug output state for the thread, if enabled
+struct DebugState {
+    /// Minimum severity of messages passed to the handler
+    min_severity : DebugSeverity,
+    /// Handler for the messages
+    handler : DebugHandler,
+}
+
+thread_local! {
+    static DEBUG : RefCell<Option<DebugState>> = const { RefCell::new(None) };
+}
+
+//a Functions
+//fp enable
+/// Enable synchronous debug output for the current context, passing
+/// messages at or above a severity to the handler
+///
+/// The context must support debug output; this is checked by
+/// [crate::RenderContext::enable_debug_output]
+pub(crate) fn enable(min_severity:DebugSeverity, handler:DebugHandler) {
+    DEBUG.with(|d| *d.borrow_mut() = Some(DebugState { min_severity, handler }));
+    apply(min_severity);
+}
+
+//fp restore
+/// Restore the debug output state of the context (if enabled) after
+/// context loss
+pub(crate) fn restore() {
+    let min_severity = DEBUG.with(|d| d.borrow().as_ref().map(|d| d.min_severity));
+    if let Some(min_severity) = min_severity {
+        apply(min_severity);
+    }
+}
+
+//fi apply
+/// Enable debug output in the context, for messages at or above a
+/// severity
+fn apply(min_severity:DebugSeverity) {
+    Backend::enable(gl::DEBUG_OUTPUT);
+    Backend::enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+    for severity in [DebugSeverity::Notification, DebugSeverity::Low,
+                     DebugSeverity::Medium, DebugSeverity::High] {
+        Backend::debug_message_control(severity.as_gl(), severity >= min_severity);
+    }
+    Backend::debug_message_callback(dispatch);
+}
+
+//fp disable
+/// Disable debug output, dropping the handler
+pub(crate) fn disable() {
+    if is_enabled() {
+        Backend::disable(gl::DEBUG_OUTPUT);
+        DEBUG.with(|d| *d.borrow_mut() = None);
+    }
+}
+
+//fp is_enabled
+/// Return true if debug output is enabled (and hence objects are
+/// labelled and debug groups used)
+pub fn is_enabled() -> bool {
+    DEBUG.with(|d| d.borrow().is_some())
+}
+
+//fi dispatch
+/// The [crate::DebugCallback] for debug output, passing messages to
+/// the handler
+fn dispatch(source:GLenum, msg_type:GLenum, id:GLuint, severity:GLenum, message:&str) {
+    let severity = DebugSeverity::of_gl(severity);
+    let message = DebugMessage { source, msg_type, id, severity, message };
+    DEBUG.with(|d| {
+        // A handler that causes a message cannot be reentered
+        if let Ok(mut d) = d.try_borrow_mut() {
+            if let Some(d) = d.as_mut() {
+                if severity >= d.min_severity {
+                    d.handler.handle(&message);
+                }
+            }
+        }
+    });
+}
+
+//fp label
+/// Label an OpenGL object (of a kind given by `identifier`, such as
+/// GL_BUFFER) if debug output is enabled
+pub fn label(identifier:GLenum, name:GLuint, label:&str) {
+    if name != 0 && is_enabled() {
+        Backend::object_label(identifier, name, label);
+    }
+}
+
+//a DebugGroup
+//tp DebugGroup
+/// A debug group, pushed when created (if debug output is enabled)
+/// and popped when dropped
+#[must_use]
+pub struct DebugGroup {
+    pushed : bool,
+}
+
+//ip DebugGroup
+impl DebugGroup {
+    //fp new
+    /// Push a named debug group if debug output is enabled
+    pub fn new(name:&str) -> Self {
+        let pushed = is_enabled();
+        if pushed {
+            Backend::push_debug_group(name);
+        }
+        Self { pushed }
+    }
+}
+
+//ip Drop for DebugGroup
+impl Drop for DebugGroup {
+    fn drop(&mut self) {
+        if self.pushed {
+            Backend::pop_debug_group();
+        }
+    }
+}
@@ -0,0 +1,404 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    decompress.rs
+@brief   CPU decompression of block-compressed textures to RGBA8
+ */
+
+//a Imports
+use crate::CompressedFormat;
+
+//a Constants
+/// ETC1/ETC2 intensity modifier tables (the positive values; the
+/// negative values are their negations)
+const ETC_MODIFIERS : [[i32; 2]; 8] = [[2, 8], [5, 17], [9, 29], [13, 42],
+                                       [18, 60], [24, 80], [33, 106], [47, 183]];
+
+/// ETC2 T and H mode distances
+const ETC_DISTANCES : [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+/// EAC modifier tables
+const EAC_MODIFIERS : [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+//a Types
+/// The 16 RGBA texels of a 4x4 block, in row-major order
+type Texels = [[u8; 4]; 16];
+
+//a Functions
+//fi decoder
+/// Get the block decoder for a compressed format, if there is one
+///
+/// Only BC1 to BC5 and the unsigned ETC2 and EAC formats have
+/// decoders; BC6H, BC7, ASTC and the signed formats do not
+fn decoder(format:CompressedFormat) -> Option<fn(&[u8], &mut Texels)> {
+    use CompressedFormat::*;
+    let decode : fn(&[u8], &mut Texels) = match format {
+        Bc1 { alpha:true, .. } => |b, t| bc1(b, t, false, true),
+        Bc1 { alpha:false, .. } => |b, t| bc1(b, t, false, false),
+        Bc2 { .. } => bc2,
+        Bc3 { .. } => bc3,
+        Bc4 { signed:false } => bc4,
+        Bc5 { signed:false } => bc5,
+        Etc2Rgb { .. } => |b, t| etc2(b, t, false),
+        Etc2RgbA1 { .. } => |b, t| etc2(b, t, true),
+        Etc2Rgba { .. } => etc2_eac,
+        EacR11 { signed:false } => eac_r11,
+        EacRg11 { signed:false } => eac_rg11,
+        _ => { return None; }
+    };
+    Some(decode)
+}
+
+//fp can_decompress
+/// Return true if images of a compressed format can be decompressed
+/// on the CPU
+pub(crate) fn can_decompress(format:CompressedFormat) -> bool {
+    decoder(format).is_some()
+}
+
+//fp decompress
+/// Decompress an image of a compressed format to tightly packed
+/// RGBA8 texels
+///
+/// Only BC1 to BC5 and the unsigned ETC2 and EAC formats are
+/// supported
+pub(crate) fn decompress(format:CompressedFormat, width:usize, height:usize, data:&[u8]) -> Result<Vec<u8>, String> {
+    let decode = decoder(format)
+        .ok_or_else(|| format!("No CPU decompression is available for {} textures", format))?;
+    let block_bytes = format.block_bytes();
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+    if data.len() < blocks_x * blocks_y * block_bytes {
+        return Err(format!("Compressed {}x{} {} image is truncated", width, height, format));
+    }
+    let mut rgba = vec![0; width * height * 4];
+    let mut texels = [[0; 4]; 16];
+    for (i, block) in data.chunks_exact(block_bytes).take(blocks_x * blocks_y).enumerate() {
+        let (bx, by) = ((i % blocks_x) * 4, (i / blocks_x) * 4);
+        decode(block, &mut texels);
+        for y in 0..4.min(height - by) {
+            for x in 0..4.min(width - bx) {
+                let offset = ((by + y) * width + bx + x) * 4;
+                rgba[offset..offset + 4].copy_from_slice(&texels[y * 4 + x]);
+            }
+        }
+    }
+    Ok(rgba)
+}
+
+//fi rgb565
+/// Expand a 5:6:5 colour to RGB8
+fn rgb565(c:u16) -> [i32; 3] {
+    let r = ((c >> 11) & 0x1f) as i32;
+    let g = ((c >> 5) & 0x3f) as i32;
+    let b = (c & 0x1f) as i32;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+//fi bc1
+/// Decode a BC1 colour block
+///
+/// A block with `c0 <= c1` has three colours and black, unless
+/// `four_colour` is set (for the colour blocks of BC2 and BC3); the
+/// black texels are transparent if `punch_through_alpha` is set
+fn bc1(block:&[u8], texels:&mut Texels, four_colour:bool, punch_through_alpha:bool) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (p0, p1) = (rgb565(c0), rgb565(c1));
+    let four_colour = four_colour || c0 > c1;
+    let mut palette = [[0u8; 4]; 4];
+    for i in 0..3 {
+        palette[0][i] = p0[i] as u8;
+        palette[1][i] = p1[i] as u8;
+        if four_colour {
+            palette[2][i] = ((2 * p0[i] + p1[i]) / 3) as u8;
+            palette[3][i] = ((p0[i] + 2 * p1[i]) / 3) as u8;
+        } else {
+            palette[2][i] = ((p0[i] + p1[i]) / 2) as u8;
+        }
+    }
+    palette[0][3] = 255;
+    palette[1][3] = 255;
+    palette[2][3] = 255;
+    palette[3][3] = if four_colour || !punch_through_alpha { 255 } else { 0 };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+}
+
+//fi bc2
+/// Decode a BC2 block - explicit alpha and a four-colour BC1 colour
+/// block
+fn bc2(block:&[u8], texels:&mut Texels) {
+    bc1(&block[8..16], texels, true, false);
+    let mut bits = [0u8; 8];
+    bits.copy_from_slice(&block[0..8]);
+    let alpha = u64::from_le_bytes(bits);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = (((alpha >> (4 * i)) & 0xf) * 17) as u8;
+    }
+}
+
+//fi bc_channel
+/// Decode a BC3 alpha (or BC4 channel) block to 16 values
+fn bc_channel(block:&[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for (i, p) in palette.iter_mut().enumerate().skip(2) {
+            *p = ((8 - i as u32) * a0 + (i as u32 - 1) * a1) / 7;
+        }
+    } else {
+        for (i, p) in palette.iter_mut().enumerate().take(6).skip(2) {
+            *p = ((6 - i as u32) * a0 + (i as u32 - 1) * a1) / 5;
+        }
+    }
+    let mut bits = [0u8; 8];
+    bits[0..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    let mut values = [0; 16];
+    for (i, v) in values.iter_mut().enumerate() {
+        *v = palette[((indices >> (3 * i)) & 7) as usize] as u8;
+    }
+    values
+}
+
+//fi bc3
+/// Decode a BC3 block - interpolated alpha and a four-colour BC1
+/// colour block
+fn bc3(block:&[u8], texels:&mut Texels) {
+    bc1(&block[8..16], texels, true, false);
+    for (texel, a) in texels.iter_mut().zip(bc_channel(&block[0..8])) {
+        texel[3] = a;
+    }
+}
+
+//fi bc4
+/// Decode an unsigned BC4 block to red
+fn bc4(block:&[u8], texels:&mut Texels) {
+    for (texel, r) in texels.iter_mut().zip(bc_channel(block)) {
+        *texel = [r, 0, 0, 255];
+    }
+}
+
+//fi bc5
+/// Decode an unsigned BC5 block to red and green
+fn bc5(block:&[u8], texels:&mut Texels) {
+    let g = bc_channel(&block[8..16]);
+    for (i, (texel, r)) in texels.iter_mut().zip(bc_channel(&block[0..8])).enumerate() {
+        *texel = [r, g[i], 0, 255];
+    }
+}
+
+//fi extend4
+/// Extend a 4-bit value to 8 bits
+fn extend4(v:u8) -> i32 {
+    (v as i32) * 17
+}
+
+//fi extend5
+/// Extend a 5-bit value to 8 bits
+fn extend5(v:i32) -> i32 {
+    (v << 3) | (v >> 2)
+}
+
+//fi clamp_rgb
+/// Clamp an RGB colour to 0 to 255
+fn clamp_rgb(c:[i32; 3]) -> [u8; 4] {
+    [c[0].clamp(0, 255) as u8, c[1].clamp(0, 255) as u8, c[2].clamp(0, 255) as u8, 255]
+}
+
+//fi offset_rgb
+/// Add an offset to each component of a colour, clamping it
+fn offset_rgb(c:[i32; 3], d:i32) -> [u8; 4] {
+    clamp_rgb([c[0] + d, c[1] + d, c[2] + d])
+}
+
+//fi etc2
+/// Decode an ETC2 RGB block, or an ETC2 RGB block with punch-through
+/// alpha (in which case the 'differential' bit indicates the block is
+/// opaque)
+fn etc2(block:&[u8], texels:&mut Texels, punchthrough:bool) {
+    let b = block;
+    let indices = u32::from_be_bytes([b[4], b[5], b[6], b[7]]);
+    let diff_bit = (b[3] & 2) != 0;
+    let opaque = !punchthrough || diff_bit;
+    // Pixel index i (in column-major order) of the 2-bit indices
+    let index = |i:usize| (((indices >> (16 + i)) & 1) << 1 | ((indices >> i) & 1)) as usize;
+    let transparent = |i:usize| !opaque && index(i) == 2;
+
+    if diff_bit || punchthrough {
+        let r = (b[0] >> 3) as i32 + (((b[0] & 7) as i8) << 5 >> 5) as i32;
+        let g = (b[1] >> 3) as i32 + (((b[1] & 7) as i8) << 5 >> 5) as i32;
+        let bl = (b[2] >> 3) as i32 + (((b[2] & 7) as i8) << 5 >> 5) as i32;
+        if !(0..32).contains(&r) {
+            // T mode
+            let c1 = [extend4(((b[0] >> 1) & 0xc) | (b[0] & 3)), extend4(b[1] >> 4), extend4(b[1] & 0xf)];
+            let c2 = [extend4(b[2] >> 4), extend4(b[2] & 0xf), extend4(b[3] >> 4)];
+            let d = ETC_DISTANCES[(((b[3] >> 1) & 6) | (b[3] & 1)) as usize];
+            let paint = [clamp_rgb(c1), offset_rgb(c2, d), clamp_rgb(c2), offset_rgb(c2, -d)];
+            etc_paint(texels, &paint, index, transparent);
+            return;
+        }
+        if !(0..32).contains(&g) {
+            // H mode
+            let r1 = (b[0] >> 3) & 0xf;
+            let g1 = ((b[0] & 7) << 1) | ((b[1] >> 4) & 1);
+            let b1 = (b[1] & 8) | ((b[1] & 3) << 1) | (b[2] >> 7);
+            let r2 = (b[2] >> 3) & 0xf;
+            let g2 = ((b[2] & 7) << 1) | (b[3] >> 7);
+            let b2 = (b[3] >> 3) & 0xf;
+            let v1 = ((r1 as u32) << 8) | ((g1 as u32) << 4) | b1 as u32;
+            let v2 = ((r2 as u32) << 8) | ((g2 as u32) << 4) | b2 as u32;
+            let d = ETC_DISTANCES[((b[3] & 4) | ((b[3] & 1) << 1) | (v1 >= v2) as u8) as usize];
+            let c1 = [extend4(r1), extend4(g1), extend4(b1)];
+            let c2 = [extend4(r2), extend4(g2), extend4(b2)];
+            let paint = [offset_rgb(c1, d), offset_rgb(c1, -d), offset_rgb(c2, d), offset_rgb(c2, -d)];
+            etc_paint(texels, &paint, index, transparent);
+            return;
+        }
+        if !(0..32).contains(&bl) {
+            // Planar mode - always opaque
+            let extend6 = |v:u8| ((v << 2) | (v >> 4)) as i32;
+            let extend7 = |v:u8| ((v << 1) | (v >> 6)) as i32;
+            let o = [extend6((b[0] >> 1) & 0x3f),
+                     extend7(((b[0] & 1) << 6) | ((b[1] >> 1) & 0x3f)),
+                     extend6(((b[1] & 1) << 5) | (b[2] & 0x18) | ((b[2] & 3) << 1) | (b[3] >> 7))];
+            let h = [extend6((((b[3] >> 2) & 0x1f) << 1) | (b[3] & 1)),
+                     extend7(b[4] >> 1),
+                     extend6(((b[4] & 1) << 5) | (b[5] >> 3))];
+            let v = [extend6(((b[5] & 7) << 3) | (b[6] >> 5)),
+                     extend7(((b[6] & 0x1f) << 2) | (b[7] >> 6)),
+                     extend6(b[7] & 0x3f)];
+            for (i, texel) in texels.iter_mut().enumerate() {
+                let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+                let c = |k:usize| (x * (h[k] - o[k]) + y * (v[k] - o[k]) + 4 * o[k] + 2) >> 2;
+                *texel = clamp_rgb([c(0), c(1), c(2)]);
+            }
+            return;
+        }
+        let base = [[extend5((b[0] >> 3) as i32), extend5((b[1] >> 3) as i32), extend5((b[2] >> 3) as i32)],
+                    [extend5(r), extend5(g), extend5(bl)]];
+        etc_individual(b, texels, base, opaque, index);
+    } else {
+        let base = [[extend4(b[0] >> 4), extend4(b[1] >> 4), extend4(b[2] >> 4)],
+                    [extend4(b[0] & 0xf), extend4(b[1] & 0xf), extend4(b[2] & 0xf)]];
+        etc_individual(b, texels, base, true, index);
+    }
+}
+
+//fi etc_paint
+/// Set the texels of a T or H mode block from their paint colours
+fn etc_paint<I:Fn(usize) -> usize, T:Fn(usize) -> bool>(texels:&mut Texels, paint:&[[u8; 4]; 4], index:I, transparent:T) {
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let ci = (i % 4) * 4 + i / 4;
+        *texel = if transparent(ci) { [0; 4] } else { paint[index(ci)] };
+    }
+}
+
+//fi etc_individual
+/// Set the texels of an individual or differential mode block from
+/// the base colours of its two sub-blocks
+fn etc_individual<I:Fn(usize) -> usize>(b:&[u8], texels:&mut Texels, base:[[i32; 3]; 2], opaque:bool, index:I) {
+    let flip = (b[3] & 1) != 0;
+    let tables = [(b[3] >> 5) as usize, ((b[3] >> 2) & 7) as usize];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let ci = x * 4 + y;
+        let sub = if flip { (y >= 2) as usize } else { (x >= 2) as usize };
+        let [small, large] = ETC_MODIFIERS[tables[sub]];
+        let modifier = match index(ci) {
+            0 => if opaque { small } else { 0 },
+            1 => large,
+            2 => -small,
+            _ => -large,
+        };
+        *texel = {
+            if !opaque && index(ci) == 2 {
+                [0; 4]
+            } else {
+                offset_rgb(base[sub], modifier)
+            }
+        };
+    }
+}
+
+//fi eac
+/// Decode an EAC block to 16 values of 11 bits, or 8 bits if `alpha`
+fn eac(block:&[u8], alpha:bool) -> [u16; 16] {
+    let base = block[0] as i32;
+    let multiplier = (block[1] >> 4) as i32;
+    let table = &EAC_MODIFIERS[(block[1] & 0xf) as usize];
+    let mut bits = [0u8; 8];
+    bits[2..8].copy_from_slice(&block[2..8]);
+    let indices = u64::from_be_bytes(bits);
+    let mut values = [0; 16];
+    for (i, v) in values.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let modifier = table[((indices >> (45 - 3 * (x * 4 + y))) & 7) as usize];
+        *v = {
+            if alpha {
+                (base + modifier * multiplier).clamp(0, 255) as u16
+            } else {
+                let m = if multiplier == 0 { modifier } else { modifier * multiplier * 8 };
+                (base * 8 + 4 + m).clamp(0, 2047) as u16
+            }
+        };
+    }
+    values
+}
+
+//fi etc2_eac
+/// Decode an ETC2 RGBA block - EAC alpha and an ETC2 RGB block
+fn etc2_eac(block:&[u8], texels:&mut Texels) {
+    etc2(&block[8..16], texels, false);
+    for (texel, a) in texels.iter_mut().zip(eac(&block[0..8], true)) {
+        texel[3] = a as u8;
+    }
+}
+
+//fi eac_r11
+/// Decode an unsigned EAC R11 block to red
+fn eac_r11(block:&[u8], texels:&mut Texels) {
+    for (texel, r) in texels.iter_mut().zip(eac(block, false)) {
+        *texel = [(r >> 3) as u8, 0, 0, 255];
+    }
+}
+
+//fi eac_rg11
+/// Decode an unsigned EAC RG11 block to red and green
+fn eac_rg11(block:&[u8], texels:&mut Texels) {
+    let g = eac(&block[8..16], false);
+    for (i, (texel, r)) in texels.iter_mut().zip(eac(&block[0..8], false)).enumerate() {
+        *texel = [(r >> 3) as u8, (g[i] >> 3) as u8, 0, 255];
+    }
+}
@@ -0,0 +1,214 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    deletion.rs
+@brief   Deferred deletion of OpenGL objects on the render thread
+ */
+
+//a Documentation
+
+/*!
+
+OpenGL objects owned by the crate (buffers, shaders, programs, VAOs,
+queries, framebuffers, textures, samplers) are not deleted when they
+are dropped, as the drop may happen with no context current (e.g.
+during shutdown, or on an asset-loading thread). Instead their names
+are pushed to a deletion queue, which is flushed on the render thread
+with [crate::RenderContext::flush_deletions] (which is also done by
+[crate::RenderContext::end_frame], and when the [crate::RenderContext]
+is dropped).
+
+There is a queue for each [crate::RenderContext], keyed by its context
+generation (see [crate::RenderContext::context_lost]); each object is
+queued for the generation it was created in, so objects may be dropped
+on any thread, and are only deleted in the context that created them.
+Objects from a lost context are discarded rather than deleted; the
+lost generations are forgotten when a context is next created, after
+which objects of any generation up to the last one forgotten are
+assumed to be from a lost context. Dropping an OpenGL object whose
+[crate::RenderContext] has been dropped (or that was created with no
+[crate::RenderContext]) is a debug assertion failure, as it could
+never be deleted.
+
+!*/
+
+//a Imports
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use gl::types::*;
+
+use crate::{Backend, GlBackend};
+use crate::{environment, texture_units};
+
+//a GlObject
+//tp GlObject
+/// An OpenGL object awaiting deletion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GlObject {
+    /// A buffer
+    Buffer(GLuint),
+    /// A shader
+    Shader(GLuint),
+    /// A program
+    Program(GLuint),
+    /// A vertex array object
+    VertexArray(GLuint),
+    /// A query
+    Query(GLuint),
+    /// A framebuffer
+    Framebuffer(GLuint),
+    /// A renderbuffer
+    Renderbuffer(GLuint),
+    /// A texture
+    Texture(GLuint),
+    /// A sampler
+    Sampler(GLuint),
+}
+
+//ip GlObject
+impl GlObject {
+    //mi delete
+    /// Delete the OpenGL object; the context must be current
+    fn delete(self) {
+        match self {
+            Self::Buffer(n) => Backend::delete_buffer(n),
+            Self::Shader(n) => Backend::delete_shader(n),
+            Self::Program(n) => Backend::delete_program(n),
+            Self::VertexArray(n) => Backend::delete_vertex_array(n),
+            Self::Query(n) => Backend::delete_query(n),
+            Self::Framebuffer(n) => Backend::delete_framebuffer(n),
+            Self::Renderbuffer(n) => Backend::delete_renderbuffer(n),
+            Self::Texture(n) => {
+                Backend::delete_texture(n);
+                texture_units::forget_texture(n);
+                environment::forget_source(n);
+            }
+            Self::Sampler(n) => {
+                Backend::delete_sampler(n);
+                texture_units::forget_sampler(n);
+            }
+        }
+    }
+
+    //mi name
+    /// Get the OpenGL name of the object
+    fn name(&self) -> GLuint {
+        match self {
+            Self::Buffer(n) | Self::Shader(n) | Self::Program(n) |
+            Self::VertexArray(n) | Self::Query(n) | Self::Framebuffer(n) |
+            Self::Renderbuffer(n) | Self::Texture(n) | Self::Sampler(n) => *n,
+        }
+    }
+}
+
+//a Statics
+/// The objects awaiting deletion for each live context generation
+static QUEUES : Mutex<Vec<(u32, Vec<GlObject>)>> = Mutex::new(Vec::new());
+
+/// The context generations that have been lost, whose objects are
+/// discarded; these are pruned when a context is created
+static LOST : Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// The latest lost context generation pruned from [LOST]
+static PRUNED : AtomicU32 = AtomicU32::new(0);
+
+//a Functions
+//fi with_queues
+/// Invoke a function with the deletion queues locked
+fn with_queues<R, F:FnOnce(&mut Vec<(u32, Vec<GlObject>)>) -> R>(f:F) -> R {
+    f(&mut QUEUES.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+//fp delete
+/// Queue an OpenGL object, created in a context generation, for
+/// deletion (ignoring the name 0 and objects from lost contexts)
+pub(crate) fn delete(generation:u32, object:GlObject) {
+    if object.name() == 0 {
+        return;
+    }
+    let queued = with_queues(|queues| {
+        queues.iter_mut()
+            .find(|(g, _)| *g == generation)
+            .map(|(_, queue)| queue.push(object))
+            .is_some()
+    });
+    if !queued && !std::thread::panicking() {
+        debug_assert!(is_lost(generation),
+                      "{:?} dropped with no RenderContext for its context, so it will never be deleted", object);
+    }
+}
+
+//fi is_lost
+/// Return true if a context generation has been lost, or may have
+/// been lost and since pruned
+fn is_lost(generation:u32) -> bool {
+    (generation != 0 && generation <= PRUNED.load(Ordering::Relaxed)) ||
+        LOST.lock().unwrap_or_else(|e| e.into_inner()).contains(&generation)
+}
+
+//fp flush
+/// Delete all the objects in the queue of a context generation,
+/// returning the number deleted; the context must be current
+pub(crate) fn flush(generation:u32) -> usize {
+    let objects = with_queues(|queues| {
+        queues.iter_mut()
+            .find(|(g, _)| *g == generation)
+            .map(|(_, queue)| std::mem::take(queue))
+            .unwrap_or_default()
+    });
+    let n = objects.len();
+    for object in objects {
+        object.delete();
+    }
+    n
+}
+
+//fp pending_deletions
+/// Get the number of objects awaiting deletion for a context
+/// generation
+pub(crate) fn pending_deletions(generation:u32) -> usize {
+    with_queues(|queues| {
+        queues.iter()
+            .find(|(g, _)| *g == generation)
+            .map_or(0, |(_, queue)| queue.len())
+    })
+}
+
+//fp context_created
+/// Add a queue for a new context generation, and prune the lost
+/// generations older than it
+pub(crate) fn context_created(generation:u32) {
+    with_queues(|queues| queues.push((generation, Vec::new())));
+    let mut lost = LOST.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pruned) = lost.iter().filter(|g| **g < generation).max() {
+        PRUNED.fetch_max(*pruned, Ordering::Relaxed);
+    }
+    lost.retain(|g| *g >= generation);
+}
+
+//fp context_dropped
+/// Discard the queue of a context generation, as its context has been
+/// dropped
+pub(crate) fn context_dropped(generation:u32) {
+    with_queues(|queues| queues.retain(|(g, _)| *g != generation));
+}
+
+//fp context_lost
+/// Discard the queue of a context generation, as its context has been
+/// lost; objects from the generation dropped later are discarded
+pub(crate) fn context_lost(generation:u32) {
+    LOST.lock().unwrap_or_else(|e| e.into_inner()).push(generation);
+    context_dropped(generation);
+}
@@ -0,0 +1,644 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    environment.rs
+@brief   Image-based lighting environments prefiltered on the GPU
+ */
+
+//a Documentation
+
+/*!
+
+An [Environment] provides image-based lighting for physically based
+programs. It is created from an equirectangular (latitude-longitude)
+HDR texture with [Environment::from_equirectangular], which renders a
+sequence of fullscreen passes into half-float textures:
+
+* the source is resampled into an environment cube map (with
+  mipmaps), whose faces are of a given size
+
+* the diffuse irradiance cube map is the cosine-weighted convolution
+  of the environment over the hemisphere of each direction, divided
+  by pi - so that the diffuse term is its product with the albedo
+
+* the prefiltered specular cube map has a mip chain of increasing
+  roughness (from 0 at the base level to 1 at the last), each level
+  importance sampling the GGX distribution of the environment (with
+  the normal, view and reflection directions the same)
+
+* the split-sum BRDF lookup texture holds the scale (red) and bias
+  (green) of the Fresnel reflectance at normal incidence, by the
+  cosine of the view angle (s) and the roughness (t); it does not
+  depend on the environment, and is shared by all of them
+
+The source is sampled with the top row of its image at t of 0 (as
+images are decoded without flipping), with the +Y axis up and the
+centre of the image towards +X.
+
+The pass programs and the BRDF lookup texture are created on first use
+and kept for the context, and the passes bind their sources to a
+texture unit that the [RenderContext] reserves once; environments are
+cached by their source texture and size, so that creating one again
+while it is in use returns the same one. The results are not recreated
+after context loss - they must be created again from the source.
+
+For the main pass, [Environment::upload] binds the textures to the
+texture units of the [TextureSlot::Irradiance],
+[TextureSlot::Prefiltered] and [TextureSlot::BrdfLut] slots, setting
+the [UniformId::Texture] sampler uniforms of a program for those
+slots, and sets [UniformId::Environment] to the highest mip level of
+the prefiltered map and the intensity of the environment.
+
+Rendering to half-float textures requires
+[GlFeature::FloatRenderTargets]. The passes leave the default
+framebuffer bound, and its viewport must be set again.
+
+!*/
+
+//a Imports
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+
+use gl::types::*;
+
+use crate::{RenderContext, ShaderClass, UniformId, TextureSlot, GlFeature};
+use crate::{Texture, TextureKind, TextureFormat, TextureImage, CubeFace, Framebuffer, GlProgram};
+use crate::{Backend, GlBackend, DebugGroup, Mat4};
+use crate::deletion::{self, GlObject};
+use crate::recovery;
+use crate::texture_units;
+
+//a Constants
+//ci IRRADIANCE_SIZE
+/// The size of the faces of the irradiance cube map
+const IRRADIANCE_SIZE : usize = 32;
+
+//ci PREFILTERED_SIZE
+/// The maximum size of the faces of the base level of the
+/// prefiltered cube map
+const PREFILTERED_SIZE : usize = 128;
+
+//ci PREFILTERED_LEVELS
+/// The maximum number of mip levels of the prefiltered cube map
+const PREFILTERED_LEVELS : usize = 5;
+
+//ci BRDF_LUT_SIZE
+/// The width and height of the BRDF lookup texture
+const BRDF_LUT_SIZE : usize = 256;
+
+//ci FULLSCREEN_VERTEX_SHADER
+/// Vertex shader of all the passes - a triangle covering the viewport,
+/// from the vertex index (with no vertex attributes)
+const FULLSCREEN_VERTEX_SHADER : &str = "#version 330 core
+out vec2 vNdc;
+void main() {
+    vNdc = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2)) * 2.0 - 1.0;
+    gl_Position = vec4(vNdc, 0.0, 1.0);
+}
+";
+
+//ci PASS_GLSL
+/// Declarations at the start of the fragment shaders of all the
+/// passes
+const PASS_GLSL : &str = "#version 330 core
+#define PI 3.14159265
+in vec2 vNdc;
+out vec4 fragColor;
+";
+
+//ci CUBE_FACE_GLSL
+/// Fragment shader function giving the direction through the fragment
+/// of the cube map face being rendered
+const CUBE_FACE_GLSL : &str = "
+uniform mat4 uFace;
+vec3 faceDirection() {
+    return normalize(mat3(uFace) * vec3(vNdc, 1.0));
+}
+";
+
+//ci GGX_GLSL
+/// Fragment shader functions for importance sampling the GGX
+/// distribution with a Hammersley sequence
+const GGX_GLSL : &str = "
+#define SAMPLES 1024u
+vec2 hammersley(uint i) {
+    uint bits = (i << 16u) | (i >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return vec2(float(i) / float(SAMPLES), float(bits) * 2.3283064365386963e-10);
+}
+vec3 sampleGgx(vec2 xi, vec3 n, float a) {
+    float phi = 2.0 * PI * xi.x;
+    float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+    vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 t = normalize(cross(up, n));
+    vec3 b = cross(n, t);
+    return normalize((t * cos(phi) + b * sin(phi)) * sinTheta + n * cosTheta);
+}
+";
+
+//ci EQUIRECTANGULAR_FRAGMENT_SHADER
+/// Fragment shader of the pass resampling the equirectangular source
+/// into a cube map face
+const EQUIRECTANGULAR_FRAGMENT_SHADER : &str = "
+uniform sampler2D uSource;
+void main() {
+    vec3 d = faceDirection();
+    vec2 uv = vec2(atan(d.z, d.x) / (2.0 * PI) + 0.5, 0.5 - asin(clamp(d.y, -1.0, 1.0)) / PI);
+    fragColor = vec4(textureLod(uSource, uv, 0.0).rgb, 1.0);
+}
+";
+
+//ci IRRADIANCE_FRAGMENT_SHADER
+/// Fragment shader of the pass convolving the environment into a face
+/// of the irradiance cube map; uParams.x is the mip level of the
+/// environment to sample
+const IRRADIANCE_FRAGMENT_SHADER : &str = "
+#define DELTA 0.025
+uniform samplerCube uSource;
+uniform vec4 uParams;
+void main() {
+    vec3 n = faceDirection();
+    vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, n));
+    up = cross(n, right);
+    vec3 irradiance = vec3(0.0);
+    float samples = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += DELTA) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += DELTA) {
+            vec3 t = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 d = t.x * right + t.y * up + t.z * n;
+            irradiance += textureLod(uSource, d, uParams.x).rgb * cos(theta) * sin(theta);
+            samples += 1.0;
+        }
+    }
+    fragColor = vec4(PI * irradiance / samples, 1.0);
+}
+";
+
+//ci PREFILTER_FRAGMENT_SHADER
+/// Fragment shader of the pass prefiltering the environment into a
+/// face of a mip level of the prefiltered cube map; uParams is the
+/// roughness, the face size and the highest mip level of the
+/// environment
+///
+/// Each sample reads the mip level of the environment whose texels
+/// cover the solid angle of the sample, to avoid aliasing
+const PREFILTER_FRAGMENT_SHADER : &str = "
+uniform samplerCube uSource;
+uniform vec4 uParams;
+void main() {
+    vec3 n = faceDirection();
+    float a = uParams.x * uParams.x;
+    float texelAngle = 4.0 * PI / (6.0 * uParams.y * uParams.y);
+    vec3 color = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < SAMPLES; i++) {
+        vec3 h = sampleGgx(hammersley(i), n, a);
+        vec3 l = normalize(2.0 * dot(n, h) * h - n);
+        float ndl = dot(n, l);
+        if (ndl > 0.0) {
+            float ndh = max(dot(n, h), 0.0);
+            float d = ndh * ndh * (a * a - 1.0) + 1.0;
+            float pdf = a * a / (4.0 * PI * d * d) + 0.0001;
+            float sampleAngle = 1.0 / (float(SAMPLES) * pdf);
+            float lod = uParams.x == 0.0 ? 0.0 : 0.5 * log2(sampleAngle / texelAngle);
+            color += textureLod(uSource, l, clamp(lod, 0.0, uParams.z)).rgb * ndl;
+            weight += ndl;
+        }
+    }
+    fragColor = vec4(color / max(weight, 0.0001), 1.0);
+}
+";
+
+//ci BRDF_FRAGMENT_SHADER
+/// Fragment shader of the pass integrating the split-sum BRDF, by the
+/// cosine of the view angle (x) and the roughness (y)
+const BRDF_FRAGMENT_SHADER : &str = "
+float geometry(float ndx, float k) {
+    return ndx / (ndx * (1.0 - k) + k);
+}
+void main() {
+    vec2 uv = vNdc * 0.5 + 0.5;
+    float ndv = max(uv.x, 0.001);
+    float a = uv.y * uv.y;
+    float k = a / 2.0;
+    vec3 n = vec3(0.0, 0.0, 1.0);
+    vec3 v = vec3(sqrt(1.0 - ndv * ndv), 0.0, ndv);
+    vec2 sum = vec2(0.0);
+    for (uint i = 0u; i < SAMPLES; i++) {
+        vec3 h = sampleGgx(hammersley(i), n, a);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+        float ndl = max(l.z, 0.0);
+        if (ndl > 0.0) {
+            float ndh = max(h.z, 0.0);
+            float vdh = max(dot(v, h), 0.0);
+            float visibility = geometry(ndv, k) * geometry(ndl, k) * vdh / (ndh * ndv);
+            float fresnel = pow(1.0 - vdh, 5.0);
+            sum += vec2((1.0 - fresnel) * visibility, fresnel * visibility);
+        }
+    }
+    fragColor = vec4(sum / float(SAMPLES), 0.0, 1.0);
+}
+";
+
+//a Passes
+//ti Passes
+/// The programs of the passes, with the vertex array object they draw
+/// with (which has no attributes) and the texture unit their source is
+/// bound to
+struct Passes {
+    /// Resampling the equirectangular source into the environment
+    equirectangular : GlProgram,
+    /// Convolving the environment into the irradiance map
+    irradiance : GlProgram,
+    /// Prefiltering the environment into the prefiltered map
+    prefilter : GlProgram,
+    /// Integrating the BRDF into the lookup texture
+    brdf : GlProgram,
+    /// The vertex array object
+    vao : GLuint,
+    /// The texture unit reserved for the source of a pass
+    unit : usize,
+    /// The context generation the objects were created in
+    generation : u32,
+}
+
+//ii Passes
+impl Passes {
+    //fi new
+    /// Compile the programs, with the texture unit reserved for
+    /// their sources by the [RenderContext]
+    fn new(render_context:&RenderContext) -> Result<Self, String> {
+        render_context.require(GlFeature::FloatRenderTargets)?;
+        let face = Some("uFace");
+        let equirectangular = Self::program("equirectangular", &[CUBE_FACE_GLSL, EQUIRECTANGULAR_FRAGMENT_SHADER],
+                                            face, Some("uSource"), None)?;
+        let irradiance = Self::program("irradiance", &[CUBE_FACE_GLSL, IRRADIANCE_FRAGMENT_SHADER],
+                                       face, Some("uSource"), Some("uParams"))?;
+        let prefilter = Self::program("prefilter", &[CUBE_FACE_GLSL, GGX_GLSL, PREFILTER_FRAGMENT_SHADER],
+                                      face, Some("uSource"), Some("uParams"))?;
+        let brdf = Self::program("BRDF", &[GGX_GLSL, BRDF_FRAGMENT_SHADER], None, None, None)?;
+        let unit = render_context.environment_unit()?;
+        let vao = Backend::gen_vertex_array();
+        Ok(Self { equirectangular, irradiance, prefilter, brdf, vao, unit, generation:recovery::generation() })
+    }
+
+    //fi program
+    /// Compile the program of a pass from fragment shader pieces (after
+    /// [PASS_GLSL]), with its face matrix, source sampler and
+    /// parameters uniforms as [UniformId::User] 0, 1 and 2
+    fn program(name:&str, pieces:&[&str], face:Option<&str>, source:Option<&str>, params:Option<&str>) -> Result<GlProgram, String> {
+        let fragment_shader = format!("{}{}", PASS_GLSL, pieces.concat());
+        let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, FULLSCREEN_VERTEX_SHADER),
+                                                       (gl::FRAGMENT_SHADER, &fragment_shader)])?;
+        for (n, uniform) in [face, source, params].iter().enumerate() {
+            if let Some(uniform) = uniform {
+                program.add_uniform_name(uniform, UniformId::User(n))?;
+            }
+        }
+        program.set_name(&format!("{} environment pass", name));
+        Ok(program)
+    }
+
+    //mi render
+    /// Render a pass into an image of a mip level of a texture (for
+    /// each face of a cube map), with a source texture and parameters
+    fn render(&self, program:&GlProgram, texture:&Texture, level:usize, source:Option<&Texture>, params:[f32; 4]) -> Result<(), String> {
+        Backend::use_program(program.id());
+        if let (Some(u), Some(source)) = (program.uniform(UniformId::User(1)), source) {
+            source.bind_unit(self.unit, None)?;
+            Backend::uniform_1i(u, self.unit as GLint);
+        }
+        if let Some(u) = program.uniform(UniformId::User(2)) {
+            Backend::uniform_4f(u, &params);
+        }
+        Backend::bind_vertex_array(self.vao);
+        if texture.kind() == TextureKind::CubeMap {
+            for face in CubeFace::ALL {
+                let framebuffer = Framebuffer::of_color_texture(texture, TextureImage::Face(face), level)?;
+                framebuffer.bind();
+                if let Some(u) = program.uniform(UniformId::User(0)) {
+                    Backend::uniform_matrix4(u, &face_basis(face));
+                }
+                Backend::draw_arrays(gl::TRIANGLES, 0, 3);
+            }
+        } else {
+            let framebuffer = Framebuffer::of_color_texture(texture, TextureImage::Image, level)?;
+            framebuffer.bind();
+            Backend::draw_arrays(gl::TRIANGLES, 0, 3);
+        }
+        Backend::bind_vertex_array(0);
+        Ok(())
+    }
+
+    //mi render_brdf_lut
+    /// Create the BRDF lookup texture
+    fn render_brdf_lut(&self) -> Result<Texture, String> {
+        let texture = Texture::new_2d(BRDF_LUT_SIZE, BRDF_LUT_SIZE, TextureFormat::Rg16F, None)?;
+        texture.bind()?;
+        Backend::tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+        Backend::tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+        texture_units::bind_texture(gl::TEXTURE_2D, 0)?;
+        texture.set_label("BRDF lookup");
+        self.render(&self.brdf, &texture, 0, None, [0.; 4])?;
+        Ok(texture)
+    }
+}
+
+//ip Drop for Passes
+impl Drop for Passes {
+    //fp drop
+    /// Delete the vertex array object
+    fn drop(&mut self) {
+        deletion::delete(self.generation, GlObject::VertexArray(self.vao));
+    }
+}
+
+//fi face_basis
+/// Get the matrix (of which the upper 3x3 is used) that maps a
+/// fullscreen position (x, y, 1) to the direction through it of a
+/// cube map face, following the cube map texture coordinate
+/// conventions (with the bottom row of the framebuffer at t of 0)
+fn face_basis(face:CubeFace) -> Mat4 {
+    let (s, t, n) : ([f32; 3], [f32; 3], [f32; 3]) = {
+        match face {
+            CubeFace::PositiveX => ([0., 0., -1.], [0., -1., 0.], [1., 0., 0.]),
+            CubeFace::NegativeX => ([0., 0., 1.], [0., -1., 0.], [-1., 0., 0.]),
+            CubeFace::PositiveY => ([1., 0., 0.], [0., 0., 1.], [0., 1., 0.]),
+            CubeFace::NegativeY => ([1., 0., 0.], [0., 0., -1.], [0., -1., 0.]),
+            CubeFace::PositiveZ => ([1., 0., 0.], [0., -1., 0.], [0., 0., 1.]),
+            CubeFace::NegativeZ => ([-1., 0., 0.], [0., -1., 0.], [0., 0., -1.]),
+        }
+    };
+    [s[0], s[1], s[2], 0.,
+     t[0], t[1], t[2], 0.,
+     n[0], n[1], n[2], 0.,
+     0., 0., 0., 1.]
+}
+
+//a Cache
+//ti Cache
+/// The pass programs, BRDF lookup texture and environments of a
+/// context generation
+#[derive(Default)]
+struct Cache {
+    /// The context generation the contents are for
+    generation : u32,
+    /// The pass programs
+    passes : Option<Rc<Passes>>,
+    /// The BRDF lookup texture
+    brdf_lut : Option<Rc<Texture>>,
+    /// The environments, by source texture and cube map size
+    environments : Vec<(GLuint, usize, Weak<Environment>)>,
+}
+
+//ii Cache
+impl Cache {
+    //mi validate
+    /// Drop the contents if they are for an earlier context generation
+    fn validate(&mut self) {
+        if !recovery::is_current(self.generation) {
+            self.generation = recovery::generation();
+            self.passes = None;
+            self.brdf_lut = None;
+            self.environments.clear();
+        }
+    }
+}
+
+//a Statics
+thread_local! {
+    /// The image-based lighting cache of the render thread
+    static CACHE : RefCell<Cache> = RefCell::new(Cache::default());
+}
+
+//a Environment
+//tp Environment
+/// Image-based lighting prefiltered from an equirectangular HDR
+/// texture, with environment, irradiance and prefiltered cube maps and
+/// the shared BRDF lookup texture
+#[derive(Debug)]
+pub struct Environment {
+    /// The environment cube map, with mipmaps
+    cube_map : Texture,
+    /// The diffuse irradiance cube map
+    irradiance : Texture,
+    /// The prefiltered specular cube map
+    prefiltered : Texture,
+    /// The BRDF lookup texture
+    brdf_lut : Rc<Texture>,
+    /// The intensity the lighting is scaled by
+    intensity : Cell<f32>,
+}
+
+//ip Environment
+impl Environment {
+    //fp from_equirectangular
+    /// Get the environment of an equirectangular 2D texture (ideally
+    /// with a float format) with cube map faces of a size, rendering
+    /// it if it is not cached
+    pub fn from_equirectangular(render_context:&RenderContext, source:&Texture, size:usize) -> Result<Rc<Self>, String> {
+        if source.kind() != TextureKind::Texture2D {
+            return Err(format!("Cannot create an environment from a {} texture", source.kind()));
+        }
+        if !source.is_current() {
+            return Err("Cannot create an environment from a texture of a lost context".to_string());
+        }
+        if !source.is_ready() {
+            return Err("Cannot create an environment from a texture that is still uploading".to_string());
+        }
+        let gl_source = source.gl_texture();
+        let cached = CACHE.with(|c| {
+            let mut c = c.borrow_mut();
+            c.validate();
+            c.environments.retain(|(_, _, e)| e.strong_count() > 0);
+            c.environments.iter()
+                .find(|(s, n, _)| *s == gl_source && *n == size)
+                .and_then(|(_, _, e)| e.upgrade())
+        });
+        if let Some(environment) = cached {
+            return Ok(environment);
+        }
+        let environment = Rc::new(Self::render(render_context, source, size)?);
+        CACHE.with(|c| {
+            c.borrow_mut().environments.push((gl_source, size, Rc::downgrade(&environment)));
+        });
+        Ok(environment)
+    }
+
+    //fi passes
+    /// Get the pass programs and BRDF lookup texture, creating them on
+    /// first use
+    fn passes(render_context:&RenderContext) -> Result<(Rc<Passes>, Rc<Texture>), String> {
+        let cached = CACHE.with(|c| {
+            let mut c = c.borrow_mut();
+            c.validate();
+            c.passes.clone().zip(c.brdf_lut.clone())
+        });
+        if let Some(cached) = cached {
+            return Ok(cached);
+        }
+        let passes = Rc::new(Passes::new(render_context)?);
+        let brdf_lut = Rc::new(passes.render_brdf_lut()?);
+        CACHE.with(|c| {
+            let mut c = c.borrow_mut();
+            c.passes = Some(passes.clone());
+            c.brdf_lut = Some(brdf_lut.clone());
+        });
+        Ok((passes, brdf_lut))
+    }
+
+    //fi render
+    /// Render the textures of the environment
+    fn render(render_context:&RenderContext, source:&Texture, size:usize) -> Result<Self, String> {
+        let _group = DebugGroup::new("environment");
+        let capabilities = [gl::DEPTH_TEST, gl::BLEND, gl::CULL_FACE, gl::SCISSOR_TEST];
+        let enabled : Vec<bool> = capabilities.iter().map(|c| Backend::is_enabled(*c)).collect();
+        for c in capabilities {
+            Backend::disable(c);
+        }
+        let result = Self::render_passes(render_context, source, size);
+        for (c, e) in capabilities.iter().zip(enabled) {
+            if e {
+                Backend::enable(*c);
+            }
+        }
+        Framebuffer::unbind();
+        result
+    }
+
+    //fi render_passes
+    /// Render the passes (with the fixed-function state already set
+    /// up)
+    fn render_passes(render_context:&RenderContext, source:&Texture, size:usize) -> Result<Self, String> {
+        let (passes, brdf_lut) = Self::passes(render_context)?;
+
+        let mut cube_map = Texture::new_cube_map(size, TextureFormat::Rgba16F, None)?;
+        cube_map.set_label("environment");
+        passes.render(&passes.equirectangular, &cube_map, 0, Some(source), [0.; 4])?;
+        cube_map.generate_mipmaps()?;
+        let max_level = (cube_map.levels() - 1) as f32;
+
+        let irradiance = Texture::new_cube_map(IRRADIANCE_SIZE, TextureFormat::Rgba16F, None)?;
+        irradiance.set_label("irradiance");
+        let irradiance_level = (size as f32 / IRRADIANCE_SIZE as f32).log2().clamp(0., max_level);
+        passes.render(&passes.irradiance, &irradiance, 0, Some(&cube_map), [irradiance_level, 0., 0., 0.])?;
+
+        let prefiltered_size = size.min(PREFILTERED_SIZE);
+        let mut prefiltered = Texture::new_cube_map(prefiltered_size, TextureFormat::Rgba16F, None)?;
+        prefiltered.generate_mipmaps()?;
+        let levels = prefiltered.levels().min(PREFILTERED_LEVELS);
+        prefiltered.bind()?;
+        let prefiltered = prefiltered.with_levels(levels)?;
+        prefiltered.set_label("prefiltered environment");
+        for level in 0..levels {
+            let roughness = if levels > 1 { level as f32 / (levels - 1) as f32 } else { 0. };
+            passes.render(&passes.prefilter, &prefiltered, level, Some(&cube_map),
+                          [roughness, size as f32, max_level, 0.])?;
+        }
+        Ok(Self { cube_map, irradiance, prefiltered, brdf_lut, intensity:Cell::new(1.) })
+    }
+
+    //ap cube_map
+    /// Get the environment cube map (with mipmaps), for example to
+    /// draw as a skybox
+    pub fn cube_map(&self) -> &Texture {
+        &self.cube_map
+    }
+
+    //ap irradiance
+    /// Get the diffuse irradiance cube map
+    pub fn irradiance(&self) -> &Texture {
+        &self.irradiance
+    }
+
+    //ap prefiltered
+    /// Get the prefiltered specular cube map
+    pub fn prefiltered(&self) -> &Texture {
+        &self.prefiltered
+    }
+
+    //ap brdf_lut
+    /// Get the BRDF lookup texture
+    pub fn brdf_lut(&self) -> &Texture {
+        &self.brdf_lut
+    }
+
+    //ap max_level
+    /// Get the highest mip level of the prefiltered cube map (that of
+    /// a roughness of 1)
+    pub fn max_level(&self) -> usize {
+        self.prefiltered.levels() - 1
+    }
+
+    //ap intensity
+    /// Get the intensity the lighting is scaled by
+    pub fn intensity(&self) -> f32 {
+        self.intensity.get()
+    }
+
+    //mp set_intensity
+    /// Set the intensity the lighting is scaled by (1 by default)
+    pub fn set_intensity(&self, intensity:f32) {
+        self.intensity.set(intensity);
+    }
+
+    //mp upload
+    /// Bind the textures to the texture units of their slots and set
+    /// the sampler and [UniformId::Environment] uniforms of a program
+    /// (which must be in use)
+    pub fn upload(&self, shader_class:&dyn ShaderClass) -> Result<(), String> {
+        for (slot, texture) in [(TextureSlot::Irradiance, &self.irradiance),
+                                (TextureSlot::Prefiltered, &self.prefiltered),
+                                (TextureSlot::BrdfLut, &*self.brdf_lut)] {
+            if let Some(u) = shader_class.uniform(UniformId::Texture(slot)) {
+                let unit = texture_units::slot_unit(slot)?;
+                texture.bind_unit(unit, None)?;
+                Backend::uniform_1i(u, unit as GLint);
+            }
+        }
+        if let Some(u) = shader_class.uniform(UniformId::Environment) {
+            Backend::uniform_4f(u, &[self.max_level() as f32, self.intensity(), 0., 0.]);
+        }
+        Ok(())
+    }
+
+    //zz All done
+}
+
+//ip Display for Environment
+impl std::fmt::Display for Environment {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "environment {} with {} prefiltered levels", self.cube_map, self.prefiltered.levels())
+    }
+}
+
+//a Functions
+//fp forget_source
+/// Drop the cache entries of environments of a source texture (when it
+/// is deleted, as its name may be reused)
+pub(crate) fn forget_source(texture:GLuint) {
+    CACHE.with(|c| c.borrow_mut().environments.retain(|(s, _, _)| *s != texture));
+}
+
+//fp clear_cache
+/// Drop the pass programs, BRDF lookup texture and environment cache
+/// (when the [crate::RenderContext] is dropped)
+pub(crate) fn clear_cache() {
+    CACHE.with(|c| *c.borrow_mut() = Cache::default());
+}
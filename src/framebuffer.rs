@@ -0,0 +1,234 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    framebuffer.rs
+@brief   An OpenGL offscreen framebuffer
+ */
+
+//a Imports
+use crate::{Texture, TextureKind, TextureImage};
+use crate::{Backend, GlBackend};
+use crate::deletion::{self, GlObject};
+use crate::recovery;
+
+//a Framebuffer
+//tp Framebuffer
+/// An offscreen OpenGL framebuffer with an RGBA8 colour renderbuffer
+/// and a 24-bit depth renderbuffer
+///
+/// This is used for rendering without a window (for example when
+/// comparing renders against golden images), and the pixels may be
+/// read back after drawing
+///
+/// Alternatively it may be a depth-only framebuffer rendering to an
+/// image of a depth texture (such as a shadow map), with
+/// [Framebuffer::of_depth_texture], or a colour-only framebuffer
+/// rendering to an image of a colour texture, with
+/// [Framebuffer::of_color_texture]
+pub struct Framebuffer {
+    /// The GL framebuffer object
+    gl_fbo : gl::types::GLuint,
+    /// The GL colour renderbuffer (0 if none)
+    gl_color : gl::types::GLuint,
+    /// The GL depth renderbuffer (0 if none)
+    gl_depth : gl::types::GLuint,
+    /// Width in pixels
+    width : usize,
+    /// Height in pixels
+    height : usize,
+    /// The context generation the objects were created in
+    generation : u32,
+}
+
+//ip Framebuffer
+impl Framebuffer {
+    //fp new
+    /// Create a new framebuffer of the given size with colour and
+    /// depth renderbuffers
+    pub fn new(width:usize, height:usize) -> Result<Self, String> {
+        let gl_fbo = Backend::gen_framebuffer();
+        Backend::bind_framebuffer(gl::FRAMEBUFFER, gl_fbo);
+
+        let gl_color = Backend::gen_renderbuffer();
+        Backend::bind_renderbuffer(gl_color);
+        Backend::renderbuffer_storage(gl::RGBA8, width as i32, height as i32);
+        Backend::framebuffer_renderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl_color);
+
+        let gl_depth = Backend::gen_renderbuffer();
+        Backend::bind_renderbuffer(gl_depth);
+        Backend::renderbuffer_storage(gl::DEPTH_COMPONENT24, width as i32, height as i32);
+        Backend::framebuffer_renderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl_depth);
+
+        Backend::bind_renderbuffer(0);
+        let status = Backend::check_framebuffer_status(gl::FRAMEBUFFER);
+        Backend::bind_framebuffer(gl::FRAMEBUFFER, 0);
+        let generation = recovery::generation();
+        let fb = Self { gl_fbo, gl_color, gl_depth, width, height, generation };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            Err(format!("Framebuffer incomplete (status {:x})", status))
+        } else {
+            Ok(fb)
+        }
+    }
+
+    //fp of_depth_texture
+    /// Create a depth-only framebuffer rendering to an image (the 2D
+    /// image, a cube map face or an array layer) of the base level of
+    /// a depth texture
+    ///
+    /// The texture must not be dropped while the framebuffer is used
+    pub fn of_depth_texture(texture:&Texture, image:TextureImage) -> Result<Self, String> {
+        if !texture.format().is_depth() {
+            return Err(format!("Cannot render depth to a {} texture", texture.format()));
+        }
+        let gl_fbo = Backend::gen_framebuffer();
+        let generation = recovery::generation();
+        let (width, height) = (texture.width(), texture.height());
+        let fb = Self { gl_fbo, gl_color:0, gl_depth:0, width, height, generation };
+        Backend::bind_framebuffer(gl::FRAMEBUFFER, gl_fbo);
+        if !Self::attach(texture, image, gl::DEPTH_ATTACHMENT, 0) {
+            Backend::bind_framebuffer(gl::FRAMEBUFFER, 0);
+            return Err(format!("Cannot render to {:?} of {}", image, texture));
+        }
+        Backend::draw_buffers(&[gl::NONE]);
+        Backend::read_buffer(gl::NONE);
+        fb.complete()
+    }
+
+    //fp of_color_texture
+    /// Create a colour-only framebuffer rendering to an image (the 2D
+    /// image, a cube map face or an array layer) of a mip level of a
+    /// colour texture; the framebuffer is the size of the mip level
+    ///
+    /// The texture must not be dropped while the framebuffer is used
+    pub fn of_color_texture(texture:&Texture, image:TextureImage, level:usize) -> Result<Self, String> {
+        if texture.format().is_depth() || texture.format().is_compressed() {
+            return Err(format!("Cannot render colour to a {} texture", texture.format()));
+        }
+        if level >= texture.levels() {
+            return Err(format!("Mip level {} of texture has no image", level));
+        }
+        let gl_fbo = Backend::gen_framebuffer();
+        let generation = recovery::generation();
+        let (width, height) = texture.level_size(level);
+        let fb = Self { gl_fbo, gl_color:0, gl_depth:0, width, height, generation };
+        Backend::bind_framebuffer(gl::FRAMEBUFFER, gl_fbo);
+        if !Self::attach(texture, image, gl::COLOR_ATTACHMENT0, level) {
+            Backend::bind_framebuffer(gl::FRAMEBUFFER, 0);
+            return Err(format!("Cannot render to {:?} of {}", image, texture));
+        }
+        Backend::draw_buffers(&[gl::COLOR_ATTACHMENT0]);
+        Backend::read_buffer(gl::COLOR_ATTACHMENT0);
+        fb.complete()
+    }
+
+    //fi attach
+    /// Attach an image of a mip level of a texture to the bound
+    /// framebuffer, returning false if the image does not suit the
+    /// texture
+    fn attach(texture:&Texture, image:TextureImage, attachment:gl::types::GLenum, level:usize) -> bool {
+        let gl_texture = texture.gl_texture();
+        let level = level as i32;
+        match (texture.kind(), image) {
+            (TextureKind::Texture2D, TextureImage::Image) => {
+                Backend::framebuffer_texture_2d(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, gl_texture, level);
+                true
+            }
+            (TextureKind::CubeMap, TextureImage::Face(face)) => {
+                Backend::framebuffer_texture_2d(gl::FRAMEBUFFER, attachment, face.target(), gl_texture, level);
+                true
+            }
+            (TextureKind::Array2D, TextureImage::Layer(layer)) if layer < texture.layers() => {
+                Backend::framebuffer_texture_layer(gl::FRAMEBUFFER, attachment, gl_texture, level, layer as i32);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    //mi complete
+    /// Check the (bound) framebuffer is complete, and unbind it
+    fn complete(self) -> Result<Self, String> {
+        let status = Backend::check_framebuffer_status(gl::FRAMEBUFFER);
+        Backend::bind_framebuffer(gl::FRAMEBUFFER, 0);
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            Err(format!("Framebuffer incomplete (status {:x})", status))
+        } else {
+            Ok(self)
+        }
+    }
+
+    //ap width
+    /// Get the width of the framebuffer in pixels
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    //ap height
+    /// Get the height of the framebuffer in pixels
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    //ap gl_framebuffer
+    /// Get the GL framebuffer object
+    pub fn gl_framebuffer(&self) -> gl::types::GLuint {
+        self.gl_fbo
+    }
+
+    //mp bind
+    /// Bind the framebuffer for drawing and set the viewport to cover it
+    pub fn bind(&self) {
+        Backend::bind_framebuffer(gl::FRAMEBUFFER, self.gl_fbo);
+        Backend::viewport(0, 0, self.width as i32, self.height as i32);
+    }
+
+    //fp unbind
+    /// Restore the default framebuffer
+    pub fn unbind() {
+        Backend::bind_framebuffer(gl::FRAMEBUFFER, 0);
+    }
+
+    //mp read_rgba
+    /// Read back the colour attachment as RGBA8 pixels, with the
+    /// *top* row first (OpenGL returns the bottom row first)
+    pub fn read_rgba(&self) -> Vec<u8> {
+        let row_length = self.width * 4;
+        let mut data = vec![0_u8; row_length * self.height];
+        Backend::bind_framebuffer(gl::READ_FRAMEBUFFER, self.gl_fbo);
+        Backend::read_buffer(gl::COLOR_ATTACHMENT0);
+        Backend::pixel_store(gl::PACK_ALIGNMENT, 1);
+        Backend::read_pixels(0, 0, self.width as i32, self.height as i32,
+                             gl::RGBA, gl::UNSIGNED_BYTE, &mut data);
+        Backend::bind_framebuffer(gl::READ_FRAMEBUFFER, 0);
+        let mut flipped = Vec::with_capacity(data.len());
+        for row in data.chunks(row_length).rev() {
+            flipped.extend_from_slice(row);
+        }
+        flipped
+    }
+
+    //zz All done
+}
+
+//ip Drop for Framebuffer
+impl Drop for Framebuffer {
+    //fp drop
+    /// Delete the framebuffer and its renderbuffers
+    fn drop(&mut self) {
+        deletion::delete(self.generation, GlObject::Framebuffer(self.gl_fbo));
+        deletion::delete(self.generation, GlObject::Renderbuffer(self.gl_color));
+        deletion::delete(self.generation, GlObject::Renderbuffer(self.gl_depth));
+    }
+}
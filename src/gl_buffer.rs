@@ -17,9 +17,164 @@ limitations under the License.
  */
 
 //a Imports
-use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use gl::types::*;
 use model3d::{BufferClient, BufferData};
-use crate::{Renderable, RenderContext};
+use crate::{Renderable, RenderContext, GlFeature};
+use crate::{Backend, GlBackend};
+use crate::debug;
+use crate::deletion::{self, GlObject};
+use crate::recovery;
+
+//a BufferObject
+//ti BufferSource
+/// The data of an OpenGL buffer, retained (if context recovery is
+/// enabled) so that it can be recreated after context loss
+#[derive(Debug)]
+struct BufferSource {
+    /// Target to bind the buffer to when uploading
+    target : GLenum,
+    /// True if the buffer uses immutable storage
+    storage : bool,
+    /// The data
+    data : Vec<u8>,
+}
+
+//ti BufferObject
+/// An OpenGL buffer, shared by the clones of a [GlBuffer], which is
+/// deleted when it is dropped
+#[derive(Debug)]
+struct BufferObject {
+    /// The OpenGL name of the buffer (0 if none)
+    gl : Cell<GLuint>,
+    /// The context generation the buffer was created in
+    generation : Cell<u32>,
+    /// The debug label of the buffer
+    label : RefCell<String>,
+    /// True if the buffer uses immutable storage, so its data cannot
+    /// be written
+    immutable : bool,
+    /// The data, if retained
+    source : Option<BufferSource>,
+}
+
+thread_local! {
+    /// The buffers created, for recreation after context loss
+    static BUFFERS : RefCell<Vec<Weak<BufferObject>>> = const { RefCell::new(Vec::new()) };
+}
+
+//ip BufferObject
+impl BufferObject {
+    //fi none
+    /// A buffer object with no OpenGL buffer
+    fn none() -> Self {
+        Self { gl:Cell::new(0), generation:Cell::new(recovery::generation()), label:RefCell::new(String::new()), immutable:false, source:None }
+    }
+
+    //fi create
+    /// Create an OpenGL buffer with the data, retaining the data if
+    /// context recovery is enabled
+    fn create(target:GLenum, bytes:&[u8], storage:bool, label:&'static str) -> Rc<Self> {
+        let gl = Self::upload(target, bytes, storage, label);
+        let source = {
+            if recovery::retain_sources() {
+                Some(BufferSource { target, storage, data:bytes.to_vec() })
+            } else {
+                None
+            }
+        };
+        let label = RefCell::new(label.to_string());
+        let object = Rc::new(Self { gl:Cell::new(gl), generation:Cell::new(recovery::generation()), label,
+                                    immutable:storage, source });
+        BUFFERS.with(|b| {
+            let mut b = b.borrow_mut();
+            if b.len() % 256 == 255 {
+                b.retain(|o| o.strong_count() > 0);
+            }
+            b.push(Rc::downgrade(&object));
+        });
+        object
+    }
+
+    //fi create_uninitialized
+    /// Create an OpenGL buffer of a size with uninitialized data for
+    /// streaming or updating; this is not recreated after context loss
+    fn create_uninitialized(target:GLenum, size:usize, usage:GLenum, label:&'static str) -> Rc<Self> {
+        let gl = Backend::gen_buffer();
+        Backend::bind_buffer(target, gl);
+        Backend::buffer_data_size(target, size, usage);
+        Backend::bind_buffer(target, 0);
+        debug::label(gl::BUFFER, gl, label);
+        let label = RefCell::new(label.to_string());
+        Rc::new(Self { gl:Cell::new(gl), generation:Cell::new(recovery::generation()), label,
+                       immutable:false, source:None })
+    }
+
+    //fi upload
+    /// Generate an OpenGL buffer and upload the data to it
+    fn upload(target:GLenum, bytes:&[u8], storage:bool, label:&str) -> GLuint {
+        if target != gl::ARRAY_BUFFER {
+            // stops the buffer messing up other VAO
+            Backend::bind_vertex_array(0);
+        }
+        let gl = Backend::gen_buffer();
+        Backend::bind_buffer(target, gl);
+        if storage {
+            // No flags, as the data is never changed or mapped
+            Backend::buffer_storage(target, bytes, 0);
+        } else {
+            Backend::buffer_data(target, bytes, gl::STATIC_DRAW);
+        }
+        Backend::bind_buffer(target, 0); // unbind to protect
+        debug::label(gl::BUFFER, gl, label);
+        gl
+    }
+
+    //mi recreate
+    /// Recreate the OpenGL buffer in the current context, if it is
+    /// stale; return false if it could not be as its data was not
+    /// retained
+    fn recreate(&self) -> bool {
+        if self.gl.get() == 0 || recovery::is_current(self.generation.get()) {
+            return true;
+        }
+        match &self.source {
+            Some(s) => {
+                self.gl.set(Self::upload(s.target, &s.data, s.storage, &self.label.borrow()));
+                self.generation.set(recovery::generation());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+//ip Drop for BufferObject
+impl Drop for BufferObject {
+    //fp drop
+    /// If an OpenGL buffer has been created for this then delete it
+    fn drop(&mut self) {
+        deletion::delete(self.generation.get(), GlObject::Buffer(self.gl.get()));
+    }
+}
+
+//fp recreate_all
+/// Recreate all the stale OpenGL buffers after context loss,
+/// returning an error if any could not be recreated
+pub(crate) fn recreate_all() -> Result<(), String> {
+    let buffers : Vec<Rc<BufferObject>> = BUFFERS.with(|b| {
+        let mut b = b.borrow_mut();
+        b.retain(|o| o.strong_count() > 0);
+        b.iter().filter_map(|o| o.upgrade()).collect()
+    });
+    let failed = buffers.iter().filter(|o| !o.recreate()).count();
+    if failed > 0 {
+        Err(format!("{} buffers could not be recreated as their data was not retained", failed))
+    } else {
+        Ok(())
+    }
+}
 
 //a GlBuffer
 //tp GlBuffer
@@ -40,14 +195,16 @@ use crate::{Renderable, RenderContext};
 #[derive(Debug, Clone)]
 pub struct GlBuffer {
     /// The OpenGL Buffer
-    gl   : Rc<gl::types::GLuint>,
+    gl   : Rc<BufferObject>,
+    /// The size of the buffer data in bytes
+    byte_length : usize,
 }
 
 //ip Default for GlBuffer
 impl Default for GlBuffer {
     fn default() -> Self {
-        let gl = Rc::new(0);
-        Self { gl }
+        let gl = Rc::new(BufferObject::none());
+        Self { gl, byte_length:0 }
     }
 }
 
@@ -57,10 +214,10 @@ impl BufferClient<Renderable> for GlBuffer {
     ///
     /// This may be called multiple times for the same [BufferData]; if the
     /// gl buffer is 0 then create, else it already exists with the same data
-    fn create(&mut self, data: &BufferData<Renderable>, _render_context: &mut RenderContext) {
+    fn create(&mut self, data: &BufferData<Renderable>, render_context: &mut RenderContext) {
         if self.is_none() {
             println!("Buffer create data");
-            self.of_data(data)
+            self.of_data(data, render_context)
         }
     }
 }
@@ -70,43 +227,45 @@ impl GlBuffer {
     //ap gl_buffer
     /// Get the gl_buffer associated with the data
     pub fn gl_buffer(&self) -> gl::types::GLuint {
-        *self.gl
+        self.gl.gl.get()
     }
 
     //ap as_ptr
     /// Get a pointer to this gl
     pub fn as_ptr(&self) -> *const gl::types::GLuint {
-        Rc::as_ptr(&self.gl)
+        self.gl.gl.as_ptr()
+    }
+
+    //ap byte_length
+    /// Get the size of the OpenGL buffer data in bytes (0 if not
+    /// initialized)
+    pub fn byte_length(&self) -> usize {
+        self.byte_length
     }
 
     //mp is_none
     /// Return true if the buffer is not initialized
     pub fn is_none(&self) -> bool {
-        *self.gl == 0
+        self.gl_buffer() == 0
     }
 
     //mp of_data
-    /// Create the OpenGL ARRAY_BUFFER buffer using STATIC_DRAW - this copies the data in to OpenGL
-    pub fn of_data(&mut self, data:&BufferData<Renderable>) {
+    /// Create the OpenGL ARRAY_BUFFER buffer - this copies the data in to OpenGL
+    ///
+    /// If the [RenderContext] supports buffer storage then the buffer
+    /// is immutable, otherwise it uses STATIC_DRAW
+    pub fn of_data(&mut self, data:&BufferData<Renderable>, render_context:&RenderContext) {
         assert!(self.is_none());
-        let mut gl : gl::types::GLuint = 0;
-        unsafe {
-            gl::GenBuffers(1, (&mut gl) as *mut gl::types::GLuint );
-            gl::BindBuffer(gl::ARRAY_BUFFER, gl);
-            gl::BufferData(gl::ARRAY_BUFFER,
-                           data.byte_length as gl::types::GLsizeiptr,
-                           data.as_ptr() as *const gl::types::GLvoid,
-                           gl::STATIC_DRAW );
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0 ); // unbind to protect
-        }
-        self.gl = Rc::new(gl);
+        let bytes = unsafe { std::slice::from_raw_parts(data.as_ptr(), data.byte_length as usize) };
+        let storage = render_context.supports(GlFeature::BufferStorage);
+        self.gl = BufferObject::create(gl::ARRAY_BUFFER, bytes, storage, "vertex data");
+        self.byte_length = bytes.len();
     }
 
     //mp of_indices
     /// Create the OpenGL ELEMENT_ARRAY_BUFFER buffer using STATIC_DRAW - this copies the data in to OpenGL
     pub fn of_indices(&mut self, view:&model3d::BufferView<Renderable>) {
         assert!(self.is_none());
-        let mut gl : gl::types::GLuint = 0;
         let ele_size = {
             use model3d::BufferElementType::*;
             match view.ele_type {
@@ -117,62 +276,140 @@ impl GlBuffer {
             }
         };
         let byte_length = ele_size * view.count;
-        unsafe {
-            // stops the indices messing up other VAO
-            gl::BindVertexArray(0);
+        let bytes = unsafe {
             let buffer = view.data.as_ptr().add(view.byte_offset as usize);
-            gl::GenBuffers(1, (&mut gl) as *mut gl::types::GLuint );
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, gl );
-            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER,
-                           byte_length as gl::types::GLsizeiptr,
-                           buffer as *const gl::types::GLvoid,
-                           gl::STATIC_DRAW );
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0 ); // unbind to protect
-        }
-        self.gl = Rc::new(gl);
+            std::slice::from_raw_parts(buffer, byte_length as usize)
+        };
+        self.gl = BufferObject::create(gl::ELEMENT_ARRAY_BUFFER, bytes, false, "indices");
+        self.byte_length = bytes.len();
     }
 
     //mp uniform_buffer
-    /// Create the OpenGL 
-    pub fn uniform_buffer<F:Sized>(&mut self, data:&[F]) {
+    /// Create the OpenGL UNIFORM_BUFFER buffer - this copies the data
+    /// in to OpenGL
+    ///
+    /// This returns an error if the [RenderContext] does not support
+    /// uniform buffers (OpenGL 3.1 or OpenGL ES 3.0); as with
+    /// [GlBuffer::of_data] the buffer is immutable if the context
+    /// supports buffer storage, otherwise it uses STATIC_DRAW
+    pub fn uniform_buffer<F:Sized>(&mut self, data:&[F], render_context:&RenderContext) -> Result<(), String> {
         assert!(self.is_none());
+        render_context.require(GlFeature::UniformBuffers)?;
         let buffer = data.as_ptr();
         let byte_length = std::mem::size_of::<F>() * data.len();
-        let mut gl : gl::types::GLuint = 0;
-        unsafe {
-            gl::BindVertexArray(0);
-            gl::GenBuffers(1, (&mut gl) as *mut gl::types::GLuint );
-            gl::BindBuffer(gl::UNIFORM_BUFFER, gl );
-            gl::BufferData(gl::UNIFORM_BUFFER,
-                           byte_length as gl::types::GLsizeiptr,
-                           buffer as *const gl::types::GLvoid,
-                           gl::STATIC_DRAW );
-            gl::BindBuffer(gl::UNIFORM_BUFFER, 0 ); // unbind to protect
-            println!("Uniform buffer {} bound @{:?}+{}", gl, buffer, byte_length);
-        }
-        self.gl = Rc::new(gl);
+        let bytes = unsafe { std::slice::from_raw_parts(buffer as *const u8, byte_length) };
+        let storage = render_context.supports(GlFeature::BufferStorage);
+        self.gl = BufferObject::create(gl::UNIFORM_BUFFER, bytes, storage, "uniform buffer");
+        println!("Uniform buffer {} bound @{:?}+{}", self.gl_buffer(), buffer, byte_length);
+        self.byte_length = byte_length;
+        Ok(())
     }
 
-    //zz All done
-}
+    //mp dynamic_uniform_buffer
+    /// Create an OpenGL UNIFORM_BUFFER of a size using DYNAMIC_DRAW,
+    /// with uninitialized data, to be written with
+    /// [GlBuffer::write] (such as every frame)
+    ///
+    /// This returns an error if the [RenderContext] does not support
+    /// uniform buffers (OpenGL 3.1 or OpenGL ES 3.0); the buffer is
+    /// not recreated after context loss
+    pub fn dynamic_uniform_buffer(&mut self, byte_length:usize, render_context:&RenderContext) -> Result<(), String> {
+        assert!(self.is_none());
+        render_context.require(GlFeature::UniformBuffers)?;
+        self.gl = BufferObject::create_uninitialized(gl::UNIFORM_BUFFER, byte_length, gl::DYNAMIC_DRAW,
+                                                     "dynamic uniform buffer");
+        self.byte_length = byte_length;
+        Ok(())
+    }
 
-//ip Drop for GlBuffer
-impl Drop for GlBuffer {
-    //fp drop
-    /// If an OpenGL buffer has been created for this then delete it
-    fn drop(&mut self) {
-        if Rc::strong_count(&self.gl)==1 && !self.is_none() {
-            unsafe {
-                gl::DeleteBuffers(1, self.as_ptr() );
-            }
+    //mp pixel_unpack_buffer
+    /// Create an OpenGL PIXEL_UNPACK_BUFFER of a size using
+    /// STREAM_DRAW, with uninitialized data, to be written with
+    /// [GlBuffer::write_mapped] and then used as the source of texture
+    /// uploads
+    ///
+    /// The buffer is not recreated after context loss
+    pub fn pixel_unpack_buffer(&mut self, byte_length:usize) {
+        assert!(self.is_none());
+        self.gl = BufferObject::create_uninitialized(gl::PIXEL_UNPACK_BUFFER, byte_length, gl::STREAM_DRAW,
+                                                     "pixel unpack buffer");
+        self.byte_length = byte_length;
+    }
+
+    //mp write_mapped
+    /// Map the whole of the buffer (invalidating its contents) by
+    /// binding it to a target, fill it, and unmap it; the buffer is
+    /// left bound to the target
+    ///
+    /// This requires buffer mapping (not WebGL)
+    pub fn write_mapped<F:FnOnce(&mut [u8])>(&self, target:GLenum, fill:F) -> Result<(), String> {
+        if self.is_none() {
+            return Err("Cannot map an uninitialized buffer".to_string());
+        }
+        if self.gl.immutable {
+            return Err(format!("Cannot map buffer {} for writing, as it is immutable", self.gl_buffer()));
+        }
+        Backend::bind_buffer(target, self.gl_buffer());
+        let access = gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_BUFFER_BIT;
+        let ptr = Backend::map_buffer_range(target, 0, self.byte_length, access);
+        if ptr.is_null() {
+            return Err(format!("Failed to map buffer {}", self.gl_buffer()));
+        }
+        fill(unsafe { std::slice::from_raw_parts_mut(ptr, self.byte_length) });
+        if Backend::unmap_buffer(target) {
+            Ok(())
+        } else {
+            Err(format!("Buffer {} was corrupted while mapped", self.gl_buffer()))
+        }
+    }
+
+    //mp write
+    /// Replace data of the buffer from a byte offset by binding it to
+    /// a target; the buffer is left bound to the target
+    ///
+    /// This returns an error if the buffer is immutable - that is,
+    /// if it was created by [GlBuffer::of_data] or
+    /// [GlBuffer::uniform_buffer] for a context that supports buffer
+    /// storage, as OpenGL does not permit its data to be changed
+    pub fn write(&self, target:GLenum, offset:usize, data:&[u8]) -> Result<(), String> {
+        if self.is_none() {
+            return Err("Cannot write to an uninitialized buffer".to_string());
+        }
+        if self.gl.immutable {
+            return Err(format!("Cannot write to buffer {}, as it is immutable", self.gl_buffer()));
+        }
+        if offset + data.len() > self.byte_length {
+            return Err(format!("Write of {} bytes at {} is beyond the {} bytes of buffer {}",
+                               data.len(), offset, self.byte_length, self.gl_buffer()));
         }
+        Backend::bind_buffer(target, self.gl_buffer());
+        Backend::buffer_sub_data(target, offset, data);
+        Ok(())
+    }
+
+    //mp is_current
+    /// Return true if the buffer was created in the current context
+    /// (false after context loss, unless it has been recreated)
+    pub(crate) fn is_current(&self) -> bool {
+        recovery::is_current(self.gl.generation.get())
     }
+
+    //mp set_label
+    /// Label the OpenGL buffer (if debug output is enabled) for debug
+    /// messages and tools; the label is reapplied if the buffer is
+    /// recreated after context loss
+    pub fn set_label(&self, label:&str) {
+        *self.gl.label.borrow_mut() = label.to_string();
+        debug::label(gl::BUFFER, self.gl_buffer(), label);
+    }
+
+    //zz All done
 }
 
 //ip Display for GlBuffer
 impl std::fmt::Display for GlBuffer {
     fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        write!(f,"GL({})", self.gl)
+        write!(f,"GL({})", self.gl_buffer())
     }
 }
 
@@ -0,0 +1,400 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    headless.rs
+@brief   Headless rendering and golden-image comparison
+ */
+
+//a Documentation
+
+/*!
+
+Support for rendering without a window or GPU, for regression testing
+of rendering.
+
+A [HeadlessContext] is an EGL context (without a surface if the
+driver supports EGL_KHR_surfaceless_context, otherwise with a 1x1
+pbuffer) for OpenGL 3.3 core; with Mesa llvmpipe this runs on
+machines with no GPU (with `EGL_PLATFORM=surfaceless` set if there is
+no display server). The GL function pointers are loaded when the
+context is created (for whichever [crate::GlBackend] is selected),
+and it is made current on the creating thread. The context owns the
+[RenderContext] for it, which is dropped (flushing its deletion
+queue) before the EGL context is destroyed. The EGL display is shared
+by all the contexts of the process, so it is never terminated.
+
+Drawing is to a [Framebuffer] whose pixels are read back as an
+[RgbaImage]; this can be compared with a golden PNG image using
+[RgbaImage::check_golden], which writes the actual and difference
+images alongside the golden image if they do not match. A missing
+golden image is an error; golden images are written (or rewritten)
+only when the environment variable `GL_MODEL_UPDATE_GOLDEN` is set.
+
+!*/
+
+//a Imports
+use std::path::{Path, PathBuf};
+
+use khronos_egl as egl;
+
+use crate::{Framebuffer, Renderable, RenderContext, ShaderInstantiable, GlProgram};
+use crate::{Backend, GlBackend};
+
+//a RgbaImage
+//tp RgbaImage
+/// An RGBA8 image, top row first
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaImage {
+    /// Width in pixels
+    pub width : usize,
+    /// Height in pixels
+    pub height : usize,
+    /// RGBA data, 4 bytes per pixel, `width*4` bytes per row
+    pub data : Vec<u8>,
+}
+
+//tp ImageDiff
+/// The result of comparing two [RgbaImage]s
+#[derive(Debug, Clone)]
+pub struct ImageDiff {
+    /// Largest difference of any channel of any pixel
+    pub max_channel_diff : u8,
+    /// Number of pixels with any channel differing by more than the tolerance
+    pub num_differing : usize,
+    /// Image with differing pixels in red, matching pixels in faded grey
+    pub diff_image : RgbaImage,
+}
+
+//ip RgbaImage
+impl RgbaImage {
+    //fp of_framebuffer
+    /// Read back the pixels of a [Framebuffer]
+    pub fn of_framebuffer(framebuffer:&Framebuffer) -> Self {
+        let width = framebuffer.width();
+        let height = framebuffer.height();
+        let data = framebuffer.read_rgba();
+        Self { width, height, data }
+    }
+
+    //fp read_png
+    /// Read a PNG file, converting it to RGBA8
+    pub fn read_png<P:AsRef<Path>>(path:P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()
+            .map_err(|e| format!("Failed to read PNG {}: {}", path.display(), e))?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)
+            .map_err(|e| format!("Failed to decode PNG {}: {}", path.display(), e))?;
+        let width = info.width as usize;
+        let height = info.height as usize;
+        let channels = match info.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::Rgb => 3,
+            png::ColorType::Rgba => 4,
+            c => { return Err(format!("Unsupported PNG color type {:?} in {}", c, path.display())); }
+        };
+        let mut data = Vec::with_capacity(width * height * 4);
+        for row in buf.chunks(info.line_size).take(height) {
+            for p in row[0..width*channels].chunks(channels) {
+                match channels {
+                    1 => data.extend_from_slice(&[p[0], p[0], p[0], 255]),
+                    2 => data.extend_from_slice(&[p[0], p[0], p[0], p[1]]),
+                    3 => data.extend_from_slice(&[p[0], p[1], p[2], 255]),
+                    _ => data.extend_from_slice(p),
+                }
+            }
+        }
+        Ok(Self { width, height, data })
+    }
+
+    //mp write_png
+    /// Write the image as an RGBA8 PNG file
+    pub fn write_png<P:AsRef<Path>>(&self, path:P) -> Result<(), String> {
+        let path = path.as_ref();
+        let file = std::fs::File::create(path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()
+            .map_err(|e| format!("Failed to write PNG {}: {}", path.display(), e))?;
+        writer.write_image_data(&self.data)
+            .map_err(|e| format!("Failed to write PNG {}: {}", path.display(), e))
+    }
+
+    //mp compare
+    /// Compare with another image of the same size; a pixel differs
+    /// if any channel differs by more than `tolerance`
+    pub fn compare(&self, other:&Self, tolerance:u8) -> Result<ImageDiff, String> {
+        if self.width != other.width || self.height != other.height {
+            return Err(format!("Image sizes differ: {}x{} and {}x{}",
+                               self.width, self.height, other.width, other.height));
+        }
+        let mut max_channel_diff = 0;
+        let mut num_differing = 0;
+        let mut data = Vec::with_capacity(self.data.len());
+        for (a, b) in self.data.chunks(4).zip(other.data.chunks(4)) {
+            let mut pixel_diff = 0;
+            for c in 0..4 {
+                let d = (a[c] as i16 - b[c] as i16).unsigned_abs() as u8;
+                pixel_diff = pixel_diff.max(d);
+            }
+            max_channel_diff = max_channel_diff.max(pixel_diff);
+            if pixel_diff > tolerance {
+                num_differing += 1;
+                data.extend_from_slice(&[255, 0, 0, 255]);
+            } else {
+                let grey = ((a[0] as u16 + a[1] as u16 + a[2] as u16) / 12) as u8;
+                data.extend_from_slice(&[grey, grey, grey, 255]);
+            }
+        }
+        let diff_image = Self { width:self.width, height:self.height, data };
+        Ok(ImageDiff { max_channel_diff, num_differing, diff_image })
+    }
+
+    //mp check_golden
+    /// Compare the image against a golden PNG file
+    ///
+    /// If the environment variable GL_MODEL_UPDATE_GOLDEN is set then
+    /// the golden image is written instead; otherwise a missing
+    /// golden file is an error (with the image written to
+    /// `<golden>.actual.png`).
+    ///
+    /// If the images differ then `<golden>.actual.png` and
+    /// `<golden>.diff.png` are written and an error returned
+    pub fn check_golden<P:AsRef<Path>>(&self, golden:P, tolerance:u8) -> Result<(), String> {
+        let golden = golden.as_ref();
+        if std::env::var_os("GL_MODEL_UPDATE_GOLDEN").is_some() {
+            return self.write_png(golden);
+        }
+        if !golden.exists() {
+            let actual_path = Self::sibling_path(golden, "actual");
+            self.write_png(&actual_path)?;
+            return Err(format!("Golden image {} is missing (set GL_MODEL_UPDATE_GOLDEN to create it); see {}",
+                               golden.display(), actual_path.display()));
+        }
+        let expected = Self::read_png(golden)?;
+        let diff = self.compare(&expected, tolerance)?;
+        if diff.num_differing == 0 {
+            return Ok(());
+        }
+        let actual_path = Self::sibling_path(golden, "actual");
+        let diff_path = Self::sibling_path(golden, "diff");
+        self.write_png(&actual_path)?;
+        diff.diff_image.write_png(&diff_path)?;
+        Err(format!("{} pixels differ from {} (max channel difference {}); see {} and {}",
+                    diff.num_differing, golden.display(), diff.max_channel_diff,
+                    actual_path.display(), diff_path.display()))
+    }
+
+    //fi sibling_path
+    /// Get `<path without .png>.<suffix>.png`
+    fn sibling_path(path:&Path, suffix:&str) -> PathBuf {
+        let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        path.with_file_name(format!("{}.{}.png", stem, suffix))
+    }
+
+    //zz All done
+}
+
+//a EglContext
+//ti EglContext
+/// The EGL display, context and (optional) pbuffer surface of a
+/// [HeadlessContext], released when dropped
+struct EglContext {
+    egl : egl::DynamicInstance<egl::EGL1_4>,
+    display : egl::Display,
+    context : egl::Context,
+    surface : Option<egl::Surface>,
+}
+
+//ip EglContext
+impl EglContext {
+    //fp new
+    /// Create a context on the default display and make it current
+    ///
+    /// The default display is shared by the whole process (including
+    /// the contexts of other threads), so it is never terminated;
+    /// initializing it again is harmless
+    fn new() -> Result<Self, String> {
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_4>::load_required() }
+            .map_err(|e| format!("Failed to load libEGL: {}", e))?;
+        let display = egl.get_display(egl::DEFAULT_DISPLAY)
+            .ok_or_else(|| "No EGL display available".to_string())?;
+        egl.initialize(display)
+            .map_err(|e| format!("Failed to initialize EGL: {}", e))?;
+        let (context, surface) = Self::create_context(&egl, display)?;
+        Ok(Self { egl, display, context, surface })
+    }
+
+    //fi create_context
+    /// Create a context (and a pbuffer surface if required) on an
+    /// initialized display and make it current, destroying whatever
+    /// was created if that fails
+    fn create_context(egl:&egl::DynamicInstance<egl::EGL1_4>, display:egl::Display) -> Result<(egl::Context, Option<egl::Surface>), String> {
+        egl.bind_api(egl::OPENGL_API)
+            .map_err(|e| format!("Failed to bind OpenGL API: {}", e))?;
+        let config_attribs = [
+            egl::SURFACE_TYPE, egl::PBUFFER_BIT,
+            egl::RENDERABLE_TYPE, egl::OPENGL_BIT,
+            egl::RED_SIZE, 8,
+            egl::GREEN_SIZE, 8,
+            egl::BLUE_SIZE, 8,
+            egl::ALPHA_SIZE, 8,
+            egl::DEPTH_SIZE, 24,
+            egl::NONE,
+        ];
+        let config = egl.choose_first_config(display, &config_attribs)
+            .map_err(|e| format!("Failed to choose EGL config: {}", e))?
+            .ok_or_else(|| "No suitable EGL config".to_string())?;
+        let context_attribs = [
+            egl::CONTEXT_MAJOR_VERSION, 3,
+            egl::CONTEXT_MINOR_VERSION, 3,
+            egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            egl::NONE,
+        ];
+        let context = egl.create_context(display, config, None, &context_attribs)
+            .map_err(|e| format!("Failed to create EGL context: {}", e))?;
+        let extensions = egl.query_string(Some(display), egl::EXTENSIONS)
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let surface = {
+            if extensions.split(' ').any(|e| e == "EGL_KHR_surfaceless_context") {
+                None
+            } else {
+                let pbuffer_attribs = [egl::WIDTH, 1, egl::HEIGHT, 1, egl::NONE];
+                match egl.create_pbuffer_surface(display, config, &pbuffer_attribs) {
+                    Ok(surface) => Some(surface),
+                    Err(e) => {
+                        let _ = egl.destroy_context(display, context);
+                        return Err(format!("Failed to create EGL pbuffer: {}", e));
+                    }
+                }
+            }
+        };
+        if let Err(e) = egl.make_current(display, surface, surface, Some(context)) {
+            if let Some(surface) = surface {
+                let _ = egl.destroy_surface(display, surface);
+            }
+            let _ = egl.destroy_context(display, context);
+            return Err(format!("Failed to make EGL context current: {}", e));
+        }
+        Ok((context, surface))
+    }
+
+    //mp load_gl
+    /// Load the GL function pointers (for whichever [crate::GlBackend]
+    /// is selected) from EGL
+    fn load_gl(&self) {
+        Backend::load_with(|s| {
+            self.egl.get_proc_address(s)
+                .map_or(std::ptr::null(), |f| f as *const std::os::raw::c_void)
+        });
+    }
+}
+
+//ip Drop for EglContext
+impl Drop for EglContext {
+    //fp drop
+    /// Release the context and surface; the display is left
+    /// initialized, as other contexts may be using it
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        if let Some(surface) = self.surface {
+            let _ = self.egl.destroy_surface(self.display, surface);
+        }
+        let _ = self.egl.destroy_context(self.display, self.context);
+    }
+}
+
+//a HeadlessContext
+//tp HeadlessContext
+/// An EGL OpenGL 3.3 core context with no window, current on the
+/// thread that created it, and the [RenderContext] for it
+pub struct HeadlessContext {
+    /// The render context, which must be dropped (flushing its
+    /// deletions) before the EGL context
+    render_context : RenderContext,
+    /// The EGL context, which is only used to release it when
+    /// dropped
+    _egl : EglContext,
+}
+
+//ip HeadlessContext
+impl HeadlessContext {
+    //fp new
+    /// Create a headless context, make it current, load the GL
+    /// function pointers and create its [RenderContext]
+    pub fn new() -> Result<Self, String> {
+        let egl = EglContext::new()?;
+        egl.load_gl();
+        let render_context = RenderContext::new();
+        Ok(Self { render_context, _egl:egl })
+    }
+
+    //ap render_context
+    /// Borrow the [RenderContext] of the context
+    pub fn render_context(&self) -> &RenderContext {
+        &self.render_context
+    }
+
+    //ap render_context_mut
+    /// Mutably borrow the [RenderContext] of the context (for example
+    /// to create the clients of model3d objects)
+    pub fn render_context_mut(&mut self) -> &mut RenderContext {
+        &mut self.render_context
+    }
+
+    //mp render
+    /// Render into a new framebuffer of the given size, cleared to
+    /// `clear_color` with depth testing enabled, and read back the
+    /// image
+    pub fn render<F:FnOnce()>(&self, width:usize, height:usize, clear_color:[f32;4], draw:F) -> Result<RgbaImage, String> {
+        let framebuffer = Framebuffer::new(width, height)?;
+        framebuffer.bind();
+        Backend::clear_color(clear_color[0], clear_color[1], clear_color[2], clear_color[3]);
+        Backend::enable(gl::DEPTH_TEST);
+        Backend::clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        draw();
+        Backend::finish();
+        crate::check_errors().map_err(|e| e.join(", "))?;
+        let image = RgbaImage::of_framebuffer(&framebuffer);
+        Framebuffer::unbind();
+        drop(framebuffer);
+        self.render_context.flush_deletions();
+        Ok(image)
+    }
+
+    //mp render_instantiable
+    /// Render an instance of a [ShaderInstantiable] with its program
+    /// and read back the image
+    pub fn render_instantiable(&self,
+                               width:usize,
+                               height:usize,
+                               program:&GlProgram,
+                               shader_instantiable:&ShaderInstantiable,
+                               instance:&model3d::Instance<Renderable>) -> Result<RgbaImage, String> {
+        self.render(width, height, [0., 0., 0., 1.], || {
+            program.set_used();
+            shader_instantiable.gl_draw(instance);
+        })
+    }
+
+    //zz All done
+}
@@ -51,21 +51,135 @@ for other uniforms). These programs expose an implementation of
 A [ShaderInstantiable] can then be created for a
 [model3d::Instantiable] and a particular instance of [ShaderClass];
 this will have appropraite OpenGL VAOs constructed, and then they may
-be drawn given a particular [model3d::Instance].
+be drawn given a particular [model3d::Instance]. Creating a
+[ShaderInstantiable] gives an [AttributeReport] of the vertex
+attributes that the shader requires but the vertices do not provide
+(and vice versa); [MissingAttributes] may supply default values for
+these, or make creation fail. An [OwnedShaderInstantiable] holds
+[std::rc::Rc]s of its shader class and instantiable instead of
+borrowing them.
 
 The draw process for a [ShaderInstantiable] is to set appropriate
 uniforms and run through the render recipe provided by model3d for the
 [model3d::Instantiable], setting more uniforms and invoking draw calls for the
 primitives.
 
+A [RenderContext] must be created (with [RenderContext::new]) once the
+OpenGL context is current; this queries the [Capabilities] of the
+context, which are used to select code paths for optional [GlFeature]s
+(or to report that they are unsupported) - hence [GlBuffer::of_data],
+[GlBuffer::uniform_buffer] and [GlBuffer::dynamic_uniform_buffer] take
+the [RenderContext]. It also holds a [ResourceRegistry] of the
+programs, textures, materials, buffers and shader instantiables
+loaded, by name and [ResourceHandle]. OpenGL objects dropped (on any
+thread) are queued for deletion, which happens when
+[RenderContext::flush_deletions] is invoked on the render thread.
+After context loss (on Android and WebGL, or a GPU reset) the buffers,
+programs, VAOs and samplers may be rebuilt with
+[RenderContext::recover_context]; textures, framebuffers and the
+objects built on them must be created again.
+
+All OpenGL calls are made through the [GlBackend] trait; by default
+this uses the `gl` crate, and with the `glow` feature the `glow` crate
+is used instead, supporting OpenGL ES 3.0 and WebGL 2 (with the GLSL
+`#version` of programs rewritten to suit).
+
+OpenGL debug output (KHR_debug) may be enabled with
+[RenderContext::enable_debug_output], routing messages to the `log`
+crate or a closure; objects are then labelled and draws are wrapped
+in debug groups, for tools such as RenderDoc.
+
+The GPU cost of drawing may be measured with a [GpuProfiler], using
+timer and pipeline statistics queries that are read back
+asynchronously, giving a [FrameReport] per frame.
+
+A [Camera] holds the view and projection matrices for a frame,
+uploading them to programs and culling instances whose bounding boxes
+are outside its view frustum.
+
+Occlusion culling of a [ShaderInstantiable] may be enabled, using
+the [BoundingBox]es of its vertex positions in occlusion queries and
+conditional rendering.
+
+A [Texture] may be a 2D texture, a cube map or a 2D array
+([TextureKind]), created from image data and with its faces or layers
+updated and its mipmaps generated afterwards; GPU-compressed
+textures are created from KTX2 and DDS files ([CompressedImage]),
+decompressing them on the CPU if the context lacks support for the
+format. With the `image` feature PNG, JPEG, HDR and EXR images may be
+decoded into textures, with `ImageOptions` for their colour space,
+alpha and orientation. Large textures may be uploaded asynchronously
+through pixel buffers with [RenderContext::upload_texture_2d], drawing
+with placeholder textures until they are ready. [Sampler] objects,
+shared by [SamplerDesc] through [RenderContext::sampler], are bound
+to texture units alongside textures to control their filtering and
+wrapping.
+
+A [Material] has textures for [TextureSlot]s (base colour, normal
+and so on); when a primitive is drawn its material's textures are
+bound to the texture units allocated to their slots by the
+[RenderContext] (skipping those already bound), and the program's
+sampler uniforms for the slots ([UniformId::Texture]) are set to the
+units.
+
+A [ShadowMap] renders the shadow casters of a directional (with
+cascades), spot or point light into a depth texture, drawing
+[ShaderInstantiable]s of a depth-only program; the shadow matrices and
+depth texture are then set as uniforms of the programs of the main
+pass ([UniformId::ShadowMap] and so on).
+
+[Lights] holds the directional, point and spot [Light]s of a scene,
+written each frame into a std140 uniform buffer that programs use as
+the [UniformId::Lights] block. For many lights a clustered mode
+assigns them to screen tiles and depth slices; alternatively the most
+relevant lights for each instance may be selected.
+
+A [BuiltinShader] is a program of the built-in library - unlit,
+vertex colour, normals, Blinn-Phong, glTF metallic-roughness PBR and
+depth-only - compiled in one call with its attributes and uniforms
+added for the crate's conventions, in variants ([ShaderOptions]) with
+skinning and normal mapping. A [Material] sets the factor uniforms of
+these programs as well as binding their textures.
+
+An [Environment] provides image-based lighting, prefiltered on the GPU
+from an equirectangular HDR texture into environment, irradiance and
+specular cube maps, with a shared split-sum BRDF lookup texture; its
+textures are bound to programs through their [TextureSlot]s, and the
+PBR program uses them with [ShaderOptions::with_environment].
+
+Many instances of many [ShaderInstantiable]s may be drawn through a
+[RenderQueue], which sorts their primitives by layer, transparency,
+program, material, VAO and depth before drawing them with minimal
+state changes.
+
+With the `headless` feature a [HeadlessContext] may be created
+without a window or GPU, rendering into a [Framebuffer] whose pixels
+can be compared against golden PNG images.
+
+With the `mock` feature the [mock_gl] module provides a recording
+mock of the OpenGL functions (for the `gl` crate backend), so that the
+GL call sequences can be unit tested without any context.
+
 !*/
 
 //a Imports and exports
+#[cfg(feature="glow")]
+extern crate dep_glow as glow;
+#[cfg(feature="image")]
+extern crate dep_image as image;
+
 pub use model3d::{Mat3, Mat4, Quat, Vec3, Vec4, Transformation};
 
 mod gl_buffer;
 mod buffer;
 mod texture;
+mod sampler;
+mod texture_units;
+mod upload;
+mod compressed;
+mod decompress;
+#[cfg(feature="image")]
+mod texture_file;
 mod material;
 mod vertices;
 mod shader_instantiable;
@@ -74,17 +188,67 @@ mod utils;
 mod traits;
 mod shader;
 mod program;
+mod framebuffer;
+mod backend;
+mod backend_gl;
+mod capabilities;
+mod debug;
+mod query;
+mod bounds;
+mod occlusion;
+mod camera;
+mod attributes;
+mod registry;
+mod deletion;
+mod recovery;
+mod render_queue;
+mod shadow;
+mod lights;
+mod shader_library;
+mod environment;
+#[cfg(feature="glow")]
+pub mod backend_glow;
+#[cfg(feature="headless")]
+mod headless;
+#[cfg(feature="mock")]
+pub mod mock_gl;
 
 pub use gl_buffer::GlBuffer;
 pub use utils::{get_shaderiv, get_programiv, check_errors, get_shader_error};
 pub use buffer::{IndexBuffer, VertexBuffer, BufferView};
-pub use texture::Texture;
+pub use texture::{Texture, TextureKind, TextureFormat, TextureImage, CubeFace};
+pub use compressed::{CompressedFormat, CompressedImage};
+#[cfg(feature="image")]
+pub use texture_file::ImageOptions;
+pub use sampler::{Sampler, SamplerDesc, Filter, MipmapMode, WrapMode, CompareFunction};
+pub use texture_units::TextureSlot;
 pub use material::Material;
 pub use vertices::Vertices;
-pub use shader_instantiable::ShaderInstantiable;
+pub use shader_instantiable::{ShaderInstantiable, OwnedShaderInstantiable};
+pub use attributes::{MissingAttributes, AttributeReport};
+pub use registry::{ResourceRegistry, ResourceHandle, ResourceKind, Resource, ResourceTable};
+pub use registry::{MemoryUsage, MemoryEntry};
 pub use renderable::{Renderable, RenderContext};
 pub use traits::ShaderClass;
 pub use shader::GlShader;
-pub use program::UniformId;
+pub use program::{UniformId, rewrite_version};
 pub use program::Program as GlProgram;
+pub use framebuffer::Framebuffer;
+pub use backend::{GlBackend, Backend, DebugCallback};
+pub use backend_gl::GlCrate;
+pub use capabilities::{Capabilities, GlFeature, GlLimits, GlProfile};
+pub use debug::{DebugSeverity, DebugMessage, DebugHandler, DebugGroup};
+pub use query::{QueryKind, QueryPool, GpuProfiler, FrameReport, ObjectReport};
+pub use bounds::{BoundingBox, max_index};
+pub use occlusion::OcclusionCulling;
+pub use camera::{Camera, Frustum, CullStats};
+pub use render_queue::{RenderQueue, SortKey, QueueStats};
+pub use shadow::{ShadowMap, ShadowKind};
+pub use lights::{Lights, Light, LightKind};
+pub use shader_library::{BuiltinShader, ShaderOptions};
+pub use environment::Environment;
+#[cfg(feature="glow")]
+pub use backend_glow::Glow;
+#[cfg(feature="headless")]
+pub use headless::{HeadlessContext, RgbaImage, ImageDiff};
 
@@ -0,0 +1,719 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    lights.rs
+@brief   Directional, point and spot lights in a uniform block
+ */
+
+//a Documentation
+
+/*!
+
+[Lights] holds the directional, point and spot [Light]s of a scene (up
+to a maximum count), which are written each frame by [Lights::update]
+into a std140 uniform buffer bound to a uniform buffer binding point.
+Programs declare the block with [Lights::glsl] and register it as
+[UniformId::Lights] (with [crate::GlProgram::add_uniform_block_name]);
+[Lights::upload] then sets the binding of the block of a program.
+
+The block is a header of four vec4s followed by the lights, each of
+four vec4s:
+
+* `uLightCount` (ivec4) - the number of lights, and 1 in y if clustered
+
+* `uClusterGrid`, `uClusterDepth` and `uClusterViewport` - the
+  cluster grid size, the depth slice parameters and the viewport
+  size, for clustered mode
+
+* each light has its world position and range, its direction and
+  kind (0 for directional, 1 for point, 2 for spot), its colour
+  multiplied by its intensity (and the intensity), and the cosines of
+  its inner and outer cone angles and its shadow map index (-1 if
+  none)
+
+For scenes with many lights, clustered mode may be enabled with
+[Lights::enable_clusters]: the view frustum is divided into a grid of
+screen tiles and exponentially-spaced depth slices, and each update
+assigns the lights to the clusters their range reaches (directional
+lights reach all of them). The cluster light lists are written to a
+float data texture ([UniformId::LightClusters]) - an offset and count
+per cluster followed by the light indices - which the fragment shader
+reads with the `lightCluster` and `clusterLight` functions of
+[Lights::glsl].
+
+Alternatively, a forward renderer may select the most relevant lights
+for each instance with [Lights::most_relevant_to], and set them in
+[UniformId::InstanceLights] with [Lights::set_instance_lights].
+
+The uniform buffer and cluster texture are created again by the next
+update after context loss.
+
+!*/
+
+//a Imports
+use std::rc::Rc;
+
+use gl::types::*;
+
+use geo_nd::vector::{self, normalize};
+
+use crate::{Renderable, RenderContext, ShaderClass, ShaderInstantiable, UniformId};
+use crate::{GlBuffer, Texture, TextureFormat, TextureImage};
+use crate::{Sampler, SamplerDesc, Filter, MipmapMode, WrapMode};
+use crate::{Camera, Mat4};
+use crate::utils::{mat4_inverse, mat4_transform};
+use crate::{Backend, GlBackend};
+
+//a Constants
+//ci HEADER_BYTES
+/// The size of the header of the uniform block in bytes
+const HEADER_BYTES : usize = 64;
+
+//ci LIGHT_FLOATS
+/// The size of a light in the uniform block in floats (four vec4s)
+const LIGHT_FLOATS : usize = 16;
+
+//ci CLUSTER_TEXTURE_WIDTH
+/// The width of the cluster data texture in texels
+const CLUSTER_TEXTURE_WIDTH : usize = 1024;
+
+//ci MAX_INSTANCE_LIGHTS
+/// The number of light indices in [UniformId::InstanceLights]
+const MAX_INSTANCE_LIGHTS : usize = 4;
+
+//a LightKind
+//tp LightKind
+/// The kind of a [Light]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+    /// A light infinitely far away, shining in a direction
+    Directional,
+    /// A light at a position shining in all directions
+    Point,
+    /// A light at a position shining in a cone
+    Spot,
+}
+
+//ip LightKind
+impl LightKind {
+    //mp code
+    /// The value of the kind in the uniform block
+    fn code(&self) -> f32 {
+        match self {
+            Self::Directional => 0.,
+            Self::Point => 1.,
+            Self::Spot => 2.,
+        }
+    }
+}
+
+//ip Display for LightKind
+impl std::fmt::Display for LightKind {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Directional => write!(f, "directional"),
+            Self::Point => write!(f, "point"),
+            Self::Spot => write!(f, "spot"),
+        }
+    }
+}
+
+//a Light
+//tp Light
+/// A directional, point or spot light, with a colour and intensity
+///
+/// Point and spot lights have a range beyond which they have no
+/// effect; spot lights have inner and outer cone half-angles (in
+/// radians) between which their intensity falls off
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    /// The kind of light
+    kind : LightKind,
+    /// The position (for point and spot lights)
+    position : [f32; 3],
+    /// The direction the light shines in (for directional and spot
+    /// lights), normalized
+    direction : [f32; 3],
+    /// The colour (linear)
+    color : [f32; 3],
+    /// The intensity
+    intensity : f32,
+    /// The range (for point and spot lights)
+    range : f32,
+    /// The inner and outer cone half-angles (for spot lights)
+    cone : (f32, f32),
+    /// The index of the light's shadow map, if any
+    shadow : Option<usize>,
+}
+
+//ip Light
+impl Light {
+    //fi new
+    /// Create a white light of intensity 1
+    fn new(kind:LightKind, position:[f32; 3], direction:[f32; 3], range:f32, cone:(f32, f32)) -> Self {
+        Self { kind, position, direction:normalize(direction), color:[1.; 3], intensity:1., range, cone, shadow:None }
+    }
+
+    //fp directional
+    /// Create a directional light shining in a direction
+    pub fn directional(direction:[f32; 3]) -> Self {
+        Self::new(LightKind::Directional, [0.; 3], direction, 0., (0., 0.))
+    }
+
+    //fp point
+    /// Create a point light at a position with a range
+    pub fn point(position:[f32; 3], range:f32) -> Self {
+        Self::new(LightKind::Point, position, [0., 0., -1.], range, (0., 0.))
+    }
+
+    //fp spot
+    /// Create a spot light at a position shining in a direction, with
+    /// a range and inner and outer cone half-angles in radians
+    pub fn spot(position:[f32; 3], direction:[f32; 3], range:f32, inner:f32, outer:f32) -> Self {
+        Self::new(LightKind::Spot, position, direction, range, (inner, outer))
+    }
+
+    //cp with_color
+    /// Set the colour and intensity of the light
+    pub fn with_color(mut self, color:[f32; 3], intensity:f32) -> Self {
+        self.set_color(color, intensity);
+        self
+    }
+
+    //cp with_shadow
+    /// Set the index of the shadow map of the light (as used for its
+    /// [UniformId::ShadowMap] and so on)
+    pub fn with_shadow(mut self, index:usize) -> Self {
+        self.shadow = Some(index);
+        self
+    }
+
+    //mp set_color
+    /// Set the colour and intensity of the light
+    pub fn set_color(&mut self, color:[f32; 3], intensity:f32) {
+        self.color = color;
+        self.intensity = intensity;
+    }
+
+    //mp set_position
+    /// Set the position of a point or spot light
+    pub fn set_position(&mut self, position:[f32; 3]) {
+        self.position = position;
+    }
+
+    //mp set_direction
+    /// Set the direction of a directional or spot light
+    pub fn set_direction(&mut self, direction:[f32; 3]) {
+        self.direction = normalize(direction);
+    }
+
+    //ap kind
+    /// Get the kind of light
+    pub fn kind(&self) -> LightKind {
+        self.kind
+    }
+
+    //ap position
+    /// Get the position of the light
+    pub fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    //ap direction
+    /// Get the (normalized) direction of the light
+    pub fn direction(&self) -> [f32; 3] {
+        self.direction
+    }
+
+    //ap range
+    /// Get the range of the light
+    pub fn range(&self) -> f32 {
+        self.range
+    }
+
+    //mi pack
+    /// Pack the light into its four vec4s of the uniform block
+    fn pack(&self) -> [f32; LIGHT_FLOATS] {
+        let (p, d, c, i) = (self.position, self.direction, self.color, self.intensity);
+        let shadow = self.shadow.map_or(-1., |s| s as f32);
+        [p[0], p[1], p[2], self.range,
+         d[0], d[1], d[2], self.kind.code(),
+         c[0] * i, c[1] * i, c[2] * i, i,
+         self.cone.0.cos(), self.cone.1.cos(), shadow, 0.]
+    }
+
+    //mi relevance
+    /// Estimate the illuminance of the light on a sphere, or None if
+    /// the sphere is out of its range (or cone)
+    fn relevance(&self, center:[f32; 3], radius:f32) -> Option<f32> {
+        let luminance = 0.2126 * self.color[0] + 0.7152 * self.color[1] + 0.0722 * self.color[2];
+        let power = luminance * self.intensity;
+        if self.kind == LightKind::Directional {
+            return Some(power);
+        }
+        let v = vector::sub(center, &self.position, 1.);
+        let length = vector::length(&v);
+        let distance = (length - radius).max(0.);
+        if distance > self.range {
+            return None;
+        }
+        if self.kind == LightKind::Spot && length > radius {
+            let cos = vector::dot(&v, &self.direction) / length;
+            let spread = (radius / length).asin();
+            if cos.clamp(-1., 1.).acos() - spread > self.cone.1 {
+                return None;
+            }
+        }
+        let window = (1. - (distance / self.range).powi(4)).max(0.).powi(2);
+        Some(power * window / (distance * distance).max(0.01))
+    }
+
+    //zz All done
+}
+
+//ip Display for Light
+impl std::fmt::Display for Light {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{} light {:?} x {}", self.kind, self.color, self.intensity)?;
+        match self.kind {
+            LightKind::Directional => write!(f, " towards {:?}", self.direction),
+            LightKind::Point => write!(f, " at {:?} range {}", self.position, self.range),
+            LightKind::Spot => write!(f, " at {:?} towards {:?} range {}", self.position, self.direction, self.range),
+        }
+    }
+}
+
+//a LightClusters
+//ti LightClusters
+/// The cluster grid of [Lights] in clustered mode, and the data
+/// texture of the light lists of the clusters
+struct LightClusters {
+    /// The number of screen tiles across and down, and depth slices
+    tiles : (usize, usize, usize),
+    /// The texture unit reserved for the data texture
+    unit : usize,
+    /// Nearest-texel sampler for the data texture
+    sampler : Rc<Sampler>,
+    /// The data texture, created (or enlarged) when required
+    texture : Option<Texture>,
+    /// The number of light indices in the last update
+    assigned : usize,
+}
+
+//ii LightClusters
+impl LightClusters {
+    //mi assign
+    /// Assign the lights to the clusters of the camera's view frustum
+    /// and write the cluster data texture; return the depth slice
+    /// parameters (near, far, scale and bias, for the slice of a view
+    /// depth d being `log(d) * scale + bias`)
+    fn assign(&mut self, lights:&[Light], camera:&Camera) -> Result<[f32; 4], String> {
+        let (nx, ny, nz) = self.tiles;
+        let (near, far) = Camera::near_far(camera.projection());
+        let inverse = mat4_inverse(camera.projection())
+            .ok_or_else(|| "Cannot cluster lights with a singular projection".to_string())?;
+        let log_ratio = (far / near).ln();
+        let scale = nz as f32 / log_ratio;
+        let bias = -(nz as f32) * near.ln() / log_ratio;
+        let slice_depth = |k:usize| near * (far / near).powf(k as f32 / nz as f32);
+        let slice_of = |d:f32| ((d.max(near).ln() * scale + bias).floor().max(0.) as usize).min(nz - 1);
+
+        // The view-space rays through the tile corners, from the near
+        // to the far plane
+        let mut rays = Vec::with_capacity((nx + 1) * (ny + 1));
+        for j in 0..=ny {
+            for i in 0..=nx {
+                let x = -1. + 2. * i as f32 / nx as f32;
+                let y = -1. + 2. * j as f32 / ny as f32;
+                rays.push((mat4_transform(&inverse, [x, y, -1.]), mat4_transform(&inverse, [x, y, 1.])));
+            }
+        }
+        let at_depth = |ray:&([f32; 3], [f32; 3]), d:f32| {
+            let t = (d + ray.0[2]) / (ray.0[2] - ray.1[2]);
+            [ray.0[0] + (ray.1[0] - ray.0[0]) * t, ray.0[1] + (ray.1[1] - ray.0[1]) * t, -d]
+        };
+
+        let mut lists : Vec<Vec<f32>> = vec![Vec::new(); nx * ny * nz];
+        for (index, light) in lights.iter().enumerate() {
+            if light.kind == LightKind::Directional {
+                for list in lists.iter_mut() {
+                    list.push(index as f32);
+                }
+                continue;
+            }
+            let center = mat4_transform(camera.view(), light.position);
+            let (depth, r) = (-center[2], light.range);
+            if depth + r < near || depth - r > far {
+                continue;
+            }
+            for k in slice_of(depth - r)..=slice_of(depth + r) {
+                let (d0, d1) = (slice_depth(k), slice_depth(k + 1));
+                for j in 0..ny {
+                    for i in 0..nx {
+                        let mut min = [f32::MAX; 3];
+                        let mut max = [f32::MIN; 3];
+                        for ray in [&rays[j * (nx + 1) + i], &rays[j * (nx + 1) + i + 1],
+                                    &rays[(j + 1) * (nx + 1) + i], &rays[(j + 1) * (nx + 1) + i + 1]] {
+                            for p in [at_depth(ray, d0), at_depth(ray, d1)] {
+                                for c in 0..3 {
+                                    min[c] = min[c].min(p[c]);
+                                    max[c] = max[c].max(p[c]);
+                                }
+                            }
+                        }
+                        let distance_sq : f32 = (0..3)
+                            .map(|c| (center[c] - center[c].clamp(min[c], max[c])).powi(2))
+                            .sum();
+                        if distance_sq <= r * r {
+                            lists[(k * ny + j) * nx + i].push(index as f32);
+                        }
+                    }
+                }
+            }
+        }
+
+        let num_clusters = lists.len();
+        self.assigned = lists.iter().map(|l| l.len()).sum();
+        let texels = 2 * num_clusters + self.assigned;
+        let rows = texels.div_ceil(CLUSTER_TEXTURE_WIDTH);
+        let mut data = Vec::with_capacity(rows * CLUSTER_TEXTURE_WIDTH);
+        let mut offset = 2 * num_clusters;
+        for list in &lists {
+            data.push(offset as f32);
+            data.push(list.len() as f32);
+            offset += list.len();
+        }
+        for list in &lists {
+            data.extend_from_slice(list);
+        }
+        data.resize(rows * CLUSTER_TEXTURE_WIDTH, 0.);
+
+        let stale = {
+            match &self.texture {
+                Some(t) => t.height() < rows || !t.is_current(),
+                None => true,
+            }
+        };
+        if stale {
+            let texture = Texture::new_2d(CLUSTER_TEXTURE_WIDTH, rows.next_power_of_two(), TextureFormat::R32F, None)?;
+            texture.set_label("light clusters");
+            self.texture = Some(texture);
+        }
+        let bytes : Vec<u8> = data.iter().flat_map(|x| x.to_ne_bytes()).collect();
+        self.texture.as_ref().unwrap()
+            .sub_image(TextureImage::Image, 0, (0, 0), (CLUSTER_TEXTURE_WIDTH, rows), &bytes)?;
+        Ok([near, far, scale, bias])
+    }
+}
+
+//a Lights
+//tp Lights
+/// The lights of a scene, up to a maximum count, in a uniform buffer
+/// bound to a uniform buffer binding point
+pub struct Lights {
+    /// The maximum number of lights (the size of the array in the
+    /// uniform block)
+    max_lights : usize,
+    /// The uniform buffer binding point
+    binding : GLuint,
+    /// The lights
+    lights : Vec<Light>,
+    /// The uniform buffer, created by the first update
+    buffer : GlBuffer,
+    /// The clusters, if clustered mode is enabled
+    clusters : Option<LightClusters>,
+}
+
+//ip Lights
+impl Lights {
+    //fp new
+    /// Create an empty set of lights with a maximum count, for
+    /// uniform buffer binding point 0
+    ///
+    /// The uniform block is 64 bytes plus 64 bytes per light; all
+    /// contexts support blocks of 16kB, and hence 255 lights
+    pub fn new(max_lights:usize) -> Self {
+        Self { max_lights, binding:0, lights:Vec::new(), buffer:GlBuffer::default(), clusters:None }
+    }
+
+    //cp with_binding
+    /// Set the uniform buffer binding point of the lights
+    pub fn with_binding(mut self, binding:usize) -> Self {
+        self.binding = binding as GLuint;
+        self
+    }
+
+    //ap max_lights
+    /// Get the maximum number of lights
+    pub fn max_lights(&self) -> usize {
+        self.max_lights
+    }
+
+    //ap binding
+    /// Get the uniform buffer binding point of the lights
+    pub fn binding(&self) -> usize {
+        self.binding as usize
+    }
+
+    //ap byte_length
+    /// Get the size of the uniform block in bytes
+    pub fn byte_length(&self) -> usize {
+        HEADER_BYTES + self.max_lights * LIGHT_FLOATS * 4
+    }
+
+    //ap lights
+    /// Get the lights
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    //ap light_mut
+    /// Get a light by index to modify it
+    pub fn light_mut(&mut self, index:usize) -> Option<&mut Light> {
+        self.lights.get_mut(index)
+    }
+
+    //mp add
+    /// Add a light, returning its index (in the uniform block)
+    pub fn add(&mut self, light:Light) -> Result<usize, String> {
+        if self.lights.len() >= self.max_lights {
+            return Err(format!("Cannot add more than {} lights", self.max_lights));
+        }
+        if light.kind != LightKind::Directional && light.range <= 0. {
+            return Err(format!("A {} light must have a positive range", light.kind));
+        }
+        self.lights.push(light);
+        Ok(self.lights.len() - 1)
+    }
+
+    //mp clear
+    /// Remove all the lights
+    pub fn clear(&mut self) {
+        self.lights.clear();
+    }
+
+    //mp enable_clusters
+    /// Enable clustered mode, with a number of screen tiles across
+    /// and down and of depth slices, reserving a texture unit for the
+    /// cluster data texture
+    pub fn enable_clusters(&mut self, render_context:&mut RenderContext, tiles:(usize, usize, usize)) -> Result<(), String> {
+        if tiles.0 == 0 || tiles.1 == 0 || tiles.2 == 0 {
+            return Err(format!("Light cluster grid {:?} must not be empty", tiles));
+        }
+        let desc = SamplerDesc::new(Filter::Nearest, Filter::Nearest)
+            .with_mipmap(MipmapMode::None)
+            .with_wrap(WrapMode::ClampToEdge);
+        let sampler = render_context.sampler(&desc);
+        let unit = render_context.reserve_texture_unit()?;
+        self.clusters = Some(LightClusters { tiles, unit, sampler, texture:None, assigned:0 });
+        Ok(())
+    }
+
+    //ap is_clustered
+    /// Return true if clustered mode is enabled
+    pub fn is_clustered(&self) -> bool {
+        self.clusters.is_some()
+    }
+
+    //ap cluster_assignments
+    /// Get the total number of light indices in the cluster lists of
+    /// the last update (0 if not clustered)
+    pub fn cluster_assignments(&self) -> usize {
+        self.clusters.as_ref().map_or(0, |c| c.assigned)
+    }
+
+    //mp update
+    /// Write the lights to the uniform buffer (creating it if
+    /// required) and bind it to the binding point; in clustered mode
+    /// also assign the lights to the clusters of the camera (with a
+    /// viewport of a width and height in pixels)
+    pub fn update(&mut self, render_context:&RenderContext, camera:&Camera, viewport:(usize, usize)) -> Result<(), String> {
+        if self.buffer.is_none() || !self.buffer.is_current() {
+            let max_size = render_context.capabilities().limits.max_uniform_block_size;
+            if self.byte_length() > max_size {
+                return Err(format!("Uniform block of {} lights ({} bytes) exceeds the maximum of {} bytes",
+                                   self.max_lights, self.byte_length(), max_size));
+            }
+            let mut buffer = GlBuffer::default();
+            buffer.dynamic_uniform_buffer(self.byte_length(), render_context)?;
+            buffer.set_label("lights");
+            self.buffer = buffer;
+        }
+        let mut header = [0.; 12];
+        let clustered = {
+            if let Some(clusters) = &mut self.clusters {
+                let depth = clusters.assign(&self.lights, camera)?;
+                let (nx, ny, nz) = clusters.tiles;
+                header[0..4].copy_from_slice(&[nx as f32, ny as f32, nz as f32, 0.]);
+                header[4..8].copy_from_slice(&depth);
+                header[8..12].copy_from_slice(&[viewport.0 as f32, viewport.1 as f32, 0., 0.]);
+                1
+            } else {
+                0
+            }
+        };
+        let mut data = Vec::with_capacity(HEADER_BYTES + self.lights.len() * LIGHT_FLOATS * 4);
+        for count in [self.lights.len() as i32, clustered, 0, 0] {
+            data.extend_from_slice(&count.to_ne_bytes());
+        }
+        for x in header {
+            data.extend_from_slice(&x.to_ne_bytes());
+        }
+        for light in &self.lights {
+            for x in light.pack() {
+                data.extend_from_slice(&x.to_ne_bytes());
+            }
+        }
+        self.buffer.write(gl::UNIFORM_BUFFER, 0, &data)?;
+        Backend::bind_buffer_base(gl::UNIFORM_BUFFER, self.binding, self.buffer.gl_buffer());
+        Ok(())
+    }
+
+    //mp upload
+    /// Set the binding of the lights uniform block of a program (which
+    /// must be in use), and in clustered mode bind the cluster data
+    /// texture to its unit and set its sampler uniform
+    pub fn upload(&self, shader_class:&dyn ShaderClass) -> Result<(), String> {
+        if let Some(block) = shader_class.uniform(UniformId::Lights) {
+            let program = shader_class.program_id().unwrap_or_else(|| {
+                let mut current = [0];
+                Backend::get_integers(gl::CURRENT_PROGRAM, &mut current);
+                current[0] as GLuint
+            });
+            Backend::uniform_block_binding(program, block as GLuint, self.binding);
+        }
+        if let Some(clusters) = &self.clusters {
+            if let (Some(u), Some(texture)) = (shader_class.uniform(UniformId::LightClusters), &clusters.texture) {
+                texture.bind_unit(clusters.unit, Some(&*clusters.sampler))?;
+                Backend::uniform_1i(u, clusters.unit as GLint);
+            }
+        }
+        Ok(())
+    }
+
+    //mp most_relevant
+    /// Get the indices of up to `n` lights that most affect a sphere
+    /// (in world space), most relevant first
+    ///
+    /// Relevance is the estimated illuminance at the nearest point of
+    /// the sphere; point and spot lights out of range (or whose cone
+    /// misses the sphere) are excluded
+    pub fn most_relevant(&self, center:[f32; 3], radius:f32, n:usize) -> Vec<usize> {
+        let mut scored : Vec<(usize, f32)> = self.lights.iter().enumerate()
+            .filter_map(|(i, l)| l.relevance(center, radius).map(|r| (i, r)))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().take(n).map(|(i, _)| i).collect()
+    }
+
+    //mp most_relevant_to
+    /// Get the indices of up to `n` lights that most affect an
+    /// instance of a [ShaderInstantiable], using the bounding sphere of
+    /// its bounding box (or its origin if it has none)
+    pub fn most_relevant_to(&self, shader_instantiable:&ShaderInstantiable, instance:&model3d::Instance<Renderable>, n:usize) -> Vec<usize> {
+        let model : Mat4 = instance.transformation.mat4();
+        let (center, radius) = {
+            if let Some(bbox) = shader_instantiable.bounds() {
+                let corners = bbox.corners().map(|c| mat4_transform(&model, c));
+                let mut center = [0.; 3];
+                for c in &corners {
+                    for (x, y) in center.iter_mut().zip(c.iter()) {
+                        *x += y / 8.;
+                    }
+                }
+                let radius = corners.iter()
+                    .map(|c| vector::distance(c, &center))
+                    .fold(0., f32::max);
+                (center, radius)
+            } else {
+                (mat4_transform(&model, [0.; 3]), 0.)
+            }
+        };
+        self.most_relevant(center, radius, n)
+    }
+
+    //mp set_instance_lights
+    /// Set the [UniformId::InstanceLights] uniform of a program (which
+    /// must be in use) to up to four light indices, padded with -1
+    pub fn set_instance_lights(&self, shader_class:&dyn ShaderClass, lights:&[usize]) {
+        if let Some(u) = shader_class.uniform(UniformId::InstanceLights) {
+            let mut indices = [-1; MAX_INSTANCE_LIGHTS];
+            for (index, light) in indices.iter_mut().zip(lights.iter()) {
+                *index = *light as GLint;
+            }
+            Backend::uniform_4i(u, &indices);
+        }
+    }
+
+    //mp glsl
+    /// Get the GLSL declarations of the lights uniform block (named
+    /// `Lights`) for the maximum count; in clustered mode this also
+    /// declares the `uLightClusters` sampler, and the functions
+    /// `lightCluster(view_depth)`, giving the offset and count of the
+    /// light list of the fragment's cluster, and `clusterLight(i)`,
+    /// giving the light index at an offset in the lists
+    pub fn glsl(&self) -> String {
+        let mut glsl = format!("#define MAX_LIGHTS {}
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+#define LIGHT_SPOT 2
+struct Light {{
+    vec4 position_range;
+    vec4 direction_kind;
+    vec4 color_intensity;
+    vec4 cone_shadow;
+}};
+layout(std140) uniform Lights {{
+    ivec4 uLightCount;
+    vec4 uClusterGrid;
+    vec4 uClusterDepth;
+    vec4 uClusterViewport;
+    Light uLights[MAX_LIGHTS];
+}};
+", self.max_lights.max(1));
+        if self.clusters.is_some() {
+            glsl.push_str(&format!("uniform highp sampler2D uLightClusters;
+float clusterTexel(int i) {{
+    return texelFetch(uLightClusters, ivec2(i % {w}, i / {w}), 0).r;
+}}
+ivec2 lightCluster(float view_depth) {{
+    ivec3 grid = ivec3(uClusterGrid.xyz);
+    ivec2 tile = ivec2(gl_FragCoord.xy / uClusterViewport.xy * uClusterGrid.xy);
+    int slice = int(log(view_depth) * uClusterDepth.z + uClusterDepth.w);
+    tile = clamp(tile, ivec2(0), grid.xy - 1);
+    slice = clamp(slice, 0, grid.z - 1);
+    int cluster = (slice * grid.y + tile.y) * grid.x + tile.x;
+    return ivec2(clusterTexel(2 * cluster), clusterTexel(2 * cluster + 1));
+}}
+int clusterLight(int i) {{
+    return int(clusterTexel(i));
+}}
+", w = CLUSTER_TEXTURE_WIDTH));
+        }
+        glsl
+    }
+
+    //zz All done
+}
+
+//ip Display for Lights
+impl std::fmt::Display for Lights {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{} of {} lights at binding {}", self.lights.len(), self.max_lights, self.binding)?;
+        if let Some(clusters) = &self.clusters {
+            write!(f, " in {:?} clusters", clusters.tiles)?;
+        }
+        Ok(())
+    }
+}
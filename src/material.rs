@@ -1,24 +1,252 @@
 //a Imports
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
 use crate::{Renderable, RenderContext};
+use crate::{Texture, Sampler, TextureSlot, ShaderClass, UniformId};
+use crate::{Backend, GlBackend};
+use crate::{texture_units, upload};
+
+//a Constants
+//ci STANDARD_SLOTS
+/// The slots that are bound to placeholder textures if a program
+/// samples them but the material has no texture for them
+const STANDARD_SLOTS : [TextureSlot; 5] = [TextureSlot::BaseColor, TextureSlot::Normal,
+                                           TextureSlot::MetallicRoughness, TextureSlot::Occlusion,
+                                           TextureSlot::Emissive];
+
+//a Statics
+//ci NEXT_MATERIAL_ID
+/// The id to give to the next [Material] created; 0 is not used, so
+/// that it can stand for 'no material'
+static NEXT_MATERIAL_ID : AtomicU32 = AtomicU32::new(1);
 
+thread_local! {
+    /// The default material, used to draw primitives that have no
+    /// material of their own
+    static DEFAULT_MATERIAL : Rc<Material> = Rc::new(Material::default());
+}
 
 //a Material
 //tp Material
-/// A null material for now
+/// A material - the textures (each with an optional [Sampler]) for
+/// the [TextureSlot]s of the primitives that use it, and the factors
+/// (as in glTF) that the textures are multiplied by
+///
+/// When drawn, each texture whose slot has a sampler uniform
+/// ([UniformId::Texture]) in the program is bound to the texture
+/// unit of the slot (unless it is already bound there), and the
+/// uniform set to that unit; a texture that is not yet ready (as it is
+/// being uploaded asynchronously), or that the material does not have
+/// for a slot other than a user slot, is replaced by a placeholder
+///
+/// The materials of [model3d] objects are not mapped: the [Material]
+/// created for each is the default (untextured, white) one, and the
+/// materials to draw with are set on each [crate::ShaderInstantiable]
+/// with [crate::ShaderInstantiable::set_material]
 #[derive(Debug)]
-pub struct Material (u32);
+pub struct Material {
+    /// A unique id for the material
+    id : u32,
+    /// The textures, by slot, with the samplers to use for them
+    textures : Vec<(TextureSlot, Rc<Texture>, Option<Rc<Sampler>>)>,
+    /// The base colour factor (linear, with alpha)
+    base_color : [f32; 4],
+    /// The emissive factor (linear)
+    emissive : [f32; 3],
+    /// The metallic and roughness factors
+    metallic_roughness : (f32, f32),
+}
+
+//ip Default for Material
+impl Default for Material {
+    fn default() -> Self {
+        let id = NEXT_MATERIAL_ID.fetch_add(1, Ordering::Relaxed);
+        Self { id, textures:Vec::new(), base_color:[1.; 4], emissive:[0.; 3], metallic_roughness:(1., 1.) }
+    }
+}
+
+//ip Display for Material
 impl std::fmt::Display for Material {
     fn fmt(&self, fmt:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        write!(fmt, "{}", self.0)
+        write!(fmt, "material [")?;
+        for (i, (slot, texture, _)) in self.textures.iter().enumerate() {
+            if i > 0 {
+                write!(fmt, ", ")?;
+            }
+            write!(fmt, "{}: {}", slot, texture)?;
+        }
+        write!(fmt, "]")
     }
 }
 
-//ip Default for Material
-impl Default for Material {
-    fn default() -> Self { Self (0) }
+//ip Material
+impl Material {
+    //fp new
+    /// Create a material with no textures
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //fp default_material
+    /// Get the default (untextured, white) material that primitives
+    /// with no material are drawn with; it is the same for every call
+    /// on a thread, so it has a single id
+    pub fn default_material() -> Rc<Self> {
+        DEFAULT_MATERIAL.with(|m| m.clone())
+    }
+
+    //mp set_texture
+    /// Set the texture (and sampler, or none to use the texture's own
+    /// parameters) for a slot, replacing any it had
+    pub fn set_texture(&mut self, slot:TextureSlot, texture:Rc<Texture>, sampler:Option<Rc<Sampler>>) {
+        self.textures.retain(|(s, _, _)| *s != slot);
+        self.textures.push((slot, texture, sampler));
+    }
+
+    //cp with_texture
+    /// Set the texture (and sampler) for a slot
+    pub fn with_texture(mut self, slot:TextureSlot, texture:Rc<Texture>, sampler:Option<Rc<Sampler>>) -> Self {
+        self.set_texture(slot, texture, sampler);
+        self
+    }
+
+    //mp set_base_color
+    /// Set the base colour factor (linear, with alpha; white by
+    /// default)
+    pub fn set_base_color(&mut self, base_color:[f32; 4]) {
+        self.base_color = base_color;
+    }
+
+    //cp with_base_color
+    /// Set the base colour factor
+    pub fn with_base_color(mut self, base_color:[f32; 4]) -> Self {
+        self.set_base_color(base_color);
+        self
+    }
+
+    //mp set_emissive
+    /// Set the emissive factor (linear; black by default)
+    pub fn set_emissive(&mut self, emissive:[f32; 3]) {
+        self.emissive = emissive;
+    }
+
+    //cp with_emissive
+    /// Set the emissive factor
+    pub fn with_emissive(mut self, emissive:[f32; 3]) -> Self {
+        self.set_emissive(emissive);
+        self
+    }
+
+    //mp set_metallic_roughness
+    /// Set the metallic and roughness factors (both 1 by default)
+    pub fn set_metallic_roughness(&mut self, metallic:f32, roughness:f32) {
+        self.metallic_roughness = (metallic, roughness);
+    }
+
+    //cp with_metallic_roughness
+    /// Set the metallic and roughness factors
+    pub fn with_metallic_roughness(mut self, metallic:f32, roughness:f32) -> Self {
+        self.set_metallic_roughness(metallic, roughness);
+        self
+    }
+
+    //ap id
+    /// Get the unique id of the material (never 0)
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    //ap base_color
+    /// Get the base colour factor
+    pub fn base_color(&self) -> [f32; 4] {
+        self.base_color
+    }
+
+    //ap emissive
+    /// Get the emissive factor
+    pub fn emissive(&self) -> [f32; 3] {
+        self.emissive
+    }
+
+    //ap metallic_roughness
+    /// Get the metallic and roughness factors
+    pub fn metallic_roughness(&self) -> (f32, f32) {
+        self.metallic_roughness
+    }
+
+    //ap texture
+    /// Get the texture for a slot, if it has one
+    pub fn texture(&self, slot:TextureSlot) -> Option<&Rc<Texture>> {
+        self.textures.iter().find(|(s, _, _)| *s == slot).map(|(_, t, _)| t)
+    }
+
+    //ap slots
+    /// Iterate over the slots that have textures
+    pub fn slots(&self) -> impl Iterator<Item = TextureSlot> + '_ {
+        self.textures.iter().map(|(s, _, _)| *s)
+    }
+
+    //mp bind
+    /// Bind the textures of the material whose slots the shader class
+    /// has sampler uniforms for to the units of the slots, and set
+    /// the uniforms (and those of the factors,
+    /// [UniformId::BaseColorFactor] and so on); the program must be in
+    /// use
+    ///
+    /// Returns the number of textures that were not already bound
+    pub fn bind(&self, shader_class:&dyn ShaderClass) -> Result<usize, String> {
+        if let Some(u) = shader_class.uniform(UniformId::BaseColorFactor) {
+            Backend::uniform_4f(u, &self.base_color);
+        }
+        if let Some(u) = shader_class.uniform(UniformId::EmissiveFactor) {
+            let e = self.emissive;
+            Backend::uniform_4f(u, &[e[0], e[1], e[2], 0.]);
+        }
+        if let Some(u) = shader_class.uniform(UniformId::MetallicRoughnessFactor) {
+            let (metallic, roughness) = self.metallic_roughness;
+            Backend::uniform_4f(u, &[metallic, roughness, 0., 0.]);
+        }
+        let mut bound = 0;
+        for (slot, texture, sampler) in &self.textures {
+            if let Some(u) = shader_class.uniform(UniformId::Texture(*slot)) {
+                let placeholder;
+                let texture = {
+                    if texture.is_ready() {
+                        texture
+                    } else {
+                        placeholder = upload::placeholder(*slot)?;
+                        &placeholder
+                    }
+                };
+                let unit = texture_units::slot_unit(*slot)?;
+                let sampler = sampler.as_ref().map_or(0, |s| s.gl_sampler());
+                if texture_units::bind(unit, texture.kind().target(), texture.gl_texture(), sampler)? {
+                    bound += 1;
+                }
+                Backend::uniform_1i(u, unit as gl::types::GLint);
+            }
+        }
+        for slot in STANDARD_SLOTS {
+            if self.texture(slot).is_some() {
+                continue;
+            }
+            if let Some(u) = shader_class.uniform(UniformId::Texture(slot)) {
+                let placeholder = upload::placeholder(slot)?;
+                let unit = texture_units::slot_unit(slot)?;
+                if texture_units::bind(unit, placeholder.kind().target(), placeholder.gl_texture(), 0)? {
+                    bound += 1;
+                }
+                Backend::uniform_1i(u, unit as gl::types::GLint);
+            }
+        }
+        Ok(bound)
+    }
 }
 
 //ip MaterialClient for Material
+/// The [model3d::Material] is not mapped, leaving the default
+/// [Material]; see [Material]
 impl model3d::MaterialClient<Renderable> for Material {
     fn create(&mut self, _material:&dyn model3d::Material<Renderable>, _render_context:&mut RenderContext) {}
     fn drop(&mut self, _material:&dyn model3d::Material<Renderable>, _render_context:&mut RenderContext) {}
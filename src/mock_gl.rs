@@ -0,0 +1,1137 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    mock_gl.rs
+@brief   Recording mock OpenGL function table for unit tests
+ */
+
+//a Documentation
+
+/*!
+
+A mock OpenGL implementation that requires no context, for unit
+testing the crate (and its clients).
+
+[install] loads the `gl` crate function pointers with mock
+functions (so the mock is not used by the `glow` backend); each call
+is recorded as a [GlCall] in a log, which can be retrieved with
+[take_calls]. Object names (buffers, VAOs, shaders, programs, etc)
+are synthesized counting up from 1.
+
+The `gl` function pointers are process-global, but the state (call
+log, object name counters and configured query results) is
+per-thread, so tests running in parallel do not interfere with each
+other.
+
+Query results default to success (shaders compile, programs link,
+framebuffers are complete, no GL errors); they may be configured
+before exercising the code under test, with for example
+[set_compile_status] and [set_attrib_location].
+
+Calls to `glGetError`, `glGetString`, `glGetStringi` and
+`glGetIntegerv` are not recorded, as they are made liberally by the
+crate to check for errors and capabilities; the errors to be returned
+may be queued with [push_error], and the context strings, integers and
+extensions set with [set_string], [set_integer] and [set_extensions].
+The strings returned remain valid until the state is next modified.
+GL_COLOR_WRITEMASK and GL_DEPTH_WRITEMASK return the masks last set
+with `glColorMask` and `glDepthMask`, and `glGetFloatv` (also not
+recorded) returns the polygon offset last set with
+`glPolygonOffset`, or the integer set with [set_integer] for other
+parameters.
+
+Query objects have their results available immediately, with a
+result of 0, unless configured with [set_query_result]; polling the
+results with `glGetQueryObject*` is not recorded. Similarly, fences
+are signaled immediately unless configured with [set_fence_signaled],
+and `glClientWaitSync` is not recorded.
+
+Objects dropped by the crate are only deleted (recording the
+`glDelete*` calls) when the deletion queue is flushed with
+[crate::RenderContext::flush_deletions]; there is a queue for each
+[crate::RenderContext], so tests checking deletions should drop the
+objects before their context.
+
+Context loss may be simulated with [lose_context], after which
+[crate::RenderContext::recover_context] should recreate the objects
+it can without deleting any names from the lost context.
+
+Any GL function that is not mocked is left unloaded, and the `gl`
+crate will panic if it is invoked.
+
+!*/
+
+//a Imports
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CStr;
+use std::os::raw::c_void;
+
+use gl::types::*;
+
+//a GlCall
+//tp GlCall
+/// A recorded call of an OpenGL function, with its arguments
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlCall {
+    /// glGenBuffers, with the names generated
+    GenBuffers(Vec<GLuint>),
+    /// glBindBuffer(target, buffer)
+    BindBuffer(GLenum, GLuint),
+    /// glBufferData(target, size, usage) with a copy of the data (if not NULL)
+    BufferData(GLenum, GLsizeiptr, GLenum, Option<Vec<u8>>),
+    /// glBufferSubData(target, offset) with a copy of the data
+    BufferSubData(GLenum, GLintptr, Vec<u8>),
+    /// glBindBufferBase(target, index, buffer)
+    BindBufferBase(GLenum, GLuint, GLuint),
+    /// glBufferStorage(target, size, flags) with a copy of the data (if not NULL)
+    BufferStorage(GLenum, GLsizeiptr, GLbitfield, Option<Vec<u8>>),
+    /// glMapBufferRange(target, offset, length, access)
+    MapBufferRange(GLenum, GLintptr, GLsizeiptr, GLbitfield),
+    /// glUnmapBuffer(target) with the data written to the mapping
+    UnmapBuffer(GLenum, Vec<u8>),
+    /// glDeleteBuffers with the names deleted
+    DeleteBuffers(Vec<GLuint>),
+    /// glGenVertexArrays, with the names generated
+    GenVertexArrays(Vec<GLuint>),
+    /// glBindVertexArray(array)
+    BindVertexArray(GLuint),
+    /// glDeleteVertexArrays with the names deleted
+    DeleteVertexArrays(Vec<GLuint>),
+    /// glEnableVertexAttribArray(index)
+    EnableVertexAttribArray(GLuint),
+    /// glVertexAttribPointer(index, size, type, normalized, stride, offset)
+    VertexAttribPointer(GLuint, GLint, GLenum, GLboolean, GLsizei, usize),
+    /// glVertexAttrib4f(index, x, y, z, w)
+    VertexAttrib4f(GLuint, f32, f32, f32, f32),
+    /// glCreateShader(kind), with the name returned
+    CreateShader(GLenum, GLuint),
+    /// glShaderSource(shader) with the source strings concatenated
+    ShaderSource(GLuint, String),
+    /// glCompileShader(shader)
+    CompileShader(GLuint),
+    /// glDeleteShader(shader)
+    DeleteShader(GLuint),
+    /// glCreateProgram, with the name returned
+    CreateProgram(GLuint),
+    /// glAttachShader(program, shader)
+    AttachShader(GLuint, GLuint),
+    /// glBindAttribLocation(program, index, name)
+    BindAttribLocation(GLuint, GLuint, String),
+    /// glDetachShader(program, shader)
+    DetachShader(GLuint, GLuint),
+    /// glLinkProgram(program)
+    LinkProgram(GLuint),
+    /// glUseProgram(program)
+    UseProgram(GLuint),
+    /// glDeleteProgram(program)
+    DeleteProgram(GLuint),
+    /// glGetAttribLocation(program, name)
+    GetAttribLocation(GLuint, String),
+    /// glGetUniformLocation(program, name)
+    GetUniformLocation(GLuint, String),
+    /// glGetUniformBlockIndex(program, name)
+    GetUniformBlockIndex(GLuint, String),
+    /// glUniformBlockBinding(program, block index, binding)
+    UniformBlockBinding(GLuint, GLuint, GLuint),
+    /// glUniformMatrix4fv(location, transpose, values)
+    UniformMatrix4fv(GLint, GLboolean, Vec<f32>),
+    /// glUniform1i(location, value)
+    Uniform1i(GLint, GLint),
+    /// glUniform4f(location, x, y, z, w)
+    Uniform4f(GLint, [f32; 4]),
+    /// glUniform4i(location, x, y, z, w)
+    Uniform4i(GLint, [GLint; 4]),
+    /// glDrawElements(mode, count, type, offset)
+    DrawElements(GLenum, GLsizei, GLenum, usize),
+    /// glDrawArrays(mode, first, count)
+    DrawArrays(GLenum, GLint, GLsizei),
+    /// glGenFramebuffers, with the names generated
+    GenFramebuffers(Vec<GLuint>),
+    /// glBindFramebuffer(target, framebuffer)
+    BindFramebuffer(GLenum, GLuint),
+    /// glDeleteFramebuffers with the names deleted
+    DeleteFramebuffers(Vec<GLuint>),
+    /// glGenRenderbuffers, with the names generated
+    GenRenderbuffers(Vec<GLuint>),
+    /// glBindRenderbuffer(target, renderbuffer)
+    BindRenderbuffer(GLenum, GLuint),
+    /// glDeleteRenderbuffers with the names deleted
+    DeleteRenderbuffers(Vec<GLuint>),
+    /// glRenderbufferStorage(target, internal format, width, height)
+    RenderbufferStorage(GLenum, GLenum, GLsizei, GLsizei),
+    /// glFramebufferRenderbuffer(target, attachment, renderbuffer target, renderbuffer)
+    FramebufferRenderbuffer(GLenum, GLenum, GLenum, GLuint),
+    /// glFramebufferTexture2D(target, attachment, texture target, texture, level)
+    FramebufferTexture2D(GLenum, GLenum, GLenum, GLuint, GLint),
+    /// glFramebufferTextureLayer(target, attachment, texture, level, layer)
+    FramebufferTextureLayer(GLenum, GLenum, GLuint, GLint, GLint),
+    /// glDrawBuffers(buffers)
+    DrawBuffers(Vec<GLenum>),
+    /// glGenTextures, with the names generated
+    GenTextures(Vec<GLuint>),
+    /// glBindTexture(target, texture)
+    BindTexture(GLenum, GLuint),
+    /// glDeleteTextures with the names deleted
+    DeleteTextures(Vec<GLuint>),
+    /// glTexImage2D(target, level, internal format, width, height,
+    /// format, type) and whether data was given
+    TexImage2D(GLenum, GLint, GLint, GLsizei, GLsizei, GLenum, GLenum, bool),
+    /// glTexImage3D(target, level, internal format, width, height,
+    /// depth, format, type) and whether data was given
+    TexImage3D(GLenum, GLint, GLint, GLsizei, GLsizei, GLsizei, GLenum, GLenum, bool),
+    /// glCompressedTexImage2D(target, level, internal format, width,
+    /// height, image size)
+    CompressedTexImage2D(GLenum, GLint, GLenum, GLsizei, GLsizei, GLsizei),
+    /// glCompressedTexImage3D(target, level, internal format, width,
+    /// height, depth, image size)
+    CompressedTexImage3D(GLenum, GLint, GLenum, GLsizei, GLsizei, GLsizei, GLsizei),
+    /// glTexSubImage2D(target, level, x, y, width, height, format, type)
+    TexSubImage2D(GLenum, GLint, GLint, GLint, GLsizei, GLsizei, GLenum, GLenum),
+    /// glTexSubImage2D(target, level, x, y, width, height, format, type)
+    /// from an offset in the PIXEL_UNPACK_BUFFER
+    TexSubImage2DBuffer(GLenum, GLint, GLint, GLint, GLsizei, GLsizei, GLenum, GLenum, usize),
+    /// glTexSubImage3D(target, level, x, y, z, width, height, depth,
+    /// format, type)
+    TexSubImage3D(GLenum, GLint, GLint, GLint, GLint, GLsizei, GLsizei, GLsizei, GLenum, GLenum),
+    /// glTexParameteri(target, pname, param)
+    TexParameteri(GLenum, GLenum, GLint),
+    /// glGenerateMipmap(target)
+    GenerateMipmap(GLenum),
+    /// glActiveTexture(texture)
+    ActiveTexture(GLenum),
+    /// glGenSamplers, with the names generated
+    GenSamplers(Vec<GLuint>),
+    /// glBindSampler(unit, sampler)
+    BindSampler(GLuint, GLuint),
+    /// glDeleteSamplers with the names deleted
+    DeleteSamplers(Vec<GLuint>),
+    /// glSamplerParameteri(sampler, pname, param)
+    SamplerParameteri(GLuint, GLenum, GLint),
+    /// glSamplerParameterf(sampler, pname, param)
+    SamplerParameterf(GLuint, GLenum, f32),
+    /// glSamplerParameterfv(sampler, pname, params) - with four values
+    SamplerParameterfv(GLuint, GLenum, Vec<f32>),
+    /// glViewport(x, y, width, height)
+    Viewport(GLint, GLint, GLsizei, GLsizei),
+    /// glClearColor(r, g, b, a)
+    ClearColor(f32, f32, f32, f32),
+    /// glClear(mask)
+    Clear(GLbitfield),
+    /// glEnable(cap)
+    Enable(GLenum),
+    /// glDisable(cap)
+    Disable(GLenum),
+    /// glReadBuffer(mode)
+    ReadBuffer(GLenum),
+    /// glPixelStorei(pname, param)
+    PixelStorei(GLenum, GLint),
+    /// glReadPixels(x, y, width, height, format, type)
+    ReadPixels(GLint, GLint, GLsizei, GLsizei, GLenum, GLenum),
+    /// glFinish
+    Finish,
+    /// glDebugMessageCallback (whether a callback was given)
+    DebugMessageCallback(bool),
+    /// glDebugMessageControl(source, type, severity, ids, enabled)
+    DebugMessageControl(GLenum, GLenum, GLenum, Vec<GLuint>, GLboolean),
+    /// glObjectLabel(identifier, name, label)
+    ObjectLabel(GLenum, GLuint, String),
+    /// glPushDebugGroup(source, id, message)
+    PushDebugGroup(GLenum, GLuint, String),
+    /// glPopDebugGroup
+    PopDebugGroup,
+    /// glGenQueries, with the names generated
+    GenQueries(Vec<GLuint>),
+    /// glDeleteQueries with the names deleted
+    DeleteQueries(Vec<GLuint>),
+    /// glBeginQuery(target, query)
+    BeginQuery(GLenum, GLuint),
+    /// glEndQuery(target)
+    EndQuery(GLenum),
+    /// glQueryCounter(query, target)
+    QueryCounter(GLuint, GLenum),
+    /// glColorMask(r, g, b, a)
+    ColorMask(GLboolean, GLboolean, GLboolean, GLboolean),
+    /// glDepthMask(flag)
+    DepthMask(GLboolean),
+    /// glPolygonOffset(factor, units)
+    PolygonOffset(f32, f32),
+    /// glBeginConditionalRender(query, mode)
+    BeginConditionalRender(GLuint, GLenum),
+    /// glEndConditionalRender
+    EndConditionalRender,
+    /// glFenceSync, with the sync object created
+    FenceSync(usize),
+    /// glDeleteSync(sync)
+    DeleteSync(usize),
+}
+
+//a MockState
+//ti MockState
+/// The per-thread state of the mock
+struct MockState {
+    /// Log of calls made
+    calls : Vec<GlCall>,
+    /// Next object name to return for each kind of object
+    next_names : HashMap<&'static str, GLuint>,
+    /// Value for GL_COMPILE_STATUS
+    compile_status : GLint,
+    /// Value for GL_LINK_STATUS
+    link_status : GLint,
+    /// Info log returned for shaders and programs
+    info_log : String,
+    /// Attribute locations by name; others are -1
+    attrib_locations : HashMap<String, GLint>,
+    /// Uniform locations by name; others are -1
+    uniform_locations : HashMap<String, GLint>,
+    /// Uniform block indices by name; others are GL_INVALID_INDEX
+    uniform_block_indices : HashMap<String, GLuint>,
+    /// Status returned by glCheckFramebufferStatus
+    framebuffer_status : GLenum,
+    /// Errors to be returned by glGetError
+    errors : VecDeque<GLenum>,
+    /// Strings returned by glGetString (NUL-terminated)
+    strings : HashMap<GLenum, Vec<u8>>,
+    /// Values returned by glGetIntegerv; others are 0
+    integers : HashMap<GLenum, GLint>,
+    /// Extensions returned by glGetStringi (NUL-terminated)
+    extensions : Vec<Vec<u8>>,
+    /// Callback and user parameter given to glDebugMessageCallback
+    debug_callback : Option<(GLDEBUGPROC, usize)>,
+    /// Results of queries (None if not yet available); others are
+    /// available with a result of 0
+    query_results : HashMap<GLuint, Option<u64>>,
+    /// Capabilities enabled with glEnable (for glIsEnabled)
+    enabled : Vec<GLenum>,
+    /// Colour components enabled for writing with glColorMask
+    color_mask : [GLboolean; 4],
+    /// Depth writes enabled with glDepthMask
+    depth_mask : GLboolean,
+    /// Polygon offset factor and units set with glPolygonOffset
+    polygon_offset : (GLfloat, GLfloat),
+    /// Buffer currently bound to GL_PIXEL_UNPACK_BUFFER
+    pixel_unpack_buffer : GLuint,
+    /// Memory of the buffer mapped with glMapBufferRange
+    mapped : Vec<u8>,
+    /// Fences (sync objects) that are not yet signaled; others are
+    /// signaled as soon as they are created
+    unsignaled : Vec<usize>,
+}
+
+//ip Default for MockState
+impl Default for MockState {
+    fn default() -> Self {
+        let mut s = Self {
+            calls : Vec::new(),
+            next_names : HashMap::new(),
+            compile_status : gl::TRUE as GLint,
+            link_status : gl::TRUE as GLint,
+            info_log : String::new(),
+            attrib_locations : HashMap::new(),
+            uniform_locations : HashMap::new(),
+            uniform_block_indices : HashMap::new(),
+            framebuffer_status : gl::FRAMEBUFFER_COMPLETE,
+            errors : VecDeque::new(),
+            strings : HashMap::new(),
+            integers : HashMap::new(),
+            extensions : Vec::new(),
+            debug_callback : None,
+            query_results : HashMap::new(),
+            enabled : Vec::new(),
+            color_mask : [gl::TRUE; 4],
+            depth_mask : gl::TRUE,
+            polygon_offset : (0., 0.),
+            pixel_unpack_buffer : 0,
+            mapped : Vec::new(),
+            unsignaled : Vec::new(),
+        };
+        s.set_string(gl::VERSION, "4.6.0 Mock");
+        s.set_string(gl::SHADING_LANGUAGE_VERSION, "4.60 Mock");
+        s.set_string(gl::VENDOR, "Mock");
+        s.set_string(gl::RENDERER, "Mock GL");
+        s.integers.insert(gl::CONTEXT_PROFILE_MASK, gl::CONTEXT_CORE_PROFILE_BIT as GLint);
+        s.integers.insert(gl::MAX_VERTEX_ATTRIBS, 16);
+        s.integers.insert(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS, 32);
+        s.integers.insert(gl::MAX_UNIFORM_BLOCK_SIZE, 65536);
+        s.integers.insert(gl::MAX_UNIFORM_BUFFER_BINDINGS, 36);
+        s.integers.insert(gl::MAX_SAMPLES, 8);
+        s
+    }
+}
+
+//ip MockState
+impl MockState {
+    //mi new_name
+    /// Allocate a new object name for a kind of object
+    fn new_name(&mut self, kind:&'static str) -> GLuint {
+        let name = self.next_names.entry(kind).or_insert(1);
+        let n = *name;
+        *name += 1;
+        n
+    }
+
+    //mi set_string
+    /// Set a string to be returned by glGetString
+    fn set_string(&mut self, name:GLenum, value:&str) {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        self.strings.insert(name, bytes);
+    }
+}
+
+thread_local! {
+    static STATE : RefCell<MockState> = RefCell::new(MockState::default());
+}
+
+//fi with_state
+fn with_state<T, F:FnOnce(&mut MockState) -> T>(f:F) -> T {
+    STATE.with(|s| f(&mut s.borrow_mut()))
+}
+
+//fi record
+fn record(call:GlCall) {
+    with_state(|s| s.calls.push(call));
+}
+
+//a Public functions
+//fp install
+/// Load the `gl` function pointers with the mock functions, and
+/// reset the state for this thread
+pub fn install() {
+    gl::load_with(proc_address);
+    reset();
+}
+
+//fp reset
+/// Reset the state for this thread - clearing the log and all
+/// configured query results
+pub fn reset() {
+    with_state(|s| *s = MockState::default());
+}
+
+//fp lose_context
+/// Simulate loss of the context for this thread: object names are
+/// synthesized from 1 again, and query results and enabled
+/// capabilities are forgotten; the call log and other configuration
+/// are kept
+pub fn lose_context() {
+    with_state(|s| {
+        s.next_names.clear();
+        s.query_results.clear();
+        s.enabled.clear();
+        s.color_mask = [gl::TRUE; 4];
+        s.depth_mask = gl::TRUE;
+        s.polygon_offset = (0., 0.);
+        s.debug_callback = None;
+        s.pixel_unpack_buffer = 0;
+        s.mapped.clear();
+        s.unsignaled.clear();
+    });
+}
+
+//fp take_calls
+/// Take the log of calls made on this thread, leaving it empty
+pub fn take_calls() -> Vec<GlCall> {
+    with_state(|s| std::mem::take(&mut s.calls))
+}
+
+//fp calls
+/// Get a copy of the log of calls made on this thread
+pub fn calls() -> Vec<GlCall> {
+    with_state(|s| s.calls.clone())
+}
+
+//fp set_compile_status
+/// Set whether shaders compile successfully
+pub fn set_compile_status(ok:bool) {
+    with_state(|s| s.compile_status = ok as GLint);
+}
+
+//fp set_link_status
+/// Set whether programs link successfully
+pub fn set_link_status(ok:bool) {
+    with_state(|s| s.link_status = ok as GLint);
+}
+
+//fp set_info_log
+/// Set the info log returned for shaders and programs
+pub fn set_info_log(log:&str) {
+    with_state(|s| s.info_log = log.to_string());
+}
+
+//fp set_attrib_location
+/// Set the location returned by glGetAttribLocation for a name
+pub fn set_attrib_location(name:&str, location:GLint) {
+    with_state(|s| { s.attrib_locations.insert(name.to_string(), location); });
+}
+
+//fp set_uniform_location
+/// Set the location returned by glGetUniformLocation for a name
+pub fn set_uniform_location(name:&str, location:GLint) {
+    with_state(|s| { s.uniform_locations.insert(name.to_string(), location); });
+}
+
+//fp set_uniform_block_index
+/// Set the index returned by glGetUniformBlockIndex for a name
+pub fn set_uniform_block_index(name:&str, index:GLuint) {
+    with_state(|s| { s.uniform_block_indices.insert(name.to_string(), index); });
+}
+
+//fp set_framebuffer_status
+/// Set the status returned by glCheckFramebufferStatus
+pub fn set_framebuffer_status(status:GLenum) {
+    with_state(|s| s.framebuffer_status = status);
+}
+
+//fp set_string
+/// Set the string returned by glGetString for a name (such as
+/// GL_VERSION, which defaults to "4.6.0 Mock")
+pub fn set_string(name:GLenum, value:&str) {
+    with_state(|s| s.set_string(name, value));
+}
+
+//fp set_integer
+/// Set the value returned by glGetIntegerv (and glGetFloatv) for a
+/// parameter
+pub fn set_integer(pname:GLenum, value:GLint) {
+    with_state(|s| { s.integers.insert(pname, value); });
+}
+
+//fp set_extensions
+/// Set the extensions reported by the context
+pub fn set_extensions(extensions:&[&str]) {
+    with_state(|s| {
+        s.extensions = extensions.iter().map(|e| {
+            let mut bytes = e.as_bytes().to_vec();
+            bytes.push(0);
+            bytes
+        }).collect();
+    });
+}
+
+//fp push_error
+/// Queue an error to be returned by glGetError
+pub fn push_error(error:GLenum) {
+    with_state(|s| s.errors.push_back(error));
+}
+
+//fp set_fence_signaled
+/// Set whether a fence (sync object) is signaled; fences are
+/// signaled when created unless set otherwise
+pub fn set_fence_signaled(fence:usize, signaled:bool) {
+    with_state(|s| {
+        s.unsignaled.retain(|f| *f != fence);
+        if !signaled {
+            s.unsignaled.push(fence);
+        }
+    });
+}
+
+//fp set_query_result
+/// Set the result of a query object, or None if the result is not
+/// yet available
+pub fn set_query_result(query:GLuint, result:Option<u64>) {
+    with_state(|s| s.query_results.insert(query, result));
+}
+
+//fp debug_message
+/// Deliver a debug message to the callback given to
+/// glDebugMessageCallback, if any, as if it had been generated by the
+/// OpenGL implementation
+pub fn debug_message(source:GLenum, msg_type:GLenum, id:GLuint, severity:GLenum, message:&str) {
+    if let Some((callback, user_param)) = with_state(|s| s.debug_callback) {
+        callback(source, msg_type, id, severity,
+                 message.len() as GLsizei, message.as_ptr() as *const GLchar,
+                 user_param as *mut c_void);
+    }
+}
+
+//a Function table
+//fi proc_address
+/// Map a GL function name to its mock, or NULL if it is not mocked
+fn proc_address(name:&str) -> *const c_void {
+    match name {
+        "glGetError" => get_error as *const c_void,
+        "glGetString" => get_string as *const c_void,
+        "glGetStringi" => get_stringi as *const c_void,
+        "glGetIntegerv" => get_integerv as *const c_void,
+        "glGetFloatv" => get_floatv as *const c_void,
+        "glBufferStorage" => buffer_storage as *const c_void,
+        "glMapBufferRange" => map_buffer_range as *const c_void,
+        "glUnmapBuffer" => unmap_buffer as *const c_void,
+        "glGenBuffers" => gen_buffers as *const c_void,
+        "glBindBuffer" => bind_buffer as *const c_void,
+        "glBufferData" => buffer_data as *const c_void,
+        "glBufferSubData" => buffer_sub_data as *const c_void,
+        "glBindBufferBase" => bind_buffer_base as *const c_void,
+        "glDeleteBuffers" => delete_buffers as *const c_void,
+        "glGenVertexArrays" => gen_vertex_arrays as *const c_void,
+        "glBindVertexArray" => bind_vertex_array as *const c_void,
+        "glDeleteVertexArrays" => delete_vertex_arrays as *const c_void,
+        "glEnableVertexAttribArray" => enable_vertex_attrib_array as *const c_void,
+        "glVertexAttribPointer" => vertex_attrib_pointer as *const c_void,
+        "glVertexAttrib4f" => vertex_attrib_4f as *const c_void,
+        "glCreateShader" => create_shader as *const c_void,
+        "glShaderSource" => shader_source as *const c_void,
+        "glCompileShader" => compile_shader as *const c_void,
+        "glGetShaderiv" => get_shaderiv as *const c_void,
+        "glGetShaderInfoLog" => get_info_log as *const c_void,
+        "glDeleteShader" => delete_shader as *const c_void,
+        "glCreateProgram" => create_program as *const c_void,
+        "glAttachShader" => attach_shader as *const c_void,
+        "glBindAttribLocation" => bind_attrib_location as *const c_void,
+        "glDetachShader" => detach_shader as *const c_void,
+        "glLinkProgram" => link_program as *const c_void,
+        "glGetProgramiv" => get_programiv as *const c_void,
+        "glGetProgramInfoLog" => get_info_log as *const c_void,
+        "glUseProgram" => use_program as *const c_void,
+        "glDeleteProgram" => delete_program as *const c_void,
+        "glGetAttribLocation" => get_attrib_location as *const c_void,
+        "glGetUniformLocation" => get_uniform_location as *const c_void,
+        "glGetUniformBlockIndex" => get_uniform_block_index as *const c_void,
+        "glUniformBlockBinding" => uniform_block_binding as *const c_void,
+        "glUniformMatrix4fv" => uniform_matrix_4fv as *const c_void,
+        "glUniform1i" => uniform_1i as *const c_void,
+        "glUniform4f" => uniform_4f as *const c_void,
+        "glUniform4i" => uniform_4i as *const c_void,
+        "glDrawElements" => draw_elements as *const c_void,
+        "glDrawArrays" => draw_arrays as *const c_void,
+        "glGenFramebuffers" => gen_framebuffers as *const c_void,
+        "glBindFramebuffer" => bind_framebuffer as *const c_void,
+        "glDeleteFramebuffers" => delete_framebuffers as *const c_void,
+        "glCheckFramebufferStatus" => check_framebuffer_status as *const c_void,
+        "glGenRenderbuffers" => gen_renderbuffers as *const c_void,
+        "glBindRenderbuffer" => bind_renderbuffer as *const c_void,
+        "glDeleteRenderbuffers" => delete_renderbuffers as *const c_void,
+        "glRenderbufferStorage" => renderbuffer_storage as *const c_void,
+        "glFramebufferRenderbuffer" => framebuffer_renderbuffer as *const c_void,
+        "glFramebufferTexture2D" => framebuffer_texture_2d as *const c_void,
+        "glFramebufferTextureLayer" => framebuffer_texture_layer as *const c_void,
+        "glDrawBuffers" => draw_buffers as *const c_void,
+        "glGenTextures" => gen_textures as *const c_void,
+        "glBindTexture" => bind_texture as *const c_void,
+        "glDeleteTextures" => delete_textures as *const c_void,
+        "glTexImage2D" => tex_image_2d as *const c_void,
+        "glTexImage3D" => tex_image_3d as *const c_void,
+        "glCompressedTexImage2D" => compressed_tex_image_2d as *const c_void,
+        "glCompressedTexImage3D" => compressed_tex_image_3d as *const c_void,
+        "glTexSubImage2D" => tex_sub_image_2d as *const c_void,
+        "glTexSubImage3D" => tex_sub_image_3d as *const c_void,
+        "glTexParameteri" => tex_parameteri as *const c_void,
+        "glGenerateMipmap" => generate_mipmap as *const c_void,
+        "glActiveTexture" => active_texture as *const c_void,
+        "glGenSamplers" => gen_samplers as *const c_void,
+        "glBindSampler" => bind_sampler as *const c_void,
+        "glDeleteSamplers" => delete_samplers as *const c_void,
+        "glSamplerParameteri" => sampler_parameteri as *const c_void,
+        "glSamplerParameterf" => sampler_parameterf as *const c_void,
+        "glSamplerParameterfv" => sampler_parameterfv as *const c_void,
+        "glViewport" => viewport as *const c_void,
+        "glClearColor" => clear_color as *const c_void,
+        "glClear" => clear as *const c_void,
+        "glEnable" => enable as *const c_void,
+        "glDisable" => disable as *const c_void,
+        "glReadBuffer" => read_buffer as *const c_void,
+        "glPixelStorei" => pixel_storei as *const c_void,
+        "glReadPixels" => read_pixels as *const c_void,
+        "glFinish" => finish as *const c_void,
+        "glDebugMessageCallback" => debug_message_callback as *const c_void,
+        "glDebugMessageControl" => debug_message_control as *const c_void,
+        "glObjectLabel" => object_label as *const c_void,
+        "glPushDebugGroup" => push_debug_group as *const c_void,
+        "glPopDebugGroup" => pop_debug_group as *const c_void,
+        "glIsEnabled" => is_enabled as *const c_void,
+        "glColorMask" => color_mask as *const c_void,
+        "glDepthMask" => depth_mask as *const c_void,
+        "glPolygonOffset" => polygon_offset as *const c_void,
+        "glBeginConditionalRender" => begin_conditional_render as *const c_void,
+        "glEndConditionalRender" => end_conditional_render as *const c_void,
+        "glFenceSync" => fence_sync as *const c_void,
+        "glClientWaitSync" => client_wait_sync as *const c_void,
+        "glDeleteSync" => delete_sync as *const c_void,
+        "glGenQueries" => gen_queries as *const c_void,
+        "glDeleteQueries" => delete_queries as *const c_void,
+        "glBeginQuery" => begin_query as *const c_void,
+        "glEndQuery" => end_query as *const c_void,
+        "glQueryCounter" => query_counter as *const c_void,
+        "glGetQueryObjectuiv" => get_query_objectuiv as *const c_void,
+        "glGetQueryObjectui64v" => get_query_objectui64v as *const c_void,
+        _ => std::ptr::null(),
+    }
+}
+
+//a Helpers
+//fi gen_names
+/// Generate `n` names of a kind into `names`, returning them
+unsafe fn gen_names(kind:&'static str, n:GLsizei, names:*mut GLuint) -> Vec<GLuint> {
+    let v : Vec<GLuint> = with_state(|s| (0..n).map(|_| s.new_name(kind)).collect());
+    for (i, name) in v.iter().enumerate() {
+        *names.add(i) = *name;
+    }
+    v
+}
+
+//fi names_of
+/// Get a Vec of `n` names from a pointer
+unsafe fn names_of(n:GLsizei, names:*const GLuint) -> Vec<GLuint> {
+    (0..n as usize).map(|i| *names.add(i)).collect()
+}
+
+//fi string_of
+/// Get a String from a NUL-terminated GL string
+unsafe fn string_of(s:*const GLchar) -> String {
+    CStr::from_ptr(s).to_string_lossy().into_owned()
+}
+
+//fi gl_string
+/// Get the bytes of a string with a length (or NUL-terminated if the
+/// length is negative)
+unsafe fn gl_string(length:GLsizei, s:*const GLchar) -> String {
+    if length < 0 {
+        string_of(s)
+    } else {
+        let bytes = std::slice::from_raw_parts(s as *const u8, length as usize);
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+//a Mock functions
+extern "system" fn get_error() -> GLenum {
+    with_state(|s| s.errors.pop_front().unwrap_or(gl::NO_ERROR))
+}
+extern "system" fn get_string(name:GLenum) -> *const GLubyte {
+    with_state(|s| s.strings.get(&name).map_or(std::ptr::null(), |v| v.as_ptr()))
+}
+extern "system" fn get_stringi(_name:GLenum, index:GLuint) -> *const GLubyte {
+    with_state(|s| s.extensions.get(index as usize).map_or(std::ptr::null(), |v| v.as_ptr()))
+}
+extern "system" fn get_integerv(pname:GLenum, data:*mut GLint) {
+    let values = with_state(|s| {
+        match pname {
+            gl::NUM_EXTENSIONS => vec![s.extensions.len() as GLint],
+            gl::COLOR_WRITEMASK => s.color_mask.iter().map(|m| *m as GLint).collect(),
+            gl::DEPTH_WRITEMASK => vec![s.depth_mask as GLint],
+            _ => vec![*s.integers.get(&pname).unwrap_or(&0)],
+        }
+    });
+    for (i, v) in values.into_iter().enumerate() {
+        unsafe { *data.add(i) = v; }
+    }
+}
+extern "system" fn get_floatv(pname:GLenum, data:*mut GLfloat) {
+    let value = with_state(|s| {
+        match pname {
+            gl::POLYGON_OFFSET_FACTOR => s.polygon_offset.0,
+            gl::POLYGON_OFFSET_UNITS => s.polygon_offset.1,
+            _ => *s.integers.get(&pname).unwrap_or(&0) as GLfloat,
+        }
+    });
+    unsafe { *data = value; }
+}
+extern "system" fn gen_buffers(n:GLsizei, buffers:*mut GLuint) {
+    record(GlCall::GenBuffers(unsafe { gen_names("buffer", n, buffers) }));
+}
+extern "system" fn bind_buffer(target:GLenum, buffer:GLuint) {
+    if target == gl::PIXEL_UNPACK_BUFFER {
+        with_state(|s| s.pixel_unpack_buffer = buffer);
+    }
+    record(GlCall::BindBuffer(target, buffer));
+}
+extern "system" fn buffer_data(target:GLenum, size:GLsizeiptr, data:*const c_void, usage:GLenum) {
+    let data = {
+        if data.is_null() {
+            None
+        } else {
+            Some(unsafe { std::slice::from_raw_parts(data as *const u8, size as usize) }.to_vec())
+        }
+    };
+    record(GlCall::BufferData(target, size, usage, data));
+}
+extern "system" fn buffer_sub_data(target:GLenum, offset:GLintptr, size:GLsizeiptr, data:*const c_void) {
+    let data = unsafe { std::slice::from_raw_parts(data as *const u8, size as usize) }.to_vec();
+    record(GlCall::BufferSubData(target, offset, data));
+}
+extern "system" fn bind_buffer_base(target:GLenum, index:GLuint, buffer:GLuint) {
+    record(GlCall::BindBufferBase(target, index, buffer));
+}
+extern "system" fn buffer_storage(target:GLenum, size:GLsizeiptr, data:*const c_void, flags:GLbitfield) {
+    let data = {
+        if data.is_null() {
+            None
+        } else {
+            Some(unsafe { std::slice::from_raw_parts(data as *const u8, size as usize) }.to_vec())
+        }
+    };
+    record(GlCall::BufferStorage(target, size, flags, data));
+}
+extern "system" fn map_buffer_range(target:GLenum, offset:GLintptr, length:GLsizeiptr, access:GLbitfield) -> *mut c_void {
+    record(GlCall::MapBufferRange(target, offset, length, access));
+    with_state(|s| {
+        s.mapped = vec![0; length as usize];
+        s.mapped.as_mut_ptr() as *mut c_void
+    })
+}
+extern "system" fn unmap_buffer(target:GLenum) -> GLboolean {
+    let data = with_state(|s| std::mem::take(&mut s.mapped));
+    record(GlCall::UnmapBuffer(target, data));
+    gl::TRUE
+}
+extern "system" fn delete_buffers(n:GLsizei, buffers:*const GLuint) {
+    record(GlCall::DeleteBuffers(unsafe { names_of(n, buffers) }));
+}
+extern "system" fn gen_vertex_arrays(n:GLsizei, arrays:*mut GLuint) {
+    record(GlCall::GenVertexArrays(unsafe { gen_names("vertex_array", n, arrays) }));
+}
+extern "system" fn bind_vertex_array(array:GLuint) {
+    record(GlCall::BindVertexArray(array));
+}
+extern "system" fn delete_vertex_arrays(n:GLsizei, arrays:*const GLuint) {
+    record(GlCall::DeleteVertexArrays(unsafe { names_of(n, arrays) }));
+}
+extern "system" fn enable_vertex_attrib_array(index:GLuint) {
+    record(GlCall::EnableVertexAttribArray(index));
+}
+extern "system" fn vertex_attrib_pointer(index:GLuint, size:GLint, type_:GLenum, normalized:GLboolean, stride:GLsizei, pointer:*const c_void) {
+    record(GlCall::VertexAttribPointer(index, size, type_, normalized, stride, pointer as usize));
+}
+extern "system" fn vertex_attrib_4f(index:GLuint, x:f32, y:f32, z:f32, w:f32) {
+    record(GlCall::VertexAttrib4f(index, x, y, z, w));
+}
+extern "system" fn create_shader(kind:GLenum) -> GLuint {
+    let shader = with_state(|s| s.new_name("shader"));
+    record(GlCall::CreateShader(kind, shader));
+    shader
+}
+extern "system" fn shader_source(shader:GLuint, count:GLsizei, strings:*const *const GLchar, lengths:*const GLint) {
+    let mut source = String::new();
+    for i in 0..count as usize {
+        let length = if lengths.is_null() { -1 } else { unsafe { *lengths.add(i) } };
+        source.push_str(&unsafe { gl_string(length, *strings.add(i)) });
+    }
+    record(GlCall::ShaderSource(shader, source));
+}
+extern "system" fn compile_shader(shader:GLuint) {
+    record(GlCall::CompileShader(shader));
+}
+extern "system" fn get_shaderiv(_shader:GLuint, pname:GLenum, params:*mut GLint) {
+    let value = with_state(|s| {
+        match pname {
+            gl::COMPILE_STATUS => s.compile_status,
+            gl::INFO_LOG_LENGTH => s.info_log.len() as GLint + 1,
+            _ => 0,
+        }
+    });
+    unsafe { *params = value; }
+}
+extern "system" fn get_info_log(_object:GLuint, buf_size:GLsizei, length:*mut GLsizei, info_log:*mut GLchar) {
+    let log = with_state(|s| s.info_log.clone());
+    let n = log.len().min((buf_size as usize).saturating_sub(1));
+    unsafe {
+        std::ptr::copy_nonoverlapping(log.as_ptr() as *const GLchar, info_log, n);
+        *info_log.add(n) = 0;
+        if !length.is_null() {
+            *length = n as GLsizei;
+        }
+    }
+}
+extern "system" fn delete_shader(shader:GLuint) {
+    record(GlCall::DeleteShader(shader));
+}
+extern "system" fn create_program() -> GLuint {
+    let program = with_state(|s| s.new_name("program"));
+    record(GlCall::CreateProgram(program));
+    program
+}
+extern "system" fn attach_shader(program:GLuint, shader:GLuint) {
+    record(GlCall::AttachShader(program, shader));
+}
+extern "system" fn bind_attrib_location(program:GLuint, index:GLuint, name:*const GLchar) {
+    let name = unsafe { string_of(name) };
+    record(GlCall::BindAttribLocation(program, index, name));
+}
+extern "system" fn detach_shader(program:GLuint, shader:GLuint) {
+    record(GlCall::DetachShader(program, shader));
+}
+extern "system" fn link_program(program:GLuint) {
+    record(GlCall::LinkProgram(program));
+}
+extern "system" fn get_programiv(_program:GLuint, pname:GLenum, params:*mut GLint) {
+    let value = with_state(|s| {
+        match pname {
+            gl::LINK_STATUS => s.link_status,
+            gl::INFO_LOG_LENGTH => s.info_log.len() as GLint + 1,
+            _ => 0,
+        }
+    });
+    unsafe { *params = value; }
+}
+extern "system" fn use_program(program:GLuint) {
+    record(GlCall::UseProgram(program));
+}
+extern "system" fn delete_program(program:GLuint) {
+    record(GlCall::DeleteProgram(program));
+}
+extern "system" fn get_attrib_location(program:GLuint, name:*const GLchar) -> GLint {
+    let name = unsafe { string_of(name) };
+    let location = with_state(|s| *s.attrib_locations.get(&name).unwrap_or(&-1));
+    record(GlCall::GetAttribLocation(program, name));
+    location
+}
+extern "system" fn get_uniform_location(program:GLuint, name:*const GLchar) -> GLint {
+    let name = unsafe { string_of(name) };
+    let location = with_state(|s| *s.uniform_locations.get(&name).unwrap_or(&-1));
+    record(GlCall::GetUniformLocation(program, name));
+    location
+}
+extern "system" fn get_uniform_block_index(program:GLuint, name:*const GLchar) -> GLuint {
+    let name = unsafe { string_of(name) };
+    let index = with_state(|s| *s.uniform_block_indices.get(&name).unwrap_or(&gl::INVALID_INDEX));
+    record(GlCall::GetUniformBlockIndex(program, name));
+    index
+}
+extern "system" fn uniform_block_binding(program:GLuint, block_index:GLuint, binding:GLuint) {
+    record(GlCall::UniformBlockBinding(program, block_index, binding));
+}
+extern "system" fn uniform_matrix_4fv(location:GLint, count:GLsizei, transpose:GLboolean, value:*const GLfloat) {
+    let values = unsafe { std::slice::from_raw_parts(value, 16 * count as usize) }.to_vec();
+    record(GlCall::UniformMatrix4fv(location, transpose, values));
+}
+extern "system" fn uniform_1i(location:GLint, value:GLint) {
+    record(GlCall::Uniform1i(location, value));
+}
+extern "system" fn uniform_4f(location:GLint, x:GLfloat, y:GLfloat, z:GLfloat, w:GLfloat) {
+    record(GlCall::Uniform4f(location, [x, y, z, w]));
+}
+extern "system" fn uniform_4i(location:GLint, x:GLint, y:GLint, z:GLint, w:GLint) {
+    record(GlCall::Uniform4i(location, [x, y, z, w]));
+}
+extern "system" fn draw_elements(mode:GLenum, count:GLsizei, type_:GLenum, indices:*const c_void) {
+    record(GlCall::DrawElements(mode, count, type_, indices as usize));
+}
+extern "system" fn draw_arrays(mode:GLenum, first:GLint, count:GLsizei) {
+    record(GlCall::DrawArrays(mode, first, count));
+}
+extern "system" fn gen_framebuffers(n:GLsizei, framebuffers:*mut GLuint) {
+    record(GlCall::GenFramebuffers(unsafe { gen_names("framebuffer", n, framebuffers) }));
+}
+extern "system" fn bind_framebuffer(target:GLenum, framebuffer:GLuint) {
+    record(GlCall::BindFramebuffer(target, framebuffer));
+}
+extern "system" fn delete_framebuffers(n:GLsizei, framebuffers:*const GLuint) {
+    record(GlCall::DeleteFramebuffers(unsafe { names_of(n, framebuffers) }));
+}
+extern "system" fn check_framebuffer_status(_target:GLenum) -> GLenum {
+    with_state(|s| s.framebuffer_status)
+}
+extern "system" fn gen_renderbuffers(n:GLsizei, renderbuffers:*mut GLuint) {
+    record(GlCall::GenRenderbuffers(unsafe { gen_names("renderbuffer", n, renderbuffers) }));
+}
+extern "system" fn bind_renderbuffer(target:GLenum, renderbuffer:GLuint) {
+    record(GlCall::BindRenderbuffer(target, renderbuffer));
+}
+extern "system" fn delete_renderbuffers(n:GLsizei, renderbuffers:*const GLuint) {
+    record(GlCall::DeleteRenderbuffers(unsafe { names_of(n, renderbuffers) }));
+}
+extern "system" fn renderbuffer_storage(target:GLenum, internal_format:GLenum, width:GLsizei, height:GLsizei) {
+    record(GlCall::RenderbufferStorage(target, internal_format, width, height));
+}
+extern "system" fn framebuffer_renderbuffer(target:GLenum, attachment:GLenum, rb_target:GLenum, renderbuffer:GLuint) {
+    record(GlCall::FramebufferRenderbuffer(target, attachment, rb_target, renderbuffer));
+}
+extern "system" fn framebuffer_texture_2d(target:GLenum, attachment:GLenum, tex_target:GLenum, texture:GLuint, level:GLint) {
+    record(GlCall::FramebufferTexture2D(target, attachment, tex_target, texture, level));
+}
+extern "system" fn framebuffer_texture_layer(target:GLenum, attachment:GLenum, texture:GLuint, level:GLint, layer:GLint) {
+    record(GlCall::FramebufferTextureLayer(target, attachment, texture, level, layer));
+}
+extern "system" fn draw_buffers(n:GLsizei, buffers:*const GLenum) {
+    let buffers = unsafe { std::slice::from_raw_parts(buffers, n as usize) }.to_vec();
+    record(GlCall::DrawBuffers(buffers));
+}
+extern "system" fn gen_textures(n:GLsizei, textures:*mut GLuint) {
+    record(GlCall::GenTextures(unsafe { gen_names("texture", n, textures) }));
+}
+extern "system" fn bind_texture(target:GLenum, texture:GLuint) {
+    record(GlCall::BindTexture(target, texture));
+}
+extern "system" fn delete_textures(n:GLsizei, textures:*const GLuint) {
+    record(GlCall::DeleteTextures(unsafe { names_of(n, textures) }));
+}
+extern "system" fn tex_image_2d(target:GLenum, level:GLint, internal_format:GLint, width:GLsizei, height:GLsizei,
+                                _border:GLint, format:GLenum, type_:GLenum, data:*const c_void) {
+    record(GlCall::TexImage2D(target, level, internal_format, width, height, format, type_, !data.is_null()));
+}
+extern "system" fn tex_image_3d(target:GLenum, level:GLint, internal_format:GLint, width:GLsizei, height:GLsizei, depth:GLsizei,
+                                _border:GLint, format:GLenum, type_:GLenum, data:*const c_void) {
+    record(GlCall::TexImage3D(target, level, internal_format, width, height, depth, format, type_, !data.is_null()));
+}
+extern "system" fn compressed_tex_image_2d(target:GLenum, level:GLint, internal_format:GLenum, width:GLsizei, height:GLsizei,
+                                           _border:GLint, image_size:GLsizei, _data:*const c_void) {
+    record(GlCall::CompressedTexImage2D(target, level, internal_format, width, height, image_size));
+}
+extern "system" fn compressed_tex_image_3d(target:GLenum, level:GLint, internal_format:GLenum,
+                                           width:GLsizei, height:GLsizei, depth:GLsizei,
+                                           _border:GLint, image_size:GLsizei, _data:*const c_void) {
+    record(GlCall::CompressedTexImage3D(target, level, internal_format, width, height, depth, image_size));
+}
+extern "system" fn tex_sub_image_2d(target:GLenum, level:GLint, x:GLint, y:GLint, width:GLsizei, height:GLsizei,
+                                    format:GLenum, type_:GLenum, data:*const c_void) {
+    if with_state(|s| s.pixel_unpack_buffer) != 0 {
+        record(GlCall::TexSubImage2DBuffer(target, level, x, y, width, height, format, type_, data as usize));
+    } else {
+        record(GlCall::TexSubImage2D(target, level, x, y, width, height, format, type_));
+    }
+}
+extern "system" fn tex_sub_image_3d(target:GLenum, level:GLint, x:GLint, y:GLint, z:GLint,
+                                    width:GLsizei, height:GLsizei, depth:GLsizei,
+                                    format:GLenum, type_:GLenum, _data:*const c_void) {
+    record(GlCall::TexSubImage3D(target, level, x, y, z, width, height, depth, format, type_));
+}
+extern "system" fn tex_parameteri(target:GLenum, pname:GLenum, param:GLint) {
+    record(GlCall::TexParameteri(target, pname, param));
+}
+extern "system" fn generate_mipmap(target:GLenum) {
+    record(GlCall::GenerateMipmap(target));
+}
+extern "system" fn active_texture(texture:GLenum) {
+    record(GlCall::ActiveTexture(texture));
+}
+extern "system" fn gen_samplers(n:GLsizei, samplers:*mut GLuint) {
+    record(GlCall::GenSamplers(unsafe { gen_names("sampler", n, samplers) }));
+}
+extern "system" fn bind_sampler(unit:GLuint, sampler:GLuint) {
+    record(GlCall::BindSampler(unit, sampler));
+}
+extern "system" fn delete_samplers(n:GLsizei, samplers:*const GLuint) {
+    record(GlCall::DeleteSamplers(unsafe { names_of(n, samplers) }));
+}
+extern "system" fn sampler_parameteri(sampler:GLuint, pname:GLenum, param:GLint) {
+    record(GlCall::SamplerParameteri(sampler, pname, param));
+}
+extern "system" fn sampler_parameterf(sampler:GLuint, pname:GLenum, param:f32) {
+    record(GlCall::SamplerParameterf(sampler, pname, param));
+}
+extern "system" fn sampler_parameterfv(sampler:GLuint, pname:GLenum, params:*const f32) {
+    let values = unsafe { std::slice::from_raw_parts(params, 4) }.to_vec();
+    record(GlCall::SamplerParameterfv(sampler, pname, values));
+}
+extern "system" fn viewport(x:GLint, y:GLint, width:GLsizei, height:GLsizei) {
+    record(GlCall::Viewport(x, y, width, height));
+}
+extern "system" fn clear_color(r:GLfloat, g:GLfloat, b:GLfloat, a:GLfloat) {
+    record(GlCall::ClearColor(r, g, b, a));
+}
+extern "system" fn clear(mask:GLbitfield) {
+    record(GlCall::Clear(mask));
+}
+extern "system" fn enable(cap:GLenum) {
+    with_state(|s| if !s.enabled.contains(&cap) { s.enabled.push(cap) });
+    record(GlCall::Enable(cap));
+}
+extern "system" fn disable(cap:GLenum) {
+    with_state(|s| s.enabled.retain(|c| *c != cap));
+    record(GlCall::Disable(cap));
+}
+extern "system" fn is_enabled(cap:GLenum) -> GLboolean {
+    with_state(|s| s.enabled.contains(&cap) as GLboolean)
+}
+extern "system" fn color_mask(r:GLboolean, g:GLboolean, b:GLboolean, a:GLboolean) {
+    with_state(|s| s.color_mask = [r, g, b, a]);
+    record(GlCall::ColorMask(r, g, b, a));
+}
+extern "system" fn depth_mask(flag:GLboolean) {
+    with_state(|s| s.depth_mask = flag);
+    record(GlCall::DepthMask(flag));
+}
+extern "system" fn polygon_offset(factor:GLfloat, units:GLfloat) {
+    with_state(|s| s.polygon_offset = (factor, units));
+    record(GlCall::PolygonOffset(factor, units));
+}
+extern "system" fn read_buffer(mode:GLenum) {
+    record(GlCall::ReadBuffer(mode));
+}
+extern "system" fn pixel_storei(pname:GLenum, param:GLint) {
+    record(GlCall::PixelStorei(pname, param));
+}
+extern "system" fn read_pixels(x:GLint, y:GLint, width:GLsizei, height:GLsizei, format:GLenum, type_:GLenum, _pixels:*mut c_void) {
+    record(GlCall::ReadPixels(x, y, width, height, format, type_));
+}
+extern "system" fn finish() {
+    record(GlCall::Finish);
+}
+extern "system" fn debug_message_callback(callback:GLDEBUGPROC, user_param:*const c_void) {
+    with_state(|s| s.debug_callback = Some((callback, user_param as usize)));
+    record(GlCall::DebugMessageCallback(true));
+}
+extern "system" fn debug_message_control(source:GLenum, type_:GLenum, severity:GLenum, count:GLsizei, ids:*const GLuint, enabled:GLboolean) {
+    let ids = if ids.is_null() { Vec::new() } else { unsafe { names_of(count, ids) } };
+    record(GlCall::DebugMessageControl(source, type_, severity, ids, enabled));
+}
+extern "system" fn object_label(identifier:GLenum, name:GLuint, length:GLsizei, label:*const GLchar) {
+    record(GlCall::ObjectLabel(identifier, name, unsafe { gl_string(length, label) }));
+}
+extern "system" fn push_debug_group(source:GLenum, id:GLuint, length:GLsizei, message:*const GLchar) {
+    record(GlCall::PushDebugGroup(source, id, unsafe { gl_string(length, message) }));
+}
+extern "system" fn pop_debug_group() {
+    record(GlCall::PopDebugGroup);
+}
+extern "system" fn gen_queries(n:GLsizei, ids:*mut GLuint) {
+    record(GlCall::GenQueries(unsafe { gen_names("query", n, ids) }));
+}
+extern "system" fn delete_queries(n:GLsizei, ids:*const GLuint) {
+    record(GlCall::DeleteQueries(unsafe { names_of(n, ids) }));
+}
+extern "system" fn begin_query(target:GLenum, id:GLuint) {
+    record(GlCall::BeginQuery(target, id));
+}
+extern "system" fn end_query(target:GLenum) {
+    record(GlCall::EndQuery(target));
+}
+extern "system" fn query_counter(id:GLuint, target:GLenum) {
+    record(GlCall::QueryCounter(id, target));
+}
+extern "system" fn get_query_objectuiv(id:GLuint, pname:GLenum, params:*mut GLuint) {
+    let result = with_state(|s| *s.query_results.get(&id).unwrap_or(&Some(0)));
+    let value = match pname {
+        gl::QUERY_RESULT_AVAILABLE => result.is_some() as GLuint,
+        _ => result.unwrap_or(0) as GLuint,
+    };
+    unsafe { *params = value; }
+}
+extern "system" fn get_query_objectui64v(id:GLuint, _pname:GLenum, params:*mut GLuint64) {
+    let result = with_state(|s| *s.query_results.get(&id).unwrap_or(&Some(0)));
+    unsafe { *params = result.unwrap_or(0); }
+}
+extern "system" fn begin_conditional_render(id:GLuint, mode:GLenum) {
+    record(GlCall::BeginConditionalRender(id, mode));
+}
+extern "system" fn end_conditional_render() {
+    record(GlCall::EndConditionalRender);
+}
+extern "system" fn fence_sync(_condition:GLenum, _flags:GLbitfield) -> GLsync {
+    let sync = with_state(|s| s.new_name("sync")) as usize;
+    record(GlCall::FenceSync(sync));
+    sync as GLsync
+}
+extern "system" fn client_wait_sync(sync:GLsync, _flags:GLbitfield, _timeout:GLuint64) -> GLenum {
+    if with_state(|s| s.unsignaled.contains(&(sync as usize))) {
+        gl::TIMEOUT_EXPIRED
+    } else {
+        gl::ALREADY_SIGNALED
+    }
+}
+extern "system" fn delete_sync(sync:GLsync) {
+    record(GlCall::DeleteSync(sync as usize));
+}
@@ -0,0 +1,355 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    occlusion.rs
+@brief   Occlusion culling of instantiables using bounding boxes
+ */
+
+//a Documentation
+
+/*!
+
+Occlusion culling draws the bounding boxes of the vertices of a
+[crate::ShaderInstantiable] (with colour and depth writes disabled)
+inside a GL_ANY_SAMPLES_PASSED query, before drawing the
+instantiable itself.
+
+To avoid stalling on the query the real draw uses the result of the
+*previous* frame's query: with conditional rendering (desktop OpenGL
+3.0) the draw is made inside glBeginConditionalRender in
+GL_QUERY_NO_WAIT mode; otherwise the previous result is used by the
+CPU if it is available, and the last known visibility if not.
+
+Each instance drawn has its own pair of queries, used alternately in
+successive frames. Instances are identified by a key given by the
+caller ([crate::ShaderInstantiable::gl_draw_keyed]), which must stay
+the same for an instance from frame to frame and be unique among the
+instances drawn with the [crate::ShaderInstantiable]; draws without a
+key are not culled. A new key is treated as a new instance, drawn
+until its queries have results. Frames must be ended with
+[crate::RenderContext::end_frame]; the queries of instances not drawn
+in the previous frame are reused for other instances.
+
+The boxes are drawn with the program in use for the instantiable,
+using its position attribute and its model and mesh matrices, so they
+are transformed as the instantiable is. Face culling is disabled
+while the boxes are drawn, so that a camera inside a box does not
+cull the object; the face culling and the colour and depth write
+masks are restored after the boxes are drawn.
+
+Objects are drawn in the frame after they become visible, so a
+frame may briefly miss an object that has just come into view.
+
+Only one occlusion query may be active at a time, so the test is
+skipped (and the instantiable drawn) while a [crate::GpuProfiler]
+has a GL_SAMPLES_PASSED query active.
+
+!*/
+
+//a Imports
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use gl::types::*;
+
+use crate::{Renderable, RenderContext, GlFeature, ShaderClass, UniformId};
+use crate::{Backend, GlBackend};
+use crate::deletion::{self, GlObject};
+use crate::recovery;
+use crate::query;
+
+//a Constants
+//ci BOX_INDICES
+/// Indices of the corners of a [crate::BoundingBox] for its twelve
+/// triangles (see [crate::BoundingBox::corners])
+const BOX_INDICES : [u8; 36] = [
+    0, 2, 6,  0, 6, 4, // -x
+    1, 5, 7,  1, 7, 3, // +x
+    0, 4, 5,  0, 5, 1, // -y
+    2, 3, 7,  2, 7, 6, // +y
+    0, 1, 3,  0, 3, 2, // -z
+    4, 6, 7,  4, 7, 5, // +z
+];
+
+//a Frames
+thread_local! {
+    /// Number of frames ended on this thread
+    static FRAME : Cell<u64> = const { Cell::new(0) };
+}
+
+//fp end_frame
+/// Mark the end of a frame, so that the instances drawn next use the
+/// other query of their pairs
+pub(crate) fn end_frame() {
+    FRAME.with(|f| f.set(f.get() + 1));
+}
+
+//fi frame
+/// Get the number of the current frame
+fn frame() -> u64 {
+    FRAME.with(|f| f.get())
+}
+
+//a InstanceQueries
+//ti InstanceQueries
+/// The occlusion queries of an instance, used alternately in
+/// successive frames
+struct InstanceQueries {
+    /// Queries for even and odd frames
+    queries : [GLuint; 2],
+    /// Frame in which the instance was last tested
+    tested : Option<u64>,
+    /// Visibility from the latest query result available
+    visible : bool,
+}
+
+//a OcclusionCulling
+//tp OcclusionCulling
+/// The bounding box VAOs and occlusion queries for occlusion culling
+/// of a [crate::ShaderInstantiable]
+pub struct OcclusionCulling {
+    /// Buffer of the eight corners of the box of each vertices
+    box_buffer : GLuint,
+    /// Buffer of [BOX_INDICES]
+    index_buffer : GLuint,
+    /// VAO for the box of each vertices
+    vaos : Vec<GLuint>,
+    /// Boxes to draw, as (vertices index, matrix index) pairs
+    boxes : Vec<(usize, usize)>,
+    /// Queries for each instance drawn, by the key given for it
+    instances : RefCell<HashMap<usize, InstanceQueries>>,
+    /// Queries of instances no longer drawn, for reuse
+    free : RefCell<Vec<GLuint>>,
+    /// Frame in which instances were last drawn
+    frame : Cell<u64>,
+    /// Key of the instance last tested
+    current : Cell<usize>,
+    /// True if the draw of the current instance is conditional
+    conditional_draw : Cell<bool>,
+    /// True if conditional rendering is used
+    conditional : bool,
+    /// The context generation the objects were created in
+    generation : u32,
+}
+
+//ip OcclusionCulling
+impl OcclusionCulling {
+    //fp new
+    /// Create the bounding box VAOs and queries for an instantiable
+    /// with a shader class
+    ///
+    /// All the vertices of the instantiable must have bounding boxes
+    /// (from Float32 positions) and the shader class must have a
+    /// position attribute
+    pub(crate) fn new(render_context:&RenderContext,
+                      shader_class:&dyn ShaderClass,
+                      instantiable:&model3d::Instantiable<Renderable>) -> Result<Self, String> {
+        render_context.require(GlFeature::OcclusionQueries)?;
+        let conditional = render_context.supports(GlFeature::ConditionalRender);
+        let position = shader_class.attributes().iter()
+            .find(|(_, va)| *va == model3d::VertexAttr::Position)
+            .map(|(index, _)| *index)
+            .ok_or_else(|| "Occlusion culling requires the shader to have a position attribute".to_string())?;
+
+        let mut corners : Vec<u8> = Vec::new();
+        for (i, v) in instantiable.vertices.iter().enumerate() {
+            let bounds = v.bounds()
+                .ok_or_else(|| format!("Occlusion culling requires a bounding box for vertices {}", i))?;
+            for c in bounds.corners().iter().flatten() {
+                corners.extend_from_slice(&c.to_ne_bytes());
+            }
+        }
+        let mut boxes = Vec::new();
+        for (i, p) in instantiable.render_recipe.primitives.iter().enumerate() {
+            let b = (p.vertices_index(), instantiable.render_recipe.matrix_for_primitives[i]);
+            if !boxes.contains(&b) {
+                boxes.push(b);
+            }
+        }
+
+        Backend::bind_vertex_array(0);
+        let box_buffer = Backend::gen_buffer();
+        Backend::bind_buffer(gl::ARRAY_BUFFER, box_buffer);
+        Backend::buffer_data(gl::ARRAY_BUFFER, &corners, gl::STATIC_DRAW);
+        let index_buffer = Backend::gen_buffer();
+        Backend::bind_buffer(gl::ELEMENT_ARRAY_BUFFER, index_buffer);
+        Backend::buffer_data(gl::ELEMENT_ARRAY_BUFFER, &BOX_INDICES, gl::STATIC_DRAW);
+        Backend::bind_buffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        let mut vaos = Vec::new();
+        for i in 0..instantiable.vertices.len() {
+            let vao = Backend::gen_vertex_array();
+            Backend::bind_vertex_array(vao);
+            Backend::bind_buffer(gl::ELEMENT_ARRAY_BUFFER, index_buffer);
+            Backend::bind_buffer(gl::ARRAY_BUFFER, box_buffer);
+            Backend::enable_vertex_attrib_array(position);
+            Backend::vertex_attrib_pointer(position, 3, gl::FLOAT, false, 0, i * 8 * 12);
+            vaos.push(vao);
+        }
+        Backend::bind_vertex_array(0);
+        Backend::bind_buffer(gl::ARRAY_BUFFER, 0);
+        Ok(Self { box_buffer, index_buffer, vaos, boxes,
+                  instances : RefCell::new(HashMap::new()),
+                  free : RefCell::new(Vec::new()),
+                  frame : Cell::new(frame()),
+                  current : Cell::new(0),
+                  conditional_draw : Cell::new(false),
+                  conditional,
+                  generation : recovery::generation(),
+        })
+    }
+
+    //ap is_visible
+    /// Return the visibility of the instance with a key from the
+    /// latest occlusion query result available (true if there is none
+    /// yet)
+    pub fn is_visible(&self, key:usize) -> bool {
+        match self.instances.borrow().get(&key) {
+            Some(i) => i.visible,
+            None => true,
+        }
+    }
+
+    //ap is_current
+    /// Return true if the objects are for the current context (and
+    /// were not lost with a previous context)
+    pub fn is_current(&self) -> bool {
+        recovery::is_current(self.generation)
+    }
+
+    //ap is_conditional
+    /// Return true if conditional rendering is used
+    pub fn is_conditional(&self) -> bool {
+        self.conditional
+    }
+
+    //mp test
+    /// Draw the bounding boxes of the instance with a key inside its
+    /// occlusion query for the current frame; the program must be in
+    /// use with its model matrix set
+    ///
+    /// Only one occlusion query may be active at a time, so the test
+    /// is skipped (returning false) while a [crate::GpuProfiler]
+    /// samples passed query is active; the instantiable should then
+    /// be drawn without culling
+    pub(crate) fn test(&self,
+                       shader_class:&dyn ShaderClass,
+                       matrices:&[model3d::Mat4],
+                       key:usize) -> bool {
+        if query::samples_query_active() {
+            return false;
+        }
+        let frame = frame();
+        let mut instances = self.instances.borrow_mut();
+        if self.frame.get() != frame {
+            self.frame.set(frame);
+            let mut free = self.free.borrow_mut();
+            instances.retain(|_, i| {
+                let drawn = matches!(i.tested, Some(t) if t + 1 >= frame);
+                if !drawn {
+                    free.extend_from_slice(&i.queries);
+                }
+                drawn
+            });
+        }
+        self.current.set(key);
+        let instance = instances.entry(key).or_insert_with(|| {
+            let mut free = self.free.borrow_mut();
+            let mut query = || free.pop().unwrap_or_else(Backend::gen_query);
+            InstanceQueries { queries:[query(), query()], tested:None, visible:true }
+        });
+        let query = instance.queries[(frame % 2) as usize];
+
+        let cull_face = Backend::is_enabled(gl::CULL_FACE);
+        if cull_face {
+            Backend::disable(gl::CULL_FACE);
+        }
+        let mut color_mask = [1; 4];
+        Backend::get_integers(gl::COLOR_WRITEMASK, &mut color_mask);
+        let depth_mask = Backend::get_integer(gl::DEPTH_WRITEMASK) != 0;
+        Backend::color_mask(false, false, false, false);
+        Backend::depth_mask(false);
+        Backend::begin_query(gl::ANY_SAMPLES_PASSED, query);
+        let mesh_matrix = shader_class.uniform(UniformId::MeshMatrix);
+        for (v, m) in &self.boxes {
+            if let Some(u) = mesh_matrix {
+                Backend::uniform_matrix4(u, &matrices[*m]);
+            }
+            Backend::bind_vertex_array(self.vaos[*v]);
+            Backend::draw_elements(gl::TRIANGLES, BOX_INDICES.len() as GLsizei, gl::UNSIGNED_BYTE, 0);
+        }
+        Backend::end_query(gl::ANY_SAMPLES_PASSED);
+        Backend::color_mask(color_mask[0] != 0, color_mask[1] != 0, color_mask[2] != 0, color_mask[3] != 0);
+        Backend::depth_mask(depth_mask);
+        if cull_face {
+            Backend::enable(gl::CULL_FACE);
+        }
+        true
+    }
+
+    //mp begin_draw
+    /// Begin the real draw of the instance just tested, using its
+    /// query from the previous frame; return false if the draw should
+    /// be skipped
+    pub(crate) fn begin_draw(&self) -> bool {
+        let frame = self.frame.get();
+        let mut instances = self.instances.borrow_mut();
+        let instance = instances.get_mut(&self.current.get()).unwrap();
+        let previous_tested = frame > 0 && instance.tested == Some(frame - 1);
+        instance.tested = Some(frame);
+        self.conditional_draw.set(false);
+        if !previous_tested {
+            return true;
+        }
+        let previous = instance.queries[((frame + 1) % 2) as usize];
+        if Backend::query_result_available(previous) {
+            instance.visible = Backend::query_result(previous) != 0;
+        }
+        if self.conditional {
+            Backend::begin_conditional_render(previous, gl::QUERY_NO_WAIT);
+            self.conditional_draw.set(true);
+            true
+        } else {
+            instance.visible
+        }
+    }
+
+    //mp end_draw
+    /// End the real draw of the instance
+    pub(crate) fn end_draw(&self) {
+        if self.conditional_draw.take() {
+            Backend::end_conditional_render();
+        }
+    }
+
+    //zz All done
+}
+
+//ip Drop for OcclusionCulling
+impl Drop for OcclusionCulling {
+    fn drop(&mut self) {
+        for vao in &self.vaos {
+            deletion::delete(self.generation, GlObject::VertexArray(*vao));
+        }
+        deletion::delete(self.generation, GlObject::Buffer(self.box_buffer));
+        deletion::delete(self.generation, GlObject::Buffer(self.index_buffer));
+        for instance in self.instances.borrow().values() {
+            for query in &instance.queries {
+                deletion::delete(self.generation, GlObject::Query(*query));
+            }
+        }
+        for query in self.free.borrow().iter() {
+            deletion::delete(self.generation, GlObject::Query(*query));
+        }
+    }
+}
@@ -22,25 +22,127 @@ limitations under the License.
 
 A shader program consists of a number of [GlShader]s linked together
 
+Shader sources given to [Program::compile_program] have their
+`#version` header rewritten to suit the context: `#version 330 core`
+(or later) becomes `#version 300 es` with default precision
+qualifiers for OpenGL ES and WebGL, and `#version 300 es` becomes
+`#version 330 core` for desktop OpenGL.
+
 !*/
 
 //a Imports
+use std::cell::{Cell, RefCell};
 use std::ffi::CString;
 
-use crate::utils;
 use crate::GlShader;
 use crate::ShaderClass;
+use crate::TextureSlot;
+use crate::{Backend, GlBackend};
+use crate::debug;
+use crate::deletion::{self, GlObject};
+use crate::recovery;
+
+//a Constants
+//ci GLES_PRECISION
+/// Types given a highp default precision after the `#version` for
+/// OpenGL ES, unless the source declares one; sampler3D and the array
+/// and shadow samplers have no default precision in GLSL ES 3.00
+const GLES_PRECISION : &[&str] = &["float", "int", "sampler3D", "sampler2DArray",
+                                   "sampler2DShadow", "samplerCubeShadow", "sampler2DArrayShadow"];
+
+//a Functions
+//fi declares_precision
+/// Return true if GLSL source has a default precision statement for a type
+fn declares_precision(src:&str, ty:&str) -> bool {
+    src.lines().any(|line| {
+        let mut words = line.split(|c:char| c.is_whitespace() || c == ';').filter(|w| !w.is_empty());
+        words.next() == Some("precision") && words.nth(1) == Some(ty)
+    })
+}
+
+//fp rewrite_version
+/// Rewrite the `#version` header of GLSL source for a desktop
+/// OpenGL context or an OpenGL ES context
+///
+/// Any desktop GLSL version (such as `#version 150`) is rewritten
+/// to `#version 300 es` for an OpenGL ES context, with default
+/// precisions added after any `#extension` directives that follow
+/// it; source without a `#version` line, or whose version already
+/// matches the context (including GLSL ES `#version 100`), is
+/// returned unchanged
+pub fn rewrite_version(src:&str, gles:bool) -> String {
+    let mut lines = src.lines().peekable();
+    let mut result = String::new();
+    let mut rewritten = false;
+    let mut add_precisions = false;
+    for line in &mut lines {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("//") {
+            result.push_str(line);
+            result.push('\n');
+            continue;
+        }
+        let mut words = trimmed.split_whitespace();
+        if words.next() == Some("#version") {
+            let version : usize = words.next().and_then(|v| v.parse().ok()).unwrap_or(0);
+            let is_es = words.next() == Some("es");
+            if gles && !is_es && version > 100 {
+                result.push_str("#version 300 es\n");
+                add_precisions = true;
+                rewritten = true;
+            } else if !gles && is_es {
+                result.push_str("#version 330 core\n");
+                rewritten = true;
+            }
+        }
+        if !rewritten {
+            return src.to_string();
+        }
+        break;
+    }
+    if add_precisions {
+        while let Some(line) = lines.next_if(|line| {
+            let trimmed = line.trim();
+            trimmed.is_empty() || trimmed.starts_with("//") || trimmed.starts_with("#extension")
+        }) {
+            result.push_str(line);
+            result.push('\n');
+        }
+        for ty in GLES_PRECISION {
+            if !declares_precision(src, ty) {
+                result.push_str(&format!("precision highp {};\n", ty));
+            }
+        }
+    }
+    for line in lines {
+        result.push_str(line);
+        result.push('\n');
+    }
+    result
+}
 
 //a Program
 //tp Program
 /// A shader program, with its 'known' attributes and uniforms
 pub struct Program {
     /// The GL ID of the program
-    id: gl::types::GLuint,
+    id: Cell<gl::types::GLuint>,
+    /// The context generation the program was created in
+    generation: Cell<u32>,
+    /// Kind and source of the shaders, retained so that the program
+    /// can be recreated after context loss
+    sources : Vec<(gl::types::GLenum, String)>,
     /// attribute names
     attributes : Vec<(gl::types::GLuint, model3d::VertexAttr)>,
+    /// Names of the attributes, 1-to-1 with attributes
+    attribute_names : Vec<String>,
     /// attribute names
-    uniforms : Vec<(gl::types::GLint, UniformId)>,
+    uniforms : Vec<(Cell<gl::types::GLint>, UniformId)>,
+    /// Names of the uniforms (or uniform blocks), 1-to-1 with uniforms
+    uniform_names : Vec<String>,
+    /// Binding points set for uniform blocks, applied again when the
+    /// program is recreated
+    block_bindings : RefCell<Vec<(UniformId, gl::types::GLuint)>>,
 }
 
 //tp UniformId
@@ -49,6 +151,11 @@ pub struct Program {
 pub enum UniformId {
     /// The view matrix uniform - once per framebuffer render
     ViewMatrix,
+    /// The projection matrix uniform - once per framebuffer render
+    ProjectionMatrix,
+    /// The product of the projection and view matrices - once per
+    /// framebuffer render
+    ViewProjectionMatrix,
     /// The model matrix uniform - once per model instance
     ModelMatrix,
     /// The mesh matrix uniform - once per model mesh
@@ -61,16 +168,69 @@ pub enum UniformId {
     User(usize),
     /// User uniform buffer - dependent on the program.
     Buffer(usize),
+    /// Sampler uniform for the texture of a material slot - set to
+    /// the texture unit of the slot when the material is bound
+    Texture(TextureSlot),
+    /// Base colour factor (vec4) of a [crate::Material] - set when
+    /// the material is bound
+    BaseColorFactor,
+    /// Emissive factor (vec4, with w of 0) of a [crate::Material]
+    EmissiveFactor,
+    /// Metallic (x) and roughness (y) factors (vec4) of a
+    /// [crate::Material]
+    MetallicRoughnessFactor,
+    /// Shadow matrices (mat4 array, one per cascade) of a
+    /// [crate::ShadowMap], by index, from world space to shadow map
+    /// texture coordinates and depth
+    ShadowMatrices(usize),
+    /// Cascade split distances (vec4) of a [crate::ShadowMap], by
+    /// index - the view depths of the far ends of the cascades
+    ShadowSplits(usize),
+    /// Light of a [crate::ShadowMap] (vec4), by index - the position
+    /// and range of a point or spot light, or the direction (with w
+    /// of 0) of a directional light
+    ShadowLight(usize),
+    /// Sampler uniform for the depth texture of a [crate::ShadowMap],
+    /// by index
+    ShadowMap(usize),
+    /// The uniform block of the [crate::Lights] - bound to their
+    /// uniform buffer binding point
+    Lights,
+    /// Sampler uniform for the cluster light lists of the
+    /// [crate::Lights] (in clustered mode)
+    LightClusters,
+    /// The indices (ivec4, -1 for none) of the most relevant lights
+    /// for an instance - once per model instance
+    InstanceLights,
+    /// Image-based lighting parameters (vec4) of an
+    /// [crate::Environment] - the highest mip level of its prefiltered
+    /// map (x) and its intensity (y)
+    Environment,
+}
+
+//ip UniformId
+impl UniformId {
+    //mp is_block
+    /// Return true if the uniform is a uniform block (with a block
+    /// index rather than a location)
+    pub fn is_block(&self) -> bool {
+        matches!(self, Self::Buffer(_) | Self::Lights)
+    }
 }
 
 ///ip Program
 impl Program {
     //fp compile_program
     /// Compile a program from a slice of kind/source pairs
+    ///
+    /// The `#version` of each source is rewritten to suit the
+    /// context (desktop OpenGL or OpenGL ES)
     pub fn compile_program(srcs:&[(gl::types::GLenum, &str)]) ->  Result<Self, String> {
+        let gles = Backend::is_gles();
         let mut shaders = Vec::new();
         for (kind, src) in srcs {
-            shaders.push( GlShader::from_source(&CString::new(*src).unwrap(), *kind)? );
+            let src = rewrite_version(src, gles);
+            shaders.push( GlShader::from_source(&CString::new(src).unwrap(), *kind)? );
         }
         Self::from_shaders(shaders)
     }
@@ -78,94 +238,183 @@ impl Program {
     //mp add_attr_name
     /// Add an attribute to the [Program] from its name (that should be in the shader source)
     pub fn add_attr_name(&mut self, name:&str, vertex_attr:model3d::VertexAttr) -> Result<&mut Self, String> {
-        let name_c = CString::new(name).unwrap();
-        let attr_index = unsafe {gl::GetAttribLocation( self.id, name_c.as_ptr() ) };
-        if attr_index < 0 {
-            Err(format!("Unable to find attribute {} in program", name))
-        } else {
-            self.attributes.push( (attr_index as gl::types::GLuint, vertex_attr) );
+        if let Some(attr_index) = Backend::attrib_location(self.id(), name) {
+            self.attributes.push( (attr_index, vertex_attr) );
+            self.attribute_names.push(name.to_string());
             Ok(self)
+        } else {
+            Err(format!("Unable to find attribute {} in program", name))
         }
     }
 
     //mp add_uniform_name
     /// Add a uniform to the [Program] from its name (that should be in the shader source)
     pub fn add_uniform_name(&mut self, name:&str, uniform_id:UniformId) -> Result<&mut Self, String> {
-        let name_c = CString::new(name).unwrap();
-        let uniform_index = unsafe { gl::GetUniformLocation( self.id, name_c.as_ptr() ) };
-        if uniform_index == (gl::INVALID_INDEX as i32) {
-            Err(format!("Unable to find uniform {} in program", name))
-        } else {
-            self.uniforms.push( (uniform_index as gl::types::GLint, uniform_id) );
+        if let Some(uniform_index) = Backend::uniform_location(self.id(), name) {
+            self.uniforms.push( (Cell::new(uniform_index), uniform_id) );
+            self.uniform_names.push(name.to_string());
             Ok(self)
+        } else {
+            Err(format!("Unable to find uniform {} in program", name))
         }
     }
 
     //mp add_uniform_buffer_name
     /// Add a uniform buffer (or 'block') to the [Program] from its name (that should be in the shader source)
     pub fn add_uniform_buffer_name(&mut self, name:&str, id:usize) -> Result<&mut Self, String> {
-        let name_c = CString::new(name).unwrap();
-        let uniform_index = unsafe { gl::GetUniformBlockIndex( self.id, name_c.as_ptr() ) };
-        if uniform_index == gl::INVALID_INDEX {
-            Err(format!("Unable to find uniform block {} in program", name))
-        } else {
-            self.uniforms.push( (uniform_index as gl::types::GLint, UniformId::Buffer(id)) );
+        self.add_uniform_block_name(name, UniformId::Buffer(id))
+    }
+
+    //mp add_uniform_block_name
+    /// Add a uniform block to the [Program] from its name (that
+    /// should be in the shader source) for a block [UniformId] (such
+    /// as [UniformId::Lights])
+    pub fn add_uniform_block_name(&mut self, name:&str, uniform_id:UniformId) -> Result<&mut Self, String> {
+        if !uniform_id.is_block() {
+            return Err(format!("Uniform {:?} is not a uniform block", uniform_id));
+        }
+        if let Some(uniform_index) = Backend::uniform_block_index(self.id(), name) {
+            self.uniforms.push( (Cell::new(uniform_index as gl::types::GLint), uniform_id) );
+            self.uniform_names.push(name.to_string());
             Ok(self)
+        } else {
+            Err(format!("Unable to find uniform block {} in program", name))
         }
     }
 
-    //fp from_shaders
-    /// Create a program from a slice of shaders; link the shaders together
-    pub fn from_shaders(shaders: Vec<GlShader>) -> Result<Self, String> {
-        let program_id = unsafe {
-            let program_id = gl::CreateProgram();
-            for shader in &shaders {
-                gl::AttachShader(program_id, shader.id());
-            }
-            gl::LinkProgram(program_id);
-            program_id
-        };
-
-        if utils::get_programiv(program_id, gl::LINK_STATUS) == 0 {
-            let err = utils::get_shader_error( program_id,
-                                         |id| utils::get_programiv(id, gl::INFO_LOG_LENGTH),
-                                         |id, len, buf| unsafe {
-                                             gl::GetProgramInfoLog( id, len, std::ptr::null_mut(), buf)
-                                         } );
-            Err(format!("Shader linking error {}", err))?;
-        }                
-        utils::check_errors().expect("Linked");
+    //mp set_uniform_block_binding
+    /// Bind a uniform block of the program (added with
+    /// [Program::add_uniform_block_name]) to a uniform buffer binding
+    /// point; the binding is applied again if the program is
+    /// recreated after context loss
+    pub fn set_uniform_block_binding(&self, uniform_id:UniformId, binding:gl::types::GLuint) -> Result<(), String> {
+        let block = self.uniform_block(uniform_id)
+            .ok_or_else(|| format!("Uniform block {:?} has not been added to the program", uniform_id))?;
+        Backend::uniform_block_binding(self.id(), block, binding);
+        let mut block_bindings = self.block_bindings.borrow_mut();
+        block_bindings.retain(|(u, _)| *u != uniform_id);
+        block_bindings.push((uniform_id, binding));
+        Ok(())
+    }
 
-        for shader in shaders {
-            unsafe {
-                gl::DetachShader(program_id, shader.id());
-                // Don't delete the shader - that happens when the shader is dropped
-            }
+    //mi uniform_block
+    /// Get the index of a uniform block added to the program, if it
+    /// is found
+    fn uniform_block(&self, uniform_id:UniformId) -> Option<gl::types::GLuint> {
+        if !uniform_id.is_block() {
+            return None;
         }
+        self.uniforms.iter()
+            .find(|(index, u)| *u == uniform_id && index.get() >= 0)
+            .map(|(index, _)| index.get() as gl::types::GLuint)
+    }
 
+    //fp from_shaders
+    /// Create a program from a slice of shaders; link the shaders together
+    pub fn from_shaders(shaders: Vec<GlShader>) -> Result<Self, String> {
+        let program_id = Self::link(&shaders, &[])?;
+        let sources = shaders.iter().map(|s| (s.kind(), s.source().to_string())).collect();
         let attributes = Vec::new();
         let uniforms = Vec::new();
         Ok(Program {
-            id: program_id,
+            id: Cell::new(program_id),
+            generation: Cell::new(recovery::generation()),
+            sources,
             attributes,
+            attribute_names: Vec::new(),
             uniforms,
+            uniform_names: Vec::new(),
+            block_bindings: RefCell::new(Vec::new()),
         }
         )
     }
 
+    //fi link
+    /// Link shaders into a new program, with attributes bound to
+    /// locations, returning the program id
+    fn link(shaders:&[GlShader], attributes:&[(gl::types::GLuint, &str)]) -> Result<gl::types::GLuint, String> {
+        let program_id = Backend::create_program();
+        for shader in shaders {
+            Backend::attach_shader(program_id, shader.id());
+        }
+        for (index, name) in attributes {
+            Backend::bind_attrib_location(program_id, *index, name);
+        }
+        Backend::link_program(program_id);
+
+        if !Backend::program_link_status(program_id) {
+            let err = Backend::program_info_log(program_id);
+            Backend::delete_program(program_id);
+            Err(format!("Shader linking error {}", err))?;
+        }                
+        crate::check_errors().expect("Linked");
+
+        for shader in shaders {
+            Backend::detach_shader(program_id, shader.id());
+            // Don't delete the shader - that happens when the shader is dropped
+        }
+        Ok(program_id)
+    }
+
+    //mp recreate
+    /// Recreate the program after context loss (if it is stale) from
+    /// its retained shader sources, with the same attribute locations,
+    /// finding its uniforms again and applying the uniform block
+    /// bindings set with [Program::set_uniform_block_binding]
+    ///
+    /// This is done by [crate::RenderContext::recover_context] for
+    /// programs in the [crate::ResourceRegistry]
+    pub fn recreate(&self) -> Result<(), String> {
+        if recovery::is_current(self.generation.get()) {
+            return Ok(());
+        }
+        let mut shaders = Vec::new();
+        for (kind, src) in &self.sources {
+            shaders.push( GlShader::from_source(&CString::new(src.as_str()).unwrap(), *kind)? );
+        }
+        let attributes : Vec<(gl::types::GLuint, &str)> = self.attributes.iter()
+            .zip(self.attribute_names.iter())
+            .map(|((index, _), name)| (*index, name.as_str()))
+            .collect();
+        let program_id = Self::link(&shaders, &attributes)?;
+        for ((gl_id, uniform_id), name) in self.uniforms.iter().zip(self.uniform_names.iter()) {
+            let index = {
+                if uniform_id.is_block() {
+                    Backend::uniform_block_index(program_id, name).map(|i| i as gl::types::GLint)
+                } else {
+                    Backend::uniform_location(program_id, name)
+                }
+            };
+            gl_id.set(index.unwrap_or(-1));
+        }
+        self.id.set(program_id);
+        self.generation.set(recovery::generation());
+        for (uniform_id, binding) in self.block_bindings.borrow().iter() {
+            if let Some(block) = self.uniform_block(*uniform_id) {
+                Backend::uniform_block_binding(program_id, block, *binding);
+            }
+        }
+        Ok(())
+    }
+
     //fp id
     /// Get the program id
     #[inline]
     pub fn id(&self) -> gl::types::GLuint {
-        self.id
+        self.id.get()
+    }
+
+    //mp set_name
+    /// Label the program (if debug output is enabled) for debug
+    /// messages and tools
+    pub fn set_name(&self, name:&str) {
+        debug::label(gl::PROGRAM, self.id(), name);
     }
 
     //fp set_used
     /// Use the program
     pub fn set_used(&self) {
-        unsafe {
-            gl::UseProgram(self.id());
-        }
+        Backend::use_program(self.id());
     }
 }
 
@@ -174,9 +423,7 @@ impl Drop for Program {
     //fp drop
     /// Drop requires the GLProgram to be deleted
     fn drop(&mut self) {
-        unsafe {
-            gl::DeleteProgram(self.id);
-        }
+        deletion::delete(self.generation.get(), GlObject::Program(self.id()));
     }
 
     //zz All done
@@ -184,6 +431,10 @@ impl Drop for Program {
 
 //ip ShaderClass for Program
 impl ShaderClass for Program {
+    fn program_id(&self) -> Option<gl::types::GLuint>
+    {
+        Some(self.id())
+    }
     fn attributes(&self) -> &[(gl::types::GLuint, model3d::VertexAttr)]
     {
         &self.attributes
@@ -192,7 +443,7 @@ impl ShaderClass for Program {
     {
         for (gl_id, u) in &self.uniforms {
             if *u == uniform_id {
-                return Some(*gl_id);
+                return Some(gl_id.get());
             }
         }
         None
@@ -0,0 +1,534 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    query.rs
+@brief   GPU timer and pipeline statistics queries
+ */
+
+//a Documentation
+
+/*!
+
+A [GpuProfiler] measures the GPU cost of drawing named objects, using
+OpenGL query objects of the [QueryKind]s it is created with.
+
+Each frame is bracketed by [GpuProfiler::begin_frame] and
+[GpuProfiler::end_frame], and each object drawn within it by
+[GpuProfiler::begin_object] and [GpuProfiler::end_object] (as
+performed by [crate::ShaderInstantiable::gl_draw_profiled]).
+
+Query results are read back asynchronously: a frame's results are
+collected only once they are all available, which is normally a few
+frames later, so that the CPU does not stall waiting for the GPU. If
+more than a maximum number of frames are outstanding then the oldest
+is waited for. A [FrameReport] is then available from
+[GpuProfiler::take_report]; if reports are not taken, only the most
+recent are kept.
+
+Calling these out of order (e.g. beginning a frame twice, or ending
+an object that was not begun) returns an error, and leaves the
+profiler unchanged.
+
+The query objects are pooled, and reused once their results have been
+read.
+
+With GL_EXT_disjoint_timer_query (OpenGL ES and WebGL) the GPU may
+report a disjoint event, such as a change of clock frequency, that
+makes timer results meaningless; the timings of every frame
+outstanding when one is found are then discarded, and their reports
+are marked as [FrameReport::disjoint].
+
+!*/
+
+//a Imports
+use std::cell::Cell;
+use std::collections::VecDeque;
+
+use gl::types::*;
+
+use crate::{Backend, GlBackend};
+use crate::{RenderContext, GlFeature};
+use crate::deletion::{self, GlObject};
+use crate::recovery;
+
+//a Constants
+//ci GPU_DISJOINT
+/// GL_GPU_DISJOINT_EXT, which is not in the `gl` crate
+const GPU_DISJOINT : GLenum = 0x8FBB;
+
+//a QueryKind
+//tp QueryKind
+/// A kind of OpenGL query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueryKind {
+    /// GPU time in nanoseconds taken by the commands for an object
+    TimeElapsed,
+    /// GPU timestamps at the start and end of a frame, giving the
+    /// GPU time for the frame
+    Timestamp,
+    /// Number of primitives generated for an object
+    PrimitivesGenerated,
+    /// Number of samples that passed the depth test for an object
+    ///
+    /// Only one occlusion query may be active at a time, so an
+    /// occlusion-culled [crate::ShaderInstantiable] drawn with
+    /// `gl_draw_keyed` inside the object is not culled;
+    /// [crate::ShaderInstantiable::gl_draw_profiled] makes its
+    /// occlusion test before the object's queries begin
+    SamplesPassed,
+}
+
+//ip QueryKind
+impl QueryKind {
+    //mp target
+    /// The OpenGL query target
+    pub fn target(self) -> GLenum {
+        match self {
+            Self::TimeElapsed => gl::TIME_ELAPSED,
+            Self::Timestamp => gl::TIMESTAMP,
+            Self::PrimitivesGenerated => gl::PRIMITIVES_GENERATED,
+            Self::SamplesPassed => gl::SAMPLES_PASSED,
+        }
+    }
+
+    //mp feature
+    /// The [GlFeature] required for the query
+    pub fn feature(self) -> GlFeature {
+        match self {
+            Self::TimeElapsed => GlFeature::TimerQueries,
+            Self::Timestamp => GlFeature::Timestamps,
+            Self::PrimitivesGenerated => GlFeature::PrimitiveQueries,
+            Self::SamplesPassed => GlFeature::SampleQueries,
+        }
+    }
+}
+
+//a Active samples query
+thread_local! {
+    /// True while a [GpuProfiler] has a SAMPLES_PASSED query active;
+    /// occlusion queries cannot be begun during this
+    static SAMPLES_QUERY_ACTIVE : Cell<bool> = const { Cell::new(false) };
+}
+
+//fp samples_query_active
+/// Return true if a [GpuProfiler] has a SAMPLES_PASSED query active
+/// on this thread
+pub(crate) fn samples_query_active() -> bool {
+    SAMPLES_QUERY_ACTIVE.with(|a| a.get())
+}
+
+//a QueryPool
+//tp QueryPool
+/// A pool of OpenGL query objects; queries are taken from the pool
+/// and returned when their results have been read
+#[derive(Debug, Default)]
+pub struct QueryPool {
+    /// All the queries created by the pool
+    allocated : Vec<GLuint>,
+    /// Queries available for reuse
+    free : Vec<GLuint>,
+    /// The context generation the queries were created in
+    generation : u32,
+}
+
+//ip QueryPool
+impl QueryPool {
+    //mp get
+    /// Get a query from the pool, creating one if none are free
+    ///
+    /// Queries lost with a previous context are forgotten
+    pub fn get(&mut self) -> GLuint {
+        if !recovery::is_current(self.generation) {
+            self.allocated.clear();
+            self.free.clear();
+            self.generation = recovery::generation();
+        }
+        if let Some(query) = self.free.pop() {
+            query
+        } else {
+            let query = Backend::gen_query();
+            self.allocated.push(query);
+            query
+        }
+    }
+
+    //mp put
+    /// Return a query to the pool
+    pub fn put(&mut self, query:GLuint) {
+        self.free.push(query);
+    }
+}
+
+//ip Drop for QueryPool
+impl Drop for QueryPool {
+    fn drop(&mut self) {
+        for query in &self.allocated {
+            deletion::delete(self.generation, GlObject::Query(*query));
+        }
+    }
+}
+
+//a Reports
+//tp ObjectReport
+/// The GPU cost of drawing an object in a frame
+#[derive(Debug, Clone, Default)]
+pub struct ObjectReport {
+    /// Name of the object
+    pub name : String,
+    /// GPU time in nanoseconds, if timed
+    pub gpu_time_ns : Option<u64>,
+    /// Primitives generated, if queried
+    pub primitives_generated : Option<u64>,
+    /// Samples passed, if queried
+    pub samples_passed : Option<u64>,
+    /// Number of draw calls
+    pub draw_calls : usize,
+    /// Number of triangles submitted
+    pub triangles : usize,
+}
+
+//tp FrameReport
+/// The GPU cost of a frame, by object
+#[derive(Debug, Clone, Default)]
+pub struct FrameReport {
+    /// Frame number (counting from 0 for the first frame profiled)
+    pub frame : usize,
+    /// GPU time in nanoseconds for the whole frame, if timestamps
+    /// were queried
+    pub gpu_frame_time_ns : Option<u64>,
+    /// Reports for the objects drawn, in the order they were drawn
+    pub objects : Vec<ObjectReport>,
+    /// True if the GPU reported a disjoint event while the frame's
+    /// queries were outstanding, so its timings have been discarded
+    pub disjoint : bool,
+}
+
+//ip FrameReport
+impl FrameReport {
+    //mp draw_calls
+    /// Total number of draw calls in the frame
+    pub fn draw_calls(&self) -> usize {
+        self.objects.iter().map(|o| o.draw_calls).sum()
+    }
+
+    //mp triangles
+    /// Total number of triangles in the frame
+    pub fn triangles(&self) -> usize {
+        self.objects.iter().map(|o| o.triangles).sum()
+    }
+
+    //mp gpu_time_ns
+    /// Total GPU time of the objects in the frame, if timed
+    pub fn gpu_time_ns(&self) -> Option<u64> {
+        self.objects.iter().map(|o| o.gpu_time_ns).sum()
+    }
+}
+
+//ip Display for FrameReport
+impl std::fmt::Display for FrameReport {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "Frame {}", self.frame)?;
+        if let Some(t) = self.gpu_frame_time_ns {
+            write!(f, ": {:.3}ms GPU", t as f64 / 1.0E6)?;
+        }
+        if self.disjoint {
+            write!(f, " (disjoint)")?;
+        }
+        writeln!(f, ", {} draw calls, {} triangles", self.draw_calls(), self.triangles())?;
+        for o in &self.objects {
+            write!(f, "  {:<24}", o.name)?;
+            match o.gpu_time_ns {
+                Some(t) => write!(f, " {:>9.3}ms", t as f64 / 1.0E6)?,
+                None => write!(f, " {:>11}", "-")?,
+            }
+            write!(f, " {:>6} draws {:>9} triangles", o.draw_calls, o.triangles)?;
+            if let Some(p) = o.primitives_generated {
+                write!(f, " {:>9} primitives", p)?;
+            }
+            if let Some(s) = o.samples_passed {
+                write!(f, " {:>10} samples", s)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+//a Pending queries
+//ti PendingObject
+/// The queries for an object drawn in a frame whose results are
+/// outstanding
+struct PendingObject {
+    name : String,
+    queries : Vec<(QueryKind, GLuint)>,
+    draw_calls : usize,
+    triangles : usize,
+}
+
+//ti PendingFrame
+/// A frame whose query results are outstanding
+struct PendingFrame {
+    frame : usize,
+    timestamps : Option<(GLuint, GLuint)>,
+    objects : Vec<PendingObject>,
+    /// True if a disjoint event was found while the frame was
+    /// outstanding
+    disjoint : bool,
+}
+
+//ip PendingFrame
+impl PendingFrame {
+    //mi queries
+    /// Iterate over all the queries of the frame
+    fn queries(&self) -> impl Iterator<Item = GLuint> + '_ {
+        self.timestamps.iter().flat_map(|(s, e)| [*s, *e])
+            .chain(self.objects.iter().flat_map(|o| o.queries.iter().map(|(_, q)| *q)))
+    }
+
+    //mi is_available
+    /// Return true if all the query results for the frame are available
+    fn is_available(&self) -> bool {
+        self.queries().all(Backend::query_result_available)
+    }
+
+    //mi report
+    /// Read the query results (waiting if required) into a
+    /// [FrameReport], returning the queries to the pool; timer results
+    /// are discarded if the frame is disjoint
+    fn report(self, pool:&mut QueryPool) -> FrameReport {
+        let gpu_frame_time_ns = self.timestamps.filter(|_| !self.disjoint).map(|(s, e)| {
+            Backend::query_result(e).saturating_sub(Backend::query_result(s))
+        });
+        let mut objects = Vec::new();
+        for o in &self.objects {
+            let mut report = ObjectReport { name:o.name.clone(),
+                                            draw_calls:o.draw_calls,
+                                            triangles:o.triangles,
+                                            ..Default::default() };
+            for (kind, query) in &o.queries {
+                let result = Some(Backend::query_result(*query));
+                match kind {
+                    QueryKind::TimeElapsed if self.disjoint => (),
+                    QueryKind::TimeElapsed => { report.gpu_time_ns = result; }
+                    QueryKind::PrimitivesGenerated => { report.primitives_generated = result; }
+                    QueryKind::SamplesPassed => { report.samples_passed = result; }
+                    QueryKind::Timestamp => (),
+                }
+            }
+            objects.push(report);
+        }
+        for query in self.queries() {
+            pool.put(query);
+        }
+        FrameReport { frame:self.frame, gpu_frame_time_ns, objects, disjoint:self.disjoint }
+    }
+}
+
+//a GpuProfiler
+//tp GpuProfiler
+/// Profiles the GPU cost of named objects in frames, using pooled
+/// OpenGL queries read back asynchronously
+pub struct GpuProfiler {
+    /// Kinds of query made for each object
+    object_kinds : Vec<QueryKind>,
+    /// True if timestamps are recorded for each frame
+    timestamps : bool,
+    /// Pool of query objects
+    pool : QueryPool,
+    /// Number of the next frame
+    frame : usize,
+    /// Maximum number of frames with outstanding results
+    max_pending : usize,
+    /// The frame being recorded
+    current : Option<PendingFrame>,
+    /// The object being recorded
+    active : Option<PendingObject>,
+    /// Frames with outstanding results, oldest first
+    pending : VecDeque<PendingFrame>,
+    /// Reports of frames whose results have been read, oldest first
+    reports : VecDeque<FrameReport>,
+    /// Maximum number of reports kept
+    max_reports : usize,
+    /// True if GL_GPU_DISJOINT_EXT is checked before reading timer
+    /// results
+    check_disjoint : bool,
+}
+
+//ip GpuProfiler
+impl GpuProfiler {
+    //fp new
+    /// Create a new [GpuProfiler] making the kinds of query given;
+    /// this returns an error if the context does not support them
+    pub fn new(render_context:&RenderContext, kinds:&[QueryKind]) -> Result<Self, String> {
+        for kind in kinds {
+            render_context.require(kind.feature())?;
+        }
+        let timestamps = kinds.contains(&QueryKind::Timestamp);
+        let capabilities = render_context.capabilities();
+        let check_disjoint = {
+            (timestamps || kinds.contains(&QueryKind::TimeElapsed)) &&
+                (capabilities.has_extension("GL_EXT_disjoint_timer_query") ||
+                 capabilities.has_extension("EXT_disjoint_timer_query_webgl2"))
+        };
+        let mut object_kinds = Vec::new();
+        for kind in kinds {
+            if *kind != QueryKind::Timestamp && !object_kinds.contains(kind) {
+                object_kinds.push(*kind);
+            }
+        }
+        Ok(Self { object_kinds,
+                  timestamps,
+                  pool : QueryPool::default(),
+                  frame : 0,
+                  max_pending : 4,
+                  current : None,
+                  active : None,
+                  pending : VecDeque::new(),
+                  reports : VecDeque::new(),
+                  max_reports : 16,
+                  check_disjoint,
+        })
+    }
+
+    //mp set_max_pending
+    /// Set the maximum number of frames whose results may be
+    /// outstanding before the oldest is waited for (default 4)
+    pub fn set_max_pending(&mut self, max_pending:usize) {
+        self.max_pending = max_pending.max(1);
+    }
+
+    //mp set_max_reports
+    /// Set the maximum number of reports kept for
+    /// [GpuProfiler::take_report] (default 16); older reports are
+    /// discarded
+    pub fn set_max_reports(&mut self, max_reports:usize) {
+        self.max_reports = max_reports.max(1);
+        while self.reports.len() > self.max_reports {
+            self.reports.pop_front();
+        }
+    }
+
+    //mp begin_frame
+    /// Begin profiling a frame
+    pub fn begin_frame(&mut self) -> Result<(), String> {
+        if self.current.is_some() {
+            return Err("GpuProfiler::begin_frame called twice without end_frame".into());
+        }
+        let timestamps = {
+            if self.timestamps {
+                let start = self.pool.get();
+                Backend::query_counter(start);
+                Some((start, 0))
+            } else {
+                None
+            }
+        };
+        self.current = Some(PendingFrame { frame:self.frame, timestamps, objects:Vec::new(), disjoint:false });
+        self.frame += 1;
+        Ok(())
+    }
+
+    //mp begin_object
+    /// Begin profiling the drawing of a named object in the frame
+    pub fn begin_object(&mut self, name:&str) -> Result<(), String> {
+        if self.current.is_none() {
+            return Err(format!("GpuProfiler::begin_object called for {} outside a frame", name));
+        }
+        if let Some(active) = &self.active {
+            return Err(format!("GpuProfiler objects cannot be nested ({} begun within {})", name, active.name));
+        }
+        let mut queries = Vec::new();
+        for kind in &self.object_kinds {
+            let query = self.pool.get();
+            Backend::begin_query(kind.target(), query);
+            queries.push((*kind, query));
+        }
+        if self.object_kinds.contains(&QueryKind::SamplesPassed) {
+            SAMPLES_QUERY_ACTIVE.with(|a| a.set(true));
+        }
+        self.active = Some(PendingObject { name:name.to_string(), queries, draw_calls:0, triangles:0 });
+        Ok(())
+    }
+
+    //mp end_object
+    /// End profiling the object, recording the draw calls and
+    /// triangles it submitted
+    pub fn end_object(&mut self, draw_calls:usize, triangles:usize) -> Result<(), String> {
+        let current = self.current.as_mut()
+            .ok_or_else(|| "GpuProfiler::end_object called outside a frame".to_string())?;
+        let mut object = self.active.take()
+            .ok_or_else(|| "GpuProfiler::end_object called without begin_object".to_string())?;
+        for (kind, _) in &object.queries {
+            Backend::end_query(kind.target());
+        }
+        SAMPLES_QUERY_ACTIVE.with(|a| a.set(false));
+        object.draw_calls = draw_calls;
+        object.triangles = triangles;
+        current.objects.push(object);
+        Ok(())
+    }
+
+    //mp end_frame
+    /// End profiling the frame, and collect the results of any
+    /// earlier frames that are available
+    pub fn end_frame(&mut self) -> Result<(), String> {
+        if let Some(active) = &self.active {
+            return Err(format!("GpuProfiler::end_frame called before end_object for {}", active.name));
+        }
+        let mut frame = self.current.take()
+            .ok_or_else(|| "GpuProfiler::end_frame called without begin_frame".to_string())?;
+        if let Some((start, _)) = frame.timestamps {
+            let end = self.pool.get();
+            Backend::query_counter(end);
+            frame.timestamps = Some((start, end));
+        }
+        self.pending.push_back(frame);
+        self.collect();
+        Ok(())
+    }
+
+    //mi collect
+    /// Collect the results of frames that are available, and wait for
+    /// the oldest frames if there are too many outstanding
+    ///
+    /// If the GPU reports a disjoint event then all the outstanding
+    /// frames are marked as disjoint; reading GL_GPU_DISJOINT_EXT
+    /// clears it
+    fn collect(&mut self) {
+        if self.check_disjoint && Backend::get_integer(GPU_DISJOINT) != 0 {
+            for frame in self.pending.iter_mut() {
+                frame.disjoint = true;
+            }
+        }
+        while let Some(frame) = self.pending.front() {
+            if self.pending.len() <= self.max_pending && !frame.is_available() {
+                break;
+            }
+            let frame = self.pending.pop_front().unwrap();
+            let report = frame.report(&mut self.pool);
+            self.reports.push_back(report);
+            if self.reports.len() > self.max_reports {
+                self.reports.pop_front();
+            }
+        }
+    }
+
+    //mp take_report
+    /// Take the oldest [FrameReport] whose results have been collected
+    pub fn take_report(&mut self) -> Option<FrameReport> {
+        self.reports.pop_front()
+    }
+
+    //zz All done
+}
@@ -0,0 +1,129 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    recovery.rs
+@brief   Context generations for recovery from context loss
+ */
+
+//a Documentation
+
+/*!
+
+When an OpenGL context is lost (on Android and WebGL, or after a GPU
+reset) every OpenGL name held by the crate becomes invalid.
+
+The crate counts context *generations*; each OpenGL object records the
+generation it was created in. Each [crate::RenderContext] starts a new
+generation (which is current on the thread that created it), and when
+the context is lost ([crate::RenderContext::context_lost]) it starts
+another, so that objects from the old context are known to be stale,
+and are never deleted (as their names may have been reused in the new
+context).
+
+[crate::RenderContext::recover_context] then rebuilds the objects
+that can be rebuilt:
+
+* [crate::GlBuffer]s are re-uploaded from their retained data - this
+  is only retained if [crate::RenderContext::enable_context_recovery]
+  was invoked before they were created, for the context current on
+  the thread (the [crate::RenderContext] most recently created on it)
+
+* [crate::GlProgram]s in the [crate::ResourceRegistry] are recompiled
+  from their retained sources, with the same attribute locations;
+  other programs must be recreated with [crate::GlProgram::recreate];
+  the uniform block bindings set with
+  [crate::GlProgram::set_uniform_block_binding] are applied again
+
+* the VAOs of [crate::ShaderInstantiable]s are rebuilt
+
+* the [crate::Sampler]s of the [crate::RenderContext] are rebuilt
+
+The placeholder textures of materials and the cached passes of
+[crate::Environment]s are created again when next required, and the
+[crate::Lights] buffers by their next update. Occlusion culling is
+disabled for stale [crate::ShaderInstantiable]s (it may be enabled
+again).
+
+Nothing else is recreated: textures (including those of materials,
+shadow maps and environments), framebuffers, [crate::ShadowMap]s,
+[crate::Environment]s, query pools and GPU profilers from the old
+context must be dropped and created again by the application, as must
+buffers whose data was not retained.
+
+!*/
+
+//a Imports
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+//a Statics
+/// The last context generation started (by any thread)
+static GENERATION : AtomicU32 = AtomicU32::new(0);
+
+thread_local! {
+    /// The generation of the context current on this thread
+    static CURRENT : Cell<u32> = const { Cell::new(0) };
+
+    /// True if the sources of objects created for the context current
+    /// on this thread should be retained for recovery
+    static RETAIN_SOURCES : Cell<bool> = const { Cell::new(false) };
+}
+
+//a Functions
+//fp generation
+/// Get the generation of the context current on this thread
+pub(crate) fn generation() -> u32 {
+    CURRENT.with(|c| c.get())
+}
+
+//fp is_current
+/// Return true if an object created in a context generation is for
+/// the current context
+pub(crate) fn is_current(generation:u32) -> bool {
+    generation == self::generation()
+}
+
+//fp new_generation
+/// Start a new context generation, for a new context or as the
+/// context has been lost, making it current on this thread
+pub(crate) fn new_generation() -> u32 {
+    let generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+    CURRENT.with(|c| c.set(generation));
+    generation
+}
+
+//fp context_dropped
+/// Note that the context of a generation has been dropped, so that if
+/// it is current on this thread there is no longer a current context
+pub(crate) fn context_dropped(generation:u32) {
+    if self::generation() == generation {
+        CURRENT.with(|c| c.set(0));
+        set_retain_sources(false);
+    }
+}
+
+//fp retain_sources
+/// Return true if the data of buffers created for the context current
+/// on this thread should be retained so that they can be recreated
+/// after context loss
+pub(crate) fn retain_sources() -> bool {
+    RETAIN_SOURCES.with(|r| r.get())
+}
+
+//fp set_retain_sources
+/// Set whether the data of buffers created from now on for the
+/// context current on this thread should be retained
+pub(crate) fn set_retain_sources(retain:bool) {
+    RETAIN_SOURCES.with(|r| r.set(retain));
+}
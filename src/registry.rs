@@ -0,0 +1,406 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    registry.rs
+@brief   Registry of GL resources with generational handles
+ */
+
+//a Documentation
+
+/*!
+
+The [ResourceRegistry] of a [crate::RenderContext] keeps the GL
+resources that an application has loaded - programs, textures,
+materials, buffers and shader instantiables - each with a name, so
+that they can be listed, found by name and inspected.
+
+Adding a resource returns a [ResourceHandle], which is an index into
+the table for its kind with a generation; when a resource is released
+its slot's generation is incremented, so stale handles no longer find
+it (rather than finding whatever later reuses the slot).
+
+Resources are held as [Rc]s, so they may be used while registered;
+released resources are kept until the next frame boundary
+([ResourceRegistry::end_frame]), so that a resource released while a
+frame is being built is not deleted until the frame is done.
+
+!*/
+
+//a Imports
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{GlBuffer, GlProgram, Texture, Material, OwnedShaderInstantiable};
+
+//a ResourceKind
+//tp ResourceKind
+/// The kinds of resource held in a [ResourceRegistry]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    /// A [GlProgram]
+    Program,
+    /// A [Texture]
+    Texture,
+    /// A [Material]
+    Material,
+    /// A [GlBuffer]
+    Buffer,
+    /// An [OwnedShaderInstantiable]
+    ShaderInstantiable,
+}
+
+//ip Display for ResourceKind
+impl std::fmt::Display for ResourceKind {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let s = match self {
+            Self::Program => "program",
+            Self::Texture => "texture",
+            Self::Material => "material",
+            Self::Buffer => "buffer",
+            Self::ShaderInstantiable => "shader instantiable",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+//a Resource
+//tt Resource
+/// A type of resource that may be held in a [ResourceRegistry]
+pub trait Resource : Sized + 'static {
+    /// The kind of the resource
+    const KIND : ResourceKind;
+
+    /// The number of bytes of GPU memory used by the resource, if
+    /// known
+    fn byte_size(&self) -> usize { 0 }
+
+    /// Label the GL objects of the resource with its name in the
+    /// registry (if debug output is enabled)
+    fn set_label(&self, _name:&str) {}
+
+    /// Borrow the table of resources of this kind in a registry
+    fn table(registry:&ResourceRegistry) -> &ResourceTable<Self>;
+
+    /// Mutably borrow the table of resources of this kind in a registry
+    fn table_mut(registry:&mut ResourceRegistry) -> &mut ResourceTable<Self>;
+}
+
+//ip Resource for GlProgram
+impl Resource for GlProgram {
+    const KIND : ResourceKind = ResourceKind::Program;
+    fn set_label(&self, name:&str) { self.set_name(name) }
+    fn table(registry:&ResourceRegistry) -> &ResourceTable<Self> { &registry.programs }
+    fn table_mut(registry:&mut ResourceRegistry) -> &mut ResourceTable<Self> { &mut registry.programs }
+}
+
+//ip Resource for Texture
+impl Resource for Texture {
+    const KIND : ResourceKind = ResourceKind::Texture;
+    fn byte_size(&self) -> usize { self.byte_size() }
+    fn set_label(&self, name:&str) { self.set_label(name) }
+    fn table(registry:&ResourceRegistry) -> &ResourceTable<Self> { &registry.textures }
+    fn table_mut(registry:&mut ResourceRegistry) -> &mut ResourceTable<Self> { &mut registry.textures }
+}
+
+//ip Resource for Material
+impl Resource for Material {
+    const KIND : ResourceKind = ResourceKind::Material;
+    fn table(registry:&ResourceRegistry) -> &ResourceTable<Self> { &registry.materials }
+    fn table_mut(registry:&mut ResourceRegistry) -> &mut ResourceTable<Self> { &mut registry.materials }
+}
+
+//ip Resource for GlBuffer
+impl Resource for GlBuffer {
+    const KIND : ResourceKind = ResourceKind::Buffer;
+    fn byte_size(&self) -> usize { self.byte_length() }
+    fn set_label(&self, name:&str) { self.set_label(name) }
+    fn table(registry:&ResourceRegistry) -> &ResourceTable<Self> { &registry.buffers }
+    fn table_mut(registry:&mut ResourceRegistry) -> &mut ResourceTable<Self> { &mut registry.buffers }
+}
+
+//ip Resource for OwnedShaderInstantiable
+impl Resource for OwnedShaderInstantiable {
+    const KIND : ResourceKind = ResourceKind::ShaderInstantiable;
+    fn table(registry:&ResourceRegistry) -> &ResourceTable<Self> { &registry.shader_instantiables }
+    fn table_mut(registry:&mut ResourceRegistry) -> &mut ResourceTable<Self> { &mut registry.shader_instantiables }
+}
+
+//a ResourceHandle
+//tp ResourceHandle
+/// A generational handle to a resource in a [ResourceRegistry]
+pub struct ResourceHandle<T:Resource> {
+    index : u32,
+    generation : u32,
+    phantom : PhantomData<fn() -> T>,
+}
+
+//ip Clone, Copy, PartialEq, Eq, Hash, Debug for ResourceHandle
+impl <T:Resource> Clone for ResourceHandle<T> {
+    fn clone(&self) -> Self { *self }
+}
+impl <T:Resource> Copy for ResourceHandle<T> {}
+impl <T:Resource> PartialEq for ResourceHandle<T> {
+    fn eq(&self, other:&Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+impl <T:Resource> Eq for ResourceHandle<T> {}
+impl <T:Resource> std::hash::Hash for ResourceHandle<T> {
+    fn hash<H:std::hash::Hasher>(&self, state:&mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+impl <T:Resource> std::fmt::Debug for ResourceHandle<T> {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{}#{}.{}", T::KIND, self.index, self.generation)
+    }
+}
+
+//a ResourceTable
+//ti Slot
+/// A slot in a [ResourceTable], with the generation of the resource
+/// in it (or of the next resource to use it)
+struct Slot<T> {
+    generation : u32,
+    entry : Option<(String, Rc<T>)>,
+}
+
+//tp ResourceTable
+/// The table of resources of one kind in a [ResourceRegistry]
+pub struct ResourceTable<T> {
+    /// Slots, indexed by handle index
+    slots : Vec<Slot<T>>,
+    /// Indices of empty slots
+    free : Vec<u32>,
+    /// Resources released since the last frame boundary
+    released : Vec<Rc<T>>,
+}
+
+//ip Default for ResourceTable
+impl <T> Default for ResourceTable<T> {
+    fn default() -> Self {
+        Self { slots:Vec::new(), free:Vec::new(), released:Vec::new() }
+    }
+}
+
+//ip ResourceTable
+impl <T:Resource> ResourceTable<T> {
+    //mi insert
+    fn insert(&mut self, name:&str, resource:Rc<T>) -> ResourceHandle<T> {
+        let entry = Some((name.to_string(), resource));
+        let index = {
+            if let Some(index) = self.free.pop() {
+                self.slots[index as usize].entry = entry;
+                index
+            } else {
+                self.slots.push(Slot { generation:0, entry });
+                (self.slots.len() - 1) as u32
+            }
+        };
+        let generation = self.slots[index as usize].generation;
+        ResourceHandle { index, generation, phantom:PhantomData }
+    }
+
+    //mi entry
+    fn entry(&self, handle:ResourceHandle<T>) -> Option<&(String, Rc<T>)> {
+        let slot = self.slots.get(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.entry.as_ref()
+    }
+
+    //mi release
+    fn release(&mut self, handle:ResourceHandle<T>) -> bool {
+        let slot = match self.slots.get_mut(handle.index as usize) {
+            Some(slot) if slot.generation == handle.generation => slot,
+            _ => { return false; }
+        };
+        let resource = match slot.entry.take() {
+            Some((_, resource)) => resource,
+            None => { return false; }
+        };
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.released.push(resource);
+        true
+    }
+
+    //mi iter
+    fn iter(&self) -> impl Iterator<Item = (ResourceHandle<T>, &str, &Rc<T>)> {
+        self.slots.iter().enumerate().filter_map(|(i, slot)| {
+            slot.entry.as_ref().map(|(name, resource)| {
+                let handle = ResourceHandle { index:i as u32, generation:slot.generation, phantom:PhantomData };
+                (handle, name.as_str(), resource)
+            })
+        })
+    }
+
+    //mi end_frame
+    fn end_frame(&mut self) {
+        self.released.clear();
+    }
+
+    //mi memory_usage
+    fn memory_usage(&self, usage:&mut MemoryUsage) {
+        for (_, name, resource) in self.iter() {
+            usage.entries.push(MemoryEntry { kind:T::KIND, name:name.to_string(), bytes:resource.byte_size() });
+        }
+    }
+}
+
+//a MemoryUsage
+//tp MemoryEntry
+/// The GPU memory used by a resource in a [ResourceRegistry]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryEntry {
+    /// Kind of resource
+    pub kind : ResourceKind,
+    /// Name of the resource
+    pub name : String,
+    /// Bytes of GPU memory (0 if not known)
+    pub bytes : usize,
+}
+
+//tp MemoryUsage
+/// A summary of the GPU memory used by the resources in a
+/// [ResourceRegistry]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// An entry for each resource
+    pub entries : Vec<MemoryEntry>,
+}
+
+//ip MemoryUsage
+impl MemoryUsage {
+    //ap total
+    /// Get the total bytes used by the resources
+    pub fn total(&self) -> usize {
+        self.entries.iter().map(|e| e.bytes).sum()
+    }
+
+    //ap total_of
+    /// Get the total bytes used by the resources of one kind
+    pub fn total_of(&self, kind:ResourceKind) -> usize {
+        self.entries.iter().filter(|e| e.kind == kind).map(|e| e.bytes).sum()
+    }
+}
+
+//ip Display for MemoryUsage
+impl std::fmt::Display for MemoryUsage {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        for e in &self.entries {
+            writeln!(f, "{:20} {:32} {:>12}", e.kind.to_string(), e.name, e.bytes)?;
+        }
+        writeln!(f, "{:53} {:>12}", "total", self.total())
+    }
+}
+
+//a ResourceRegistry
+//tp ResourceRegistry
+/// A registry of the GL resources loaded, with names, issuing
+/// [ResourceHandle]s
+#[derive(Default)]
+pub struct ResourceRegistry {
+    programs : ResourceTable<GlProgram>,
+    textures : ResourceTable<Texture>,
+    materials : ResourceTable<Material>,
+    buffers : ResourceTable<GlBuffer>,
+    shader_instantiables : ResourceTable<OwnedShaderInstantiable>,
+}
+
+//ip ResourceRegistry
+impl ResourceRegistry {
+    //fp new
+    /// Create an empty [ResourceRegistry]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //mp add
+    /// Add a resource with a name, returning its handle
+    pub fn add<T:Resource>(&mut self, name:&str, resource:T) -> ResourceHandle<T> {
+        self.add_rc(name, Rc::new(resource))
+    }
+
+    //mp add_rc
+    /// Add a shared resource with a name, returning its handle; the
+    /// GL objects of the resource are labelled with the name
+    pub fn add_rc<T:Resource>(&mut self, name:&str, resource:Rc<T>) -> ResourceHandle<T> {
+        resource.set_label(name);
+        T::table_mut(self).insert(name, resource)
+    }
+
+    //ap get
+    /// Get a resource, if the handle is still valid
+    pub fn get<T:Resource>(&self, handle:ResourceHandle<T>) -> Option<&Rc<T>> {
+        T::table(self).entry(handle).map(|(_, resource)| resource)
+    }
+
+    //ap name
+    /// Get the name of a resource, if the handle is still valid
+    pub fn name<T:Resource>(&self, handle:ResourceHandle<T>) -> Option<&str> {
+        T::table(self).entry(handle).map(|(name, _)| name.as_str())
+    }
+
+    //ap find
+    /// Find the first resource of a kind with a name
+    pub fn find<T:Resource>(&self, name:&str) -> Option<ResourceHandle<T>> {
+        T::table(self).iter()
+            .find(|(_, n, _)| *n == name)
+            .map(|(handle, _, _)| handle)
+    }
+
+    //ap iter
+    /// Iterate over the resources of a kind, with their handles and
+    /// names
+    pub fn iter<T:Resource>(&self) -> impl Iterator<Item = (ResourceHandle<T>, &str, &Rc<T>)> {
+        T::table(self).iter()
+    }
+
+    //mp release
+    /// Release a resource, invalidating its handle; it is dropped (if
+    /// not otherwise shared) at the next frame boundary
+    ///
+    /// Return false if the handle was not valid
+    pub fn release<T:Resource>(&mut self, handle:ResourceHandle<T>) -> bool {
+        T::table_mut(self).release(handle)
+    }
+
+    //mp end_frame
+    /// Drop the resources released during the frame
+    pub fn end_frame(&mut self) {
+        self.shader_instantiables.end_frame();
+        self.materials.end_frame();
+        self.textures.end_frame();
+        self.programs.end_frame();
+        self.buffers.end_frame();
+    }
+
+    //ap memory_usage
+    /// Get a summary of the GPU memory used by the registered
+    /// resources
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage::default();
+        self.programs.memory_usage(&mut usage);
+        self.textures.memory_usage(&mut usage);
+        self.materials.memory_usage(&mut usage);
+        self.buffers.memory_usage(&mut usage);
+        self.shader_instantiables.memory_usage(&mut usage);
+        usage
+    }
+
+    //zz All done
+}
@@ -0,0 +1,297 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    render_queue.rs
+@brief   A render queue of primitives sorted to minimize state changes
+ */
+
+//a Documentation
+
+/*!
+
+A [RenderQueue] collects the primitives of instances of
+[ShaderInstantiable]s for a frame, and submits them in an order given
+by a 64-bit [SortKey] for each, so that programs, materials and VAOs
+are changed as little as possible.
+
+The key is ordered (most significant first) by:
+
+* layer (4 bits), so that layers are drawn in order
+
+* transparency (1 bit), so that opaque primitives are drawn first
+
+* for opaque primitives: program (12 bits), material id (12 bits),
+  VAO (15 bits) and then depth (20 bits) front-to-back
+
+* for transparent primitives: depth (20 bits) back-to-front, then
+  program, material and VAO
+
+The depth is the distance in front of the camera of the center of the
+bounding box of the instance (or its origin), quantized as the top
+bits of its floating point representation (which orders positive
+floats correctly).
+
+The caller is responsible for enabling blending for transparent
+layers; occlusion culling, profiling and debug groups of the
+[ShaderInstantiable]s are not used when submitting a queue. Shader
+classes that do not provide a program id are drawn with whatever
+program is current.
+
+!*/
+
+//a Imports
+use crate::{Renderable, ShaderInstantiable, Camera, Mat4};
+use crate::utils::{mat4_mul, mat4_transform};
+use crate::{Backend, GlBackend};
+
+//a SortKey
+//tp SortKey
+/// A 64-bit sort key for a primitive in a [RenderQueue]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SortKey(pub u64);
+
+//ip SortKey
+impl SortKey {
+    //fp new
+    /// Build a sort key
+    pub fn new(layer:u8, transparent:bool, program:u32, material:u32, vao:u32, depth:f32) -> Self {
+        let layer = (layer as u64 & 0xf) << 60;
+        let program = program as u64 & 0xfff;
+        let material = material as u64 & 0xfff;
+        let vao = vao as u64 & 0x7fff;
+        let depth = Self::quantize_depth(depth);
+        if transparent {
+            let depth = 0xfffff - depth;
+            Self(layer | (1 << 59) | (depth << 39) | (program << 27) | (material << 15) | vao)
+        } else {
+            Self(layer | (program << 47) | (material << 35) | (vao << 20) | depth)
+        }
+    }
+
+    //fi quantize_depth
+    /// Quantize a depth to 20 bits, preserving the ordering of
+    /// positive depths; negative depths (behind the camera) are 0
+    fn quantize_depth(depth:f32) -> u64 {
+        ((depth.max(0.).to_bits() >> 11) & 0xfffff) as u64
+    }
+
+    //ap layer
+    /// Get the layer of the key
+    pub fn layer(&self) -> u8 {
+        (self.0 >> 60) as u8
+    }
+
+    //ap is_transparent
+    /// Return true if the key is for a transparent primitive
+    pub fn is_transparent(&self) -> bool {
+        (self.0 >> 59) & 1 != 0
+    }
+}
+
+//a QueueStats
+//tp QueueStats
+/// Statistics of the submission of a [RenderQueue]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Number of draw calls
+    pub draw_calls : usize,
+    /// Number of triangles drawn
+    pub triangles : usize,
+    /// Number of times the program was changed
+    pub program_changes : usize,
+    /// Number of times the VAO was changed
+    pub vao_changes : usize,
+    /// Number of times a material was bound
+    pub material_changes : usize,
+    /// Number of textures bound (that were not already bound to
+    /// their texture units)
+    pub texture_binds : usize,
+}
+
+//ip Display for QueueStats
+impl std::fmt::Display for QueueStats {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{} draw calls, {} triangles, {} program changes, {} VAO changes, {} material changes, {} texture binds",
+               self.draw_calls, self.triangles, self.program_changes, self.vao_changes,
+               self.material_changes, self.texture_binds)
+    }
+}
+
+//a RenderQueue
+//ti RenderItem
+/// A primitive of an instance in a [RenderQueue]
+struct RenderItem<'a> {
+    key : SortKey,
+    shader_instantiable : &'a ShaderInstantiable<'a>,
+    instance : &'a model3d::Instance<'a, Renderable>,
+    primitive : usize,
+}
+
+//fi same_vertices
+/// Return true if two items draw the same vertices of the same
+/// [ShaderInstantiable], and so have the same missing attribute values
+fn same_vertices(a:&RenderItem, b:&RenderItem) -> bool {
+    std::ptr::eq(a.shader_instantiable, b.shader_instantiable) &&
+        a.shader_instantiable.primitive(a.primitive).vertices_index() ==
+        b.shader_instantiable.primitive(b.primitive).vertices_index()
+}
+
+//tp RenderQueue
+/// A queue of primitives of instances, to be sorted and then drawn
+/// with minimal state changes
+#[derive(Default)]
+pub struct RenderQueue<'a> {
+    /// The view matrix used for depths
+    view : Option<Mat4>,
+    /// The items queued
+    items : Vec<RenderItem<'a>>,
+}
+
+//ip RenderQueue
+impl <'a> RenderQueue<'a> {
+    //fp new
+    /// Create a new, empty, [RenderQueue]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //mp begin
+    /// Empty the queue, and set the view matrix from a camera for the
+    /// depths of the items added
+    pub fn begin(&mut self, camera:&Camera) {
+        self.items.clear();
+        self.view = Some(*camera.view());
+    }
+
+    //ap len
+    /// Get the number of primitives queued
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    //ap is_empty
+    /// Return true if no primitives are queued
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    //mp add
+    /// Add all the primitives of an instance of a
+    /// [ShaderInstantiable] to the queue, in a layer (0 to 15) and as
+    /// opaque or transparent
+    pub fn add(&mut self,
+               shader_instantiable:&'a ShaderInstantiable<'a>,
+               instance:&'a model3d::Instance<'a, Renderable>,
+               layer:u8,
+               transparent:bool) {
+        let center = shader_instantiable.bounds().map_or([0.; 3], |b| b.center());
+        let depth = {
+            if let Some(view) = &self.view {
+                let model_view = mat4_mul(view, &instance.transformation.mat4());
+                -mat4_transform(&model_view, center)[2]
+            } else {
+                0.
+            }
+        };
+        let program = shader_instantiable.shader_class().program_id().unwrap_or(0);
+        for primitive in 0..shader_instantiable.num_primitives() {
+            let material = shader_instantiable.material_id(primitive);
+            let vao = shader_instantiable.gl_vao(primitive);
+            let key = SortKey::new(layer, transparent, program, material, vao, depth);
+            self.items.push(RenderItem { key, shader_instantiable, instance, primitive });
+        }
+    }
+
+    //mp sort
+    /// Sort the queue by the keys of the primitives
+    pub fn sort(&mut self) {
+        self.items.sort_by_key(|item| item.key);
+    }
+
+    //mp submit
+    /// Sort the queue and draw its primitives, setting the program,
+    /// material, VAO and matrices only when they change
+    ///
+    /// If a camera is given then its matrices are uploaded to each
+    /// program when it is first used
+    pub fn submit(&mut self, camera:Option<&Camera>) -> QueueStats {
+        self.sort();
+        let mut stats = QueueStats::default();
+        let mut program = None;
+        let mut programs_uploaded = Vec::new();
+        let mut vao = None;
+        let mut bound : Option<&RenderItem> = None;
+        let mut instance : Option<*const model3d::Instance<Renderable>> = None;
+        let mut mesh_matrix : Option<(*const ShaderInstantiable, usize)> = None;
+        let mut material = None;
+        for item in &self.items {
+            let si = item.shader_instantiable;
+            let item_program = si.shader_class().program_id();
+            let program_changed = program != Some(item_program);
+            if program_changed {
+                if let Some(id) = item_program {
+                    Backend::use_program(id);
+                }
+                program = Some(item_program);
+                stats.program_changes += 1;
+                if let Some(camera) = camera {
+                    if item_program.is_none() || !programs_uploaded.contains(&item_program) {
+                        camera.upload(si.shader_class());
+                        programs_uploaded.push(item_program);
+                    }
+                }
+            }
+            let item_instance = item.instance as *const model3d::Instance<Renderable>;
+            if program_changed || instance != Some(item_instance) {
+                si.set_model_matrix(item.instance);
+                instance = Some(item_instance);
+            }
+            let item_mesh_matrix = (si as *const ShaderInstantiable, si.mesh_matrix_index(item.primitive));
+            if program_changed || mesh_matrix != Some(item_mesh_matrix) {
+                si.set_mesh_matrix(item_mesh_matrix.1);
+                mesh_matrix = Some(item_mesh_matrix);
+            }
+            let item_material = si.material_id(item.primitive);
+            if program_changed || material != Some(item_material) {
+                stats.texture_binds += si.bind_material(item.primitive);
+                material = Some(item_material);
+                stats.material_changes += 1;
+            }
+            let item_vao = si.gl_vao(item.primitive);
+            if vao != Some(item_vao) {
+                if let Some(bound) = bound {
+                    bound.shader_instantiable.unbind_vertices(bound.primitive);
+                }
+                si.bind_vertices(item.primitive);
+                vao = Some(item_vao);
+                bound = Some(item);
+                stats.vao_changes += 1;
+            } else if let Some(previous) = bound.filter(|b| !same_vertices(b, item)) {
+                // A VAO shared by shader instantiables, whose missing
+                // attribute values may differ
+                previous.shader_instantiable.unbind_vertices(previous.primitive);
+                si.set_attribute_defaults(item.primitive);
+                bound = Some(item);
+            }
+            stats.triangles += si.draw_primitive(item.primitive);
+            stats.draw_calls += 1;
+        }
+        if let Some(bound) = bound {
+            bound.shader_instantiable.unbind_vertices(bound.primitive);
+        }
+        stats
+    }
+
+    //zz All done
+}
@@ -17,7 +17,16 @@ limitations under the License.
  */
 
 //a Imports
+use std::rc::Rc;
+use std::cell::RefCell;
+
 use crate::{GlBuffer, BufferView, Texture, Material, Vertices};
+use crate::{Capabilities, GlFeature};
+use crate::{debug, DebugSeverity, DebugHandler};
+use crate::{ResourceRegistry, GlProgram};
+use crate::{deletion, recovery, gl_buffer, shader_instantiable, texture_units, upload, environment, occlusion};
+use crate::{Backend, GlBackend};
+use crate::{Sampler, SamplerDesc, TextureSlot, TextureFormat};
 
 //a Renderable
 //tp Renderable
@@ -26,8 +35,308 @@ pub struct Renderable {
 }
 
 //tp RenderContext
-/// The OpenGL context is global, and so no data is required for the [RenderContext]
+/// The OpenGL context is global, but its capabilities are queried
+/// when the [RenderContext] is created, so that code paths can be
+/// selected depending on the version and extensions
 pub struct RenderContext {
+    /// Capabilities of the OpenGL context
+    capabilities : Capabilities,
+    /// Registry of the resources loaded
+    registry : ResourceRegistry,
+    /// True if the context has been lost and not yet recovered
+    lost : bool,
+    /// Samplers created, shared by description
+    samplers : Vec<Rc<Sampler>>,
+    /// Asynchronous texture uploads in flight
+    uploads : Vec<upload::PendingUpload>,
+    /// The context generation, which keys its deletion queue
+    generation : u32,
+    /// The texture units allocated, and their bindings
+    texture_units : Rc<RefCell<texture_units::TextureUnits>>,
+}
+
+//ip RenderContext
+impl RenderContext {
+    //fp new
+    /// Create a [RenderContext] for the current OpenGL context,
+    /// querying its capabilities
+    pub fn new() -> Self {
+        let capabilities = Capabilities::query();
+        let registry = ResourceRegistry::new();
+        let generation = recovery::new_generation();
+        recovery::set_retain_sources(false);
+        deletion::context_created(generation);
+        let texture_units = Rc::new(RefCell::new(texture_units::TextureUnits::default()));
+        texture_units::make_current(&texture_units);
+        let context = Self { capabilities, registry, lost:false, samplers:Vec::new(), uploads:Vec::new(),
+                             generation, texture_units };
+        context.configure();
+        context
+    }
+
+    //mi configure
+    /// Set the OpenGL state that the crate expects for the context,
+    /// such as seamless cube map filtering where it is available, and
+    /// the number of texture units that may be allocated
+    fn configure(&self) {
+        if self.supports(GlFeature::SeamlessCubeMap) {
+            Backend::enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+        }
+        self.texture_units.borrow_mut().configure(self.capabilities.limits.max_texture_units);
+    }
+
+    //ap capabilities
+    /// Borrow the capabilities of the OpenGL context
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    //mp supports
+    /// Return true if the OpenGL context supports a [GlFeature]
+    pub fn supports(&self, feature:GlFeature) -> bool {
+        self.capabilities.supports(feature)
+    }
+
+    //mp require
+    /// Return an 'unsupported' error if the OpenGL context does not
+    /// support a [GlFeature]
+    pub fn require(&self, feature:GlFeature) -> Result<(), String> {
+        self.capabilities.require(feature)
+    }
+
+    //mp enable_debug_output
+    /// Enable OpenGL debug output (KHR_debug), sending messages at
+    /// or above a severity to a [DebugHandler]
+    ///
+    /// While enabled, OpenGL objects created by the crate are
+    /// labelled, and drawing uses debug groups
+    pub fn enable_debug_output(&self, min_severity:DebugSeverity, handler:DebugHandler) -> Result<(), String> {
+        self.require(GlFeature::DebugOutput)?;
+        debug::enable(min_severity, handler);
+        Ok(())
+    }
+
+    //mp disable_debug_output
+    /// Disable OpenGL debug output, if it was enabled
+    pub fn disable_debug_output(&self) {
+        debug::disable();
+    }
+
+    //mp sampler
+    /// Get a [Sampler] for a description, sharing the sampler
+    /// previously created for the same description if there is one
+    pub fn sampler(&mut self, desc:&SamplerDesc) -> Rc<Sampler> {
+        if let Some(sampler) = self.samplers.iter().find(|s| s.desc() == desc) {
+            return sampler.clone();
+        }
+        let sampler = Rc::new(Sampler::new(desc, &self.capabilities));
+        self.samplers.push(sampler.clone());
+        sampler
+    }
+
+    //mp texture_unit
+    /// Get the texture unit for a [TextureSlot], allocating it if it
+    /// has not been used before; the unit is the same for all
+    /// programs and materials
+    pub fn texture_unit(&self, slot:TextureSlot) -> Result<usize, String> {
+        self.texture_units.borrow_mut().slot_unit(slot)
+    }
+
+    //mp reserve_texture_unit
+    /// Reserve a texture unit for the application, which is not
+    /// used for any [TextureSlot] (e.g. for a shadow map)
+    pub fn reserve_texture_unit(&self) -> Result<usize, String> {
+        self.texture_units.borrow_mut().reserve()
+    }
+
+    //mi environment_unit
+    /// Get the texture unit reserved for the sources of the
+    /// [crate::Environment] passes
+    pub(crate) fn environment_unit(&self) -> Result<usize, String> {
+        self.texture_units.borrow_mut().environment_unit()
+    }
+
+    //mp upload_texture_2d
+    /// Create a 2D texture from image data, uploading it
+    /// asynchronously through a pixel buffer if the context supports
+    /// it ([GlFeature::AsyncTextureUploads]), and generating mipmaps
+    /// if required
+    ///
+    /// The texture is not ready ([Texture::is_ready]) until a later
+    /// frame, and until then materials draw with a placeholder
+    /// texture in its place; its mipmaps are generated when it
+    /// becomes ready. If the upload is not asynchronous then the
+    /// texture is ready immediately
+    pub fn upload_texture_2d(&mut self, width:usize, height:usize, format:TextureFormat, data:&[u8], mipmaps:bool) -> Result<Rc<Texture>, String> {
+        if !self.supports(GlFeature::AsyncTextureUploads) {
+            let mut texture = Texture::new_2d(width, height, format, Some(data))?;
+            if mipmaps {
+                texture.generate_mipmaps()?;
+            }
+            return Ok(Rc::new(texture));
+        }
+        let upload = upload::PendingUpload::new(width, height, format, data, mipmaps)?;
+        let texture = upload.texture().clone();
+        self.uploads.push(upload);
+        Ok(texture)
+    }
+
+    //mp poll_uploads
+    /// Mark the textures whose asynchronous uploads have completed as
+    /// ready (generating their mipmaps if required), releasing their
+    /// pixel buffers, and return the number completed; this is
+    /// invoked by [RenderContext::end_frame]
+    pub fn poll_uploads(&mut self) -> usize {
+        let pending = self.uploads.len();
+        self.uploads.retain(|u| !u.poll());
+        pending - self.uploads.len()
+    }
+
+    //ap pending_uploads
+    /// Get the number of asynchronous texture uploads in flight, and
+    /// the total size of their pixel buffers in bytes
+    pub fn pending_uploads(&self) -> (usize, usize) {
+        (self.uploads.len(), self.uploads.iter().map(|u| u.byte_length()).sum())
+    }
+
+    //ap registry
+    /// Borrow the [ResourceRegistry] of resources loaded
+    pub fn registry(&self) -> &ResourceRegistry {
+        &self.registry
+    }
+
+    //ap registry_mut
+    /// Mutably borrow the [ResourceRegistry] of resources loaded
+    pub fn registry_mut(&mut self) -> &mut ResourceRegistry {
+        &mut self.registry
+    }
+
+    //mp end_frame
+    /// Mark a frame boundary, moving occlusion culling on to the
+    /// next frame's queries, dropping the resources released from
+    /// the registry during the frame, polling the asynchronous
+    /// texture uploads and flushing the deletion queue; this must be
+    /// called with the OpenGL context current
+    pub fn end_frame(&mut self) {
+        occlusion::end_frame();
+        self.registry.end_frame();
+        self.poll_uploads();
+        self.flush_deletions();
+    }
+
+    //mp flush_deletions
+    /// Delete the OpenGL objects that have been dropped (on any
+    /// thread) since the last flush, returning the number deleted;
+    /// this must be called with the OpenGL context current
+    pub fn flush_deletions(&self) -> usize {
+        deletion::flush(self.generation)
+    }
+
+    //mp enable_context_recovery
+    /// Retain the data of buffers created from now on for this
+    /// context (which must be the one most recently created on the
+    /// thread), so that they can be recreated by
+    /// [RenderContext::recover_context] after context loss
+    pub fn enable_context_recovery(&self) {
+        recovery::set_retain_sources(true);
+    }
+
+    //mp context_lost
+    /// Mark all the OpenGL objects of the crate as invalid, as the
+    /// context has been lost; they will not be deleted, and no
+    /// OpenGL calls should be made until the context is recovered
+    ///
+    /// Asynchronous texture uploads in flight are abandoned; their
+    /// textures never become ready ([Texture::is_ready]) and are no
+    /// longer current ([Texture::is_current]), so (as with all
+    /// textures) the caller must upload them again once the context
+    /// is recovered
+    pub fn context_lost(&mut self) {
+        deletion::context_lost(self.generation);
+        self.generation = recovery::new_generation();
+        deletion::context_created(self.generation);
+        self.uploads.clear();
+        self.lost = true;
+    }
+
+    //ap is_context_lost
+    /// Return true if the context has been lost and not yet recovered
+    pub fn is_context_lost(&self) -> bool {
+        self.lost
+    }
+
+    //mp recover_context
+    /// Rebuild the OpenGL objects of the crate in a new (current)
+    /// context, after context loss
+    ///
+    /// The capabilities are queried again, debug output is restored,
+    /// buffers are re-uploaded (if their data was retained), the
+    /// programs in the registry are recompiled and all the VAOs and
+    /// samplers are rebuilt. Programs not in the registry must be
+    /// recreated with [GlProgram::recreate].
+    ///
+    /// Textures, framebuffers, shadow maps, environments, query pools
+    /// and GPU profilers are *not* recreated; they must be dropped
+    /// and created again.
+    ///
+    /// Errors for objects that could not be recreated are combined
+    /// into one error, after recreating all the others
+    pub fn recover_context(&mut self) -> Result<(), String> {
+        if !self.lost {
+            self.context_lost();
+        }
+        self.lost = false;
+        self.capabilities = Capabilities::query();
+        self.configure();
+        debug::restore();
+        let mut errors = Vec::new();
+        if let Err(e) = gl_buffer::recreate_all() {
+            errors.push(e);
+        }
+        for (_, name, program) in self.registry.iter::<GlProgram>() {
+            if let Err(e) = program.recreate() {
+                errors.push(format!("program {}: {}", name, e));
+            }
+        }
+        shader_instantiable::recreate_vaos();
+        for sampler in &self.samplers {
+            sampler.recreate();
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    //ap pending_deletions
+    /// Get the number of dropped OpenGL objects awaiting deletion
+    pub fn pending_deletions(&self) -> usize {
+        deletion::pending_deletions(self.generation)
+    }
+}
+
+//ip Default for RenderContext
+impl Default for RenderContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//ip Drop for RenderContext
+impl Drop for RenderContext {
+    /// Drop the registered resources and delete all the OpenGL
+    /// objects dropped; the OpenGL context must still be current
+    fn drop(&mut self) {
+        self.registry = ResourceRegistry::new();
+        self.samplers.clear();
+        self.uploads.clear();
+        upload::clear_placeholders();
+        environment::clear_cache();
+        deletion::flush(self.generation);
+        deletion::context_dropped(self.generation);
+        recovery::context_dropped(self.generation);
+    }
 }
 
 //ip model3d::Renderable for Renderable
@@ -0,0 +1,444 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    sampler.rs
+@brief   OpenGL sampler objects, decoupled from textures
+ */
+
+//a Documentation
+
+/*!
+
+A [Sampler] is an OpenGL sampler object, created from a
+[SamplerDesc] describing the filtering, wrapping, level of detail and
+depth comparison used when sampling a texture. A sampler bound to a
+texture unit overrides the sampling parameters of the texture bound
+to the unit, so one [crate::Texture] may be sampled in many ways
+without its parameters being changed.
+
+Samplers should be obtained from [crate::RenderContext::sampler],
+which shares one [Sampler] between all the users of the same
+description. A [SamplerDesc] may be created directly from the
+OpenGL enumerations used by glTF samplers with
+[SamplerDesc::of_gl_enums].
+
+Anisotropy is clamped to the limit of the context, and ignored if
+anisotropic filtering is not supported; the LOD bias is ignored for
+OpenGL ES, which does not have it. Without
+[GlFeature::TextureBorderClamp] (OpenGL ES before 3.2, and WebGL)
+[WrapMode::ClampToBorder] falls back to [WrapMode::ClampToEdge], and
+the border colour is not set.
+
+!*/
+
+//a Imports
+use std::cell::Cell;
+
+use gl::types::*;
+
+use crate::{Backend, GlBackend};
+use crate::{Capabilities, GlFeature};
+use crate::debug;
+use crate::deletion::{self, GlObject};
+use crate::recovery;
+use crate::texture_units;
+
+//a Constants
+//ci TEXTURE_MAX_ANISOTROPY
+/// GL_TEXTURE_MAX_ANISOTROPY, which is not in the `gl` crate
+const TEXTURE_MAX_ANISOTROPY : GLenum = 0x84FE;
+
+//a Filter, MipmapMode, WrapMode, CompareFunction
+//tp Filter
+/// Texel filtering for magnification and minification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Filter {
+    /// Use the nearest texel
+    Nearest,
+    /// Interpolate the nearest texels
+    Linear,
+}
+
+//tp MipmapMode
+/// Selection of mip levels when minifying
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MipmapMode {
+    /// Use only the base level
+    None,
+    /// Use the nearest mip level
+    Nearest,
+    /// Interpolate between the two nearest mip levels
+    Linear,
+}
+
+//tp WrapMode
+/// Handling of texture coordinates outside 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WrapMode {
+    /// Repeat the texture
+    Repeat,
+    /// Repeat the texture, mirroring every other repeat
+    MirroredRepeat,
+    /// Use the edge texels
+    ClampToEdge,
+    /// Use the border colour - OpenGL, or OpenGL ES 3.2
+    ClampToBorder,
+}
+
+//ip WrapMode
+impl WrapMode {
+    //fi gl
+    fn gl(&self) -> GLint {
+        (match self {
+            Self::Repeat => gl::REPEAT,
+            Self::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Self::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Self::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }) as GLint
+    }
+
+    //fi of_gl
+    fn of_gl(wrap:GLenum) -> Result<Self, String> {
+        match wrap {
+            gl::REPEAT => Ok(Self::Repeat),
+            gl::MIRRORED_REPEAT => Ok(Self::MirroredRepeat),
+            gl::CLAMP_TO_EDGE => Ok(Self::ClampToEdge),
+            gl::CLAMP_TO_BORDER => Ok(Self::ClampToBorder),
+            _ => Err(format!("Unknown sampler wrap mode {}", wrap)),
+        }
+    }
+}
+
+//tp CompareFunction
+/// Depth comparison function for sampling depth textures (such as
+/// shadow maps) with shadow samplers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompareFunction {
+    /// Never passes
+    Never,
+    /// Passes if the reference is less than the texel
+    Less,
+    /// Passes if the reference is equal to the texel
+    Equal,
+    /// Passes if the reference is less than or equal to the texel
+    LessEqual,
+    /// Passes if the reference is greater than the texel
+    Greater,
+    /// Passes if the reference is not equal to the texel
+    NotEqual,
+    /// Passes if the reference is greater than or equal to the texel
+    GreaterEqual,
+    /// Always passes
+    Always,
+}
+
+//ip CompareFunction
+impl CompareFunction {
+    //fi gl
+    fn gl(&self) -> GLint {
+        (gl::NEVER + (*self as GLenum)) as GLint
+    }
+}
+
+//a SamplerDesc
+//tp SamplerDesc
+/// A description of how a texture is sampled
+///
+/// The default is bilinear filtering with repeat wrapping, without
+/// mipmapping, so that it may sample any texture; mipmapping must be
+/// requested with [SamplerDesc::with_mipmap]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplerDesc {
+    /// Magnification filter
+    pub mag_filter : Filter,
+    /// Minification filter
+    pub min_filter : Filter,
+    /// Selection of mip levels when minifying
+    pub mipmap : MipmapMode,
+    /// Wrapping of the S, T and R coordinates
+    pub wrap : [WrapMode; 3],
+    /// Maximum anisotropy (1.0 for none)
+    pub max_anisotropy : f32,
+    /// Bias added to the level of detail
+    pub lod_bias : f32,
+    /// Minimum and maximum level of detail
+    pub lod_range : (f32, f32),
+    /// Border colour for [WrapMode::ClampToBorder]
+    pub border_color : [f32; 4],
+    /// Depth comparison, if any
+    pub compare : Option<CompareFunction>,
+}
+
+//ip Default for SamplerDesc
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self {
+            mag_filter : Filter::Linear,
+            min_filter : Filter::Linear,
+            mipmap : MipmapMode::None,
+            wrap : [WrapMode::Repeat; 3],
+            max_anisotropy : 1.0,
+            lod_bias : 0.0,
+            lod_range : (-1000.0, 1000.0),
+            border_color : [0.0; 4],
+            compare : None,
+        }
+    }
+}
+
+//ip SamplerDesc
+impl SamplerDesc {
+    //fp new
+    /// Create a sampler description with filters and no mipmapping,
+    /// clamping to the edge
+    pub fn new(mag_filter:Filter, min_filter:Filter) -> Self {
+        Self { mag_filter, min_filter,
+               mipmap : MipmapMode::None,
+               wrap : [WrapMode::ClampToEdge; 3],
+               ..Default::default()
+        }
+    }
+
+    //fp of_gl_enums
+    /// Create a sampler description from the OpenGL enumerations used
+    /// by glTF samplers - a magnification filter, a minification
+    /// filter (which may include the mipmap mode) and S and T wrap
+    /// modes; filters that are not given use the defaults
+    pub fn of_gl_enums(mag_filter:Option<GLenum>, min_filter:Option<GLenum>, wrap_s:GLenum, wrap_t:GLenum) -> Result<Self, String> {
+        let mut desc = Self::default();
+        if let Some(mag_filter) = mag_filter {
+            desc.mag_filter = match mag_filter {
+                gl::NEAREST => Filter::Nearest,
+                gl::LINEAR => Filter::Linear,
+                _ => return Err(format!("Unknown sampler magnification filter {}", mag_filter)),
+            };
+        }
+        if let Some(min_filter) = min_filter {
+            (desc.min_filter, desc.mipmap) = match min_filter {
+                gl::NEAREST => (Filter::Nearest, MipmapMode::None),
+                gl::LINEAR => (Filter::Linear, MipmapMode::None),
+                gl::NEAREST_MIPMAP_NEAREST => (Filter::Nearest, MipmapMode::Nearest),
+                gl::LINEAR_MIPMAP_NEAREST => (Filter::Linear, MipmapMode::Nearest),
+                gl::NEAREST_MIPMAP_LINEAR => (Filter::Nearest, MipmapMode::Linear),
+                gl::LINEAR_MIPMAP_LINEAR => (Filter::Linear, MipmapMode::Linear),
+                _ => return Err(format!("Unknown sampler minification filter {}", min_filter)),
+            };
+        }
+        desc.wrap[0] = WrapMode::of_gl(wrap_s)?;
+        desc.wrap[1] = WrapMode::of_gl(wrap_t)?;
+        Ok(desc)
+    }
+
+    //cp with_mipmap
+    /// Set the mipmap mode
+    pub fn with_mipmap(mut self, mipmap:MipmapMode) -> Self {
+        self.mipmap = mipmap;
+        self
+    }
+
+    //cp with_wrap
+    /// Set the wrap mode of all the coordinates
+    pub fn with_wrap(mut self, wrap:WrapMode) -> Self {
+        self.wrap = [wrap; 3];
+        self
+    }
+
+    //cp with_anisotropy
+    /// Set the maximum anisotropy
+    pub fn with_anisotropy(mut self, max_anisotropy:f32) -> Self {
+        self.max_anisotropy = max_anisotropy;
+        self
+    }
+
+    //cp with_lod
+    /// Set the level of detail bias and range
+    pub fn with_lod(mut self, bias:f32, min:f32, max:f32) -> Self {
+        self.lod_bias = bias;
+        self.lod_range = (min, max);
+        self
+    }
+
+    //cp with_border_color
+    /// Set the border colour, and clamp to the border
+    pub fn with_border_color(mut self, border_color:[f32; 4]) -> Self {
+        self.border_color = border_color;
+        self.wrap = [WrapMode::ClampToBorder; 3];
+        self
+    }
+
+    //cp with_compare
+    /// Set the depth comparison, for shadow samplers
+    pub fn with_compare(mut self, compare:CompareFunction) -> Self {
+        self.compare = Some(compare);
+        self
+    }
+
+    //mi min_filter_gl
+    /// Get the OpenGL minification filter
+    fn min_filter_gl(&self) -> GLint {
+        (match (self.min_filter, self.mipmap) {
+            (Filter::Nearest, MipmapMode::None) => gl::NEAREST,
+            (Filter::Linear, MipmapMode::None) => gl::LINEAR,
+            (Filter::Nearest, MipmapMode::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, MipmapMode::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Nearest, MipmapMode::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, MipmapMode::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        }) as GLint
+    }
+
+    //mi mag_filter_gl
+    /// Get the OpenGL magnification filter
+    fn mag_filter_gl(&self) -> GLint {
+        (match self.mag_filter {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        }) as GLint
+    }
+}
+
+//a Sampler
+//tp Sampler
+/// An OpenGL sampler object, deleted when it is dropped
+#[derive(Debug)]
+pub struct Sampler {
+    /// The OpenGL sampler name
+    gl : Cell<GLuint>,
+    /// The context generation the sampler was created in
+    generation : Cell<u32>,
+    /// The description of the sampler
+    desc : SamplerDesc,
+    /// Maximum anisotropy supported (0 if none)
+    max_anisotropy : f32,
+    /// True if the context is OpenGL ES
+    is_gles : bool,
+    /// True if the context supports border colours
+    border_clamp : bool,
+}
+
+//ip Sampler
+impl Sampler {
+    //fp new
+    /// Create a sampler from a description for a context
+    ///
+    /// Usually [crate::RenderContext::sampler] should be used
+    /// instead, to share samplers
+    pub fn new(desc:&SamplerDesc, capabilities:&Capabilities) -> Self {
+        let max_anisotropy = {
+            if capabilities.supports(GlFeature::AnisotropicFiltering) {
+                capabilities.limits.max_anisotropy
+            } else {
+                0.
+            }
+        };
+        let sampler = Self {
+            gl : Cell::new(0),
+            generation : Cell::new(recovery::generation()),
+            desc : desc.clone(),
+            max_anisotropy,
+            is_gles : capabilities.is_gles(),
+            border_clamp : capabilities.supports(GlFeature::TextureBorderClamp),
+        };
+        sampler.gl.set(sampler.build());
+        sampler
+    }
+
+    //mi wrap_gl
+    /// Get the OpenGL wrap mode for a texture coordinate, clamping to
+    /// the edge if border colours are not supported
+    fn wrap_gl(&self, i:usize) -> GLint {
+        match self.desc.wrap[i] {
+            WrapMode::ClampToBorder if !self.border_clamp => WrapMode::ClampToEdge.gl(),
+            wrap => wrap.gl(),
+        }
+    }
+
+    //mi build
+    /// Create the OpenGL sampler and set its parameters
+    fn build(&self) -> GLuint {
+        let desc = &self.desc;
+        let gl = Backend::gen_sampler();
+        Backend::sampler_parameter_i(gl, gl::TEXTURE_MIN_FILTER, desc.min_filter_gl());
+        Backend::sampler_parameter_i(gl, gl::TEXTURE_MAG_FILTER, desc.mag_filter_gl());
+        Backend::sampler_parameter_i(gl, gl::TEXTURE_WRAP_S, self.wrap_gl(0));
+        Backend::sampler_parameter_i(gl, gl::TEXTURE_WRAP_T, self.wrap_gl(1));
+        Backend::sampler_parameter_i(gl, gl::TEXTURE_WRAP_R, self.wrap_gl(2));
+        Backend::sampler_parameter_f(gl, gl::TEXTURE_MIN_LOD, desc.lod_range.0);
+        Backend::sampler_parameter_f(gl, gl::TEXTURE_MAX_LOD, desc.lod_range.1);
+        if desc.lod_bias != 0.0 && !self.is_gles {
+            Backend::sampler_parameter_f(gl, gl::TEXTURE_LOD_BIAS, desc.lod_bias);
+        }
+        if desc.max_anisotropy > 1.0 && self.max_anisotropy > 1.0 {
+            let anisotropy = desc.max_anisotropy.min(self.max_anisotropy);
+            Backend::sampler_parameter_f(gl, TEXTURE_MAX_ANISOTROPY, anisotropy);
+        }
+        if self.border_clamp && desc.wrap.contains(&WrapMode::ClampToBorder) {
+            Backend::sampler_parameter_fv(gl, gl::TEXTURE_BORDER_COLOR, &desc.border_color);
+        }
+        if let Some(compare) = desc.compare {
+            Backend::sampler_parameter_i(gl, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as GLint);
+            Backend::sampler_parameter_i(gl, gl::TEXTURE_COMPARE_FUNC, compare.gl());
+        }
+        gl
+    }
+
+    //mp recreate
+    /// Recreate the sampler after context loss, if it is stale
+    pub(crate) fn recreate(&self) {
+        if !recovery::is_current(self.generation.get()) {
+            self.gl.set(self.build());
+            self.generation.set(recovery::generation());
+        }
+    }
+
+    //ap gl_sampler
+    /// Get the OpenGL sampler name
+    pub fn gl_sampler(&self) -> GLuint {
+        self.gl.get()
+    }
+
+    //ap desc
+    /// Borrow the description of the sampler
+    pub fn desc(&self) -> &SamplerDesc {
+        &self.desc
+    }
+
+    //mp bind
+    /// Bind the sampler to a texture unit
+    pub fn bind(&self, unit:usize) -> Result<(), String> {
+        texture_units::bind_sampler(unit, self.gl.get())
+    }
+
+    //fp unbind
+    /// Unbind any sampler from a texture unit, so that the texture
+    /// parameters are used
+    pub fn unbind(unit:usize) -> Result<(), String> {
+        texture_units::bind_sampler(unit, 0)
+    }
+
+    //mp set_label
+    /// Label the OpenGL sampler (if debug output is enabled) for
+    /// debug messages and tools
+    pub fn set_label(&self, label:&str) {
+        debug::label(gl::SAMPLER, self.gl.get(), label);
+    }
+}
+
+//ip Drop for Sampler
+impl Drop for Sampler {
+    //fp drop
+    /// Drop requires the sampler to be deleted
+    fn drop(&mut self) {
+        deletion::delete(self.generation.get(), GlObject::Sampler(self.gl.get()));
+    }
+}
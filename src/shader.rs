@@ -21,6 +21,10 @@ use gl;
 use std;
 use std::ffi::CStr;
 
+use crate::{Backend, GlBackend};
+use crate::deletion::{self, GlObject};
+use crate::recovery;
+
 //a GlShader
 //tp GlShader
 /// An OpenGL shader, of any kind, which can be created from source.
@@ -30,6 +34,12 @@ use std::ffi::CStr;
 pub struct GlShader {
     /// The GL ID of the shader
     id: gl::types::GLuint,
+    /// The kind of shader (e.g. VERTEX_SHADER)
+    kind: gl::types::GLenum,
+    /// The source, retained so that programs can be recreated
+    source: String,
+    /// The context generation the shader was created in
+    generation: u32,
 }
 
 //ip GlShader
@@ -37,27 +47,21 @@ impl GlShader {
     //fp from_source
     /// Create a shader of a particular kind from source
     pub fn from_source(source: &CStr, kind: gl::types::GLenum) -> Result<Self, String> {
-        let id = 
-            unsafe {
-                let id = gl::CreateShader(kind);
-                gl::ShaderSource(id, 1, &source.as_ptr(), std::ptr::null());
-                gl::CompileShader(id);
-                id
-            };
-
-        if crate::get_shaderiv(id, gl::COMPILE_STATUS) == 0 {
-            let err = crate::get_shader_error( id,
-                                         |id| crate::get_shaderiv(id, gl::INFO_LOG_LENGTH),
-                                         |id, len, buf| unsafe {
-                                             gl::GetShaderInfoLog( id, len, std::ptr::null_mut(), buf)
-                                         } );
+        let source = source.to_str().map_err(|e| format!("Shader source is not UTF-8: {}", e))?;
+        let id = Backend::create_shader(kind);
+        Backend::shader_source(id, source);
+        Backend::compile_shader(id);
+
+        if !Backend::shader_compile_status(id) {
+            let err = Backend::shader_info_log(id);
+            Backend::delete_shader(id);
             if kind == gl::VERTEX_SHADER {
                 Err(format!("Vertex shader error {}", err))
             } else {
                 Err(format!("Fragment shader error {}", err))
             }                
         } else {
-            Ok(Self { id })
+            Ok(Self { id, kind, source:source.to_string(), generation:recovery::generation() })
         }
     }
 
@@ -78,14 +82,24 @@ impl GlShader {
     pub fn id(&self) -> gl::types::GLuint {
         self.id
     }
+
+    //ap kind
+    /// Get the kind of shader (e.g. VERTEX_SHADER)
+    pub fn kind(&self) -> gl::types::GLenum {
+        self.kind
+    }
+
+    //ap source
+    /// Get the source the shader was compiled from
+    pub fn source(&self) -> &str {
+        &self.source
+    }
 }
 
 //ip Drop for GlShader
 impl Drop for GlShader {
     fn drop(&mut self) {
-        unsafe {
-            gl::DeleteShader(self.id);
-        }
+        deletion::delete(self.generation, GlObject::Shader(self.id));
     }
 }
 
@@ -21,7 +21,19 @@ limitations under the License.
 //
 
 //a Imports
-use crate::{Renderable, Vertices, ShaderClass, UniformId};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::{Renderable, Vertices, ShaderClass, UniformId, Material};
+use crate::{IndexBuffer, VertexBuffer};
+use crate::{MissingAttributes, AttributeReport};
+use crate::{Backend, GlBackend};
+use crate::{debug, DebugGroup, GpuProfiler};
+use crate::{RenderContext, OcclusionCulling, BoundingBox};
+use crate::utils::mat4_transform;
+use crate::deletion::{self, GlObject};
+use crate::recovery;
 
 //a Vao
 /// The [Vao] *must* be owned by a [ShaderInstantiable], which borrows
@@ -30,49 +42,175 @@ use crate::{Renderable, Vertices, ShaderClass, UniformId};
 ///
 /// Because of this the [Vao] cannot outlive the [ShaderInstantiable], which
 /// cannot outlive the GL buffer for the vertices and indices etc
+///
+/// A [Vao] may be shared (through the [VAO_CACHE]) by the
+/// [ShaderInstantiable]s of shader classes with the same attribute
+/// layout for the same [Vertices]; it is deleted when the last of
+/// these is dropped
+///
+/// The [Vao] keeps (ref-counted) handles to the buffers it binds, so
+/// that after context loss it can be rebuilt by [recreate_vaos]
 struct Vao {
-    gl_vao : u32,
+    gl_vao : Cell<u32>,
+    /// The context generation the VAO was created in
+    generation : Cell<u32>,
+    /// The index buffer bound to the VAO
+    indices : IndexBuffer,
+    /// The vertex buffers bound to the VAO, with their attribute
+    /// locations
+    attributes : Vec<(gl::types::GLuint, VertexBuffer)>,
+}
+
+//ti VaoLayout
+/// The attribute location to [model3d::VertexAttr] mapping of a
+/// shader class, sorted by location
+type VaoLayout = Vec<(gl::types::GLuint, model3d::VertexAttr)>;
+
+//ti VaoCache
+/// The VAOs in use for each [Vertices] (by [Vertices::id]), with
+/// their layouts
+type VaoCache = HashMap<usize, Vec<(VaoLayout, Weak<Vao>)>>;
+
+thread_local! {
+    /// Cache of the VAOs in use, keyed by the id of the [Vertices]
+    /// they are for and the attribute layout
+    static VAO_CACHE : RefCell<VaoCache> = RefCell::new(HashMap::new());
 }
 
 impl Vao {
+    //fp shared
+    /// Get a VAO for the vertices with the attribute layout of a
+    /// shader class, reusing one from the cache if there is one
+    pub fn shared(shader_class:&dyn ShaderClass, vertices:&Vertices) -> Rc<Self> {
+        let mut layout : VaoLayout = shader_class.attributes().to_vec();
+        layout.sort_by_key(|(index, _)| *index);
+        VAO_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            cache.retain(|_, entries| {
+                entries.retain(|(_, vao)| vao.strong_count() > 0);
+                !entries.is_empty()
+            });
+            let entries = cache.entry(vertices.id()).or_default();
+            for (l, vao) in entries.iter() {
+                if *l == layout {
+                    if let Some(vao) = vao.upgrade() {
+                        return vao;
+                    }
+                }
+            }
+            let vao = Rc::new(Self::new(&layout, vertices));
+            entries.push((layout, Rc::downgrade(&vao)));
+            vao
+        })
+    }
+
     //fp new
-    pub fn new(shader_class:&dyn ShaderClass, vertices:&Vertices) -> Self {
+    pub fn new(layout:&[(gl::types::GLuint, model3d::VertexAttr)], vertices:&Vertices) -> Self {
         let (indices, position, attrs) = vertices.borrow();
-        crate::check_errors().unwrap();
-        let mut gl_vao = 0;
-        unsafe {
-            gl::GenVertexArrays(1, &mut gl_vao);
-            gl::BindVertexArray(gl_vao);
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER,
-                           indices.gl_buffer() );
-            println!("VAO {} {:?}", gl_vao, indices);
-        }
-        crate::check_errors().expect("Added indices to VAO");
-        for (index, vertex_attr) in shader_class.attributes() {
+        let mut attributes = Vec::new();
+        for (index, vertex_attr) in layout {
             if *vertex_attr == model3d::VertexAttr::Position {
-                println!(".. posn {} {}", *index, position);
-                position.bind_to_vao(*index);
-                crate::check_errors().unwrap();
+                attributes.push((*index, position.clone()));
             } else {
                 for (va, buffer) in attrs {
                     if *vertex_attr == *va {
-                        println!(".. {:?} {} {}", *vertex_attr, *index, buffer);
-                        buffer.bind_to_vao(*index);
+                        attributes.push((*index, buffer.clone()));
                     }
-                    crate::check_errors().unwrap();
                 }
             }
         }
-        unsafe {
-            gl::BindVertexArray(0);
+        let vao = Self { gl_vao:Cell::new(0), generation:Cell::new(recovery::generation()),
+                         indices:indices.clone(), attributes };
+        vao.gl_vao.set(vao.build());
+        vao
+    }
+
+    //fp recreate
+    /// Rebuild the VAO in the current context, if it is stale
+    fn recreate(&self) {
+        if !recovery::is_current(self.generation.get()) {
+            self.gl_vao.set(self.build());
+            self.generation.set(recovery::generation());
+        }
+    }
+
+    //fi build
+    /// Build an OpenGL VAO binding the index buffer and the vertex
+    /// buffers to their attribute locations
+    fn build(&self) -> u32 {
+        crate::check_errors().unwrap();
+        let gl_vao = Backend::gen_vertex_array();
+        Backend::bind_vertex_array(gl_vao);
+        self.indices.bind_to_vao();
+        println!("VAO {} {:?}", gl_vao, self.indices);
+        crate::check_errors().expect("Added indices to VAO");
+        for (index, buffer) in &self.attributes {
+            println!(".. {} {}", *index, buffer);
+            buffer.bind_to_vao(*index);
+            crate::check_errors().unwrap();
         }
+        Backend::bind_vertex_array(0);
         crate::check_errors().unwrap();
-        Self {gl_vao}
+        gl_vao
     }
     //fp bind_vao
     pub fn bind_vao(&self) {
-        unsafe {
-            gl::BindVertexArray(self.gl_vao);
+        Backend::bind_vertex_array(self.gl_vao.get());
+    }
+    //fp set_label
+    pub fn set_label(&self, label:&str) {
+        debug::label(gl::VERTEX_ARRAY, self.gl_vao.get(), label);
+    }
+}
+
+//ip Drop for Vao
+impl Drop for Vao {
+    fn drop(&mut self) {
+        deletion::delete(self.generation.get(), GlObject::VertexArray(self.gl_vao.get()));
+    }
+}
+
+//fp recreate_vaos
+/// Rebuild all the VAOs in use after context loss; the buffers of
+/// their vertices must have been recreated first
+pub(crate) fn recreate_vaos() {
+    VAO_CACHE.with(|cache| {
+        for entries in cache.borrow().values() {
+            for (_, vao) in entries {
+                if let Some(vao) = vao.upgrade() {
+                    vao.recreate();
+                }
+            }
+        }
+    });
+}
+
+//a Handle
+//ti Handle
+/// A handle to something a [ShaderInstantiable] uses, which it either
+/// borrows or shares ownership of
+enum Handle<'a, T:?Sized> {
+    Borrowed(&'a T),
+    Shared(Rc<T>),
+}
+
+//ip Clone for Handle
+impl <'a, T:?Sized> Clone for Handle<'a, T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Borrowed(t) => Self::Borrowed(t),
+            Self::Shared(t) => Self::Shared(t.clone()),
+        }
+    }
+}
+
+//ip Deref for Handle
+impl <'a, T:?Sized> std::ops::Deref for Handle<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        match self {
+            Self::Borrowed(t) => t,
+            Self::Shared(t) => t,
         }
     }
 }
@@ -94,28 +232,215 @@ impl Vao {
 /// [ShaderInstantiable].
 ///
 /// It borrows from the [model3d::Instantiable] and so does not need to its own GlBuffers
+///
+/// Alternatively it may be created with [ShaderInstantiable::new_owned]
+/// holding [Rc]s of the [model3d::Instantiable] and shader class; this
+/// is an [OwnedShaderInstantiable], which has no borrow lifetime and so
+/// may be stored alongside them (e.g. in a scene graph)
 pub struct ShaderInstantiable<'a> {
-    instantiable : &'a model3d::Instantiable<Renderable>,
-    // vaos is 1-to-1 with instantiable::vertices, specific to this shader (class)
-    vaos: Vec<Vao>,
-    shader_class : &'a dyn ShaderClass,
+    instantiable : Handle<'a, model3d::Instantiable<Renderable>>,
+    // vaos is 1-to-1 with instantiable::vertices, specific to the
+    // attribute layout of this shader (class)
+    vaos: Vec<Rc<Vao>>,
+    /// Constant values for the attributes missing from each vertices,
+    /// as (attribute location, value)
+    attribute_defaults : Vec<Vec<(gl::types::GLuint, [f32; 4])>>,
+    shader_class : Handle<'a, dyn ShaderClass + 'a>,
+    /// Name used for labels and the debug group when drawing
+    name : String,
+    /// Occlusion culling, if enabled
+    occlusion : Option<OcclusionCulling>,
+    /// Bounding box of the primitives in model space, if known
+    bounds : Option<BoundingBox>,
+    /// Materials for the material indices of the primitives
+    materials : Vec<Option<Rc<Material>>>,
 }
 
 //ip ShaderInstantiable
 impl <'a> ShaderInstantiable<'a> {
     //fp new
     /// Create a new [ShaderInstantiable]
-    pub fn new(shader_class:&'a dyn ShaderClass, instantiable: &'a model3d::Instantiable<Renderable>) -> Self {
+    ///
+    /// The VAOs are shared with any other [ShaderInstantiable]s for
+    /// the same vertices whose shader classes have the same attribute
+    /// locations for the same [model3d::VertexAttr]s
+    ///
+    /// Attributes required by the shader class but not provided by
+    /// the vertices are handled as given by `missing`; an
+    /// [AttributeReport] of these (and of attributes provided but not
+    /// used) is returned with the [ShaderInstantiable]
+    pub fn new(shader_class:&'a dyn ShaderClass,
+               instantiable: &'a model3d::Instantiable<Renderable>,
+               missing:&MissingAttributes) -> Result<(Self, AttributeReport), String> {
+        Self::create(Handle::Borrowed(shader_class), Handle::Borrowed(instantiable), missing)
+    }
+
+    //mp variant
+    /// Create a [ShaderInstantiable] for the same instantiable with
+    /// another shader class, such as a depth-only program for drawing
+    /// shadow casters ([crate::ShadowMap::depth_program]), leaving
+    /// attributes it requires that the vertices lack disabled
+    ///
+    /// The materials and occlusion culling are not copied
+    pub fn variant(&self, shader_class:&'a dyn ShaderClass) -> Result<(Self, AttributeReport), String> {
+        let (mut s, report) = Self::create(Handle::Borrowed(shader_class), self.instantiable.clone(), &MissingAttributes::new())?;
+        s.set_name(&self.name);
+        Ok((s, report))
+    }
+
+    //fi create
+    /// Create a new [ShaderInstantiable] from handles to the shader
+    /// class and instantiable
+    fn create(shader_class:Handle<'a, dyn ShaderClass + 'a>,
+              instantiable:Handle<'a, model3d::Instantiable<Renderable>>,
+              missing:&MissingAttributes) -> Result<(Self, AttributeReport), String> {
+        let mut report = AttributeReport::default();
         let mut vaos = Vec::new();
-        for v in &instantiable.vertices {
-            vaos.push(Vao::new(shader_class, v));
+        let mut attribute_defaults = Vec::new();
+        for (i, v) in instantiable.vertices.iter().enumerate() {
+            attribute_defaults.push(missing.check(&*shader_class, i, v, &mut report)?);
+            vaos.push(Vao::shared(&*shader_class, v));
+        }
+        let bounds = Self::primitive_bounds(&instantiable);
+        let s = Self { instantiable, vaos, attribute_defaults, shader_class,
+                       name:String::new(), occlusion:None, bounds, materials:Vec::new() };
+        Ok((s, report))
+    }
+
+    //fi primitive_bounds
+    /// Find the bounding box of the primitives of an instantiable
+    /// (each with its mesh matrix), if all their vertices have
+    /// bounding boxes
+    fn primitive_bounds(instantiable:&model3d::Instantiable<Renderable>) -> Option<BoundingBox> {
+        let recipe = &instantiable.render_recipe;
+        let mut bounds : Option<BoundingBox> = None;
+        for (i, p) in recipe.primitives.iter().enumerate() {
+            let vertex_bounds = instantiable.vertices[p.vertices_index()].bounds()?;
+            let matrix = &recipe.matrices[recipe.matrix_for_primitives[i]];
+            for c in vertex_bounds.corners() {
+                let c = mat4_transform(matrix, c);
+                bounds = Some(match bounds {
+                    Some(b) => b.including(c),
+                    None => BoundingBox::of_point(c),
+                });
+            }
+        }
+        bounds
+    }
+
+    //ap bounds
+    /// Get the bounding box of the primitives in model space, if
+    /// all the vertices have bounding boxes
+    pub fn bounds(&self) -> Option<&BoundingBox> {
+        self.bounds.as_ref()
+    }
+
+    //mp set_name
+    /// Set the name of the [ShaderInstantiable], used to label its
+    /// VAOs and the buffers of its vertices, and for the debug group
+    /// when drawing (if debug output is enabled)
+    ///
+    /// [model3d] objects carry no names, so until this is invoked the
+    /// name is empty and the objects are unlabelled
+    pub fn set_name(&mut self, name:&str) {
+        self.name = name.to_string();
+        for (i, (vao, vertices)) in self.vaos.iter().zip(self.instantiable.vertices.iter()).enumerate() {
+            let name = format!("{} vertices {}", name, i);
+            vao.set_label(&name);
+            vertices.set_label(&name);
         }
-        Self { instantiable, vaos, shader_class }
+    }
+
+    //mp set_material
+    /// Set the [Material] to draw the primitives with a material index
+    /// with
+    pub fn set_material(&mut self, material_index:usize, material:Rc<Material>) {
+        if material_index >= self.materials.len() {
+            self.materials.resize(material_index + 1, None);
+        }
+        self.materials[material_index] = Some(material);
+    }
+
+    //ap material
+    /// Get the [Material] for a primitive, if one has been set for
+    /// its material index
+    pub fn material(&self, primitive:usize) -> Option<&Rc<Material>> {
+        let material_index = self.primitive(primitive).material_index();
+        self.materials.get(material_index).and_then(|m| m.as_ref())
+    }
+
+    //ap material_id
+    /// Get the id of the [Material] for a primitive, or 0 if it has
+    /// none (and so is drawn with a default material)
+    pub fn material_id(&self, primitive:usize) -> u32 {
+        self.material(primitive).map_or(0, |m| m.id())
+    }
+
+    //mp enable_occlusion_culling
+    /// Enable occlusion culling, drawing the bounding boxes of the
+    /// vertices in an occlusion query before each draw, and skipping
+    /// the draw if the previous frame's boxes were not visible
+    ///
+    /// This requires occlusion query support, a position attribute in
+    /// the shader, and bounding boxes for all of the vertices
+    ///
+    /// Only draws that identify the instance by a key
+    /// ([ShaderInstantiable::gl_draw_keyed] and
+    /// [ShaderInstantiable::gl_draw_profiled] with a key) are culled
+    pub fn enable_occlusion_culling(&mut self, render_context:&RenderContext) -> Result<(), String> {
+        self.occlusion = Some(OcclusionCulling::new(render_context, &*self.shader_class, &self.instantiable)?);
+        Ok(())
+    }
+
+    //ap occlusion_culling
+    /// Get the occlusion culling state, if enabled (and not lost
+    /// with the context)
+    pub fn occlusion_culling(&self) -> Option<&OcclusionCulling> {
+        self.occlusion.as_ref().filter(|o| o.is_current())
     }
 
     // gl_draw
     /// Draw this [ShaderInstantiable] given an [model3d::Instance] data
+    ///
+    /// The instance has no identity from frame to frame, so it is not
+    /// occlusion culled; use [ShaderInstantiable::gl_draw_keyed] for
+    /// that
     pub fn gl_draw(&self, instance:&model3d::Instance<Renderable>) {
+        // Drawing can only fail when profiling
+        let _ = self.draw(instance, None, None);
+    }
+
+    //mp gl_draw_keyed
+    /// Draw this [ShaderInstantiable] given an [model3d::Instance]
+    /// data, identified by a key that the caller keeps the same for
+    /// the instance from frame to frame (and unique among the
+    /// instances drawn), occlusion culling it if enabled
+    pub fn gl_draw_keyed(&self, instance:&model3d::Instance<Renderable>, key:usize) {
+        // Drawing can only fail when profiling
+        let _ = self.draw(instance, Some(key), None);
+    }
+
+    //mp gl_draw_profiled
+    /// Draw this [ShaderInstantiable] given an [model3d::Instance]
+    /// data, profiling it as an object (with the name of the
+    /// [ShaderInstantiable]) in the current frame of a [GpuProfiler]
+    ///
+    /// If a key is given the instance is occlusion culled (if enabled)
+    /// as for [ShaderInstantiable::gl_draw_keyed]; the occlusion test
+    /// is made before the profiler's queries begin, so the bounding
+    /// box draws are not counted
+    ///
+    /// This returns an error (without drawing the primitives) if the
+    /// profiler is not in a frame, or is already profiling an object
+    pub fn gl_draw_profiled(&self, instance:&model3d::Instance<Renderable>, key:Option<usize>, profiler:&mut GpuProfiler) -> Result<(), String> {
+        self.draw(instance, key, Some(profiler))
+    }
+
+    //mi draw
+    /// Draw the primitives, occlusion culling them if a key for the
+    /// instance is given, and profiling them as an object in the
+    /// current frame of a [GpuProfiler] if one is given
+    fn draw(&self, instance:&model3d::Instance<Renderable>, key:Option<usize>, mut profiler:Option<&mut GpuProfiler>) -> Result<(), String> {
         // shader camera matrix (already set?)
         /*
         // for bone_set_pose in instance.bone_set_poses {
@@ -141,39 +466,224 @@ impl <'a> ShaderInstantiable<'a> {
     instance model matrix
     for (i, p) in render_recipe.primitives.iter().enumerate() {
 */
-            if let Some(u) = self.shader_class.uniform(UniformId::ModelMatrix) {
-                let mat = instance.transformation.mat4();
-                unsafe {gl::UniformMatrix4fv(u, 1, gl::FALSE, mat.as_ptr());}
+        let _group = DebugGroup::new(&self.name);
+        self.set_model_matrix(instance);
+        // The occlusion test draws the bounding boxes in a query; it is
+        // not used if it was skipped
+        let mut occlusion = None;
+        if let (Some(o), Some(key)) = (self.occlusion_culling(), key) {
+            if o.test(&*self.shader_class, &self.instantiable.render_recipe.matrices, key) {
+                occlusion = Some(o);
             }
-        for (i, p) in self.instantiable.render_recipe.primitives.iter().enumerate() {
+        }
+        if let Some(profiler) = profiler.as_mut() {
+            profiler.begin_object(&self.name)?;
+        }
+        let visible = {
+            match occlusion {
+                Some(occlusion) => occlusion.begin_draw(),
+                None => true,
+            }
+        };
+        let (draw_calls, triangles) = if visible { self.draw_primitives() } else { (0, 0) };
+        if let Some(occlusion) = occlusion {
+            occlusion.end_draw();
+        }
+        if let Some(profiler) = profiler {
+            profiler.end_object(draw_calls, triangles)?;
+        }
+        Ok(())
+    }
+
+    //mi draw_primitives
+    /// Draw all the primitives, returning the number of draw calls
+    /// and triangles
+    fn draw_primitives(&self) -> (usize, usize) {
+        let mut draw_calls = 0;
+        let mut triangles = 0;
+        let mut material = None;
+        for i in 0..self.instantiable.render_recipe.primitives.len() {
             // set MeshMatrix (if different to last)
-            if let Some(u) = self.shader_class.uniform(UniformId::MeshMatrix) {
-                let m = self.instantiable.render_recipe.matrix_for_primitives[i];
-                unsafe {gl::UniformMatrix4fv(u, 1, gl::FALSE, self.instantiable.render_recipe.matrices[m].as_ptr());}
+            self.set_mesh_matrix(self.instantiable.render_recipe.matrix_for_primitives[i]);
+            let material_index = self.primitive(i).material_index();
+            if material != Some(material_index) {
+                self.bind_material(i);
+                material = Some(material_index);
             }
-            // set material info to that for shader_instantiable p.material_index,(if different to last)
             // (if p.vertices_index different to last)
-            self.vaos[p.vertices_index()].bind_vao();
-            let byte_offset = p.byte_offset();
-            use model3d::PrimitiveType::*;
-            let gl_type = match p.primitive_type() {
-                Points => gl::POINTS,
-                Lines => gl::LINES,
-                LineLoop => gl::LINE_LOOP,
-                LineStrip => gl::LINE_STRIP,
-                Triangles => gl::TRIANGLES,
-                TriangleFan => gl::TRIANGLE_FAN,
-                TriangleStrip => gl::TRIANGLE_STRIP,
-            };
-            unsafe {
-                gl::DrawElements( gl_type,
-                                  p.index_count() as i32,
-                                  gl::UNSIGNED_BYTE, // index_type,
-                                  std::mem::transmute(byte_offset) );
+            self.bind_vertices(i);
+            triangles += self.draw_primitive(i);
+            self.unbind_vertices(i);
+            draw_calls += 1;
+        }
+        (draw_calls, triangles)
+    }
+
+    //ap shader_class
+    /// Get the shader class (program) that the [ShaderInstantiable] is for
+    pub fn shader_class(&self) -> &dyn ShaderClass {
+        &*self.shader_class
+    }
+
+    //ap instantiable
+    /// Get the [model3d::Instantiable] that the [ShaderInstantiable] is for
+    pub fn instantiable(&self) -> &model3d::Instantiable<Renderable> {
+        &self.instantiable
+    }
+
+    //ap num_primitives
+    /// Get the number of primitives in the render recipe
+    pub fn num_primitives(&self) -> usize {
+        self.instantiable.render_recipe.primitives.len()
+    }
+
+    //ap primitive
+    /// Get a primitive of the render recipe
+    pub fn primitive(&self, primitive:usize) -> &model3d::Primitive {
+        &self.instantiable.render_recipe.primitives[primitive]
+    }
+
+    //ap mesh_matrix_index
+    /// Get the index of the mesh matrix for a primitive
+    pub fn mesh_matrix_index(&self, primitive:usize) -> usize {
+        self.instantiable.render_recipe.matrix_for_primitives[primitive]
+    }
+
+    //ap gl_vao
+    /// Get the OpenGL VAO for a primitive
+    pub fn gl_vao(&self, primitive:usize) -> gl::types::GLuint {
+        self.vaos[self.primitive(primitive).vertices_index()].gl_vao.get()
+    }
+
+    //mp bind_vertices
+    /// Bind the VAO for a primitive, and set the constant values of
+    /// any attributes missing from its vertices
+    ///
+    /// The constant values are context state rather than VAO state, so
+    /// [Self::unbind_vertices] must be invoked after drawing
+    pub fn bind_vertices(&self, primitive:usize) {
+        let vertices_index = self.primitive(primitive).vertices_index();
+        self.vaos[vertices_index].bind_vao();
+        self.set_attribute_defaults(primitive);
+    }
+
+    //mi set_attribute_defaults
+    /// Set the constant values of any attributes missing from the
+    /// vertices of a primitive, whose VAO is bound
+    pub(crate) fn set_attribute_defaults(&self, primitive:usize) {
+        let vertices_index = self.primitive(primitive).vertices_index();
+        for (index, value) in &self.attribute_defaults[vertices_index] {
+            Backend::vertex_attrib_4f(*index, value);
+        }
+    }
+
+    //mp unbind_vertices
+    /// Reset the constant values set by [Self::bind_vertices] for a
+    /// primitive to the OpenGL initial value of (0, 0, 0, 1)
+    pub fn unbind_vertices(&self, primitive:usize) {
+        let vertices_index = self.primitive(primitive).vertices_index();
+        for (index, _) in &self.attribute_defaults[vertices_index] {
+            Backend::vertex_attrib_4f(*index, &[0., 0., 0., 1.]);
+        }
+    }
+
+    //mp bind_material
+    /// Bind the textures of the material for a primitive (or of a
+    /// default [Material], if it has none) to their texture units and
+    /// set the sampler and factor uniforms, returning the number of
+    /// textures bound; the program must be in use
+    ///
+    /// If texture units cannot be allocated for the material then
+    /// an error is logged, and its remaining textures are not bound
+    pub fn bind_material(&self, primitive:usize) -> usize {
+        let result = {
+            if let Some(material) = self.material(primitive) {
+                material.bind(&*self.shader_class)
+            } else {
+                Material::default_material().bind(&*self.shader_class)
             }
+        };
+        result.unwrap_or_else(|e| {
+            log::error!("{}: material {}: {}", self.name, self.primitive(primitive).material_index(), e);
+            0
+        })
+    }
+
+    //mp set_model_matrix
+    /// Set the model matrix uniform for an instance; the program must
+    /// be in use
+    pub fn set_model_matrix(&self, instance:&model3d::Instance<Renderable>) {
+        if let Some(u) = self.shader_class.uniform(UniformId::ModelMatrix) {
+            let mat = instance.transformation.mat4();
+            Backend::uniform_matrix4(u, &mat);
+        }
+    }
+
+    //mp set_mesh_matrix
+    /// Set the mesh matrix uniform to a matrix of the render recipe;
+    /// the program must be in use
+    pub fn set_mesh_matrix(&self, matrix:usize) {
+        if let Some(u) = self.shader_class.uniform(UniformId::MeshMatrix) {
+            Backend::uniform_matrix4(u, &self.instantiable.render_recipe.matrices[matrix]);
+        }
+    }
+
+    //mp draw_primitive
+    /// Draw a primitive, returning the number of triangles drawn; the
+    /// program must be in use, with the matrices set and the VAO
+    /// for the primitive bound
+    pub fn draw_primitive(&self, primitive:usize) -> usize {
+        let p = self.primitive(primitive);
+        let byte_offset = p.byte_offset();
+        use model3d::PrimitiveType::*;
+        let gl_type = match p.primitive_type() {
+            Points => gl::POINTS,
+            Lines => gl::LINES,
+            LineLoop => gl::LINE_LOOP,
+            LineStrip => gl::LINE_STRIP,
+            Triangles => gl::TRIANGLES,
+            TriangleFan => gl::TRIANGLE_FAN,
+            TriangleStrip => gl::TRIANGLE_STRIP,
+        };
+        Backend::draw_elements( gl_type,
+                                p.index_count() as i32,
+                                gl::UNSIGNED_BYTE, // index_type,
+                                byte_offset );
+        let index_count = p.index_count() as usize;
+        match gl_type {
+            gl::TRIANGLES => index_count / 3,
+            gl::TRIANGLE_FAN | gl::TRIANGLE_STRIP => index_count.saturating_sub(2),
+            _ => 0,
         }
     }
 
     //zz All done
 }
 
+//tp OwnedShaderInstantiable
+/// A [ShaderInstantiable] that shares ownership of its shader class and
+/// [model3d::Instantiable], rather than borrowing them
+pub type OwnedShaderInstantiable = ShaderInstantiable<'static>;
+
+//ip OwnedShaderInstantiable
+impl ShaderInstantiable<'static> {
+    //fp new_owned
+    /// Create a new [OwnedShaderInstantiable], holding [Rc]s of the
+    /// shader class and instantiable, with the same handling of
+    /// missing attributes as [ShaderInstantiable::new]
+    pub fn new_owned(shader_class:Rc<dyn ShaderClass>,
+                     instantiable:Rc<model3d::Instantiable<Renderable>>,
+                     missing:&MissingAttributes) -> Result<(Self, AttributeReport), String> {
+        Self::create(Handle::Shared(shader_class), Handle::Shared(instantiable), missing)
+    }
+
+    //mp variant_owned
+    /// Create an [OwnedShaderInstantiable] for the same instantiable
+    /// with another shader class, as [ShaderInstantiable::variant]
+    pub fn variant_owned(&self, shader_class:Rc<dyn ShaderClass>) -> Result<(Self, AttributeReport), String> {
+        let (mut s, report) = Self::create(Handle::Shared(shader_class), self.instantiable.clone(), &MissingAttributes::new())?;
+        s.set_name(&self.name);
+        Ok((s, report))
+    }
+}
+
@@ -0,0 +1,702 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    shader_library.rs
+@brief   Built-in programs with their attributes and uniforms registered
+ */
+
+//a Documentation
+
+/*!
+
+A [BuiltinShader] is compiled into a [GlProgram] with
+[BuiltinShader::compile], with its attributes and uniforms already
+added for the crate's conventions:
+
+* attributes `aPosition`, `aNormal`, `aTangent`, `aTexCoords0`,
+  `aColor`, `aJoints` and `aWeights` for the [VertexAttr]s used
+
+* `uViewMatrix` and `uProjectionMatrix` (set by
+  [crate::Camera::upload]), and `uModelMatrix` and `uMeshMatrix`
+
+* the material factors ([UniformId::BaseColorFactor] and so on) and
+  texture samplers ([UniformId::Texture]) of a [crate::Material]
+
+* the [UniformId::Lights] block of [crate::Lights] for the lit
+  programs
+
+The depth-only program has a single `uViewProjectionMatrix`
+([UniformId::ViewProjectionMatrix]) in place of the view and
+projection matrices, which is given the view-projection matrix by
+[crate::Camera::upload] or the light matrix by
+[crate::ShadowMap::render]. With [ShaderOptions::with_light_distance]
+it writes the distance from a point light (divided by its range, in
+[UniformId::ShadowLight]) as the depth, for point light shadow maps.
+
+[ShaderOptions] select variants: skinning (with `uBonesMatrices`, the
+[UniformId::BoneMatrices] that the application sets, and the joints
+and weights attributes), tangent-space normal mapping, and for the lit
+programs the [crate::Lights] they are used with (whose maximum count
+and clustered mode must match) or per-instance light selection.
+
+The PBR program may also be lit by a [crate::Environment]
+([ShaderOptions::with_environment]), with the split-sum approximation
+of its irradiance, prefiltered and BRDF lookup textures
+([UniformId::Texture] of their slots) and [UniformId::Environment];
+these must be set with [crate::Environment::upload].
+
+Lighting is computed in linear space with no tone mapping, with a
+small constant ambient term if there is no environment; shadows are
+not applied.
+
+!*/
+
+//a Imports
+use model3d::VertexAttr;
+
+use crate::{Backend, GlBackend};
+use crate::{GlProgram, Lights, TextureSlot, UniformId};
+
+//a Constants
+//ci DEFAULT_MAX_LIGHTS
+/// The maximum number of lights of lit programs if no [Lights] are
+/// given
+const DEFAULT_MAX_LIGHTS : usize = 8;
+
+//ci VERTEX_SHADER
+/// The vertex shader of all the programs, configured by defines
+const VERTEX_SHADER : &str = "
+in vec3 aPosition;
+#ifdef HAS_NORMAL
+in vec3 aNormal;
+out vec3 vNormal;
+#endif
+#ifdef NORMAL_MAP
+in vec4 aTangent;
+out vec4 vTangent;
+#endif
+#ifdef HAS_TEXCOORDS
+in vec2 aTexCoords0;
+out vec2 vTexCoords;
+#endif
+#ifdef HAS_COLOR
+in vec4 aColor;
+out vec4 vColor;
+#endif
+#ifdef SKINNING
+in vec4 aJoints;
+in vec4 aWeights;
+uniform mat4 uBonesMatrices[MAX_BONES];
+#endif
+#if defined(LIT) || defined(LIGHT_DISTANCE)
+out vec3 vWorld;
+#endif
+#ifdef LIT
+out vec3 vView;
+#endif
+#ifdef LIGHTS_CLUSTERED
+out float vDepth;
+#endif
+#ifdef HAS_PROJECTION
+uniform mat4 uViewMatrix;
+uniform mat4 uProjectionMatrix;
+#else
+uniform mat4 uViewProjectionMatrix;
+#endif
+uniform mat4 uModelMatrix;
+uniform mat4 uMeshMatrix;
+void main() {
+    mat4 model = uModelMatrix * uMeshMatrix;
+#ifdef SKINNING
+    model = model * (aWeights.x * uBonesMatrices[int(aJoints.x)] +
+                     aWeights.y * uBonesMatrices[int(aJoints.y)] +
+                     aWeights.z * uBonesMatrices[int(aJoints.z)] +
+                     aWeights.w * uBonesMatrices[int(aJoints.w)]);
+#endif
+    vec4 world = model * vec4(aPosition, 1.0);
+#ifdef HAS_NORMAL
+    vNormal = transpose(inverse(mat3(model))) * aNormal;
+#endif
+#ifdef NORMAL_MAP
+    vTangent = vec4(mat3(model) * aTangent.xyz, aTangent.w);
+#endif
+#ifdef HAS_TEXCOORDS
+    vTexCoords = aTexCoords0;
+#endif
+#ifdef HAS_COLOR
+    vColor = aColor;
+#endif
+#if defined(LIT) || defined(LIGHT_DISTANCE)
+    vWorld = world.xyz;
+#endif
+#ifdef LIT
+    vView = inverse(uViewMatrix)[3].xyz - world.xyz;
+#endif
+#ifdef LIGHTS_CLUSTERED
+    vDepth = -(uViewMatrix * world).z;
+#endif
+#ifdef HAS_PROJECTION
+    gl_Position = uProjectionMatrix * uViewMatrix * world;
+#else
+    gl_Position = uViewProjectionMatrix * world;
+#endif
+}
+";
+
+//ci NORMAL_GLSL
+/// Fragment shader function giving the (normal mapped) surface normal
+const NORMAL_GLSL : &str = "
+in vec3 vNormal;
+#ifdef NORMAL_MAP
+in vec4 vTangent;
+in vec2 vTexCoords;
+uniform sampler2D uNormalTexture;
+#endif
+vec3 surfaceNormal() {
+    vec3 n = normalize(vNormal);
+#ifdef NORMAL_MAP
+    vec3 t = normalize(vTangent.xyz - n * dot(n, vTangent.xyz));
+    vec3 b = cross(n, t) * vTangent.w;
+    vec3 m = texture(uNormalTexture, vTexCoords).xyz * 2.0 - 1.0;
+    n = normalize(mat3(t, b, n) * m);
+#endif
+    return n;
+}
+";
+
+//ci UNLIT_FRAGMENT_SHADER
+/// Fragment shader of the unlit program
+const UNLIT_FRAGMENT_SHADER : &str = "
+in vec2 vTexCoords;
+uniform vec4 uBaseColor;
+uniform sampler2D uBaseColorTexture;
+out vec4 fragColor;
+void main() {
+    fragColor = uBaseColor * texture(uBaseColorTexture, vTexCoords);
+}
+";
+
+//ci VERTEX_COLOR_FRAGMENT_SHADER
+/// Fragment shader of the vertex colour program
+const VERTEX_COLOR_FRAGMENT_SHADER : &str = "
+in vec4 vColor;
+out vec4 fragColor;
+void main() {
+    fragColor = vColor;
+}
+";
+
+//ci NORMALS_FRAGMENT_SHADER
+/// Fragment shader of the normals visualisation program, after
+/// [NORMAL_GLSL]
+const NORMALS_FRAGMENT_SHADER : &str = "
+out vec4 fragColor;
+void main() {
+    fragColor = vec4(surfaceNormal() * 0.5 + 0.5, 1.0);
+}
+";
+
+//ci DEPTH_FRAGMENT_SHADER
+/// Fragment shader of the depth-only program, optionally writing the
+/// distance from a point light divided by its range
+const DEPTH_FRAGMENT_SHADER : &str = "
+#ifdef LIGHT_DISTANCE
+in vec3 vWorld;
+uniform vec4 uLight;
+#endif
+void main() {
+#ifdef LIGHT_DISTANCE
+    gl_FragDepth = length(vWorld - uLight.xyz) / uLight.w;
+#endif
+}
+";
+
+//ci LIT_GLSL
+/// Fragment shader inputs, surface and light functions of the lit
+/// programs, after [NORMAL_GLSL] and the [Lights] declarations
+const LIT_GLSL : &str = "
+#define PI 3.14159265
+in vec3 vWorld;
+in vec3 vView;
+#ifndef NORMAL_MAP
+in vec2 vTexCoords;
+#endif
+#ifdef LIGHTS_CLUSTERED
+in float vDepth;
+#endif
+#ifdef INSTANCE_LIGHTS
+uniform ivec4 uInstanceLights;
+#endif
+uniform vec4 uBaseColor;
+uniform vec4 uEmissive;
+uniform vec4 uMetallicRoughness;
+uniform sampler2D uBaseColorTexture;
+uniform sampler2D uEmissiveTexture;
+out vec4 fragColor;
+struct Surface {
+    vec3 albedo;
+    float alpha;
+    float metallic;
+    float roughness;
+    float occlusion;
+    vec3 emissive;
+};
+vec3 incidentLight(int i, out vec3 l) {
+    Light light = uLights[i];
+    int kind = int(light.direction_kind.w);
+    vec3 radiance = light.color_intensity.rgb;
+    if (kind == LIGHT_DIRECTIONAL) {
+        l = -light.direction_kind.xyz;
+        return radiance;
+    }
+    vec3 d = light.position_range.xyz - vWorld;
+    float dist = length(d);
+    l = d / dist;
+    float window = clamp(1.0 - pow(dist / light.position_range.w, 4.0), 0.0, 1.0);
+    radiance *= window * window / max(dist * dist, 0.0001);
+    if (kind == LIGHT_SPOT) {
+        radiance *= smoothstep(light.cone_shadow.y, light.cone_shadow.x, dot(-l, light.direction_kind.xyz));
+    }
+    return radiance;
+}
+";
+
+//ci BLINN_PHONG_GLSL
+/// Surface and shading functions of the Blinn-Phong program
+const BLINN_PHONG_GLSL : &str = "
+Surface surface() {
+    vec4 base = uBaseColor * texture(uBaseColorTexture, vTexCoords);
+    Surface s;
+    s.albedo = base.rgb;
+    s.alpha = base.a;
+    s.metallic = clamp(uMetallicRoughness.x, 0.0, 1.0);
+    s.roughness = clamp(uMetallicRoughness.y, 0.04, 1.0);
+    s.occlusion = 1.0;
+    s.emissive = uEmissive.rgb * texture(uEmissiveTexture, vTexCoords).rgb;
+    return s;
+}
+vec3 shade(int i, vec3 n, vec3 v, Surface s) {
+    vec3 l;
+    vec3 radiance = incidentLight(i, l);
+    float ndl = dot(n, l);
+    if (ndl <= 0.0) {
+        return vec3(0.0);
+    }
+    vec3 h = normalize(l + v);
+    float shininess = max(2.0 / pow(s.roughness, 4.0) - 2.0, 1.0);
+    vec3 specular = mix(vec3(0.04), s.albedo, s.metallic) * pow(max(dot(n, h), 0.0), shininess);
+    vec3 diffuse = s.albedo * (1.0 - s.metallic) * ndl;
+    return radiance * (diffuse + specular);
+}
+vec3 ambient(vec3 n, vec3 v, Surface s) {
+    return 0.03 * s.albedo * s.occlusion;
+}
+";
+
+//ci PBR_GLSL
+/// Surface and shading functions of the glTF metallic-roughness
+/// program (GGX distribution, Smith-Schlick geometry and Schlick
+/// Fresnel)
+const PBR_GLSL : &str = "
+uniform sampler2D uMetallicRoughnessTexture;
+uniform sampler2D uOcclusionTexture;
+Surface surface() {
+    vec4 base = uBaseColor * texture(uBaseColorTexture, vTexCoords);
+    vec4 mr = texture(uMetallicRoughnessTexture, vTexCoords);
+    Surface s;
+    s.albedo = base.rgb;
+    s.alpha = base.a;
+    s.metallic = clamp(uMetallicRoughness.x * mr.b, 0.0, 1.0);
+    s.roughness = clamp(uMetallicRoughness.y * mr.g, 0.04, 1.0);
+    s.occlusion = texture(uOcclusionTexture, vTexCoords).r;
+    s.emissive = uEmissive.rgb * texture(uEmissiveTexture, vTexCoords).rgb;
+    return s;
+}
+vec3 shade(int i, vec3 n, vec3 v, Surface s) {
+    vec3 l;
+    vec3 radiance = incidentLight(i, l);
+    float ndl = dot(n, l);
+    if (ndl <= 0.0) {
+        return vec3(0.0);
+    }
+    vec3 h = normalize(l + v);
+    float ndv = max(dot(n, v), 0.0001);
+    float ndh = max(dot(n, h), 0.0);
+    float a2 = pow(s.roughness, 4.0);
+    float d = ndh * ndh * (a2 - 1.0) + 1.0;
+    float distribution = a2 / (PI * d * d);
+    float k = (s.roughness + 1.0) * (s.roughness + 1.0) / 8.0;
+    float geometry = ndv / (ndv * (1.0 - k) + k) * ndl / (ndl * (1.0 - k) + k);
+    vec3 f0 = mix(vec3(0.04), s.albedo, s.metallic);
+    vec3 fresnel = f0 + (1.0 - f0) * pow(1.0 - max(dot(v, h), 0.0), 5.0);
+    vec3 specular = distribution * geometry * fresnel / (4.0 * ndv * ndl);
+    vec3 diffuse = (1.0 - fresnel) * (1.0 - s.metallic) * s.albedo / PI;
+    return (diffuse + specular) * radiance * ndl;
+}
+#ifdef ENVIRONMENT
+uniform samplerCube uIrradianceMap;
+uniform samplerCube uPrefilteredMap;
+uniform sampler2D uBrdfLut;
+uniform vec4 uEnvironment;
+vec3 ambient(vec3 n, vec3 v, Surface s) {
+    float ndv = max(dot(n, v), 0.0);
+    vec3 f0 = mix(vec3(0.04), s.albedo, s.metallic);
+    vec3 fresnel = f0 + (max(vec3(1.0 - s.roughness), f0) - f0) * pow(1.0 - ndv, 5.0);
+    vec3 diffuse = (1.0 - fresnel) * (1.0 - s.metallic) * s.albedo * texture(uIrradianceMap, n).rgb;
+    vec3 prefiltered = textureLod(uPrefilteredMap, reflect(-v, n), s.roughness * uEnvironment.x).rgb;
+    vec2 brdf = texture(uBrdfLut, vec2(ndv, s.roughness)).rg;
+    vec3 specular = prefiltered * (f0 * brdf.x + brdf.y);
+    return (diffuse + specular) * s.occlusion * uEnvironment.y;
+}
+#else
+vec3 ambient(vec3 n, vec3 v, Surface s) {
+    return 0.03 * s.albedo * s.occlusion;
+}
+#endif
+";
+
+//ci LIT_MAIN
+/// The main function of the lit programs, summing the lights
+const LIT_MAIN : &str = "
+void main() {
+    Surface s = surface();
+    vec3 n = surfaceNormal();
+    vec3 v = normalize(vView);
+    vec3 color = vec3(0.0);
+#if defined(LIGHTS_CLUSTERED)
+    ivec2 cluster = lightCluster(vDepth);
+    for (int i = 0; i < cluster.y; i++) {
+        color += shade(clusterLight(cluster.x + i), n, v, s);
+    }
+#elif defined(INSTANCE_LIGHTS)
+    for (int i = 0; i < 4; i++) {
+        if (uInstanceLights[i] >= 0) {
+            color += shade(uInstanceLights[i], n, v, s);
+        }
+    }
+#else
+    for (int i = 0; i < uLightCount.x; i++) {
+        color += shade(i, n, v, s);
+    }
+#endif
+    color += ambient(n, v, s) + s.emissive;
+    fragColor = vec4(color, s.alpha);
+}
+";
+
+//a ShaderOptions
+//tp ShaderOptions
+/// The variant of a [BuiltinShader] to compile
+#[derive(Debug, Clone, Default)]
+pub struct ShaderOptions {
+    /// The number of bone matrices for skinning (0 for none)
+    max_bones : usize,
+    /// True for tangent-space normal mapping
+    normal_map : bool,
+    /// The GLSL declarations of the lights, and whether they are
+    /// clustered
+    lights : Option<(String, bool)>,
+    /// True to shade with only the [UniformId::InstanceLights]
+    instance_lights : bool,
+    /// True for image-based lighting by a [crate::Environment]
+    environment : bool,
+    /// True to write the distance from a point light as the depth
+    light_distance : bool,
+}
+
+//ip ShaderOptions
+impl ShaderOptions {
+    //fp new
+    /// Create options for the basic variant
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //cp with_skinning
+    /// Skin the vertices with their joints and weights attributes
+    /// and up to a number of bone matrices
+    pub fn with_skinning(mut self, max_bones:usize) -> Self {
+        self.max_bones = max_bones;
+        self
+    }
+
+    //cp with_normal_map
+    /// Perturb the normals with a tangent-space normal map (the
+    /// [TextureSlot::Normal] texture), requiring the tangent
+    /// attribute
+    pub fn with_normal_map(mut self) -> Self {
+        self.normal_map = true;
+        self
+    }
+
+    //cp with_lights
+    /// Declare the lights uniform block for a [Lights] (for its
+    /// maximum count and clustered mode), rather than for up to 8
+    /// unclustered lights
+    pub fn with_lights(mut self, lights:&Lights) -> Self {
+        self.lights = Some((lights.glsl(), lights.is_clustered()));
+        self
+    }
+
+    //cp with_instance_lights
+    /// Shade with only the lights of [UniformId::InstanceLights] (as
+    /// set by [Lights::set_instance_lights]) rather than all of them
+    pub fn with_instance_lights(mut self) -> Self {
+        self.instance_lights = true;
+        self
+    }
+
+    //cp with_environment
+    /// Light the PBR program with the image-based lighting of a
+    /// [crate::Environment] rather than a constant ambient term
+    pub fn with_environment(mut self) -> Self {
+        self.environment = true;
+        self
+    }
+
+    //cp with_light_distance
+    /// Write the distance from the point light of
+    /// [UniformId::ShadowLight] (divided by its range) as the depth of
+    /// the depth-only program, for point light shadow maps
+    pub fn with_light_distance(mut self) -> Self {
+        self.light_distance = true;
+        self
+    }
+}
+
+//a BuiltinShader
+//tp BuiltinShader
+/// A program of the built-in shader library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinShader {
+    /// Unlit - the base colour factor times the base colour texture
+    Unlit,
+    /// Unlit - the vertex colour
+    VertexColor,
+    /// The world-space (normal mapped) normal as a colour
+    Normals,
+    /// Blinn-Phong lighting, with the specular exponent from the
+    /// roughness factor
+    BlinnPhong,
+    /// glTF-compatible metallic-roughness physically based lighting
+    Pbr,
+    /// Depth only (for depth pre-passes and shadow maps)
+    DepthOnly,
+}
+
+//ip Display for BuiltinShader
+impl std::fmt::Display for BuiltinShader {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Unlit => write!(f, "unlit"),
+            Self::VertexColor => write!(f, "vertex colour"),
+            Self::Normals => write!(f, "normals"),
+            Self::BlinnPhong => write!(f, "Blinn-Phong"),
+            Self::Pbr => write!(f, "PBR"),
+            Self::DepthOnly => write!(f, "depth only"),
+        }
+    }
+}
+
+//ip BuiltinShader
+impl BuiltinShader {
+    //cp ALL
+    /// All the built-in shaders
+    pub const ALL : [Self; 6] = [Self::Unlit, Self::VertexColor, Self::Normals,
+                                 Self::BlinnPhong, Self::Pbr, Self::DepthOnly];
+
+    //mi is_lit
+    /// Return true for the programs that are lit
+    fn is_lit(&self) -> bool {
+        matches!(self, Self::BlinnPhong | Self::Pbr)
+    }
+
+    //mi has_normal
+    /// Return true for the programs that use the vertex normals (and
+    /// hence may be normal mapped)
+    fn has_normal(&self) -> bool {
+        matches!(self, Self::Normals | Self::BlinnPhong | Self::Pbr)
+    }
+
+    //mi check
+    /// Check that the options are valid for the shader
+    fn check(&self, options:&ShaderOptions) -> Result<(), String> {
+        if options.normal_map && !self.has_normal() {
+            return Err(format!("The {} shader cannot be normal mapped", self));
+        }
+        if options.instance_lights && options.lights.as_ref().is_some_and(|(_, clustered)| *clustered) {
+            return Err("Per-instance lights cannot be used with clustered lights".to_string());
+        }
+        if options.environment && *self != Self::Pbr {
+            return Err(format!("The {} shader cannot be lit by an environment", self));
+        }
+        if options.light_distance && *self != Self::DepthOnly {
+            return Err(format!("The {} shader cannot write light distances", self));
+        }
+        Ok(())
+    }
+
+    //mp sources
+    /// Get the vertex and fragment shader sources of a variant
+    pub fn sources(&self, options:&ShaderOptions) -> Result<(String, String), String> {
+        self.check(options)?;
+        let mut defines = String::from("#version 330 core\n");
+        let mut define = |name:&str| {
+            defines.push_str("#define ");
+            defines.push_str(name);
+            defines.push('\n');
+        };
+        if *self != Self::DepthOnly {
+            define("HAS_PROJECTION");
+        }
+        if self.has_normal() {
+            define("HAS_NORMAL");
+        }
+        if options.normal_map {
+            define("NORMAL_MAP");
+        }
+        if options.normal_map || matches!(self, Self::Unlit | Self::BlinnPhong | Self::Pbr) {
+            define("HAS_TEXCOORDS");
+        }
+        if *self == Self::VertexColor {
+            define("HAS_COLOR");
+        }
+        if options.light_distance {
+            define("LIGHT_DISTANCE");
+        }
+        if options.max_bones > 0 {
+            define("SKINNING");
+            define(&format!("MAX_BONES {}", options.max_bones));
+        }
+        let lights = {
+            if self.is_lit() {
+                define("LIT");
+                let (glsl, clustered) = options.lights.clone()
+                    .unwrap_or_else(|| (Lights::new(DEFAULT_MAX_LIGHTS).glsl(), false));
+                if clustered {
+                    define("LIGHTS_CLUSTERED");
+                } else if options.instance_lights {
+                    define("INSTANCE_LIGHTS");
+                }
+                if options.environment {
+                    define("ENVIRONMENT");
+                }
+                glsl
+            } else {
+                String::new()
+            }
+        };
+        let vertex = format!("{}{}", defines, VERTEX_SHADER);
+        let fragment = {
+            match self {
+                Self::Unlit => format!("{}{}", defines, UNLIT_FRAGMENT_SHADER),
+                Self::VertexColor => format!("{}{}", defines, VERTEX_COLOR_FRAGMENT_SHADER),
+                Self::Normals => format!("{}{}{}", defines, NORMAL_GLSL, NORMALS_FRAGMENT_SHADER),
+                Self::BlinnPhong => format!("{}{}{}{}{}{}", defines, lights, NORMAL_GLSL, LIT_GLSL, BLINN_PHONG_GLSL, LIT_MAIN),
+                Self::Pbr => format!("{}{}{}{}{}{}", defines, lights, NORMAL_GLSL, LIT_GLSL, PBR_GLSL, LIT_MAIN),
+                Self::DepthOnly => format!("{}{}", defines, DEPTH_FRAGMENT_SHADER),
+            }
+        };
+        Ok((vertex, fragment))
+    }
+
+    //mp compile
+    /// Compile a variant of the shader, adding its attributes and
+    /// uniforms
+    ///
+    /// Attributes (other than `aPosition`), uniforms and the lights
+    /// block that the shader compiler optimized out of the variant
+    /// are not added, as they are not used by it
+    pub fn compile(&self, options:&ShaderOptions) -> Result<GlProgram, String> {
+        let (vertex, fragment) = self.sources(options)?;
+        let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, &vertex),
+                                                       (gl::FRAGMENT_SHADER, &fragment)])?;
+        let mut attributes = vec![("aPosition", VertexAttr::Position)];
+        let mut uniforms = vec![("uModelMatrix", UniformId::ModelMatrix),
+                                ("uMeshMatrix", UniformId::MeshMatrix)];
+        if *self == Self::DepthOnly {
+            uniforms.push(("uViewProjectionMatrix", UniformId::ViewProjectionMatrix));
+        } else {
+            uniforms.push(("uViewMatrix", UniformId::ViewMatrix));
+            uniforms.push(("uProjectionMatrix", UniformId::ProjectionMatrix));
+        }
+        if options.light_distance {
+            uniforms.push(("uLight", UniformId::ShadowLight(0)));
+        }
+        if self.has_normal() {
+            attributes.push(("aNormal", VertexAttr::Normal));
+        }
+        if options.normal_map {
+            attributes.push(("aTangent", VertexAttr::Tangent));
+            attributes.push(("aTexCoords0", VertexAttr::TexCoords0));
+            uniforms.push(("uNormalTexture", UniformId::Texture(TextureSlot::Normal)));
+        } else if matches!(self, Self::Unlit | Self::BlinnPhong | Self::Pbr) {
+            attributes.push(("aTexCoords0", VertexAttr::TexCoords0));
+        }
+        if options.max_bones > 0 {
+            attributes.push(("aJoints", VertexAttr::Joints));
+            attributes.push(("aWeights", VertexAttr::Weights));
+            uniforms.push(("uBonesMatrices", UniformId::BoneMatrices));
+        }
+        match self {
+            Self::Unlit => {
+                uniforms.push(("uBaseColor", UniformId::BaseColorFactor));
+                uniforms.push(("uBaseColorTexture", UniformId::Texture(TextureSlot::BaseColor)));
+            }
+            Self::VertexColor => {
+                attributes.push(("aColor", VertexAttr::Color));
+            }
+            Self::BlinnPhong | Self::Pbr => {
+                uniforms.push(("uBaseColor", UniformId::BaseColorFactor));
+                uniforms.push(("uEmissive", UniformId::EmissiveFactor));
+                uniforms.push(("uMetallicRoughness", UniformId::MetallicRoughnessFactor));
+                uniforms.push(("uBaseColorTexture", UniformId::Texture(TextureSlot::BaseColor)));
+                uniforms.push(("uEmissiveTexture", UniformId::Texture(TextureSlot::Emissive)));
+                if *self == Self::Pbr {
+                    uniforms.push(("uMetallicRoughnessTexture", UniformId::Texture(TextureSlot::MetallicRoughness)));
+                    uniforms.push(("uOcclusionTexture", UniformId::Texture(TextureSlot::Occlusion)));
+                }
+                if options.environment {
+                    uniforms.push(("uIrradianceMap", UniformId::Texture(TextureSlot::Irradiance)));
+                    uniforms.push(("uPrefilteredMap", UniformId::Texture(TextureSlot::Prefiltered)));
+                    uniforms.push(("uBrdfLut", UniformId::Texture(TextureSlot::BrdfLut)));
+                    uniforms.push(("uEnvironment", UniformId::Environment));
+                }
+                if options.lights.as_ref().is_some_and(|(_, clustered)| *clustered) {
+                    uniforms.push(("uLightClusters", UniformId::LightClusters));
+                } else if options.instance_lights {
+                    uniforms.push(("uInstanceLights", UniformId::InstanceLights));
+                }
+                if Backend::uniform_block_index(program.id(), "Lights").is_some() {
+                    program.add_uniform_block_name("Lights", UniformId::Lights)?;
+                }
+            }
+            _ => (),
+        }
+        for (name, vertex_attr) in attributes {
+            if vertex_attr == VertexAttr::Position || Backend::attrib_location(program.id(), name).is_some() {
+                program.add_attr_name(name, vertex_attr)?;
+            }
+        }
+        for (name, uniform_id) in uniforms {
+            if Backend::uniform_location(program.id(), name).is_some() {
+                program.add_uniform_name(name, uniform_id)?;
+            }
+        }
+        program.set_name(&format!("builtin {}", self));
+        Ok(program)
+    }
+
+    //zz All done
+}
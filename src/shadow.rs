@@ -0,0 +1,463 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    shadow.rs
+@brief   Shadow maps rendered with depth-only programs
+ */
+
+//a Documentation
+
+/*!
+
+A [ShadowMap] is a depth texture rendered from the point of view of a
+light, with a depth-only [Framebuffer] for each of its images:
+
+* a directional light has cascaded shadow maps - a 2D array of up to
+  four layers, each covering a slice of the camera's view frustum
+  (split between uniform and logarithmic distribution) with an
+  orthographic projection, snapped to whole texels so that shadows do
+  not shimmer as the camera moves
+
+* a spot light has a single layer 2D array with a perspective
+  projection covering its cone
+
+* a point light has a cube map, storing the distance from the light
+  (divided by its range) rather than the projected depth
+
+The light matrices are set with [ShadowMap::update_directional],
+[ShadowMap::update_spot] or [ShadowMap::update_point] each frame, and
+then the shadow casters drawn into the map with [ShadowMap::render].
+Casters are [ShaderInstantiable]s of a depth-only program
+([ShadowMap::depth_program]), created from those of the main pass
+with [ShaderInstantiable::variant]; those outside the light's frustum
+are culled.
+
+For the main pass, [ShadowMap::upload] binds the depth texture (with
+a comparison [Sampler], for `sampler2DArrayShadow` or
+`samplerCubeShadow`) to a texture unit reserved for it, and sets the
+[UniformId::ShadowMap], [UniformId::ShadowMatrices],
+[UniformId::ShadowSplits] and [UniformId::ShadowLight] uniforms of a
+program for a shadow map index.
+
+The shadow map is not recreated after context loss.
+
+!*/
+
+//a Imports
+use std::rc::Rc;
+
+use gl::types::*;
+
+use geo_nd::vector::{self, normalize};
+
+use crate::{Renderable, RenderContext, ShaderClass, ShaderInstantiable, UniformId};
+use crate::{Texture, TextureFormat, TextureImage, CubeFace, Framebuffer, GlProgram};
+use crate::{BuiltinShader, ShaderOptions};
+use crate::{Sampler, SamplerDesc, Filter, MipmapMode, WrapMode, CompareFunction};
+use crate::{Camera, CullStats, Frustum, Mat4};
+use crate::utils::{mat4_mul, mat4_inverse, mat4_transform};
+use crate::{Backend, GlBackend};
+use crate::DebugGroup;
+
+//a Constants
+//ci MAX_CASCADES
+/// The maximum number of cascades of a directional light
+const MAX_CASCADES : usize = 4;
+
+//ci NEAR_FRACTION
+/// The near plane of point and spot light projections, as a fraction
+/// of their range
+const NEAR_FRACTION : f32 = 0.01;
+
+//ci BIAS
+/// Matrix mapping clip space (-1 to 1) to texture coordinates and
+/// depth (0 to 1)
+const BIAS : Mat4 = [0.5, 0., 0., 0.,
+                     0., 0.5, 0., 0.,
+                     0., 0., 0.5, 0.,
+                     0.5, 0.5, 0.5, 1.];
+
+//a ShadowKind
+//tp ShadowKind
+/// The kind of light a [ShadowMap] is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowKind {
+    /// A directional light, with cascades in a 2D array
+    Directional,
+    /// A spot light, with a single layer 2D array
+    Spot,
+    /// A point light, with a cube map of distances
+    Point,
+}
+
+//ip Display for ShadowKind
+impl std::fmt::Display for ShadowKind {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Directional => write!(f, "directional"),
+            Self::Spot => write!(f, "spot"),
+            Self::Point => write!(f, "point"),
+        }
+    }
+}
+
+//a ShadowMap
+//tp ShadowMap
+/// A shadow map for a light, with a depth-only framebuffer for each
+/// cascade (or cube face), and the light matrices
+pub struct ShadowMap {
+    /// The kind of light
+    kind : ShadowKind,
+    /// The depth texture - a 2D array, or a cube map for a point light
+    texture : Texture,
+    /// The framebuffers rendering to the layers (or faces) of the
+    /// texture
+    framebuffers : Vec<Framebuffer>,
+    /// The comparison sampler for the texture
+    sampler : Rc<Sampler>,
+    /// The texture unit reserved for the texture
+    unit : usize,
+    /// The light view-projection matrices, one per framebuffer
+    matrices : Vec<Mat4>,
+    /// The view depths of the far ends of the cascades
+    splits : [f32; 4],
+    /// The light position and range, or direction
+    light : [f32; 4],
+    /// Polygon offset factor and units for rendering
+    bias : (f32, f32),
+}
+
+//ip ShadowMap
+impl ShadowMap {
+    //fi create
+    /// Create a shadow map with a depth texture, its framebuffers, and
+    /// a reserved texture unit
+    fn create(render_context:&mut RenderContext, kind:ShadowKind, texture:Texture) -> Result<Self, String> {
+        let mut framebuffers = Vec::new();
+        if kind == ShadowKind::Point {
+            for face in CubeFace::ALL {
+                framebuffers.push(Framebuffer::of_depth_texture(&texture, TextureImage::Face(face))?);
+            }
+        } else {
+            for layer in 0..texture.layers() {
+                framebuffers.push(Framebuffer::of_depth_texture(&texture, TextureImage::Layer(layer))?);
+            }
+        }
+        texture.set_label(&format!("{} shadow map", kind));
+        let desc = SamplerDesc::new(Filter::Linear, Filter::Linear)
+            .with_mipmap(MipmapMode::None)
+            .with_wrap(WrapMode::ClampToEdge)
+            .with_compare(CompareFunction::LessEqual);
+        let sampler = render_context.sampler(&desc);
+        let unit = render_context.reserve_texture_unit()?;
+        let matrices = vec![BIAS; framebuffers.len()];
+        Ok(Self { kind, texture, framebuffers, sampler, unit, matrices,
+                  splits:[0.; 4], light:[0.; 4], bias:(2.0, 4.0) })
+    }
+
+    //fp directional
+    /// Create a cascaded shadow map for a directional light, with a
+    /// number of cascades (1 to 4) of a size in texels
+    pub fn directional(render_context:&mut RenderContext, size:usize, cascades:usize) -> Result<Self, String> {
+        if cascades == 0 || cascades > MAX_CASCADES {
+            return Err(format!("A directional shadow map must have 1 to {} cascades, not {}", MAX_CASCADES, cascades));
+        }
+        let texture = Texture::new_array(size, size, cascades, TextureFormat::Depth24, None)?;
+        Self::create(render_context, ShadowKind::Directional, texture)
+    }
+
+    //fp spot
+    /// Create a shadow map for a spot light of a size in texels
+    pub fn spot(render_context:&mut RenderContext, size:usize) -> Result<Self, String> {
+        let texture = Texture::new_array(size, size, 1, TextureFormat::Depth24, None)?;
+        Self::create(render_context, ShadowKind::Spot, texture)
+    }
+
+    //fp point
+    /// Create a cube shadow map for a point light with faces of a
+    /// size in texels
+    pub fn point(render_context:&mut RenderContext, size:usize) -> Result<Self, String> {
+        let texture = Texture::new_cube_map(size, TextureFormat::Depth24, None)?;
+        Self::create(render_context, ShadowKind::Point, texture)
+    }
+
+    //fp depth_program
+    /// Compile the depth-only program for drawing the shadow casters
+    /// of a kind of light - the [BuiltinShader::DepthOnly] program,
+    /// writing the light distance for point lights
+    ///
+    /// It has a `Position` attribute and the `ViewProjectionMatrix`
+    /// (given the light matrix), `ModelMatrix` and `MeshMatrix`
+    /// uniforms; for point lights it also has `ShadowLight(0)`
+    pub fn depth_program(kind:ShadowKind) -> Result<GlProgram, String> {
+        let options = {
+            if kind == ShadowKind::Point { ShaderOptions::new().with_light_distance() } else { ShaderOptions::new() }
+        };
+        let program = BuiltinShader::DepthOnly.compile(&options)?;
+        program.set_name(&format!("{} shadow depth", kind));
+        Ok(program)
+    }
+
+    //ap kind
+    /// Get the kind of light the shadow map is for
+    pub fn kind(&self) -> ShadowKind {
+        self.kind
+    }
+
+    //ap texture
+    /// Get the depth texture
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    //ap unit
+    /// Get the texture unit reserved for the depth texture
+    pub fn unit(&self) -> usize {
+        self.unit
+    }
+
+    //ap matrices
+    /// Get the light view-projection matrices, one per cascade (or
+    /// cube face)
+    pub fn matrices(&self) -> &[Mat4] {
+        &self.matrices
+    }
+
+    //ap splits
+    /// Get the view depths of the far ends of the cascades
+    pub fn splits(&self) -> [f32; 4] {
+        self.splits
+    }
+
+    //mp set_bias
+    /// Set the polygon offset factor and units used when rendering,
+    /// to avoid shadow acne
+    pub fn set_bias(&mut self, factor:f32, units:f32) {
+        self.bias = (factor, units);
+    }
+
+    //mp update_directional
+    /// Set the cascades of a directional light shining in a direction
+    /// to cover the view frustum of a camera up to a maximum
+    /// distance
+    ///
+    /// The splits between the cascades are a blend (by `lambda`, from
+    /// 0 to 1) of a uniform and a logarithmic distribution; casters up
+    /// to two cascade radii towards the light from a cascade are
+    /// included
+    pub fn update_directional(&mut self, camera:&Camera, direction:[f32; 3], max_distance:f32, lambda:f32) -> Result<(), String> {
+        if self.kind != ShadowKind::Directional {
+            return Err(format!("Cannot update a {} shadow map as directional", self.kind));
+        }
+        let inverse = mat4_inverse(camera.view_projection())
+            .ok_or_else(|| "Camera view-projection matrix is singular".to_string())?;
+        let (near, far) = Camera::near_far(camera.projection());
+        let shadow_far = far.min(max_distance);
+        let mut frustum_near = [[0.; 3]; 4];
+        let mut frustum_far = [[0.; 3]; 4];
+        for (i, (x, y)) in [(-1., -1.), (1., -1.), (-1., 1.), (1., 1.)].iter().enumerate() {
+            frustum_near[i] = mat4_transform(&inverse, [*x, *y, -1.]);
+            frustum_far[i] = mat4_transform(&inverse, [*x, *y, 1.]);
+        }
+        let direction = normalize(direction);
+        let up = if direction[1].abs() > 0.99 { [0., 0., 1.] } else { [0., 1., 0.] };
+        let rotation = Camera::look_at([0.; 3], direction, up);
+        let cascades = self.framebuffers.len();
+        let size = self.texture.width() as f32;
+        let mut slice_near = near;
+        self.splits = [shadow_far; 4];
+        for c in 0..cascades {
+            let fraction = (c + 1) as f32 / cascades as f32;
+            let uniform = near + (shadow_far - near) * fraction;
+            let log = near * (shadow_far / near).powf(fraction);
+            let slice_far = lambda * log + (1. - lambda) * uniform;
+            self.splits[c] = slice_far;
+
+            let mut corners = Vec::with_capacity(8);
+            for d in [slice_near, slice_far] {
+                let t = (d - near) / (far - near);
+                for i in 0..4 {
+                    let (n, f) = (frustum_near[i], frustum_far[i]);
+                    corners.push([n[0] + (f[0] - n[0]) * t, n[1] + (f[1] - n[1]) * t, n[2] + (f[2] - n[2]) * t]);
+                }
+            }
+            let mut center = [0.; 3];
+            for p in &corners {
+                for (c, x) in center.iter_mut().zip(p.iter()) {
+                    *c += x / 8.;
+                }
+            }
+            let radius = corners.iter()
+                .map(|p| vector::distance(p, &center))
+                .fold(0., f32::max);
+            let radius = (radius * 16.).ceil() / 16.;
+            let texel = 2. * radius / size;
+            let c_light = mat4_transform(&rotation, center);
+            let x = (c_light[0] / texel).floor() * texel;
+            let y = (c_light[1] / texel).floor() * texel;
+            let projection = Camera::orthographic(x - radius, x + radius, y - radius, y + radius,
+                                                  -c_light[2] - 3. * radius, -c_light[2] + radius);
+            self.matrices[c] = mat4_mul(&projection, &rotation);
+            slice_near = slice_far;
+        }
+        self.light = [direction[0], direction[1], direction[2], 0.];
+        Ok(())
+    }
+
+    //mp update_spot
+    /// Set the matrix of a spot light at a position shining in a
+    /// direction, with the half-angle (in radians) of its outer cone
+    /// and its range
+    pub fn update_spot(&mut self, position:[f32; 3], direction:[f32; 3], cone_angle:f32, range:f32) -> Result<(), String> {
+        if self.kind != ShadowKind::Spot {
+            return Err(format!("Cannot update a {} shadow map as a spot light", self.kind));
+        }
+        let target = [position[0] + direction[0], position[1] + direction[1], position[2] + direction[2]];
+        let direction = normalize(direction);
+        let up = if direction[1].abs() > 0.99 { [0., 0., 1.] } else { [0., 1., 0.] };
+        let view = Camera::look_at(position, target, up);
+        let projection = Camera::perspective(2. * cone_angle, 1., range * NEAR_FRACTION, range);
+        self.matrices[0] = mat4_mul(&projection, &view);
+        self.splits = [range; 4];
+        self.light = [position[0], position[1], position[2], range];
+        Ok(())
+    }
+
+    //mp update_point
+    /// Set the face matrices of a point light at a position with a
+    /// range
+    pub fn update_point(&mut self, position:[f32; 3], range:f32) -> Result<(), String> {
+        if self.kind != ShadowKind::Point {
+            return Err(format!("Cannot update a {} shadow map as a point light", self.kind));
+        }
+        let projection = Camera::perspective(std::f32::consts::FRAC_PI_2, 1., range * NEAR_FRACTION, range);
+        let faces : [([f32; 3], [f32; 3]); 6] = [([1., 0., 0.], [0., -1., 0.]),
+                                                  ([-1., 0., 0.], [0., -1., 0.]),
+                                                  ([0., 1., 0.], [0., 0., 1.]),
+                                                  ([0., -1., 0.], [0., 0., -1.]),
+                                                  ([0., 0., 1.], [0., -1., 0.]),
+                                                  ([0., 0., -1.], [0., -1., 0.])];
+        for (i, (axis, up)) in faces.iter().enumerate() {
+            let target = [position[0] + axis[0], position[1] + axis[1], position[2] + axis[2]];
+            let view = Camera::look_at(position, target, *up);
+            self.matrices[i] = mat4_mul(&projection, &view);
+        }
+        self.splits = [range; 4];
+        self.light = [position[0], position[1], position[2], range];
+        Ok(())
+    }
+
+    //mp render
+    /// Render the shadow casters (instances of [ShaderInstantiable]s
+    /// of the depth-only program) into each cascade (or face) of the
+    /// shadow map, culling those outside the light's frustum
+    ///
+    /// Depth testing and writing are enabled, and the polygon offset
+    /// set to the bias, while rendering; these (and whether
+    /// POLYGON_OFFSET_FILL is enabled) are restored afterwards. The
+    /// default framebuffer is bound afterwards; the viewport and the
+    /// program in use must be restored by the caller
+    pub fn render(&self, casters:&[(&ShaderInstantiable, &model3d::Instance<Renderable>)]) -> CullStats {
+        let _group = DebugGroup::new("shadow map");
+        let mut stats = CullStats::default();
+        let depth_test = Backend::is_enabled(gl::DEPTH_TEST);
+        let polygon_offset_fill = Backend::is_enabled(gl::POLYGON_OFFSET_FILL);
+        let depth_mask = Backend::get_integer(gl::DEPTH_WRITEMASK) != 0;
+        let polygon_offset = (Backend::get_float(gl::POLYGON_OFFSET_FACTOR),
+                              Backend::get_float(gl::POLYGON_OFFSET_UNITS));
+        Backend::enable(gl::DEPTH_TEST);
+        Backend::enable(gl::POLYGON_OFFSET_FILL);
+        Backend::depth_mask(true);
+        Backend::polygon_offset(self.bias.0, self.bias.1);
+        for (framebuffer, matrix) in self.framebuffers.iter().zip(self.matrices.iter()) {
+            framebuffer.bind();
+            Backend::clear(gl::DEPTH_BUFFER_BIT);
+            for (si, instance) in casters {
+                let model = instance.transformation.mat4();
+                if let Some(bbox) = si.bounds() {
+                    if !Frustum::of_matrix(&mat4_mul(matrix, &model)).intersects_box(bbox) {
+                        stats.culled += 1;
+                        continue;
+                    }
+                }
+                let shader_class = si.shader_class();
+                if let Some(program) = shader_class.program_id() {
+                    Backend::use_program(program);
+                }
+                if let Some(u) = shader_class.uniform(UniformId::ViewProjectionMatrix) {
+                    Backend::uniform_matrix4(u, matrix);
+                }
+                if let Some(u) = shader_class.uniform(UniformId::ShadowLight(0)) {
+                    Backend::uniform_4f(u, &self.light);
+                }
+                si.set_model_matrix(instance);
+                for primitive in 0..si.num_primitives() {
+                    si.set_mesh_matrix(si.mesh_matrix_index(primitive));
+                    si.bind_vertices(primitive);
+                    si.draw_primitive(primitive);
+                    si.unbind_vertices(primitive);
+                }
+                stats.drawn += 1;
+            }
+        }
+        Backend::polygon_offset(polygon_offset.0, polygon_offset.1);
+        Backend::depth_mask(depth_mask);
+        if !polygon_offset_fill {
+            Backend::disable(gl::POLYGON_OFFSET_FILL);
+        }
+        if !depth_test {
+            Backend::disable(gl::DEPTH_TEST);
+        }
+        Framebuffer::unbind();
+        stats
+    }
+
+    //mp upload
+    /// Bind the depth texture (with its comparison sampler) to its
+    /// texture unit, and set the shadow uniforms of a program (which
+    /// must be in use) for a shadow map index
+    ///
+    /// The shadow matrices map world space to texture coordinates and
+    /// depth; they are not set for point lights, whose shadow map is
+    /// sampled by the direction from the light and the distance
+    /// divided by the range
+    pub fn upload(&self, shader_class:&dyn ShaderClass, index:usize) -> Result<(), String> {
+        if let Some(u) = shader_class.uniform(UniformId::ShadowMap(index)) {
+            self.texture.bind_unit(self.unit, Some(&*self.sampler))?;
+            Backend::uniform_1i(u, self.unit as GLint);
+        }
+        if self.kind != ShadowKind::Point {
+            if let Some(u) = shader_class.uniform(UniformId::ShadowMatrices(index)) {
+                let data : Vec<f32> = self.matrices.iter().flat_map(|m| mat4_mul(&BIAS, m)).collect();
+                Backend::uniform_matrix4(u, &data);
+            }
+        }
+        if let Some(u) = shader_class.uniform(UniformId::ShadowSplits(index)) {
+            Backend::uniform_4f(u, &self.splits);
+        }
+        if let Some(u) = shader_class.uniform(UniformId::ShadowLight(index)) {
+            Backend::uniform_4f(u, &self.light);
+        }
+        Ok(())
+    }
+
+    //zz All done
+}
+
+//ip Display for ShadowMap
+impl std::fmt::Display for ShadowMap {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{} shadow map {} on unit {}", self.kind, self.texture, self.unit)
+    }
+}
@@ -1,13 +1,678 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    texture.rs
+@brief   OpenGL textures - 2D, cube maps and 2D arrays
+ */
+
+//a Documentation
+
+/*!
+
+A [Texture] is an OpenGL texture object of a [TextureKind]: a 2D
+texture, a cube map (for skyboxes and environment lighting) or a 2D
+array (for terrain layers and shadow cascades).
+
+Textures are created with their base level allocated, optionally
+initialized from image data - one image for a 2D texture, six faces
+(in [CubeFace] order) for a cube map, or N layers for a 2D array.
+Regions of any image ([TextureImage]) of any mip level may then be
+uploaded, and the mipmaps generated from the base level.
+
+Image data is tightly packed rows of pixels of the [TextureFormat],
+bottom row first; floating point formats take `f32` components.
+GPU-compressed textures are created from a [CompressedImage] (such
+as a KTX2 or DDS file) with [Texture::of_compressed].
+
+Textures are created with linear filtering (nearest for depth
+formats), repeating 2D textures and clamping cube maps and arrays to
+the edge; when drawing, a [crate::Sampler] bound to the texture unit
+with [Texture::bind_unit] overrides these.
+
+Seamless cube map filtering is enabled by the [crate::RenderContext]
+if the context supports it ([crate::GlFeature::SeamlessCubeMap]).
+
+!*/
+
 //a Imports
+use std::cell::Cell;
+
+use gl::types::*;
+
+use crate::{Backend, GlBackend};
+use crate::{Sampler, RenderContext};
+use crate::{CompressedFormat, CompressedImage};
+use crate::debug;
+use crate::deletion::{self, GlObject};
+use crate::recovery;
+use crate::texture_units;
+
+//a TextureKind
+//tp TextureKind
+/// The kind of a [Texture]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureKind {
+    /// A 2D texture (GL_TEXTURE_2D)
+    Texture2D,
+    /// A cube map of six square faces (GL_TEXTURE_CUBE_MAP)
+    CubeMap,
+    /// An array of 2D layers (GL_TEXTURE_2D_ARRAY)
+    Array2D,
+}
+
+//ip TextureKind
+impl TextureKind {
+    //ap target
+    /// Get the OpenGL target for the kind of texture
+    pub fn target(&self) -> GLenum {
+        match self {
+            Self::Texture2D => gl::TEXTURE_2D,
+            Self::CubeMap => gl::TEXTURE_CUBE_MAP,
+            Self::Array2D => gl::TEXTURE_2D_ARRAY,
+        }
+    }
+}
+
+//ip Display for TextureKind
+impl std::fmt::Display for TextureKind {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let s = match self {
+            Self::Texture2D => "2D",
+            Self::CubeMap => "cube map",
+            Self::Array2D => "2D array",
+        };
+        f.write_str(s)
+    }
+}
+
+//a CubeFace
+//tp CubeFace
+/// A face of a cube map texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    /// The +X face
+    PositiveX,
+    /// The -X face
+    NegativeX,
+    /// The +Y face
+    PositiveY,
+    /// The -Y face
+    NegativeY,
+    /// The +Z face
+    PositiveZ,
+    /// The -Z face
+    NegativeZ,
+}
+
+//ip CubeFace
+impl CubeFace {
+    //cp ALL
+    /// All the faces, in OpenGL order
+    pub const ALL : [CubeFace; 6] = [Self::PositiveX, Self::NegativeX,
+                                     Self::PositiveY, Self::NegativeY,
+                                     Self::PositiveZ, Self::NegativeZ];
+
+    //ap target
+    /// Get the OpenGL target for the face
+    pub fn target(&self) -> GLenum {
+        gl::TEXTURE_CUBE_MAP_POSITIVE_X + (*self as GLenum)
+    }
+}
+
+//a TextureImage
+//tp TextureImage
+/// An image of a [Texture] (at any mip level) for uploading data to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureImage {
+    /// The image of a 2D texture
+    Image,
+    /// A face of a cube map
+    Face(CubeFace),
+    /// A layer of a 2D array
+    Layer(usize),
+}
+
+//a TextureFormat
+//tp TextureFormat
+/// The format of the texels of a [Texture], and of the image data
+/// uploaded to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    /// 8-bit red
+    R8,
+    /// 8-bit red, green
+    Rg8,
+    /// 8-bit red, green, blue
+    Rgb8,
+    /// 8-bit red, green, blue, alpha
+    Rgba8,
+    /// 8-bit sRGB red, green, blue
+    Srgb8,
+    /// 8-bit sRGB red, green, blue with linear alpha
+    Srgb8Alpha8,
+    /// Half float red (uploaded as f32)
+    R16F,
+    /// Half float red, green (uploaded as f32)
+    Rg16F,
+    /// Half float red, green, blue (uploaded as f32)
+    Rgb16F,
+    /// Half float red, green, blue, alpha (uploaded as f32)
+    Rgba16F,
+    /// Float red
+    R32F,
+    /// Float red, green, blue, alpha
+    Rgba32F,
+    /// 16-bit depth
+    Depth16,
+    /// 24-bit depth (uploaded as u32)
+    Depth24,
+    /// Float depth
+    Depth32F,
+    /// A GPU-compressed format; image data cannot be uploaded to
+    /// regions of these textures
+    Compressed(CompressedFormat),
+}
+
+//ip TextureFormat
+impl TextureFormat {
+    //ap internal_format
+    /// Get the OpenGL sized internal format
+    pub fn internal_format(&self) -> GLenum {
+        match self {
+            Self::R8 => gl::R8,
+            Self::Rg8 => gl::RG8,
+            Self::Rgb8 => gl::RGB8,
+            Self::Rgba8 => gl::RGBA8,
+            Self::Srgb8 => gl::SRGB8,
+            Self::Srgb8Alpha8 => gl::SRGB8_ALPHA8,
+            Self::R16F => gl::R16F,
+            Self::Rg16F => gl::RG16F,
+            Self::Rgb16F => gl::RGB16F,
+            Self::Rgba16F => gl::RGBA16F,
+            Self::R32F => gl::R32F,
+            Self::Rgba32F => gl::RGBA32F,
+            Self::Depth16 => gl::DEPTH_COMPONENT16,
+            Self::Depth24 => gl::DEPTH_COMPONENT24,
+            Self::Depth32F => gl::DEPTH_COMPONENT32F,
+            Self::Compressed(c) => c.internal_format(),
+        }
+    }
+
+    //ap pixel_format
+    /// Get the OpenGL (format, type) of image data (which is
+    /// GL_NONE for compressed formats)
+    pub fn pixel_format(&self) -> (GLenum, GLenum) {
+        match self {
+            Self::R8 => (gl::RED, gl::UNSIGNED_BYTE),
+            Self::Rg8 => (gl::RG, gl::UNSIGNED_BYTE),
+            Self::Rgb8 | Self::Srgb8 => (gl::RGB, gl::UNSIGNED_BYTE),
+            Self::Rgba8 | Self::Srgb8Alpha8 => (gl::RGBA, gl::UNSIGNED_BYTE),
+            Self::R16F | Self::R32F => (gl::RED, gl::FLOAT),
+            Self::Rg16F => (gl::RG, gl::FLOAT),
+            Self::Rgb16F => (gl::RGB, gl::FLOAT),
+            Self::Rgba16F | Self::Rgba32F => (gl::RGBA, gl::FLOAT),
+            Self::Depth16 => (gl::DEPTH_COMPONENT, gl::UNSIGNED_SHORT),
+            Self::Depth24 => (gl::DEPTH_COMPONENT, gl::UNSIGNED_INT),
+            Self::Depth32F => (gl::DEPTH_COMPONENT, gl::FLOAT),
+            Self::Compressed(_) => (gl::NONE, gl::NONE),
+        }
+    }
+
+    //ap bytes_per_pixel
+    /// Get the number of bytes per pixel of image data (0 for
+    /// compressed formats)
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            Self::R8 => 1,
+            Self::Rg8 | Self::Depth16 => 2,
+            Self::Rgb8 | Self::Srgb8 => 3,
+            Self::Rgba8 | Self::Srgb8Alpha8 => 4,
+            Self::R16F | Self::R32F | Self::Depth24 | Self::Depth32F => 4,
+            Self::Rg16F => 8,
+            Self::Rgb16F => 12,
+            Self::Rgba16F | Self::Rgba32F => 16,
+            Self::Compressed(_) => 0,
+        }
+    }
+
+    //ap image_bytes
+    /// Get the number of bytes of image data of a size; this is
+    /// usize::MAX if a compressed image is too large to be represented
+    pub fn image_bytes(&self, width:usize, height:usize) -> usize {
+        match self {
+            Self::Compressed(c) => c.image_bytes(width, height).unwrap_or(usize::MAX),
+            _ => width * height * self.bytes_per_pixel(),
+        }
+    }
+
+    //ap is_compressed
+    /// Return true if the format is a GPU-compressed format
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, Self::Compressed(_))
+    }
+
+    //ap is_depth
+    /// Return true if the format is a depth format
+    pub fn is_depth(&self) -> bool {
+        matches!(self, Self::Depth16 | Self::Depth24 | Self::Depth32F)
+    }
+}
+
+//ip Display for TextureFormat
+impl std::fmt::Display for TextureFormat {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Compressed(c) => c.fmt(f),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
 
 //a Texture
 //tp Texture
-/// A null texture for now
+/// An OpenGL texture, deleted when it is dropped
 #[derive(Debug)]
-pub struct Texture (u32);
+pub struct Texture {
+    /// The OpenGL texture name
+    gl : GLuint,
+    /// The context generation the texture was created in
+    generation : u32,
+    /// The kind of texture
+    kind : TextureKind,
+    /// The format of the texels
+    format : TextureFormat,
+    /// Width of the base level
+    width : usize,
+    /// Height of the base level
+    height : usize,
+    /// Number of layers (6 for a cube map, 1 for a 2D texture)
+    layers : usize,
+    /// Number of mip levels with images (a cell, as mipmaps of
+    /// asynchronous uploads are generated when they complete)
+    levels : Cell<usize>,
+    /// False while an asynchronous upload of the image data is in
+    /// flight
+    ready : Cell<bool>,
+}
+
+//ip Texture
+impl Texture {
+    //fp create
+    /// Create the OpenGL texture, bound to its target, with default
+    /// (linear, non-mipmapped) filtering; the maximum level is set to
+    /// 0 so that the texture is complete without mipmaps
+    pub(crate) fn create(kind:TextureKind, format:TextureFormat, width:usize, height:usize, layers:usize) -> Result<Self, String> {
+        if width == 0 || height == 0 || layers == 0 {
+            return Err(format!("Cannot create an empty {} texture", kind));
+        }
+        let gl = Backend::gen_texture();
+        let target = kind.target();
+        if let Err(e) = texture_units::bind_texture(target, gl) {
+            Backend::delete_texture(gl);
+            return Err(e);
+        }
+        Backend::pixel_store(gl::UNPACK_ALIGNMENT, 1);
+        let filter = if format.is_depth() { gl::NEAREST } else { gl::LINEAR };
+        Backend::tex_parameter_i(target, gl::TEXTURE_MIN_FILTER, filter as GLint);
+        Backend::tex_parameter_i(target, gl::TEXTURE_MAG_FILTER, filter as GLint);
+        let wrap = if kind == TextureKind::Texture2D { gl::REPEAT } else { gl::CLAMP_TO_EDGE };
+        Backend::tex_parameter_i(target, gl::TEXTURE_WRAP_S, wrap as GLint);
+        Backend::tex_parameter_i(target, gl::TEXTURE_WRAP_T, wrap as GLint);
+        if kind == TextureKind::CubeMap {
+            Backend::tex_parameter_i(target, gl::TEXTURE_WRAP_R, wrap as GLint);
+        }
+        Backend::tex_parameter_i(target, gl::TEXTURE_MAX_LEVEL, 0);
+        Ok(Self { gl, generation:recovery::generation(), kind, format, width, height, layers, levels:Cell::new(1), ready:Cell::new(true) })
+    }
+
+    //fp new_2d
+    /// Create a 2D texture, initializing it with image data if given
+    pub fn new_2d(width:usize, height:usize, format:TextureFormat, data:Option<&[u8]>) -> Result<Self, String> {
+        let texture = Self::create(TextureKind::Texture2D, format, width, height, 1)?;
+        if let Some(data) = data {
+            texture.check_data(width, height, data)?;
+        }
+        Backend::tex_image_2d(gl::TEXTURE_2D, 0, format.internal_format(),
+                              (width as GLsizei, height as GLsizei), format.pixel_format(), data);
+        texture_units::bind_texture(gl::TEXTURE_2D, 0)?;
+        Ok(texture)
+    }
+
+    //fp new_cube_map
+    /// Create a cube map with square faces, initializing them with
+    /// six images (in [CubeFace] order) if given
+    pub fn new_cube_map(size:usize, format:TextureFormat, faces:Option<[&[u8]; 6]>) -> Result<Self, String> {
+        let texture = Self::create(TextureKind::CubeMap, format, size, size, 6)?;
+        if let Some(faces) = &faces {
+            for data in faces {
+                texture.check_data(size, size, data)?;
+            }
+        }
+        for (i, face) in CubeFace::ALL.iter().enumerate() {
+            Backend::tex_image_2d(face.target(), 0, format.internal_format(),
+                                  (size as GLsizei, size as GLsizei), format.pixel_format(),
+                                  faces.map(|f| f[i]));
+        }
+        texture_units::bind_texture(gl::TEXTURE_CUBE_MAP, 0)?;
+        Ok(texture)
+    }
+
+    //fp new_array
+    /// Create a 2D array texture of a number of layers, initializing
+    /// them with images (one per layer) if given
+    pub fn new_array(width:usize, height:usize, layers:usize, format:TextureFormat, data:Option<&[&[u8]]>) -> Result<Self, String> {
+        let texture = Self::create(TextureKind::Array2D, format, width, height, layers)?;
+        if let Some(data) = data {
+            if data.len() != layers {
+                return Err(format!("Texture array of {} layers given {} images", layers, data.len()));
+            }
+            for d in data {
+                texture.check_data(width, height, d)?;
+            }
+        }
+        Backend::tex_image_3d(gl::TEXTURE_2D_ARRAY, 0, format.internal_format(),
+                              (width as GLsizei, height as GLsizei, layers as GLsizei),
+                              format.pixel_format(), None);
+        if let Some(data) = data {
+            for (layer, d) in data.iter().enumerate() {
+                Backend::tex_sub_image_3d(gl::TEXTURE_2D_ARRAY, 0, (0, 0, layer as GLint),
+                                          (width as GLsizei, height as GLsizei, 1),
+                                          format.pixel_format(), d);
+            }
+        }
+        texture_units::bind_texture(gl::TEXTURE_2D_ARRAY, 0)?;
+        Ok(texture)
+    }
+
+    //fp of_compressed
+    /// Create a texture from a [CompressedImage], with all its mip
+    /// levels
+    ///
+    /// If the context does not support the compressed format, then
+    /// if `decompress` is true the image is decompressed to RGBA8,
+    /// otherwise an error is returned; BC6H, BC7, ASTC and the signed
+    /// formats cannot be decompressed
+    /// ([CompressedFormat::can_decompress]), so for these an
+    /// 'unsupported' error is returned whatever `decompress` is
+    pub fn of_compressed(image:&CompressedImage, render_context:&RenderContext, decompress:bool) -> Result<Self, String> {
+        image.create_texture(render_context.capabilities(), decompress)
+    }
+
+    //fp of_ktx2
+    /// Create a texture from a KTX2 container of compressed data
+    pub fn of_ktx2(bytes:&[u8], render_context:&RenderContext, decompress:bool) -> Result<Self, String> {
+        Self::of_compressed(&CompressedImage::of_ktx2(bytes)?, render_context, decompress)
+    }
+
+    //fp of_dds
+    /// Create a texture from a DDS container of compressed data
+    pub fn of_dds(bytes:&[u8], render_context:&RenderContext, decompress:bool) -> Result<Self, String> {
+        Self::of_compressed(&CompressedImage::of_dds(bytes)?, render_context, decompress)
+    }
+
+    //cp with_levels
+    /// Record the number of mip levels uploaded to the (bound)
+    /// texture, limiting sampling to those and using trilinear
+    /// filtering if there is more than one; the texture is unbound
+    pub(crate) fn with_levels(self, levels:usize) -> Result<Self, String> {
+        let target = self.kind.target();
+        self.levels.set(levels);
+        Backend::tex_parameter_i(target, gl::TEXTURE_MAX_LEVEL, levels as GLint - 1);
+        if levels > 1 {
+            Backend::tex_parameter_i(target, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as GLint);
+        }
+        texture_units::bind_texture(target, 0)?;
+        Ok(self)
+    }
+
+    //ap gl_texture
+    /// Get the OpenGL texture name
+    pub fn gl_texture(&self) -> GLuint {
+        self.gl
+    }
+
+    //ap kind
+    /// Get the kind of texture
+    pub fn kind(&self) -> TextureKind {
+        self.kind
+    }
+
+    //ap format
+    /// Get the format of the texture
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    //ap width
+    /// Get the width of the base level
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    //ap height
+    /// Get the height of the base level
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    //ap layers
+    /// Get the number of layers (6 for a cube map, 1 for a 2D texture)
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    //ap levels
+    /// Get the number of mip levels with images
+    pub fn levels(&self) -> usize {
+        self.levels.get()
+    }
+
+    //ap is_ready
+    /// Return true if the image data of the texture has been
+    /// uploaded; false while an asynchronous upload (with
+    /// [RenderContext::upload_texture_2d]) is in flight, and for good
+    /// if the context was lost during the upload
+    pub fn is_ready(&self) -> bool {
+        self.ready.get()
+    }
+
+    //mp set_ready
+    /// Set whether the image data of the texture has been uploaded
+    pub(crate) fn set_ready(&self, ready:bool) {
+        self.ready.set(ready);
+    }
+
+    //ap is_current
+    /// Return true if the texture was created in the current context
+    /// (textures are not recreated after context loss)
+    pub fn is_current(&self) -> bool {
+        recovery::is_current(self.generation)
+    }
+
+    //ap byte_size
+    /// Get an estimate of the size of the texture in bytes, for all
+    /// its mip levels
+    pub fn byte_size(&self) -> usize {
+        (0..self.levels())
+            .map(|l| self.level_size(l))
+            .map(|(w, h)| self.format.image_bytes(w, h) * self.layers)
+            .sum()
+    }
+
+    //mi level_size
+    /// Get the width and height of a mip level
+    pub(crate) fn level_size(&self, level:usize) -> (usize, usize) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    //mi check_data
+    /// Check that image data is large enough for a region
+    fn check_data(&self, width:usize, height:usize, data:&[u8]) -> Result<(), String> {
+        let required = self.format.image_bytes(width, height);
+        if data.len() < required {
+            Err(format!("Image data of {} bytes is too small for {}x{} {} ({} bytes)",
+                        data.len(), width, height, self.format, required))
+        } else {
+            Ok(())
+        }
+    }
+
+    //mp bind
+    /// Bind the texture to its target of the active texture unit
+    pub fn bind(&self) -> Result<(), String> {
+        texture_units::bind_texture(self.kind.target(), self.gl)
+    }
+
+    //mp bind_unit
+    /// Bind the texture to a texture unit, with a [Sampler] (or none,
+    /// to use the sampling parameters of the texture itself)
+    ///
+    /// Nothing is bound if the texture and sampler are already bound
+    /// to the unit
+    pub fn bind_unit(&self, unit:usize, sampler:Option<&Sampler>) -> Result<(), String> {
+        let sampler = sampler.map_or(0, |s| s.gl_sampler());
+        texture_units::bind(unit, self.kind.target(), self.gl, sampler)?;
+        Ok(())
+    }
+
+    //mp sub_image
+    /// Upload image data to a region (offset and size) of an image
+    /// of the texture at a mip level
+    ///
+    /// The image must suit the kind of texture: [TextureImage::Image]
+    /// for a 2D texture, a face for a cube map, and a layer for an
+    /// array
+    pub fn sub_image(&self, image:TextureImage, level:usize, offset:(usize, usize), size:(usize, usize), data:&[u8]) -> Result<(), String> {
+        if self.format.is_compressed() {
+            return Err(format!("Cannot upload image data to a {} texture", self.format));
+        }
+        if level >= self.levels() {
+            return Err(format!("Mip level {} of texture has no image", level));
+        }
+        let (level_width, level_height) = self.level_size(level);
+        if offset.0 + size.0 > level_width || offset.1 + size.1 > level_height {
+            return Err(format!("Region {}x{} at ({}, {}) is outside the {}x{} mip level {}",
+                               size.0, size.1, offset.0, offset.1, level_width, level_height, level));
+        }
+        self.check_data(size.0, size.1, data)?;
+        let target = self.kind.target();
+        let level_gl = level as GLint;
+        let offset_gl = (offset.0 as GLint, offset.1 as GLint);
+        let size_gl = (size.0 as GLsizei, size.1 as GLsizei);
+        match (self.kind, image) {
+            (TextureKind::Texture2D, TextureImage::Image) => {
+                self.bind()?;
+                Backend::pixel_store(gl::UNPACK_ALIGNMENT, 1);
+                Backend::tex_sub_image_2d(target, level_gl, offset_gl, size_gl, self.format.pixel_format(), data);
+            }
+            (TextureKind::CubeMap, TextureImage::Face(face)) => {
+                self.bind()?;
+                Backend::pixel_store(gl::UNPACK_ALIGNMENT, 1);
+                Backend::tex_sub_image_2d(face.target(), level_gl, offset_gl, size_gl, self.format.pixel_format(), data);
+            }
+            (TextureKind::Array2D, TextureImage::Layer(layer)) if layer < self.layers => {
+                self.bind()?;
+                Backend::pixel_store(gl::UNPACK_ALIGNMENT, 1);
+                Backend::tex_sub_image_3d(target, level_gl, (offset_gl.0, offset_gl.1, layer as GLint),
+                                          (size_gl.0, size_gl.1, 1), self.format.pixel_format(), data);
+            }
+            _ => {
+                return Err(format!("Cannot upload to {:?} of a {} texture with {} layers", image, self.kind, self.layers));
+            }
+        }
+        texture_units::bind_texture(target, 0)?;
+        Ok(())
+    }
+
+    //mp set_face
+    /// Upload a complete face of the base level of a cube map
+    pub fn set_face(&self, face:CubeFace, data:&[u8]) -> Result<(), String> {
+        self.sub_image(TextureImage::Face(face), 0, (0, 0), (self.width, self.height), data)
+    }
+
+    //mp set_layer
+    /// Upload a complete layer of the base level of a 2D array
+    pub fn set_layer(&self, layer:usize, data:&[u8]) -> Result<(), String> {
+        self.sub_image(TextureImage::Layer(layer), 0, (0, 0), (self.width, self.height), data)
+    }
+
+    //mp generate_mipmaps
+    /// Generate the mipmaps of the texture from its base level, and
+    /// use trilinear filtering
+    ///
+    /// Mip levels may then have regions uploaded with
+    /// [Texture::sub_image]
+    pub fn generate_mipmaps(&mut self) -> Result<(), String> {
+        self.check_mipmaps()?;
+        self.gl_generate_mipmaps()
+    }
+
+    //mp check_mipmaps
+    /// Return an error if mipmaps cannot be generated for the format
+    /// of the texture
+    pub(crate) fn check_mipmaps(&self) -> Result<(), String> {
+        if self.format.is_depth() || self.format.is_compressed() {
+            return Err(format!("Cannot generate mipmaps for a {} texture", self.format));
+        }
+        Ok(())
+    }
+
+    //mp gl_generate_mipmaps
+    /// Generate the mipmaps of a texture whose format has been
+    /// checked, once its base level image has been uploaded
+    pub(crate) fn gl_generate_mipmaps(&self) -> Result<(), String> {
+        let target = self.kind.target();
+        let largest = self.width.max(self.height);
+        let levels = (usize::BITS - largest.leading_zeros()) as usize;
+        self.bind()?;
+        // The maximum level limits the levels generated, so it must be
+        // raised first
+        Backend::tex_parameter_i(target, gl::TEXTURE_MAX_LEVEL, levels as GLint - 1);
+        Backend::generate_mipmap(target);
+        Backend::tex_parameter_i(target, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as GLint);
+        texture_units::bind_texture(target, 0)?;
+        self.levels.set(levels);
+        Ok(())
+    }
+
+    //mp set_label
+    /// Label the OpenGL texture (if debug output is enabled) for
+    /// debug messages and tools
+    pub fn set_label(&self, label:&str) {
+        debug::label(gl::TEXTURE, self.gl, label);
+    }
+
+    //zz All done
+}
+
+//ip Drop for Texture
+impl Drop for Texture {
+    //fp drop
+    /// Drop requires the texture to be deleted
+    fn drop(&mut self) {
+        deletion::delete(self.generation, GlObject::Texture(self.gl));
+    }
+}
+
+//ip Display for Texture
 impl std::fmt::Display for Texture {
     fn fmt(&self, fmt:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        write!(fmt, "{}", self.0)
+        write!(fmt, "{} {}x{}", self.kind, self.width, self.height)?;
+        if self.kind == TextureKind::Array2D {
+            write!(fmt, "x{}", self.layers)?;
+        }
+        write!(fmt, " {} GL({})", self.format, self.gl)
     }
 }
 
@@ -0,0 +1,247 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    texture_file.rs
+@brief   Decoding PNG, JPEG, HDR and EXR images into textures
+ */
+
+//a Documentation
+
+/*!
+
+With the `image` feature a 2D [Texture] may be created from the bytes
+(or path) of a PNG, JPEG, Radiance HDR or OpenEXR image, decoded with
+the pure-Rust decoders of the `image` crate.
+
+The [TextureFormat] is chosen from the image and the [ImageOptions]:
+
+* 8-bit images are `Srgb8` or `Srgb8Alpha8` if they are sRGB
+  encoded (the default) - grayscale is expanded to RGB, as there is
+  no single-channel sRGB format, and RGB to RGBA if mipmaps are to be
+  generated, as `Srgb8` is not color-renderable in OpenGL ES 3.0 -
+  and otherwise `R8`, `Rgb8` or `Rgba8` by their number of channels;
+  grayscale with alpha is expanded to RGBA (rather than `Rg8`, which
+  would leave the alpha in the green channel)
+
+* 16-bit images are reduced to 8 bits if they are sRGB encoded, and
+  otherwise are stored as half floats in `R16F`, `Rgb16F` or
+  `Rgba16F` (keeping 11 significant bits, as there are no 16-bit
+  normalized formats in OpenGL ES 3.0), with grayscale and alpha
+  expanded to RGBA
+
+* floating point (HDR and EXR) images are always linear, and are
+  `Rgb16F` or `Rgba16F`
+
+RGB half float images are expanded to `Rgba16F` (with an alpha of 1)
+if mipmaps are to be generated, as `Rgb16F` is not color-renderable
+in OpenGL ES 3.0, even with the color buffer float extensions (which
+make `Rgba16F` color-renderable).
+
+Alpha may be premultiplied (in linear space, for sRGB images), and
+the image flipped vertically - image files are top row first, whereas
+OpenGL texture coordinates have the bottom row first. Mipmaps are
+generated by default.
+
+!*/
+
+//a Imports
+use std::path::Path;
+
+use crate::image;
+use crate::{Texture, TextureFormat};
+
+//a ImageOptions
+//tp ImageOptions
+/// Options for decoding an image file into a [Texture]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageOptions {
+    /// True if 8-bit and 16-bit image data is sRGB encoded (colour
+    /// textures), false if it is linear (normal maps, roughness
+    /// maps, etc)
+    pub srgb : bool,
+    /// True if the colour channels should be multiplied by the alpha
+    pub premultiply_alpha : bool,
+    /// True if the image should be flipped vertically, so that its
+    /// bottom row is first
+    pub flip_y : bool,
+    /// True if mipmaps should be generated
+    pub mipmaps : bool,
+}
+
+//ip Default for ImageOptions
+impl Default for ImageOptions {
+    /// sRGB colour, not premultiplied or flipped, with mipmaps
+    fn default() -> Self {
+        Self { srgb:true, premultiply_alpha:false, flip_y:false, mipmaps:true }
+    }
+}
+
+//ip ImageOptions
+impl ImageOptions {
+    //cp with_srgb
+    /// Set whether 8-bit and 16-bit image data is sRGB encoded
+    pub fn with_srgb(mut self, srgb:bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    //cp with_premultiplied_alpha
+    /// Set whether the colour channels are multiplied by the alpha
+    pub fn with_premultiplied_alpha(mut self, premultiply_alpha:bool) -> Self {
+        self.premultiply_alpha = premultiply_alpha;
+        self
+    }
+
+    //cp with_flip_y
+    /// Set whether the image is flipped vertically
+    pub fn with_flip_y(mut self, flip_y:bool) -> Self {
+        self.flip_y = flip_y;
+        self
+    }
+
+    //cp with_mipmaps
+    /// Set whether mipmaps are generated
+    pub fn with_mipmaps(mut self, mipmaps:bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+}
+
+//a Functions
+//fi srgb_to_linear
+/// Convert an sRGB encoded value (0 to 1) to linear
+fn srgb_to_linear(v:f32) -> f32 {
+    if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+}
+
+//fi linear_to_srgb
+/// Convert a linear value (0 to 1) to sRGB encoded
+fn linear_to_srgb(v:f32) -> f32 {
+    if v <= 0.0031308 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 }
+}
+
+//fi premultiply_u8
+/// Premultiply the colour channels of 8-bit pixels by their alpha
+/// (the last channel)
+fn premultiply_u8(data:&mut [u8], channels:usize, srgb:bool) {
+    for pixel in data.chunks_exact_mut(channels) {
+        let (colour, alpha) = pixel.split_at_mut(channels - 1);
+        let a = alpha[0] as f32 / 255.0;
+        for c in colour {
+            let v = *c as f32 / 255.0;
+            let v = if srgb { linear_to_srgb(srgb_to_linear(v) * a) } else { v * a };
+            *c = (v * 255.0 + 0.5) as u8;
+        }
+    }
+}
+
+//fi premultiply_f32
+/// Premultiply the colour channels of floating point pixels by their
+/// alpha (the last channel)
+fn premultiply_f32(data:&mut [f32], channels:usize) {
+    for pixel in data.chunks_exact_mut(channels) {
+        let (colour, alpha) = pixel.split_at_mut(channels - 1);
+        for c in colour {
+            *c *= alpha[0];
+        }
+    }
+}
+
+//fi decode
+/// Decode an image file to its size, texture format and texel data
+fn decode(bytes:&[u8], options:&ImageOptions) -> Result<(usize, usize, TextureFormat, Vec<u8>), String> {
+    let mut img = image::load_from_memory(bytes)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    if options.flip_y {
+        img = img.flipv();
+    }
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let color = img.color();
+    let alpha = color.has_alpha();
+    let mut channels = color.channel_count() as usize;
+    let bytes_per_channel = color.bytes_per_pixel() as usize / channels;
+    let float = matches!(color, image::ColorType::Rgb32F | image::ColorType::Rgba32F);
+    let premultiply = options.premultiply_alpha && alpha;
+
+    if bytes_per_channel <= 2 && options.srgb {
+        channels = if alpha || options.mipmaps { 4 } else { 3 };
+        let (format, mut data) = {
+            if channels == 4 {
+                (TextureFormat::Srgb8Alpha8, img.to_rgba8().into_raw())
+            } else {
+                (TextureFormat::Srgb8, img.to_rgb8().into_raw())
+            }
+        };
+        if premultiply {
+            premultiply_u8(&mut data, channels, true);
+        }
+        Ok((width, height, format, data))
+    } else if bytes_per_channel == 1 {
+        let mut data = {
+            if channels == 2 {
+                channels = 4;
+                img.to_rgba8().into_raw()
+            } else {
+                img.into_bytes()
+            }
+        };
+        let format = [TextureFormat::R8, TextureFormat::Rg8, TextureFormat::Rgb8, TextureFormat::Rgba8][channels - 1];
+        if premultiply {
+            premultiply_u8(&mut data, channels, false);
+        }
+        Ok((width, height, format, data))
+    } else {
+        let mut data : Vec<f32> = {
+            if channels == 2 || (channels == 3 && options.mipmaps) {
+                channels = 4;
+                img.to_rgba32f().into_raw()
+            } else if float {
+                img.into_bytes().chunks_exact(4).map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]])).collect()
+            } else {
+                img.into_bytes().chunks_exact(2).map(|b| u16::from_ne_bytes([b[0], b[1]]) as f32 / 65535.0).collect()
+            }
+        };
+        if premultiply {
+            premultiply_f32(&mut data, channels);
+        }
+        let format = [TextureFormat::R16F, TextureFormat::Rg16F, TextureFormat::Rgb16F, TextureFormat::Rgba16F][channels - 1];
+        let data = data.iter().flat_map(|f| f.to_ne_bytes()).collect();
+        Ok((width, height, format, data))
+    }
+}
+
+//a Texture
+//ip Texture
+impl Texture {
+    //fp of_image
+    /// Create a 2D texture from the bytes of a PNG, JPEG, HDR or EXR
+    /// image file
+    pub fn of_image(bytes:&[u8], options:&ImageOptions) -> Result<Self, String> {
+        let (width, height, format, data) = decode(bytes, options)?;
+        let mut texture = Self::new_2d(width, height, format, Some(&data))?;
+        if options.mipmaps {
+            texture.generate_mipmaps()?;
+        }
+        Ok(texture)
+    }
+
+    //fp read_image
+    /// Create a 2D texture from a PNG, JPEG, HDR or EXR image file
+    pub fn read_image<P:AsRef<Path>>(path:P, options:&ImageOptions) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read image {}: {}", path.display(), e))?;
+        Self::of_image(&bytes, options)
+    }
+}
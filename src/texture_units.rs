@@ -0,0 +1,337 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    texture_units.rs
+@brief   Texture unit allocation and the textures bound to each unit
+ */
+
+//a Documentation
+
+/*!
+
+Texture units are allocated by each [crate::RenderContext] - one per
+[TextureSlot] in use (the first time the slot is drawn with), and
+others reserved for the application with
+[crate::RenderContext::reserve_texture_unit] (such as for shadow maps
+and environment maps). The unit of a slot does not change, so the
+sampler uniform of a program for a slot is always set to the same
+unit.
+
+The [crate::RenderContext] owns its texture units; the crate uses
+those of the context most recently created on the thread (which
+is the current OpenGL context), so that drawing and texture uploads
+need not be given the [crate::RenderContext].
+
+The textures and samplers bound to each unit (and the active unit)
+are cached, so that drawing a material whose textures are already
+bound does not rebind them; for this, all texture and sampler binding
+must be performed through the crate (e.g. [crate::Texture::bind_unit])
+rather than directly. The cache is discarded when the context is
+lost, and a texture or sampler is forgotten when it is deleted (so
+that a new object reusing its name is bound).
+
+Binding or allocating texture units with no [crate::RenderContext]
+on the thread is an error.
+
+!*/
+
+//a Imports
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use gl::types::*;
+
+use crate::{Backend, GlBackend};
+use crate::recovery;
+
+//a TextureSlot
+//tp TextureSlot
+/// The use of a texture of a [crate::Material] (or of an
+/// [crate::Environment]), with a
+/// [crate::UniformId::Texture] for its sampler uniform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureSlot {
+    /// Base colour (albedo), sRGB
+    BaseColor,
+    /// Tangent-space normal map
+    Normal,
+    /// Metallic (blue) and roughness (green)
+    MetallicRoughness,
+    /// Ambient occlusion (red)
+    Occlusion,
+    /// Emissive colour, sRGB
+    Emissive,
+    /// Diffuse irradiance cube map of an [crate::Environment]
+    Irradiance,
+    /// Prefiltered specular cube map of an [crate::Environment], with
+    /// increasing roughness down its mip chain
+    Prefiltered,
+    /// Split-sum BRDF lookup texture of an [crate::Environment]
+    BrdfLut,
+    /// User texture - dependent on the program
+    User(usize),
+}
+
+//ip Display for TextureSlot
+impl std::fmt::Display for TextureSlot {
+    fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::BaseColor => write!(f, "base colour"),
+            Self::Normal => write!(f, "normal"),
+            Self::MetallicRoughness => write!(f, "metallic-roughness"),
+            Self::Occlusion => write!(f, "occlusion"),
+            Self::Emissive => write!(f, "emissive"),
+            Self::Irradiance => write!(f, "irradiance"),
+            Self::Prefiltered => write!(f, "prefiltered environment"),
+            Self::BrdfLut => write!(f, "BRDF lookup"),
+            Self::User(n) => write!(f, "user {}", n),
+        }
+    }
+}
+
+//a TextureUnits
+//ti Unit
+/// The state of a texture unit
+#[derive(Debug, Default)]
+struct Unit {
+    /// The slot the unit is allocated to, or None if it is reserved
+    /// for the application
+    slot : Option<TextureSlot>,
+    /// The textures bound to the unit, by target
+    textures : Vec<(GLenum, GLuint)>,
+    /// The sampler bound to the unit
+    sampler : GLuint,
+}
+
+//tp TextureUnits
+/// The allocated texture units, and the bindings of all units, of a
+/// [crate::RenderContext]
+#[derive(Debug, Default)]
+pub(crate) struct TextureUnits {
+    /// The context generation the bindings are for
+    generation : u32,
+    /// The number of texture units of the context
+    max_units : usize,
+    /// The number of units allocated (to slots or reserved)
+    allocated : usize,
+    /// The active texture unit
+    active : usize,
+    /// The units with bindings (and all allocated units)
+    units : Vec<Unit>,
+    /// The unit reserved for the sources of the
+    /// [crate::Environment] passes, when first required
+    environment_unit : Option<usize>,
+}
+
+//ip TextureUnits
+impl TextureUnits {
+    //mp configure
+    /// Set the number of texture units of the context (when a
+    /// [crate::RenderContext] is created or recovered)
+    pub(crate) fn configure(&mut self, max_units:usize) {
+        self.max_units = max_units;
+    }
+
+    //mp slot_unit
+    /// Get the texture unit for a slot, allocating one if required
+    pub(crate) fn slot_unit(&mut self, slot:TextureSlot) -> Result<usize, String> {
+        match self.units.iter().take(self.allocated).position(|unit| unit.slot == Some(slot)) {
+            Some(unit) => Ok(unit),
+            None => self.allocate(Some(slot)),
+        }
+    }
+
+    //mp reserve
+    /// Reserve a texture unit for the application
+    pub(crate) fn reserve(&mut self) -> Result<usize, String> {
+        self.allocate(None)
+    }
+
+    //mp environment_unit
+    /// Get the unit reserved for the sources of the
+    /// [crate::Environment] passes, reserving it on first use (so
+    /// that recreating the passes does not reserve another)
+    pub(crate) fn environment_unit(&mut self) -> Result<usize, String> {
+        if let Some(unit) = self.environment_unit {
+            return Ok(unit);
+        }
+        let unit = self.reserve()?;
+        self.environment_unit = Some(unit);
+        Ok(unit)
+    }
+
+    //mi check_generation
+    /// Discard the bindings if they are for an earlier (lost) context
+    fn check_generation(&mut self) {
+        if !recovery::is_current(self.generation) {
+            self.generation = recovery::generation();
+            self.active = 0;
+            for u in self.units.iter_mut() {
+                u.textures.clear();
+                u.sampler = 0;
+            }
+        }
+    }
+
+    //mi unit
+    /// Get the state of a unit, which is current for the context
+    fn unit(&mut self, unit:usize) -> &mut Unit {
+        self.check_generation();
+        if unit >= self.units.len() {
+            self.units.resize_with(unit + 1, Unit::default);
+        }
+        &mut self.units[unit]
+    }
+
+    //mi allocate
+    /// Allocate the next texture unit, to a slot or reserved
+    fn allocate(&mut self, slot:Option<TextureSlot>) -> Result<usize, String> {
+        let unit = self.allocated;
+        if unit >= self.max_units {
+            return Err(format!("All {} texture units are allocated", self.max_units));
+        }
+        self.allocated += 1;
+        self.unit(unit).slot = slot;
+        Ok(unit)
+    }
+
+    //mi activate
+    /// Make a unit active, if it is not already
+    fn activate(&mut self, unit:usize) {
+        self.unit(unit);
+        if self.active != unit {
+            Backend::active_texture(unit as GLuint);
+            self.active = unit;
+        }
+    }
+
+    //mi bind_texture
+    /// Bind a texture to a target of the active unit, if it is not
+    /// already bound; return true if it was bound
+    fn bind_texture(&mut self, target:GLenum, texture:GLuint) -> bool {
+        self.check_generation();
+        let active = self.active;
+        let textures = &mut self.unit(active).textures;
+        match textures.iter_mut().find(|(t, _)| *t == target) {
+            Some((_, bound)) if *bound == texture => { return false; }
+            Some((_, bound)) => { *bound = texture; }
+            None => { textures.push((target, texture)); }
+        }
+        Backend::bind_texture(target, texture);
+        true
+    }
+
+    //mi bind_sampler
+    /// Bind a sampler to a unit, if it is not already bound; return
+    /// true if it was bound
+    fn bind_sampler(&mut self, unit:usize, sampler:GLuint) -> bool {
+        let u = self.unit(unit);
+        if u.sampler == sampler {
+            return false;
+        }
+        u.sampler = sampler;
+        Backend::bind_sampler(unit as GLuint, sampler);
+        true
+    }
+}
+
+//a Statics
+thread_local! {
+    /// The texture units of the [crate::RenderContext] most recently
+    /// created on this thread
+    static CURRENT : RefCell<Weak<RefCell<TextureUnits>>> = const { RefCell::new(Weak::new()) };
+}
+
+//a Functions
+//fp make_current
+/// Make the texture units of a [crate::RenderContext] those used by
+/// the crate on this thread
+pub(crate) fn make_current(units:&Rc<RefCell<TextureUnits>>) {
+    CURRENT.with(|c| *c.borrow_mut() = Rc::downgrade(units));
+}
+
+//fi with_units
+/// Invoke a function with the current texture units; this returns an
+/// error if there is no [crate::RenderContext] on the thread
+fn with_units<R, F:FnOnce(&mut TextureUnits) -> R>(f:F) -> Result<R, String> {
+    let units = CURRENT.with(|c| c.borrow().upgrade())
+        .ok_or_else(|| "Texture units used with no RenderContext".to_string())?;
+    let result = f(&mut units.borrow_mut());
+    Ok(result)
+}
+
+//fp slot_unit
+/// Get the texture unit for a slot, allocating one if required
+pub(crate) fn slot_unit(slot:TextureSlot) -> Result<usize, String> {
+    with_units(|u| u.slot_unit(slot))?
+}
+
+//fp bind_texture
+/// Bind a texture to a target of the active texture unit (for
+/// uploading to it)
+pub(crate) fn bind_texture(target:GLenum, texture:GLuint) -> Result<(), String> {
+    with_units(|u| { u.bind_texture(target, texture); })
+}
+
+//fp bind_sampler
+/// Bind a sampler (or 0 for none) to a texture unit
+pub(crate) fn bind_sampler(unit:usize, sampler:GLuint) -> Result<(), String> {
+    with_units(|u| { u.bind_sampler(unit, sampler); })
+}
+
+//fp bind
+/// Bind a texture to a target of a texture unit with a sampler (or 0
+/// for none), if they are not already bound; return true if anything
+/// was bound
+pub(crate) fn bind(unit:usize, target:GLenum, texture:GLuint, sampler:GLuint) -> Result<bool, String> {
+    with_units(|u| {
+        let textures = &u.unit(unit).textures;
+        if textures.iter().any(|(t, bound)| *t == target && *bound == texture) {
+            u.bind_sampler(unit, sampler)
+        } else {
+            u.activate(unit);
+            u.bind_texture(target, texture);
+            u.bind_sampler(unit, sampler);
+            true
+        }
+    })
+}
+
+//fp forget_texture
+/// Forget a texture (as it has been deleted, which unbinds it); with
+/// no [crate::RenderContext] there are no bindings to forget
+pub(crate) fn forget_texture(texture:GLuint) {
+    let _ = with_units(|u| {
+        for unit in u.units.iter_mut() {
+            for (_, bound) in unit.textures.iter_mut() {
+                if *bound == texture {
+                    *bound = 0;
+                }
+            }
+        }
+    });
+}
+
+//fp forget_sampler
+/// Forget a sampler (as it has been deleted, which unbinds it); with
+/// no [crate::RenderContext] there are no bindings to forget
+pub(crate) fn forget_sampler(sampler:GLuint) {
+    let _ = with_units(|u| {
+        for unit in u.units.iter_mut() {
+            if unit.sampler == sampler {
+                unit.sampler = 0;
+            }
+        }
+    });
+}
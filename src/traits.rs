@@ -9,6 +9,15 @@ pub trait Texture : Sized + std::fmt::Debug {
 //tt ShaderClass
 /// Trait required for a shader program
 pub trait ShaderClass {
+    /// Get the OpenGL program id, to make the program current
+    ///
+    /// Shader classes that do not provide this are drawn (by a
+    /// [crate::RenderQueue] or shadow pass) with whatever program is
+    /// current
+    fn program_id(&self) -> Option<gl::types::GLuint> {
+        None
+    }
+
     /// Borrow a slice of attribute / program attribute location pairings
     fn attributes(&self) -> &[(gl::types::GLuint, model3d::VertexAttr)];
 
@@ -0,0 +1,203 @@
+/*a Copyright
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+
+@file    upload.rs
+@brief   Asynchronous texture uploads through pixel buffer objects
+ */
+
+//a Documentation
+
+/*!
+
+Large textures may be uploaded without stalling the frame with
+[crate::RenderContext::upload_texture_2d]: the image data is written
+into a mapped PIXEL_UNPACK_BUFFER, the texture image is specified
+from that buffer (so that the driver copies it to the texture
+asynchronously), and a fence is inserted after the upload.
+
+The texture is returned immediately, but it is not ready
+([crate::Texture::is_ready]) until its fence is found to be signaled
+by [crate::RenderContext::poll_uploads] (which is invoked at the end
+of each frame); its mipmaps are then generated (if required), and the
+pixel buffer is released. Generating the mipmaps before the fence is
+signaled would make the driver wait for the upload to complete.
+
+While a texture of a [crate::Material] is not ready, drawing binds a
+1x1 placeholder texture for its [TextureSlot] instead - white, except
+for a flat normal map and a black emissive map.
+
+!*/
+
+//a Imports
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gl::types::*;
+
+use crate::{Backend, GlBackend};
+use crate::{GlBuffer, Texture, TextureFormat, TextureSlot};
+use crate::recovery;
+use crate::texture_units;
+
+//a PendingUpload
+//tp PendingUpload
+/// An asynchronous upload of the base level of a 2D texture that is
+/// in flight
+#[derive(Debug)]
+pub(crate) struct PendingUpload {
+    /// The texture being uploaded to
+    texture : Rc<Texture>,
+    /// The pixel buffer the data is uploaded from, released with the
+    /// upload
+    buffer : GlBuffer,
+    /// The fence after the upload (0 if none could be created)
+    fence : usize,
+    /// The context generation the fence was created in
+    generation : u32,
+    /// True if mipmaps are to be generated when the upload completes
+    mipmaps : bool,
+}
+
+//ip PendingUpload
+impl PendingUpload {
+    //fp new
+    /// Create a 2D texture and start uploading image data to it
+    /// through a pixel buffer; mipmaps, if required, are generated
+    /// when the upload completes
+    pub(crate) fn new(width:usize, height:usize, format:TextureFormat, data:&[u8], mipmaps:bool) -> Result<Self, String> {
+        if format.is_compressed() {
+            return Err(format!("Cannot upload a {} texture asynchronously", format));
+        }
+        let byte_length = format.image_bytes(width, height);
+        if data.len() < byte_length {
+            return Err(format!("Image data of {} bytes is too small for {}x{} {} ({} bytes)",
+                               data.len(), width, height, format, byte_length));
+        }
+        let texture = Texture::new_2d(width, height, format, None)?;
+        if mipmaps {
+            texture.check_mipmaps()?;
+        }
+        let mut buffer = GlBuffer::default();
+        buffer.pixel_unpack_buffer(byte_length);
+        let written = buffer.write_mapped(gl::PIXEL_UNPACK_BUFFER, |mapped| {
+            mapped.copy_from_slice(&data[0..byte_length]);
+        }).and_then(|()| {
+            texture.bind()?;
+            Backend::pixel_store(gl::UNPACK_ALIGNMENT, 1);
+            Backend::tex_sub_image_2d_buffer(gl::TEXTURE_2D, 0, (0, 0), (width as GLsizei, height as GLsizei),
+                                             format.pixel_format(), 0);
+            texture_units::bind_texture(gl::TEXTURE_2D, 0)
+        });
+        Backend::bind_buffer(gl::PIXEL_UNPACK_BUFFER, 0);
+        written?;
+        let fence = Backend::fence_sync();
+        texture.set_ready(false);
+        let texture = Rc::new(texture);
+        Ok(Self { texture, buffer, fence, generation:recovery::generation(), mipmaps })
+    }
+
+    //ap texture
+    /// Get the texture being uploaded to
+    pub(crate) fn texture(&self) -> &Rc<Texture> {
+        &self.texture
+    }
+
+    //ap byte_length
+    /// Get the size of the pixel buffer in bytes
+    pub(crate) fn byte_length(&self) -> usize {
+        self.buffer.byte_length()
+    }
+
+    //mp poll
+    /// Return true, generating the mipmaps (if required) and marking
+    /// the texture ready, if the upload has completed
+    ///
+    /// A failure to generate the mipmaps is logged, and the texture
+    /// is used with just its base level
+    pub(crate) fn poll(&self) -> bool {
+        if self.fence != 0 && !Backend::fence_signaled(self.fence) {
+            return false;
+        }
+        if self.mipmaps {
+            if let Err(e) = self.texture.gl_generate_mipmaps() {
+                log::error!("Texture upload: {}", e);
+            }
+        }
+        self.texture.set_ready(true);
+        true
+    }
+}
+
+//ip Drop for PendingUpload
+impl Drop for PendingUpload {
+    //fp drop
+    /// Delete the fence, if it is in the current context; this is
+    /// only dropped on the render thread, so it is deleted directly
+    fn drop(&mut self) {
+        if self.fence != 0 && recovery::is_current(self.generation) {
+            Backend::delete_fence(self.fence);
+        }
+    }
+}
+
+//a Placeholders
+//ti Placeholders
+/// The placeholder textures of the slots, created when first required
+#[derive(Debug, Default)]
+struct Placeholders {
+    /// The context generation the textures are for
+    generation : u32,
+    /// The textures created, by slot
+    textures : Vec<(TextureSlot, Rc<Texture>)>,
+}
+
+//a Statics
+thread_local! {
+    /// The placeholder textures of the render thread
+    static PLACEHOLDERS : RefCell<Placeholders> = RefCell::new(Placeholders::default());
+}
+
+//a Functions
+//fp placeholder
+/// Get the placeholder texture for a slot, creating it if required
+pub(crate) fn placeholder(slot:TextureSlot) -> Result<Rc<Texture>, String> {
+    PLACEHOLDERS.with(|p| {
+        let mut p = p.borrow_mut();
+        if !recovery::is_current(p.generation) {
+            p.generation = recovery::generation();
+            p.textures.clear();
+        }
+        if let Some((_, texture)) = p.textures.iter().find(|(s, _)| *s == slot) {
+            return Ok(texture.clone());
+        }
+        let texel : [u8; 4] = {
+            match slot {
+                TextureSlot::Normal => [128, 128, 255, 255],
+                TextureSlot::Emissive => [0, 0, 0, 255],
+                _ => [255, 255, 255, 255],
+            }
+        };
+        let texture = Rc::new(Texture::new_2d(1, 1, TextureFormat::Rgba8, Some(&texel))?);
+        texture.set_label("placeholder");
+        p.textures.push((slot, texture.clone()));
+        Ok(texture)
+    })
+}
+
+//fp clear_placeholders
+/// Drop the placeholder textures (when the [crate::RenderContext] is
+/// dropped)
+pub(crate) fn clear_placeholders() {
+    PLACEHOLDERS.with(|p| p.borrow_mut().textures.clear());
+}
@@ -1,6 +1,11 @@
 use std;
 use std::ffi::{CString};
 
+use geo_nd::matrix;
+
+use crate::{Backend, GlBackend};
+use crate::Mat4;
+
 //a Functions
 //fp create_whitespace_cstring_with_len
 /// Create a CString of 'len' spaces (with null termination)
@@ -19,18 +24,30 @@ pub fn create_whitespace_cstring_with_len(len: usize) -> CString {
 pub fn check_errors() -> Result<(), Vec<String>> {
     let mut v = Vec::new();
     loop {
-        match unsafe {gl::GetError()} {
+        match Backend::get_error() {
             gl::NO_ERROR => {
                 break;
             },
             gl::INVALID_ENUM => {
-                v.push(format!("Invalid enum (Gl error)"));
+                v.push("Invalid enum (Gl error)".to_string());
             }
             gl::INVALID_VALUE =>  {
-                v.push(format!("Invalid value (Gl error)"));
+                v.push("Invalid value (Gl error)".to_string());
             }
             gl::INVALID_OPERATION =>  {
-                v.push(format!("Invalid operation (Gl error)"));
+                v.push("Invalid operation (Gl error)".to_string());
+            }
+            gl::INVALID_FRAMEBUFFER_OPERATION =>  {
+                v.push("Invalid framebuffer operation (Gl error)".to_string());
+            }
+            gl::OUT_OF_MEMORY =>  {
+                v.push("Out of memory (Gl error)".to_string());
+            }
+            gl::STACK_OVERFLOW =>  {
+                v.push("Stack overflow (Gl error)".to_string());
+            }
+            gl::STACK_UNDERFLOW =>  {
+                v.push("Stack underflow (Gl error)".to_string());
             }
             x => {
                 v.push(format!("GL had error {}",x));
@@ -46,6 +63,9 @@ pub fn check_errors() -> Result<(), Vec<String>> {
 
 //fp get_shaderiv
 /// Get an integer value from a particular shader
+///
+/// This uses the `gl` crate directly, and so is not supported by
+/// other backends
 pub fn get_shaderiv(id : gl::types::GLuint, x:gl::types::GLuint) -> gl::types::GLint {
     unsafe {
         let mut r = 0;
@@ -56,6 +76,9 @@ pub fn get_shaderiv(id : gl::types::GLuint, x:gl::types::GLuint) -> gl::types::G
 
 //fp get_programiv
 /// Get an integer value from a particular program
+///
+/// This uses the `gl` crate directly, and so is not supported by
+/// other backends
 pub fn get_programiv(id : gl::types::GLuint, x:gl::types::GLuint) -> gl::types::GLint {
     unsafe {
         let mut r = 0;
@@ -73,3 +96,42 @@ pub fn get_shader_error<F:FnOnce(gl::types::GLuint)->i32, E:FnOnce(gl::types::GL
     e(id, len, error.as_ptr() as *mut gl::types::GLchar);
     format!("{}", error.to_string_lossy())
 }
+
+//a Matrix functions
+//fp mat4_mul
+/// Multiply two column-major 4x4 matrices (a * b)
+///
+/// geo-nd multiplies row-major matrices, so the operands are swapped
+pub(crate) fn mat4_mul(a:&Mat4, b:&Mat4) -> Mat4 {
+    matrix::multiply4(b, a)
+}
+
+//fp mat4_transform
+/// Transform a point by a column-major 4x4 matrix, with perspective
+/// division
+pub(crate) fn mat4_transform(m:&Mat4, p:[f32; 3]) -> [f32; 3] {
+    // The point as a row vector times the transposed matrix
+    let v = matrix::multiply::<f32, 4, 16, 4, 1, 4, 4>(&[p[0], p[1], p[2], 1.], m);
+    let w = if v[3] == 0. { 1. } else { v[3] };
+    [v[0] / w, v[1] / w, v[2] / w]
+}
+
+//fp mat4_inverse
+/// Invert a column-major 4x4 matrix, returning None if it is singular
+/// or its inverse is not finite
+///
+/// Only a zero determinant is rejected, not a small one, as the
+/// determinant of a valid projection may be tiny (that of an
+/// orthographic projection 1000 units across is about 1E-8)
+pub(crate) fn mat4_inverse(m:&Mat4) -> Option<Mat4> {
+    let det = matrix::determinant4(m);
+    if det == 0. || !det.is_finite() {
+        return None;
+    }
+    let inverse = matrix::inverse4(m);
+    if inverse.iter().all(|x| x.is_finite()) {
+        Some(inverse)
+    } else {
+        None
+    }
+}
@@ -17,9 +17,16 @@ limitations under the License.
  */
 
 //a Imports
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use model3d::{VertexAttr};
 
-use crate::{IndexBuffer, VertexBuffer, Renderable, RenderContext};
+use crate::{IndexBuffer, VertexBuffer, Renderable, RenderContext, BoundingBox};
+
+//a Statics
+//ci NEXT_VERTICES_ID
+/// The id to give to the next [Vertices] created
+static NEXT_VERTICES_ID : AtomicUsize = AtomicUsize::new(0);
 
 //a Vertices
 //tp Vertices
@@ -30,17 +37,52 @@ use crate::{IndexBuffer, VertexBuffer, Renderable, RenderContext};
 /// invoking create_client on a [model3d::Object]
 #[derive(Debug, Clone)]
 pub struct Vertices {
+    /// A unique id, shared only by clones (which use the same buffers)
+    id         : usize,
     indices    : IndexBuffer,
     position   : VertexBuffer,
     attrs      : Vec<(VertexAttr, VertexBuffer)>,
+    /// Bounding box of the positions used by the indices, if known
+    bounds     : Option<BoundingBox>,
 }
 
 impl Vertices {
+    //fi next_id
+    /// Allocate a unique id for a new [Vertices]
+    fn next_id() -> usize {
+        NEXT_VERTICES_ID.fetch_add(1, Ordering::Relaxed)
+    }
+
+    //ap id
+    /// Get the id of the vertices, which identifies their buffers
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
     //fp borrow
     /// Borrow the indices, positions, and the array of other attributes
     pub fn borrow(&self) -> (&IndexBuffer, &VertexBuffer, &Vec<(VertexAttr, VertexBuffer)>) {
         (&self.indices, &self.position, &self.attrs)
     }
+
+    //ap bounds
+    /// Get the bounding box of the vertex positions, if known
+    pub fn bounds(&self) -> Option<&BoundingBox> {
+        self.bounds.as_ref()
+    }
+
+    //mp set_label
+    /// Label the OpenGL buffers of the indices and vertex data (if
+    /// debug output is enabled) with a name
+    pub fn set_label(&self, name:&str) {
+        self.indices.set_label(&format!("{} indices", name));
+        self.position.set_label(&format!("{} vertex data", name));
+        for (attr, buffer) in &self.attrs {
+            if buffer.gl_buffer() != self.position.gl_buffer() {
+                buffer.set_label(&format!("{} {:?} data", name, attr));
+            }
+        }
+    }
 }
 
 //ip Display for Vertices
@@ -58,7 +100,9 @@ impl Default for Vertices {
         let indices  = IndexBuffer::default();
         let position = VertexBuffer::default();
         let attrs = Vec::new();
-        Self { indices, position, attrs }
+        let bounds = None;
+        let id = Self::next_id();
+        Self { id, indices, position, attrs, bounds }
     }
 
 }
@@ -74,6 +118,8 @@ impl model3d::VerticesClient<Renderable> for Vertices {
         for (attr, buffer) in vertices.iter_attrs() {
             attrs.push( (*attr, buffer.borrow_client().as_vertex_buffer().clone() ) );
         }
-        Self { indices, position, attrs }
+        let bounds = BoundingBox::of_view(vertices.borrow_position(), vertices.borrow_indices());
+        let id = Self::next_id();
+        Self { id, indices, position, attrs, bounds }
     }
 }
@@ -0,0 +1,121 @@
+//! Tests of [gl_model::MissingAttributes] and of the
+//! [gl_model::AttributeReport] of a [gl_model::ShaderInstantiable]
+//! (with the mock GL)
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+mod common;
+
+use gl_model::{AttributeReport, MissingAttributes};
+use model3d::VertexAttr;
+
+#[test]
+fn defaults_replace_earlier_defaults() {
+    let missing = MissingAttributes::new()
+        .with_default(VertexAttr::Color, [1., 0., 0., 1.])
+        .with_default(VertexAttr::Normal, [0., 0., 1., 0.])
+        .with_default(VertexAttr::Color, [0., 1., 0., 1.]);
+    assert_eq!(missing.default_of(VertexAttr::Color), Some(&[0., 1., 0., 1.]));
+    assert_eq!(missing.default_of(VertexAttr::Normal), Some(&[0., 0., 1., 0.]));
+    assert_eq!(missing.default_of(VertexAttr::Tangent), None);
+}
+
+#[test]
+fn reports_are_clean_without_missing_attributes() {
+    let mut report = AttributeReport::default();
+    assert!(report.is_clean());
+    assert_eq!(report.to_string(), "");
+    report.defaulted.push((0, VertexAttr::Color));
+    report.unused.push((1, VertexAttr::Normal));
+    assert!(report.is_clean());
+    report.missing.push((0, VertexAttr::Tangent));
+    assert!(!report.is_clean());
+    assert_eq!(report.to_string(),
+               "vertices 0: Tangent missing\nvertices 0: Color missing, using default\nvertices 1: Normal unused\n");
+}
+
+/// Compile a program with attributes (at locations from 0 up)
+#[cfg(all(feature="mock", not(feature="glow")))]
+fn program(attributes:&[(&str, VertexAttr)]) -> gl_model::GlProgram {
+    use gl_model::mock_gl;
+    let mut source = "#version 330 core\n".to_string();
+    for (i, (name, _)) in attributes.iter().enumerate() {
+        mock_gl::set_attrib_location(name, i as i32);
+        source += &format!("in vec4 {};\n", name);
+    }
+    source += "void main() {}\n";
+    let mut program = gl_model::GlProgram::compile_program(&[(gl::VERTEX_SHADER, &source)]).unwrap();
+    for (name, vertex_attr) in attributes {
+        program.add_attr_name(name, *vertex_attr).unwrap();
+    }
+    program
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn missing_and_defaulted_attributes() {
+    use gl_model::mock_gl::{self, GlCall};
+    use gl_model::{RenderContext, ShaderInstantiable};
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    let program = program(&[("position", VertexAttr::Position),
+                            ("normal", VertexAttr::Normal),
+                            ("color", VertexAttr::Color)]);
+
+    let (_, report) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    assert_eq!(report.missing, vec![(0, VertexAttr::Normal), (0, VertexAttr::Color)]);
+    assert!(report.defaulted.is_empty() && report.unused.is_empty());
+    assert!(!report.is_clean());
+
+    let missing = MissingAttributes::new().with_default(VertexAttr::Color, [1., 0., 0., 1.]);
+    let (shader_instantiable, report) = ShaderInstantiable::new(&program, &instantiable, &missing).unwrap();
+    assert_eq!(report.missing, vec![(0, VertexAttr::Normal)]);
+    assert_eq!(report.defaulted, vec![(0, VertexAttr::Color)]);
+
+    // The default is set for the draw, and reset afterwards
+    mock_gl::take_calls();
+    shader_instantiable.gl_draw(&instantiable.instantiate());
+    let attribs : Vec<_> = mock_gl::take_calls().into_iter().filter(|c| matches!(c, GlCall::VertexAttrib4f(..))).collect();
+    assert_eq!(attribs, vec![GlCall::VertexAttrib4f(2, 1., 0., 0., 1.),
+                             GlCall::VertexAttrib4f(2, 0., 0., 0., 1.)]);
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn fail_if_missing() {
+    use gl_model::mock_gl;
+    use gl_model::{RenderContext, ShaderInstantiable};
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    let program = program(&[("position", VertexAttr::Position), ("color", VertexAttr::Color)]);
+    let missing = MissingAttributes::new().fail_if_missing();
+    let e = ShaderInstantiable::new(&program, &instantiable, &missing).err().unwrap();
+    assert!(e.contains("Color"), "{}", e);
+
+    // A default satisfies the requirement
+    let missing = missing.with_default(VertexAttr::Color, [1.; 4]);
+    let (_, report) = ShaderInstantiable::new(&program, &instantiable, &missing).unwrap();
+    assert!(report.is_clean());
+    assert_eq!(report.defaulted, vec![(0, VertexAttr::Color)]);
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn unused_attributes() {
+    use gl_model::mock_gl;
+    use gl_model::{RenderContext, ShaderInstantiable};
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    let positioned = program(&[("position", VertexAttr::Position)]);
+    let (_, report) = ShaderInstantiable::new(&positioned, &instantiable, &MissingAttributes::new()).unwrap();
+    assert_eq!(report, AttributeReport::default());
+
+    // A shader without a position attribute does not use the positions
+    let colored = program(&[("color", VertexAttr::Color)]);
+    let missing = MissingAttributes::new().with_default(VertexAttr::Color, [1.; 4]);
+    let (_, report) = ShaderInstantiable::new(&colored, &instantiable, &missing).unwrap();
+    assert_eq!(report.unused, vec![(0, VertexAttr::Position)]);
+    assert_eq!(report.to_string(), "vertices 0: Color missing, using default\nvertices 0: Position unused\n");
+}
@@ -0,0 +1,55 @@
+//! Tests of the glow backend's object name tables, with a headless
+//! EGL context
+//!
+//! These are skipped (with a message) if no EGL display can be
+//! initialized; with Mesa and no display server set
+//! `EGL_PLATFORM=surfaceless`.
+#![cfg(all(feature="headless", feature="glow"))]
+
+mod common;
+
+use common::headless;
+use gl_model::GlProgram;
+use gl_model::{Backend, GlBackend};
+
+const VERTEX_SHADER : &str = "#version 330 core
+uniform vec4 offset;
+void main() { gl_Position = offset; }
+";
+
+const FRAGMENT_SHADER : &str = "#version 330 core
+out vec4 fragColor;
+void main() { fragColor = vec4(1.0); }
+";
+
+#[test]
+fn uniform_locations() {
+    let Some(_context) = headless() else { return; };
+    let program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, VERTEX_SHADER),
+                                              (gl::FRAGMENT_SHADER, FRAGMENT_SHADER)]).unwrap();
+    let location = Backend::uniform_location(program.id(), "offset");
+    assert!(location.is_some());
+    assert_eq!(Backend::uniform_location(program.id(), "offset"), location);
+    assert_eq!(Backend::uniform_location(program.id(), "missing"), None);
+    assert_eq!(Backend::uniform_location(program.id() + 100, "offset"), None);
+}
+
+#[test]
+fn unknown_names_are_errors() {
+    let Some(_context) = headless() else { return; };
+    while Backend::get_error() != gl::NO_ERROR {}
+    Backend::link_program(100);
+    Backend::uniform_block_binding(100, 0, 0);
+    assert_eq!(Backend::get_error(), gl::INVALID_VALUE);
+    assert_eq!(Backend::get_error(), gl::NO_ERROR);
+    assert!(!Backend::program_link_status(100));
+    assert_eq!(Backend::program_info_log(100), "");
+    assert_eq!(Backend::get_error(), gl::INVALID_VALUE);
+    Backend::compile_shader(100);
+    assert!(!Backend::shader_compile_status(100));
+    assert_eq!(Backend::shader_info_log(100), "");
+    assert_eq!(Backend::get_error(), gl::INVALID_VALUE);
+    assert!(!Backend::query_result_available(100));
+    assert_eq!(Backend::get_error(), gl::INVALID_OPERATION);
+    assert_eq!(Backend::get_error(), gl::NO_ERROR);
+}
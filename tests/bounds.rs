@@ -0,0 +1,68 @@
+//! Tests of the bounding boxes of vertex positions in buffer data
+//! shared with other attributes and indices
+
+use gl_model::{BoundingBox, max_index};
+use model3d::BufferElementType;
+
+/// Buffer data with four vertices of interleaved positions and
+/// normals (24 bytes each), followed by six Int8 indices that use only
+/// the first three vertices; the fourth vertex is far from the others
+fn shared_data() -> Vec<u8> {
+    let vertices : [[f32; 6]; 4] = [
+        [0., 0., 0., 0., 0., 1.],
+        [1., 0., 0., 0., 0., 1.],
+        [0., 2., -1., 0., 0., 1.],
+        [1000., -1000., 1000., 0., 0., 1.],
+    ];
+    let mut data = Vec::new();
+    for v in vertices.iter() {
+        for x in v {
+            data.extend_from_slice(&x.to_ne_bytes());
+        }
+    }
+    data.extend_from_slice(&[0, 1, 2, 2, 1, 0]);
+    data
+}
+
+#[test]
+fn max_index_of_each_type() {
+    let data = shared_data();
+    assert_eq!(max_index(&data, 96, BufferElementType::Int8, 6), Some(2));
+    let mut data = Vec::new();
+    for i in [3_u16, 300, 7] {
+        data.extend_from_slice(&i.to_ne_bytes());
+    }
+    assert_eq!(max_index(&data, 0, BufferElementType::Int16, 3), Some(300));
+    assert_eq!(max_index(&data, 2, BufferElementType::Int16, 2), Some(300));
+    let data = 70000_u32.to_ne_bytes();
+    assert_eq!(max_index(&data, 0, BufferElementType::Int32, 1), Some(70000));
+}
+
+#[test]
+fn max_index_out_of_range() {
+    let data = shared_data();
+    assert_eq!(max_index(&data, 96, BufferElementType::Int8, 7), None);
+    assert_eq!(max_index(&data, 96, BufferElementType::Int8, 0), None);
+    assert_eq!(max_index(&data, 0, BufferElementType::Float32, 1), None);
+    assert_eq!(max_index(&data, usize::MAX, BufferElementType::Int32, 2), None);
+}
+
+#[test]
+fn positions_stop_at_the_vertex_count() {
+    let data = shared_data();
+    let vertex_count = max_index(&data, 96, BufferElementType::Int8, 6).unwrap() + 1;
+    let bbox = BoundingBox::of_positions(&data, 0, 24, vertex_count).unwrap();
+    assert_eq!(bbox.min, [0., 0., -1.]);
+    assert_eq!(bbox.max, [1., 2., 0.]);
+    let bbox = BoundingBox::of_positions(&data, 0, 24, 4).unwrap();
+    assert_eq!(bbox.max, [1000., 2., 1000.]);
+}
+
+#[test]
+fn positions_outside_the_data() {
+    let data = shared_data();
+    assert_eq!(BoundingBox::of_positions(&data, 0, 24, 0), None);
+    assert_eq!(BoundingBox::of_positions(&data, 0, 24, 5), None);
+    assert_eq!(BoundingBox::of_positions(&data, 36, 24, 4), None);
+    assert_eq!(BoundingBox::of_positions(&data, 0, usize::MAX, 2), None);
+}
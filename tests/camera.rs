@@ -0,0 +1,142 @@
+//! Tests of the camera matrices and frustum culling, and of the
+//! uniforms set by [gl_model::Camera::upload] (with the mock GL)
+
+use gl_model::{BoundingBox, Camera, Frustum, Mat4};
+
+/// Transform a point by a column-major matrix, with the perspective
+/// divide
+fn transform(m:&Mat4, p:[f32; 3]) -> [f32; 3] {
+    let mut r = [0.; 4];
+    for (i, x) in r.iter_mut().enumerate() {
+        *x = m[i] * p[0] + m[4 + i] * p[1] + m[8 + i] * p[2] + m[12 + i];
+    }
+    [r[0] / r[3], r[1] / r[3], r[2] / r[3]]
+}
+
+fn assert_near(a:[f32; 3], b:[f32; 3]) {
+    for i in 0..3 {
+        assert!((a[i] - b[i]).abs() < 1.0e-5, "{:?} should be {:?}", a, b);
+    }
+}
+
+fn bbox(min:[f32; 3], max:[f32; 3]) -> BoundingBox {
+    BoundingBox::of_point(min).including(max)
+}
+
+const IDENTITY : Mat4 = [1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1.];
+
+#[test]
+fn frustum_of_identity_is_the_clip_cube() {
+    let frustum = Frustum::of_matrix(&IDENTITY);
+    assert_eq!(frustum.planes,
+               [[1., 0., 0., 1.], [-1., 0., 0., 1.],
+                [0., 1., 0., 1.], [0., -1., 0., 1.],
+                [0., 0., 1., 1.], [0., 0., -1., 1.]]);
+}
+
+#[test]
+fn frustum_of_perspective() {
+    let projection = Camera::perspective(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 100.0);
+    let frustum = Frustum::of_matrix(&projection);
+    // Points are inside a plane if ax+by+cz+d >= 0
+    let inside = |p:[f32; 3]| frustum.planes.iter().all(|q| q[0] * p[0] + q[1] * p[1] + q[2] * p[2] + q[3] >= -1.0e-4);
+    assert!(inside([0., 0., -1.]));
+    assert!(inside([0., 0., -100.]));
+    assert!(inside([9., 9., -10.]));
+    assert!(!inside([11., 0., -10.]));
+    assert!(!inside([0., -11., -10.]));
+    assert!(!inside([0., 0., -0.5]));
+    assert!(!inside([0., 0., -101.]));
+    assert!(!inside([0., 0., 1.]));
+}
+
+#[test]
+fn intersects_box() {
+    let frustum = Frustum::of_matrix(&IDENTITY);
+    assert!(frustum.intersects_box(&bbox([-0.5, -0.5, -0.5], [0.5, 0.5, 0.5])));
+    assert!(frustum.intersects_box(&bbox([-5., -5., -5.], [5., 5., 5.])));
+    assert!(frustum.intersects_box(&bbox([0.5, 0.5, 0.5], [2., 2., 2.])));
+    assert!(frustum.intersects_box(&bbox([1., 1., 1.], [2., 2., 2.])));
+    assert!(!frustum.intersects_box(&bbox([1.5, 0., 0.], [2., 0.5, 0.5])));
+    assert!(!frustum.intersects_box(&bbox([0., -3., 0.], [0.5, -2., 0.5])));
+    assert!(!frustum.intersects_box(&bbox([0., 0., 1.01], [0., 0., 2.])));
+}
+
+#[test]
+fn look_at() {
+    let view = Camera::look_at([0., 0., 5.], [0., 0., 0.], [0., 1., 0.]);
+    assert_near(transform(&view, [0., 0., 5.]), [0., 0., 0.]);
+    assert_near(transform(&view, [0., 0., 0.]), [0., 0., -5.]);
+    assert_near(transform(&view, [1., 2., 5.]), [1., 2., 0.]);
+
+    let view = Camera::look_at([3., 0., 0.], [0., 0., 0.], [0., 1., 0.]);
+    assert_near(transform(&view, [0., 0., 0.]), [0., 0., -3.]);
+    assert_near(transform(&view, [3., 1., 0.]), [0., 1., 0.]);
+    // Looking along -x, so +z is to the left
+    assert_near(transform(&view, [3., 0., 1.]), [-1., 0., 0.]);
+}
+
+#[test]
+fn orthographic() {
+    let projection = Camera::orthographic(-2., 4., -1., 3., 1., 11.);
+    assert_near(transform(&projection, [-2., -1., -1.]), [-1., -1., -1.]);
+    assert_near(transform(&projection, [4., 3., -11.]), [1., 1., 1.]);
+    assert_near(transform(&projection, [1., 1., -6.]), [0., 0., 0.]);
+    let (near, far) = Camera::near_far(&projection);
+    assert!((near - 1.).abs() < 1.0e-5 && (far - 11.).abs() < 1.0e-4, "{} {}", near, far);
+    let (near, far) = Camera::near_far(&Camera::perspective(1.0, 1.5, 0.1, 200.));
+    assert!((near - 0.1).abs() < 1.0e-5 && (far - 200.).abs() < 1.0e-2, "{} {}", near, far);
+}
+
+#[test]
+fn visibility_of_transformed_boxes() {
+    let view = Camera::look_at([0., 0., 10.], [0., 0., 0.], [0., 1., 0.]);
+    let camera = Camera::new(view, Camera::perspective(1.0, 1.0, 0.1, 100.));
+    let unit = bbox([-1., -1., -1.], [1., 1., 1.]);
+    assert!(camera.is_visible(&unit, &IDENTITY));
+    let mut behind = IDENTITY;
+    behind[14] = 20.;
+    assert!(!camera.is_visible(&unit, &behind));
+    let mut aside = IDENTITY;
+    aside[12] = 50.;
+    assert!(!camera.is_visible(&unit, &aside));
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn upload_sets_each_matrix_uniform() {
+    use gl_model::mock_gl::{self, GlCall};
+    use gl_model::{GlProgram, RenderContext, UniformId};
+    mock_gl::install();
+    let _render_context = RenderContext::new();
+    mock_gl::set_uniform_location("view", 1);
+    mock_gl::set_uniform_location("projection", 2);
+    mock_gl::set_uniform_location("view_projection", 3);
+    let source = "#version 330 core\nvoid main() {}\n";
+    let view = Camera::look_at([0., 0., 10.], [0., 0., 0.], [0., 1., 0.]);
+    let camera = Camera::new(view, Camera::orthographic(-1., 1., -1., 1., 1., 20.));
+
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, source)]).unwrap();
+    program.add_uniform_name("view", UniformId::ViewMatrix).unwrap();
+    program.add_uniform_name("projection", UniformId::ProjectionMatrix).unwrap();
+    mock_gl::take_calls();
+    camera.upload(&program);
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::UniformMatrix4fv(1, gl::FALSE, camera.view().to_vec()),
+                    GlCall::UniformMatrix4fv(2, gl::FALSE, camera.projection().to_vec())]);
+
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, source)]).unwrap();
+    program.add_uniform_name("view_projection", UniformId::ViewProjectionMatrix).unwrap();
+    mock_gl::take_calls();
+    camera.upload(&program);
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::UniformMatrix4fv(3, gl::FALSE, camera.view_projection().to_vec())]);
+
+    // A view matrix alone is just the view matrix
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, source)]).unwrap();
+    program.add_uniform_name("view", UniformId::ViewMatrix).unwrap();
+    mock_gl::take_calls();
+    camera.upload(&program);
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::UniformMatrix4fv(1, gl::FALSE, camera.view().to_vec())]);
+}
@@ -0,0 +1,172 @@
+//! Tests of the detection of OpenGL capabilities and features, with
+//! the version strings and extensions set in the mock GL
+#![cfg(all(feature="mock", not(feature="glow")))]
+
+use gl_model::mock_gl::{self, GlCall};
+use gl_model::{Capabilities, GlBuffer, GlFeature, GlProfile, RenderContext};
+
+#[test]
+fn desktop_core_profile() {
+    mock_gl::install();
+    mock_gl::set_integer(gl::MAX_SAMPLES, 4);
+    let capabilities = Capabilities::query();
+    assert_eq!(capabilities.version, (4, 6));
+    assert_eq!(capabilities.glsl_version, (4, 60));
+    assert_eq!(capabilities.profile, GlProfile::Core);
+    assert_eq!(capabilities.vendor, "Mock");
+    assert_eq!(capabilities.renderer, "Mock GL");
+    assert_eq!(capabilities.limits.max_vertex_attribs, 16);
+    assert_eq!(capabilities.limits.max_texture_units, 32);
+    assert_eq!(capabilities.limits.max_samples, 4);
+    assert!(capabilities.supports(GlFeature::UniformBuffers));
+    assert!(capabilities.supports(GlFeature::BufferStorage));
+    assert!(capabilities.supports(GlFeature::DebugOutput));
+    assert!(capabilities.supports(GlFeature::SeamlessCubeMap));
+    assert!(!capabilities.supports(GlFeature::TextureCompressionS3tc));
+    assert!(!capabilities.supports(GlFeature::TextureCompressionAstc));
+}
+
+#[test]
+fn desktop_version_and_extensions() {
+    mock_gl::install();
+    mock_gl::set_string(gl::VERSION, "3.3.0 Mock");
+    mock_gl::set_string(gl::SHADING_LANGUAGE_VERSION, "3.30 Mock");
+    let capabilities = Capabilities::query();
+    assert_eq!(capabilities.version, (3, 3));
+    assert!(capabilities.supports(GlFeature::UniformBuffers));
+    assert!(!capabilities.supports(GlFeature::BufferStorage));
+    assert!(!capabilities.supports(GlFeature::DebugOutput));
+    assert!(!capabilities.supports(GlFeature::AnisotropicFiltering));
+    assert_eq!(capabilities.limits.max_anisotropy, 0.);
+
+    mock_gl::set_extensions(&["GL_ARB_buffer_storage", "GL_KHR_debug",
+                              "GL_EXT_texture_filter_anisotropic", "GL_EXT_texture_compression_s3tc"]);
+    mock_gl::set_integer(0x84FF, 16);
+    let capabilities = Capabilities::query();
+    assert!(capabilities.has_extension("GL_KHR_debug"));
+    assert!(capabilities.supports(GlFeature::BufferStorage));
+    assert!(capabilities.supports(GlFeature::DebugOutput));
+    assert!(capabilities.supports(GlFeature::AnisotropicFiltering));
+    assert_eq!(capabilities.limits.max_anisotropy, 16.);
+    assert!(capabilities.supports(GlFeature::TextureCompressionS3tc));
+    assert!(!capabilities.supports(GlFeature::TextureCompressionS3tcSrgb));
+}
+
+#[test]
+fn compatibility_profile() {
+    mock_gl::install();
+    mock_gl::set_integer(gl::CONTEXT_PROFILE_MASK, gl::CONTEXT_COMPATIBILITY_PROFILE_BIT as i32);
+    assert_eq!(Capabilities::query().profile, GlProfile::Compatibility);
+    mock_gl::set_string(gl::VERSION, "2.1 Mock");
+    mock_gl::set_integer(gl::CONTEXT_PROFILE_MASK, gl::CONTEXT_CORE_PROFILE_BIT as i32);
+    let capabilities = Capabilities::query();
+    assert_eq!(capabilities.profile, GlProfile::Compatibility);
+    assert!(!capabilities.supports(GlFeature::UniformBuffers));
+}
+
+#[test]
+fn opengl_es() {
+    mock_gl::install();
+    mock_gl::set_string(gl::VERSION, "OpenGL ES 3.0 Mock");
+    mock_gl::set_string(gl::SHADING_LANGUAGE_VERSION, "OpenGL ES GLSL ES 3.00");
+    let capabilities = Capabilities::query();
+    assert!(capabilities.is_gles());
+    assert_eq!(capabilities.profile, GlProfile::Es);
+    assert_eq!(capabilities.version, (3, 0));
+    assert_eq!(capabilities.glsl_version, (3, 0));
+    assert!(capabilities.supports(GlFeature::UniformBuffers));
+    assert!(capabilities.supports(GlFeature::TextureCompressionEtc2));
+    assert!(capabilities.supports(GlFeature::OcclusionQueries));
+    assert!(!capabilities.supports(GlFeature::DebugOutput));
+    assert!(!capabilities.supports(GlFeature::BufferStorage));
+    assert!(!capabilities.supports(GlFeature::SeamlessCubeMap));
+    assert!(!capabilities.supports(GlFeature::FloatRenderTargets));
+    mock_gl::set_extensions(&["GL_EXT_buffer_storage", "GL_EXT_color_buffer_float", "GL_ARB_seamless_cube_map",
+                              "GL_KHR_debug"]);
+    let capabilities = Capabilities::query();
+    assert!(capabilities.supports(GlFeature::BufferStorage));
+    assert!(capabilities.supports(GlFeature::FloatRenderTargets));
+    assert!(!capabilities.supports(GlFeature::SeamlessCubeMap));
+    assert!(!capabilities.supports(GlFeature::DebugOutput));
+    mock_gl::set_string(gl::VERSION, "OpenGL ES 3.2 Mock");
+    assert!(Capabilities::query().supports(GlFeature::DebugOutput));
+}
+
+#[test]
+fn webgl_version() {
+    mock_gl::install();
+    mock_gl::set_string(gl::VERSION, "OpenGL ES 3.0 (WebGL 2.0 (OpenGL ES 3.0 Chromium))");
+    assert_eq!(Capabilities::query().version, (3, 0));
+    mock_gl::set_string(gl::VERSION, "WebGL 2.0 (OpenGL ES 3.0 Chromium)");
+    assert_eq!(Capabilities::query().version, (3, 0));
+}
+
+#[test]
+fn direct_state_access_and_multi_draw_indirect() {
+    mock_gl::install();
+    let capabilities = Capabilities::query();
+    assert!(capabilities.supports(GlFeature::DirectStateAccess));
+    assert!(capabilities.supports(GlFeature::MultiDrawIndirect));
+
+    mock_gl::set_string(gl::VERSION, "4.4.0 Mock");
+    let capabilities = Capabilities::query();
+    assert!(!capabilities.supports(GlFeature::DirectStateAccess));
+    assert!(capabilities.supports(GlFeature::MultiDrawIndirect));
+    mock_gl::set_string(gl::VERSION, "4.2.0 Mock");
+    let render_context = RenderContext::new();
+    let e = render_context.require(GlFeature::DirectStateAccess).unwrap_err();
+    assert!(e.contains("'direct state access' is unsupported"), "{}", e);
+    let e = render_context.require(GlFeature::MultiDrawIndirect).unwrap_err();
+    assert!(e.contains("'multi-draw indirect' is unsupported"), "{}", e);
+    mock_gl::set_extensions(&["GL_ARB_direct_state_access", "GL_ARB_multi_draw_indirect"]);
+    let render_context = RenderContext::new();
+    assert!(render_context.require(GlFeature::DirectStateAccess).is_ok());
+    assert!(render_context.require(GlFeature::MultiDrawIndirect).is_ok());
+
+    mock_gl::set_string(gl::VERSION, "OpenGL ES 3.2 Mock");
+    mock_gl::set_string(gl::SHADING_LANGUAGE_VERSION, "OpenGL ES GLSL ES 3.20");
+    mock_gl::set_extensions(&[]);
+    let capabilities = Capabilities::query();
+    assert!(!capabilities.supports(GlFeature::DirectStateAccess));
+    assert!(!capabilities.supports(GlFeature::MultiDrawIndirect));
+    mock_gl::set_extensions(&["GL_EXT_multi_draw_indirect"]);
+    assert!(Capabilities::query().supports(GlFeature::MultiDrawIndirect));
+}
+
+#[test]
+fn require_reports_unsupported_features() {
+    mock_gl::install();
+    mock_gl::set_string(gl::VERSION, "3.0 Mock");
+    let render_context = RenderContext::new();
+    assert!(render_context.require(GlFeature::PrimitiveQueries).is_ok());
+    let e = render_context.require(GlFeature::UniformBuffers).unwrap_err();
+    assert!(e.contains("'uniform buffers' is unsupported by Mock GL"), "{}", e);
+    assert!(e.contains("OpenGL 3.0"), "{}", e);
+    let mut buffer = GlBuffer::default();
+    assert!(buffer.uniform_buffer(&[0.0f32; 4], &render_context).is_err());
+    assert!(buffer.dynamic_uniform_buffer(16, &render_context).is_err());
+    assert!(buffer.is_none());
+}
+
+#[test]
+fn buffer_storage_selects_the_code_path() {
+    mock_gl::install();
+    mock_gl::set_string(gl::VERSION, "4.3.0 Mock");
+    let render_context = RenderContext::new();
+    let mut buffer = GlBuffer::default();
+    mock_gl::take_calls();
+    buffer.uniform_buffer(&[0u8; 4], &render_context).unwrap();
+    assert!(mock_gl::take_calls().contains(&GlCall::BufferData(gl::UNIFORM_BUFFER, 4, gl::STATIC_DRAW, Some(vec![0; 4]))));
+    drop(buffer);
+
+    mock_gl::set_extensions(&["GL_ARB_buffer_storage"]);
+    let render_context = RenderContext::new();
+    let mut buffer = GlBuffer::default();
+    mock_gl::take_calls();
+    buffer.uniform_buffer(&[0u8; 4], &render_context).unwrap();
+    assert!(mock_gl::take_calls().contains(&GlCall::BufferStorage(gl::UNIFORM_BUFFER, 4, 0, Some(vec![0; 4]))));
+    // Immutable buffers cannot be written
+    assert!(buffer.write(gl::UNIFORM_BUFFER, 0, &[1]).is_err());
+    assert!(buffer.write_mapped(gl::UNIFORM_BUFFER, |_| ()).is_err());
+    assert!(!mock_gl::take_calls().iter().any(|c| matches!(c, GlCall::BufferSubData(..) | GlCall::BindBuffer(..))));
+}
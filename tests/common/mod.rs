@@ -0,0 +1,33 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use gl_model::{Renderable, RenderContext};
+
+/// Create an instantiable of the model3d example triangle (of a size,
+/// with positions and indices only) with its clients in a render
+/// context
+pub fn triangle(render_context:&mut RenderContext, size:f32) -> model3d::Instantiable<Renderable> {
+    let mut example_vertices = model3d::ExampleVertices::new();
+    model3d::example_objects::triangle::new::<Renderable>(&mut example_vertices, size);
+    let mut object : model3d::Object<Renderable> = model3d::Object::new();
+    let v_id = object.add_vertices(example_vertices.borrow_vertices(0));
+    let mut mesh = model3d::Mesh::default();
+    mesh.add_primitive(model3d::Primitive::new(model3d::PrimitiveType::Triangles, v_id, 0, 3, 0));
+    object.add_component(model3d::Component::new(None, Some(mesh)));
+    object.analyze();
+    object.create_client(render_context);
+    object.into_instantiable()
+}
+
+/// Create a headless context, or return None (with a message, so
+/// that the test is skipped) if no EGL display can be initialized
+#[cfg(feature="headless")]
+pub fn headless() -> Option<gl_model::HeadlessContext> {
+    match gl_model::HeadlessContext::new() {
+        Ok(context) => Some(context),
+        Err(e) => {
+            eprintln!("Skipping headless test: {}", e);
+            None
+        }
+    }
+}
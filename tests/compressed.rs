@@ -0,0 +1,231 @@
+//! Tests of the parsing of compressed texture containers, and of the
+//! CPU decompression of compressed blocks
+
+use gl_model::{CompressedImage, CompressedFormat, TextureKind};
+
+const KTX2_IDENTIFIER : [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+
+/// A DXT1 block (4x4 texels) of a single colour
+const DXT1_BLOCK : [u8; 8] = [0x00, 0xF8, 0x00, 0xF8, 0, 0, 0, 0];
+
+fn dds_header(width:u32, height:u32, mip_map_count:u32) -> Vec<u8> {
+    let mut dds = vec![0u8; 128];
+    dds[0..4].copy_from_slice(b"DDS ");
+    dds[4..8].copy_from_slice(&124u32.to_le_bytes());
+    dds[8..12].copy_from_slice(&0x21007u32.to_le_bytes());
+    dds[12..16].copy_from_slice(&height.to_le_bytes());
+    dds[16..20].copy_from_slice(&width.to_le_bytes());
+    dds[28..32].copy_from_slice(&mip_map_count.to_le_bytes());
+    dds[76..80].copy_from_slice(&32u32.to_le_bytes());
+    dds[80..84].copy_from_slice(&4u32.to_le_bytes());
+    dds[84..88].copy_from_slice(b"DXT1");
+    dds
+}
+
+fn ktx2_header(width:u32, height:u32, level_count:u32) -> Vec<u8> {
+    let mut ktx2 = vec![0u8; 80];
+    ktx2[0..12].copy_from_slice(&KTX2_IDENTIFIER);
+    ktx2[12..16].copy_from_slice(&131u32.to_le_bytes());
+    ktx2[20..24].copy_from_slice(&width.to_le_bytes());
+    ktx2[24..28].copy_from_slice(&height.to_le_bytes());
+    ktx2[36..40].copy_from_slice(&1u32.to_le_bytes());
+    ktx2[40..44].copy_from_slice(&level_count.to_le_bytes());
+    ktx2
+}
+
+#[test]
+fn dds_mip_chain() {
+    let mut dds = dds_header(8, 4, 4);
+    // Two blocks for the 8x4 base level, then one for each of 4x2, 2x1 and 1x1
+    for _ in 0..5 {
+        dds.extend_from_slice(&DXT1_BLOCK);
+    }
+    let image = CompressedImage::of_dds(&dds).unwrap();
+    assert_eq!(image.format(), CompressedFormat::Bc1 { alpha:true, srgb:false });
+    assert_eq!(image.kind(), TextureKind::Texture2D);
+    assert_eq!(image.levels(), 4);
+}
+
+#[test]
+fn dds_too_many_levels() {
+    let mut dds = dds_header(8, 4, 5);
+    for _ in 0..6 {
+        dds.extend_from_slice(&DXT1_BLOCK);
+    }
+    assert!(CompressedImage::of_dds(&dds).is_err());
+    assert!(CompressedImage::of_dds(&dds_header(8, 4, 0xffff_ffff)).is_err());
+}
+
+#[test]
+fn ktx2_too_many_levels() {
+    let mut ktx2 = ktx2_header(8, 4, 65);
+    ktx2.resize(80 + 65 * 24, 0);
+    assert!(CompressedImage::of_ktx2(&ktx2).is_err());
+    assert!(CompressedImage::of_ktx2(&ktx2_header(1, 1, 2)).is_err());
+}
+
+#[test]
+fn oversized_headers() {
+    let bc3 = CompressedFormat::Bc3 { srgb:false };
+    assert!(bc3.image_bytes(usize::MAX, usize::MAX).is_err());
+    assert_eq!(bc3.image_bytes(8, 5), Ok(64));
+    // A 4Gx4G DXT5 image has more bytes than can be represented
+    let mut dds = dds_header(0xffff_ffff, 0xffff_ffff, 1);
+    dds[84..88].copy_from_slice(b"DXT5");
+    assert!(CompressedImage::of_dds(&dds).is_err());
+    // A KTX2 array with 4G layers of 4Gx4G images
+    let mut ktx2 = ktx2_header(0xffff_ffff, 0xffff_ffff, 1);
+    ktx2[32..36].copy_from_slice(&0xffff_ffffu32.to_le_bytes());
+    ktx2.resize(80 + 24, 0);
+    assert!(CompressedImage::of_ktx2(&ktx2).is_err());
+    // A KTX2 mip level whose end overflows
+    let mut ktx2 = ktx2_header(4, 4, 1);
+    ktx2.resize(80 + 24, 0);
+    ktx2[80..88].copy_from_slice(&u64::MAX.to_le_bytes());
+    ktx2[88..96].copy_from_slice(&8u64.to_le_bytes());
+    assert!(CompressedImage::of_ktx2(&ktx2).is_err());
+}
+
+/// Decompress a single 4x4 block of a format, returning its texels in
+/// row-major order
+fn decode(format:CompressedFormat, block:&[u8]) -> Vec<[u8; 4]> {
+    let image = CompressedImage::new(format, TextureKind::Texture2D, 4, 4, 1, vec![block.to_vec()]).unwrap();
+    image.decompress(0, 0).unwrap()
+        .chunks(4)
+        .map(|t| [t[0], t[1], t[2], t[3]])
+        .collect()
+}
+
+#[test]
+fn bc1_four_colour() {
+    // c0 = red > c1 = blue, indices 0, 1, 2, 3 in each row
+    let block = [0x00, 0xF8, 0x1F, 0x00, 0xE4, 0xE4, 0xE4, 0xE4];
+    let expected = [[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255]];
+    for alpha in [false, true] {
+        let texels = decode(CompressedFormat::Bc1 { alpha, srgb:false }, &block);
+        for row in texels.chunks(4) {
+            assert_eq!(row, expected);
+        }
+    }
+}
+
+#[test]
+fn bc1_three_colour() {
+    // c0 = blue <= c1 = red, so index 2 is the midpoint and 3 is black
+    let block = [0x1F, 0x00, 0x00, 0xF8, 0xE4, 0xE4, 0xE4, 0xE4];
+    let texels = decode(CompressedFormat::Bc1 { alpha:false, srgb:false }, &block);
+    assert_eq!(&texels[0..4], [[0, 0, 255, 255], [255, 0, 0, 255], [127, 0, 127, 255], [0, 0, 0, 255]]);
+    let texels = decode(CompressedFormat::Bc1 { alpha:true, srgb:false }, &block);
+    assert_eq!(&texels[0..4], [[0, 0, 255, 255], [255, 0, 0, 255], [127, 0, 127, 255], [0, 0, 0, 0]]);
+}
+
+#[test]
+fn bc3_alpha() {
+    // Alpha indices 0 to 7 in each half of the block; the colour block
+    // has c0 <= c1 but is always four-colour, with index 3 in every texel
+    let colour = [0x1F, 0x00, 0x00, 0xF8, 0xFF, 0xFF, 0xFF, 0xFF];
+    let indices = [0x88, 0xC6, 0xFA, 0x88, 0xC6, 0xFA];
+    let format = CompressedFormat::Bc3 { srgb:false };
+
+    // a0 > a1: eight interpolated values
+    let mut block = vec![70, 0];
+    block.extend_from_slice(&indices);
+    block.extend_from_slice(&colour);
+    let texels = decode(format, &block);
+    let alphas : Vec<u8> = texels.iter().map(|t| t[3]).collect();
+    assert_eq!(&alphas[0..8], [70, 0, 60, 50, 40, 30, 20, 10]);
+    assert_eq!(&alphas[8..16], &alphas[0..8]);
+    assert!(texels.iter().all(|t| t[0..3] == [170, 0, 85]));
+
+    // a0 <= a1: six interpolated values, then 0 and 255
+    let mut block = vec![0, 50];
+    block.extend_from_slice(&indices);
+    block.extend_from_slice(&colour);
+    let alphas : Vec<u8> = decode(format, &block).iter().map(|t| t[3]).collect();
+    assert_eq!(&alphas[0..8], [0, 50, 10, 20, 30, 40, 0, 255]);
+}
+
+#[test]
+fn etc2_individual() {
+    // Base colours 136 (left) and 68 (right), tables 0 and 1, with
+    // texel (3, 0) at index 3 and texel (1, 2) at index 1
+    let block = [0x84, 0x84, 0x84, 0x04, 0x10, 0x00, 0x10, 0x40];
+    let texels = decode(CompressedFormat::Etc2Rgb { srgb:false }, &block);
+    let grey = |v:u8| [v, v, v, 255];
+    assert_eq!(&texels[0..4], [grey(138), grey(138), grey(73), grey(51)]);
+    assert_eq!(texels[2 * 4 + 1], grey(144));
+    assert_eq!(texels[3 * 4 + 3], grey(73));
+}
+
+#[test]
+fn etc2_differential() {
+    // Base colours 132 (top) and 115 (bottom, with a delta of -2),
+    // tables 2 and 3, flipped, with texel (0, 3) at index 2
+    let block = [0x86, 0x86, 0x86, 0x4F, 0x00, 0x08, 0x00, 0x00];
+    let texels = decode(CompressedFormat::Etc2Rgb { srgb:false }, &block);
+    let grey = |v:u8| [v, v, v, 255];
+    assert_eq!(&texels[0..4], [grey(141); 4]);
+    assert_eq!(&texels[2 * 4..3 * 4], [grey(128); 4]);
+    assert_eq!(texels[3 * 4], grey(102));
+}
+
+/// Indices 0, 1, 2 and 3 for the texels of the first row
+const ETC2_ROW_INDICES : [u8; 4] = [0x11, 0x00, 0x10, 0x10];
+
+#[test]
+fn etc2_t_mode() {
+    // Red overflows; colours (170, 85, 170) and (68, 136, 34), distance 16
+    let mut block = vec![0xF2, 0x5A, 0x48, 0x27];
+    block.extend_from_slice(&ETC2_ROW_INDICES);
+    let texels = decode(CompressedFormat::Etc2Rgb { srgb:false }, &block);
+    assert_eq!(&texels[0..4], [[170, 85, 170, 255], [84, 152, 50, 255], [68, 136, 34, 255], [52, 120, 18, 255]]);
+    assert_eq!(texels[4], [170, 85, 170, 255]);
+}
+
+#[test]
+fn etc2_h_mode() {
+    // Green overflows; colours (136, 102, 170) and (51, 153, 85), distance 32
+    let mut block = vec![0x43, 0x0D, 0x1C, 0xAE];
+    block.extend_from_slice(&ETC2_ROW_INDICES);
+    let texels = decode(CompressedFormat::Etc2Rgb { srgb:false }, &block);
+    assert_eq!(&texels[0..4], [[168, 134, 202, 255], [104, 70, 138, 255], [83, 185, 117, 255], [19, 121, 53, 255]]);
+}
+
+#[test]
+fn etc2_planar() {
+    // Blue overflows; red ramps from 0 to 255 horizontally and green
+    // vertically
+    let block = [0x00, 0x00, 0x04, 0x7F, 0x00, 0x00, 0x1F, 0xC0];
+    let texels = decode(CompressedFormat::Etc2Rgb { srgb:false }, &block);
+    let ramp = [0, 64, 128, 191];
+    for (i, texel) in texels.iter().enumerate() {
+        assert_eq!(*texel, [ramp[i % 4], ramp[i / 4], 0, 255], "texel {}", i);
+    }
+}
+
+#[test]
+fn formats_without_decompression() {
+    assert!(CompressedFormat::Bc1 { alpha:false, srgb:true }.can_decompress());
+    assert!(CompressedFormat::EacRg11 { signed:false }.can_decompress());
+    assert!(!CompressedFormat::Bc6h { signed:false }.can_decompress());
+    assert!(!CompressedFormat::Bc7 { srgb:false }.can_decompress());
+    assert!(!CompressedFormat::Astc { block:(4, 4), srgb:false }.can_decompress());
+    let image = CompressedImage::new(CompressedFormat::Bc7 { srgb:false }, TextureKind::Texture2D, 4, 4, 1, vec![vec![0; 16]]).unwrap();
+    assert!(image.decompress(0, 0).is_err());
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn unsupported_formats_are_not_decompressed() {
+    use gl_model::{mock_gl, RenderContext, Texture};
+    mock_gl::install();
+    let render_context = RenderContext::new();
+    let image = CompressedImage::new(CompressedFormat::Astc { block:(4, 4), srgb:false }, TextureKind::Texture2D,
+                                     4, 4, 1, vec![vec![0; 16]]).unwrap();
+    let e = Texture::of_compressed(&image, &render_context, true).unwrap_err();
+    assert!(e.contains("cannot be decompressed"), "{}", e);
+    let image = CompressedImage::new(CompressedFormat::Bc1 { alpha:false, srgb:false }, TextureKind::Texture2D,
+                                     4, 4, 1, vec![DXT1_BLOCK.to_vec()]).unwrap();
+    assert_eq!(Texture::of_compressed(&image, &render_context, true).unwrap().format(),
+               gl_model::TextureFormat::Rgba8);
+}
@@ -0,0 +1,62 @@
+//! Tests of rendering environments for image-based lighting, with the
+//! GL calls recorded by the mock GL
+#![cfg(all(feature="mock", not(feature="glow")))]
+
+use std::rc::Rc;
+
+use gl_model::mock_gl::{self, GlCall};
+use gl_model::{Environment, RenderContext, Texture, TextureFormat};
+
+/// Set the uniform locations of the pass programs, and create an
+/// equirectangular source texture
+fn source() -> Texture {
+    for (i, u) in ["uFace", "uSource", "uParams"].iter().enumerate() {
+        mock_gl::set_uniform_location(u, i as i32 + 1);
+    }
+    Texture::new_2d(64, 32, TextureFormat::Rgba16F, None).unwrap()
+}
+
+/// Count the draws of the passes in a list of calls
+fn draws(calls:&[GlCall]) -> usize {
+    calls.iter().filter(|c| matches!(c, GlCall::DrawArrays(gl::TRIANGLES, 0, 3))).count()
+}
+
+#[test]
+fn environments_are_cached() {
+    mock_gl::install();
+    let render_context = RenderContext::new();
+    let source = source();
+    mock_gl::take_calls();
+    let environment = Environment::from_equirectangular(&render_context, &source, 64).unwrap();
+    // The BRDF lookup texture, then the cube map, irradiance and the
+    // five levels of the prefiltered map for each face
+    assert_eq!(draws(&mock_gl::take_calls()), 1 + 6 + 6 + 6 * 5);
+    assert_eq!(environment.max_level(), 4);
+    let again = Environment::from_equirectangular(&render_context, &source, 64).unwrap();
+    assert!(Rc::ptr_eq(&environment, &again));
+    assert_eq!(draws(&mock_gl::take_calls()), 0);
+    let smaller = Environment::from_equirectangular(&render_context, &source, 32).unwrap();
+    assert!(!Rc::ptr_eq(&environment, &smaller));
+    assert_eq!(draws(&mock_gl::take_calls()), 6 + 6 + 6 * 5);
+}
+
+#[test]
+fn passes_reserve_one_texture_unit() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let first = render_context.reserve_texture_unit().unwrap();
+    let source_unit = GlCall::ActiveTexture(gl::TEXTURE0 + first as u32 + 1);
+    let environment = Environment::from_equirectangular(&render_context, &source(), 32).unwrap();
+    assert!(mock_gl::take_calls().contains(&source_unit));
+    drop(environment);
+
+    // The passes are created again for the recovered context, with
+    // the same unit
+    mock_gl::lose_context();
+    render_context.context_lost();
+    render_context.recover_context().unwrap();
+    let environment = Environment::from_equirectangular(&render_context, &source(), 32).unwrap();
+    assert!(mock_gl::take_calls().contains(&source_unit));
+    drop(environment);
+    assert_eq!(render_context.reserve_texture_unit().unwrap(), first + 2);
+}
@@ -0,0 +1,123 @@
+//! Golden-image tests rendering with a headless EGL context
+//!
+//! These are skipped (with a message) if no EGL display can be
+//! initialized; with Mesa and no display server set
+//! `EGL_PLATFORM=surfaceless`. Set `GL_MODEL_UPDATE_GOLDEN` to rewrite
+//! the golden images.
+#![cfg(feature="headless")]
+
+mod common;
+
+use common::headless;
+use gl_model::{GlProgram, RgbaImage};
+use gl_model::{ShaderInstantiable, MissingAttributes};
+use gl_model::{BuiltinShader, ShaderOptions, Lights};
+
+const TRIANGLE_VERTEX_SHADER : &str = "#version 330 core
+const vec3 COLORS[3] = vec3[3](vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0));
+in vec3 position;
+out vec3 vColor;
+void main() {
+    vColor = COLORS[gl_VertexID];
+    gl_Position = vec4(position, 1.0);
+}
+";
+
+const TRIANGLE_FRAGMENT_SHADER : &str = "#version 330 core
+in vec3 vColor;
+out vec4 fragColor;
+void main() {
+    fragColor = vec4(vColor, 1.0);
+}
+";
+
+fn golden(name:&str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(name)
+}
+
+fn image(width:usize, height:usize, pixels:&[[u8; 4]]) -> RgbaImage {
+    RgbaImage { width, height, data:pixels.concat() }
+}
+
+#[test]
+fn compare_images() {
+    let a = image(2, 1, &[[120, 30, 60, 255], [0, 0, 0, 255]]);
+    let diff = a.compare(&a, 0).unwrap();
+    assert_eq!((diff.max_channel_diff, diff.num_differing), (0, 0));
+    // Matching pixels are a faded grey of the first image
+    assert_eq!(diff.diff_image, image(2, 1, &[[17, 17, 17, 255], [0, 0, 0, 255]]));
+
+    let b = image(2, 1, &[[123, 30, 60, 255], [0, 0, 0, 200]]);
+    let diff = a.compare(&b, 3).unwrap();
+    assert_eq!((diff.max_channel_diff, diff.num_differing), (55, 1));
+    assert_eq!(diff.diff_image, image(2, 1, &[[17, 17, 17, 255], [255, 0, 0, 255]]));
+    let diff = a.compare(&b, 2).unwrap();
+    assert_eq!(diff.num_differing, 2);
+    assert_eq!(diff.diff_image.data[0..4], [255, 0, 0, 255]);
+
+    assert!(a.compare(&image(1, 2, &[[0; 4], [0; 4]]), 255).is_err());
+}
+
+#[test]
+fn golden_triangle() {
+    let Some(mut context) = headless() else { return; };
+    let instantiable = common::triangle(context.render_context_mut(), 0.75);
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, TRIANGLE_VERTEX_SHADER),
+                                                  (gl::FRAGMENT_SHADER, TRIANGLE_FRAGMENT_SHADER)]).unwrap();
+    program.add_attr_name("position", model3d::VertexAttr::Position).unwrap();
+    let (shader_instantiable, report) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    assert!(report.is_clean());
+    let instance = instantiable.instantiate();
+    let image = context.render_instantiable(32, 32, &program, &shader_instantiable, &instance).unwrap();
+    assert_eq!((image.width, image.height), (32, 32));
+    image.check_golden(golden("triangle.png"), 2).unwrap();
+}
+
+#[test]
+fn missing_golden_is_an_error() {
+    if std::env::var_os("GL_MODEL_UPDATE_GOLDEN").is_some() {
+        return;
+    }
+    let image = RgbaImage { width:1, height:1, data:vec![0, 0, 0, 255] };
+    let dir = std::env::temp_dir().join(format!("gl_model_golden_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let missing = dir.join("missing.png");
+    assert!(image.check_golden(&missing, 0).is_err());
+    assert!(!missing.exists());
+    assert!(dir.join("missing.actual.png").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn builtin_shader_variants_compile() {
+    let Some(mut context) = headless() else { return; };
+    let lights = Lights::new(4);
+    let mut clustered = Lights::new(16);
+    clustered.enable_clusters(context.render_context_mut(), (4, 4, 8)).unwrap();
+    let variants = [ShaderOptions::new(),
+                    ShaderOptions::new().with_skinning(4),
+                    ShaderOptions::new().with_normal_map(),
+                    ShaderOptions::new().with_skinning(4).with_normal_map(),
+                    ShaderOptions::new().with_lights(&lights),
+                    ShaderOptions::new().with_lights(&clustered),
+                    ShaderOptions::new().with_instance_lights(),
+                    ShaderOptions::new().with_lights(&lights).with_instance_lights(),
+                    ShaderOptions::new().with_environment(),
+                    ShaderOptions::new().with_environment().with_normal_map().with_skinning(4),
+                    ShaderOptions::new().with_light_distance(),
+                    ShaderOptions::new().with_light_distance().with_skinning(4)];
+    let mut compiled = 0;
+    for shader in BuiltinShader::ALL {
+        for options in &variants {
+            // Skip the combinations that the shader does not support
+            if shader.sources(options).is_err() {
+                continue;
+            }
+            if let Err(e) = shader.compile(options) {
+                panic!("{} shader with {:?} failed to compile: {}", shader, options, e);
+            }
+            compiled += 1;
+        }
+    }
+    assert!(compiled >= 2 * BuiltinShader::ALL.len());
+}
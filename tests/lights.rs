@@ -0,0 +1,116 @@
+//! Tests of the relevance of lights, and of the uniform block and
+//! cluster lists written by [gl_model::Lights::update] (with the mock
+//! GL)
+
+use gl_model::{Light, Lights};
+
+#[test]
+fn most_relevant() {
+    let mut lights = Lights::new(8);
+    lights.add(Light::directional([0., -1., 0.]).with_color([1.; 3], 0.1)).unwrap();
+    lights.add(Light::point([0., 0., 2.], 10.)).unwrap();
+    lights.add(Light::point([0., 0., 50.], 10.)).unwrap();
+    lights.add(Light::spot([0., 5., 0.], [0., 1., 0.], 20., 0.2, 0.3)).unwrap();
+    lights.add(Light::point([0., 0., 1.], 10.)).unwrap();
+    // The far point light is out of range, and the spot light points away
+    assert_eq!(lights.most_relevant([0.; 3], 0.5, 8), vec![4, 1, 0]);
+    assert_eq!(lights.most_relevant([0.; 3], 0.5, 2), vec![4, 1]);
+    assert_eq!(lights.most_relevant([0.; 3], 0.5, 0), Vec::<usize>::new());
+    // A large enough sphere is in range of the far point light
+    assert!(lights.most_relevant([0.; 3], 45., 8).contains(&2));
+    // The spot light reaches a sphere in its cone
+    assert_eq!(lights.most_relevant([0., 6.5, 0.], 0.5, 1), vec![3]);
+}
+
+#[test]
+fn lights_are_limited() {
+    let mut lights = Lights::new(1);
+    assert!(lights.add(Light::point([0.; 3], 0.)).is_err());
+    assert_eq!(lights.add(Light::point([0.; 3], 1.)).unwrap(), 0);
+    assert!(lights.add(Light::directional([0., -1., 0.])).is_err());
+    assert_eq!(lights.byte_length(), 128);
+}
+
+/// Get the words of the uniform buffer data written by the last
+/// update, as floats
+#[cfg(all(feature="mock", not(feature="glow")))]
+fn uniform_buffer_floats(calls:&[gl_model::mock_gl::GlCall]) -> Vec<f32> {
+    use gl_model::mock_gl::GlCall;
+    let data = calls.iter().rev().find_map(|c| {
+        match c {
+            GlCall::BufferSubData(gl::UNIFORM_BUFFER, 0, data) => Some(data),
+            _ => None,
+        }
+    }).unwrap();
+    data.chunks(4).map(|w| f32::from_ne_bytes([w[0], w[1], w[2], w[3]])).collect()
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn std140_layout() {
+    use gl_model::mock_gl::{self, GlCall};
+    use gl_model::{Camera, RenderContext};
+    mock_gl::install();
+    let render_context = RenderContext::new();
+    let mut lights = Lights::new(2).with_binding(3);
+    assert_eq!(lights.byte_length(), 64 + 2 * 64);
+    lights.add(Light::point([1., 2., 3.], 4.).with_color([0.5, 0.25, 1.], 2.).with_shadow(1)).unwrap();
+    lights.add(Light::spot([0.; 3], [0., 0., -2.], 10., 0., std::f32::consts::FRAC_PI_2)).unwrap();
+    mock_gl::take_calls();
+    lights.update(&render_context, &Camera::default(), (640, 480)).unwrap();
+    let calls = mock_gl::take_calls();
+    let floats = uniform_buffer_floats(&calls);
+    assert_eq!(floats.len(), 16 + 2 * 16);
+    // The light count and clustered flag are ints
+    assert_eq!(floats[0].to_bits(), 2);
+    assert_eq!(floats[1].to_bits(), 0);
+    // Each light is four vec4s, after the four vec4s of the header
+    assert_eq!(floats[16..32], [1., 2., 3., 4.,
+                                0., 0., -1., 1.,
+                                1., 0.5, 2., 2.,
+                                1., 1., 1., 0.]);
+    assert_eq!(floats[36..40], [0., 0., -1., 2.]);
+    assert!(floats[45].abs() < 1.0e-6);
+    assert_eq!(floats[46], -1.);
+    assert!(calls.iter().any(|c| matches!(c, GlCall::BindBufferBase(gl::UNIFORM_BUFFER, 3, b) if *b != 0)));
+    let glsl = lights.glsl();
+    assert!(glsl.contains("#define MAX_LIGHTS 2\n"));
+    assert!(glsl.contains("layout(std140) uniform Lights {\n    ivec4 uLightCount;\n    vec4 uClusterGrid;\n    vec4 uClusterDepth;\n    vec4 uClusterViewport;\n    Light uLights[MAX_LIGHTS];\n};"));
+    assert!(!glsl.contains("uLightClusters"));
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn cluster_assignment() {
+    use gl_model::mock_gl;
+    use gl_model::{Camera, RenderContext};
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let mut lights = Lights::new(4);
+    lights.enable_clusters(&mut render_context, (2, 2, 4)).unwrap();
+    assert!(lights.is_clustered());
+    assert!(lights.glsl().contains("uniform highp sampler2D uLightClusters;"));
+    // Slices of depth 1 to 100 are split at 3.16, 10 and 31.6
+    let view = Camera::look_at([0.; 3], [0., 0., -1.], [0., 1., 0.]);
+    let camera = Camera::new(view, Camera::perspective(1.0, 1.0, 1., 100.));
+    lights.add(Light::directional([0., -1., 0.])).unwrap();
+    lights.add(Light::point([0., 0., -10.], 1.)).unwrap();
+    lights.add(Light::point([0., 0., 10.], 1.)).unwrap();
+    lights.add(Light::point([50., 0., -10.], 1.)).unwrap();
+    lights.update(&render_context, &camera, (640, 480)).unwrap();
+    // The directional light is in all 16 clusters; the point light in
+    // front is in the 4 tiles of the 2 slices around depth 10; the
+    // others are behind the camera or outside the frustum
+    assert_eq!(lights.cluster_assignments(), 16 + 8);
+
+    let floats = uniform_buffer_floats(&mock_gl::take_calls());
+    assert_eq!(floats[0].to_bits(), 4);
+    assert_eq!(floats[1].to_bits(), 1);
+    assert_eq!(floats[4..8], [2., 2., 4., 0.]);
+    assert!((floats[8] - 1.).abs() < 1.0e-4 && (floats[9] - 100.).abs() < 1.0e-2, "{:?}", &floats[8..10]);
+    assert_eq!(floats[12..16], [640., 480., 0., 0.]);
+
+    lights.clear();
+    lights.update(&render_context, &camera, (640, 480)).unwrap();
+    assert_eq!(lights.cluster_assignments(), 0);
+}
@@ -0,0 +1,307 @@
+//! Tests of the GL call sequences made by the crate, recorded with
+//! the mock GL function table (which replaces the `gl` crate
+//! functions, so is not used by the `glow` backend)
+#![cfg(all(feature="mock", not(feature="glow")))]
+
+mod common;
+
+use gl_model::mock_gl::{self, GlCall};
+use gl_model::{RenderContext, GlBuffer, GlProgram};
+use gl_model::{DebugSeverity, DebugHandler, UniformId};
+use gl_model::{ShaderInstantiable, MissingAttributes};
+
+const VERTEX_SHADER : &str = "#version 330 core
+in vec3 position;
+void main() { gl_Position = vec4(position, 1.0); }
+";
+
+const FRAGMENT_SHADER : &str = "#version 330 core
+out vec4 fragColor;
+void main() { fragColor = vec4(1.0); }
+";
+
+fn bytes_of(data:&[f32]) -> Vec<u8> {
+    data.iter().flat_map(|f| f.to_ne_bytes()).collect()
+}
+
+#[test]
+fn shader_source_lengths() {
+    mock_gl::install();
+    let source = b"void main() {}trailing";
+    let strings = [source.as_ptr() as *const gl::types::GLchar];
+    let lengths = [14];
+    unsafe { gl::ShaderSource(7, 1, strings.as_ptr(), lengths.as_ptr()) };
+    let nul_terminated = b"void main() {}\0";
+    let strings = [nul_terminated.as_ptr() as *const gl::types::GLchar];
+    let lengths = [-1];
+    unsafe { gl::ShaderSource(8, 1, strings.as_ptr(), lengths.as_ptr()) };
+    unsafe { gl::ShaderSource(9, 1, strings.as_ptr(), std::ptr::null()) };
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::ShaderSource(7, "void main() {}".to_string()),
+                    GlCall::ShaderSource(8, "void main() {}".to_string()),
+                    GlCall::ShaderSource(9, "void main() {}".to_string())]);
+}
+
+#[test]
+fn uniform_buffer_creation() {
+    mock_gl::install();
+    let render_context = RenderContext::new();
+    mock_gl::take_calls();
+    let data = [1.0f32, 2.0, 3.0, 4.0];
+    let mut buffer = GlBuffer::default();
+    buffer.uniform_buffer(&data, &render_context).unwrap();
+    assert_eq!(buffer.byte_length(), 16);
+    let name = buffer.gl_buffer();
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::BindVertexArray(0),
+                    GlCall::GenBuffers(vec![name]),
+                    GlCall::BindBuffer(gl::UNIFORM_BUFFER, name),
+                    GlCall::BufferStorage(gl::UNIFORM_BUFFER, 16, 0, Some(bytes_of(&data))),
+                    GlCall::BindBuffer(gl::UNIFORM_BUFFER, 0)]);
+}
+
+#[test]
+fn dynamic_buffer_writes() {
+    mock_gl::install();
+    let render_context = RenderContext::new();
+    mock_gl::take_calls();
+    let mut buffer = GlBuffer::default();
+    buffer.dynamic_uniform_buffer(32, &render_context).unwrap();
+    let name = buffer.gl_buffer();
+    buffer.write(gl::UNIFORM_BUFFER, 8, &[1, 2, 3, 4]).unwrap();
+    assert!(buffer.write(gl::UNIFORM_BUFFER, 30, &[1, 2, 3, 4]).is_err());
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::GenBuffers(vec![name]),
+                    GlCall::BindBuffer(gl::UNIFORM_BUFFER, name),
+                    GlCall::BufferData(gl::UNIFORM_BUFFER, 32, gl::DYNAMIC_DRAW, None),
+                    GlCall::BindBuffer(gl::UNIFORM_BUFFER, 0),
+                    GlCall::BindBuffer(gl::UNIFORM_BUFFER, name),
+                    GlCall::BufferSubData(gl::UNIFORM_BUFFER, 8, vec![1, 2, 3, 4])]);
+}
+
+#[test]
+fn vao_setup() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    mock_gl::set_attrib_location("position", 2);
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, VERTEX_SHADER),
+                                                  (gl::FRAGMENT_SHADER, FRAGMENT_SHADER)]).unwrap();
+    program.add_attr_name("position", model3d::VertexAttr::Position).unwrap();
+    mock_gl::take_calls();
+    let (shader_instantiable, report) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    assert!(report.is_clean());
+    let vao = shader_instantiable.gl_vao(0);
+    // The triangle's index and position buffers are the first created
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::GenVertexArrays(vec![vao]),
+                    GlCall::BindVertexArray(vao),
+                    GlCall::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 1),
+                    GlCall::BindBuffer(gl::ARRAY_BUFFER, 2),
+                    GlCall::EnableVertexAttribArray(2),
+                    GlCall::VertexAttribPointer(2, 3, gl::FLOAT, gl::FALSE, 0, 0),
+                    GlCall::BindVertexArray(0)]);
+}
+
+#[test]
+fn program_and_draw() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    mock_gl::set_attrib_location("position", 2);
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, VERTEX_SHADER),
+                                                  (gl::FRAGMENT_SHADER, FRAGMENT_SHADER)]).unwrap();
+    program.add_attr_name("position", model3d::VertexAttr::Position).unwrap();
+    let (shader_instantiable, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    let instance = instantiable.instantiate();
+    mock_gl::take_calls();
+    program.set_used();
+    shader_instantiable.gl_draw(&instance);
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::UseProgram(program.id()),
+                    GlCall::BindVertexArray(shader_instantiable.gl_vao(0)),
+                    GlCall::DrawElements(gl::TRIANGLES, 3, gl::UNSIGNED_BYTE, 0)]);
+}
+
+#[test]
+fn registry_names_label_objects() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    render_context.enable_debug_output(DebugSeverity::High, DebugHandler::Log).unwrap();
+    let mut buffer = GlBuffer::default();
+    buffer.uniform_buffer(&[0.0f32; 4], &render_context).unwrap();
+    let name = buffer.gl_buffer();
+    mock_gl::take_calls();
+    render_context.registry_mut().add("camera", buffer);
+    assert_eq!(mock_gl::take_calls(), vec![GlCall::ObjectLabel(gl::BUFFER, name, "camera".to_string())]);
+}
+
+#[test]
+fn deletions_are_queued_per_context() {
+    mock_gl::install();
+    let first = RenderContext::new();
+    let mut buffer = GlBuffer::default();
+    buffer.uniform_buffer(&[0.0f32; 4], &first).unwrap();
+    let name = buffer.gl_buffer();
+    let second = RenderContext::new();
+    drop(buffer);
+    assert_eq!(first.pending_deletions(), 1);
+    assert_eq!(second.pending_deletions(), 0);
+    assert_eq!(second.flush_deletions(), 0);
+    mock_gl::take_calls();
+    assert_eq!(first.flush_deletions(), 1);
+    assert_eq!(mock_gl::take_calls(), vec![GlCall::DeleteBuffers(vec![name])]);
+}
+
+#[test]
+fn context_recovery() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    render_context.enable_context_recovery();
+    let data = [1.0f32, 2.0, 3.0, 4.0];
+    let mut buffer = GlBuffer::default();
+    buffer.uniform_buffer(&data, &render_context).unwrap();
+    let program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, VERTEX_SHADER),
+                                              (gl::FRAGMENT_SHADER, FRAGMENT_SHADER)]).unwrap();
+    let handle = render_context.registry_mut().add("program", program);
+    mock_gl::take_calls();
+    mock_gl::lose_context();
+    render_context.context_lost();
+    assert!(render_context.is_context_lost());
+    render_context.recover_context().unwrap();
+    assert!(!render_context.is_context_lost());
+    // Names are synthesized from 1 again in the new context
+    assert_eq!(buffer.gl_buffer(), 1);
+    assert_eq!(render_context.registry().get(handle).unwrap().id(), 1);
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS),
+                    GlCall::BindVertexArray(0),
+                    GlCall::GenBuffers(vec![1]),
+                    GlCall::BindBuffer(gl::UNIFORM_BUFFER, 1),
+                    GlCall::BufferStorage(gl::UNIFORM_BUFFER, 16, 0, Some(bytes_of(&data))),
+                    GlCall::BindBuffer(gl::UNIFORM_BUFFER, 0),
+                    GlCall::CreateShader(gl::VERTEX_SHADER, 1),
+                    GlCall::ShaderSource(1, VERTEX_SHADER.to_string()),
+                    GlCall::CompileShader(1),
+                    GlCall::CreateShader(gl::FRAGMENT_SHADER, 2),
+                    GlCall::ShaderSource(2, FRAGMENT_SHADER.to_string()),
+                    GlCall::CompileShader(2),
+                    GlCall::CreateProgram(1),
+                    GlCall::AttachShader(1, 1),
+                    GlCall::AttachShader(1, 2),
+                    GlCall::LinkProgram(1),
+                    GlCall::DetachShader(1, 1),
+                    GlCall::DetachShader(1, 2)]);
+    // Only the shaders of the new program are deleted, not the old names
+    render_context.flush_deletions();
+    assert_eq!(mock_gl::take_calls(), vec![GlCall::DeleteShader(1), GlCall::DeleteShader(2)]);
+}
+
+#[test]
+fn objects_of_lost_contexts_are_discarded() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let mut buffer = GlBuffer::default();
+    buffer.uniform_buffer(&[0.0f32; 4], &render_context).unwrap();
+    render_context.context_lost();
+    drop(buffer);
+    assert_eq!(render_context.pending_deletions(), 0);
+    mock_gl::take_calls();
+    assert_eq!(render_context.flush_deletions(), 0);
+    assert_eq!(mock_gl::take_calls(), vec![]);
+}
+
+#[test]
+fn objects_of_pruned_lost_contexts_are_discarded() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let mut buffer = GlBuffer::default();
+    buffer.uniform_buffer(&[0.0f32; 4], &render_context).unwrap();
+    // Each loss prunes the lost generations as it starts a new one,
+    // so the buffer's generation is no longer recorded as lost
+    render_context.context_lost();
+    render_context.context_lost();
+    drop(buffer);
+    assert_eq!(render_context.pending_deletions(), 0);
+    mock_gl::take_calls();
+    assert_eq!(render_context.flush_deletions(), 0);
+    assert_eq!(mock_gl::take_calls(), vec![]);
+}
+
+#[test]
+#[should_panic(expected = "will never be deleted")]
+fn objects_outliving_their_context_assert() {
+    mock_gl::install();
+    let render_context = RenderContext::new();
+    let mut buffer = GlBuffer::default();
+    buffer.uniform_buffer(&[0.0f32; 4], &render_context).unwrap();
+    let _other = RenderContext::new();
+    drop(render_context);
+    drop(buffer);
+}
+
+#[test]
+#[should_panic(expected = "will never be deleted")]
+fn objects_created_after_their_context_is_dropped_assert() {
+    mock_gl::install();
+    drop(RenderContext::new());
+    let program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, VERTEX_SHADER),
+                                              (gl::FRAGMENT_SHADER, FRAGMENT_SHADER)]).unwrap();
+    drop(program);
+}
+
+#[test]
+fn uniform_block_bindings_are_recovered() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    mock_gl::set_uniform_block_index("Camera", 2);
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, VERTEX_SHADER),
+                                                  (gl::FRAGMENT_SHADER, FRAGMENT_SHADER)]).unwrap();
+    program.add_uniform_buffer_name("Camera", 0).unwrap();
+    assert!(program.set_uniform_block_binding(UniformId::Buffer(1), 3).is_err());
+    assert!(program.set_uniform_block_binding(UniformId::ViewMatrix, 3).is_err());
+    mock_gl::take_calls();
+    program.set_uniform_block_binding(UniformId::Buffer(0), 3).unwrap();
+    program.set_uniform_block_binding(UniformId::Buffer(0), 4).unwrap();
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::UniformBlockBinding(program.id(), 2, 3),
+                    GlCall::UniformBlockBinding(program.id(), 2, 4)]);
+    let handle = render_context.registry_mut().add("program", program);
+    mock_gl::lose_context();
+    render_context.context_lost();
+    render_context.recover_context().unwrap();
+    let id = render_context.registry().get(handle).unwrap().id();
+    let calls = mock_gl::take_calls();
+    let link = calls.iter().position(|c| *c == GlCall::LinkProgram(id)).unwrap();
+    let bindings : Vec<_> = calls.iter().enumerate().filter(|(_, c)| matches!(c, GlCall::UniformBlockBinding(..))).collect();
+    assert_eq!(bindings.len(), 1);
+    assert!(bindings[0].0 > link);
+    assert_eq!(*bindings[0].1, GlCall::UniformBlockBinding(id, 2, 4));
+}
+
+#[test]
+fn source_retention_is_per_context() {
+    mock_gl::install();
+    let first = RenderContext::new();
+    first.enable_context_recovery();
+    drop(first);
+    let mut render_context = RenderContext::new();
+    let mut buffer = GlBuffer::default();
+    buffer.uniform_buffer(&[0.0f32; 4], &render_context).unwrap();
+    mock_gl::lose_context();
+    render_context.context_lost();
+    assert!(render_context.recover_context().is_err());
+    drop(buffer);
+
+    let other_thread = std::thread::spawn(|| {
+        mock_gl::install();
+        let render_context = RenderContext::new();
+        render_context.enable_context_recovery();
+    });
+    other_thread.join().unwrap();
+    let mut buffer = GlBuffer::default();
+    buffer.uniform_buffer(&[0.0f32; 4], &render_context).unwrap();
+    mock_gl::lose_context();
+    render_context.context_lost();
+    assert!(render_context.recover_context().is_err());
+}
@@ -0,0 +1,181 @@
+//! Tests of occlusion culling of [gl_model::ShaderInstantiable]s, with
+//! query results set through the mock GL
+#![cfg(all(feature="mock", not(feature="glow")))]
+
+mod common;
+
+use gl_model::mock_gl::{self, GlCall};
+use gl_model::{GlProgram, MissingAttributes, RenderContext, ShaderInstantiable};
+
+/// Compile a program with a position attribute
+fn program() -> GlProgram {
+    mock_gl::set_attrib_location("position", 0);
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, "#version 300 es\nin vec3 position;\nvoid main() {}\n")]).unwrap();
+    program.add_attr_name("position", model3d::VertexAttr::Position).unwrap();
+    program
+}
+
+/// Get the occlusion queries begun in a list of calls
+fn occlusion_queries(calls:&[GlCall]) -> Vec<u32> {
+    calls.iter().filter_map(|c| {
+        match c {
+            GlCall::BeginQuery(gl::ANY_SAMPLES_PASSED, q) => Some(*q),
+            _ => None,
+        }
+    }).collect()
+}
+
+/// Count the draws of the triangle (not its bounding box) in a list
+/// of calls
+fn triangle_draws(calls:&[GlCall]) -> usize {
+    calls.iter().filter(|c| matches!(c, GlCall::DrawElements(gl::TRIANGLES, 3, _, _))).count()
+}
+
+#[test]
+fn instances_keep_their_queries_when_reordered() {
+    mock_gl::install();
+    mock_gl::set_string(gl::VERSION, "OpenGL ES 3.0 Mock");
+    mock_gl::set_string(gl::SHADING_LANGUAGE_VERSION, "OpenGL ES GLSL ES 3.00 Mock");
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    let program = program();
+    let (mut shader_instantiable, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    shader_instantiable.enable_occlusion_culling(&render_context).unwrap();
+    assert!(!shader_instantiable.occlusion_culling().unwrap().is_conditional());
+    let (hidden, shown) = (instantiable.instantiate(), instantiable.instantiate());
+
+    mock_gl::take_calls();
+    shader_instantiable.gl_draw_keyed(&hidden, 0);
+    shader_instantiable.gl_draw_keyed(&shown, 1);
+    let calls = mock_gl::take_calls();
+    assert_eq!(triangle_draws(&calls), 2);
+    let queries = occlusion_queries(&calls);
+    assert_eq!(queries.len(), 2);
+    mock_gl::set_query_result(queries[0], Some(0));
+    mock_gl::set_query_result(queries[1], Some(1));
+    render_context.end_frame();
+
+    // Drawn in the other order, each instance uses its own result
+    shader_instantiable.gl_draw_keyed(&shown, 1);
+    shader_instantiable.gl_draw_keyed(&hidden, 0);
+    assert_eq!(triangle_draws(&mock_gl::take_calls()), 1);
+    let occlusion = shader_instantiable.occlusion_culling().unwrap();
+    assert!(!occlusion.is_visible(0));
+    assert!(occlusion.is_visible(1));
+}
+
+#[test]
+fn instances_keep_their_queries_when_moved() {
+    mock_gl::install();
+    mock_gl::set_string(gl::VERSION, "OpenGL ES 3.0 Mock");
+    mock_gl::set_string(gl::SHADING_LANGUAGE_VERSION, "OpenGL ES GLSL ES 3.00 Mock");
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    let program = program();
+    let (mut shader_instantiable, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    shader_instantiable.enable_occlusion_culling(&render_context).unwrap();
+    let mut instances = vec![instantiable.instantiate()];
+
+    mock_gl::take_calls();
+    for (key, instance) in instances.iter().enumerate() {
+        shader_instantiable.gl_draw_keyed(instance, key);
+    }
+    let queries = occlusion_queries(&mock_gl::take_calls());
+    mock_gl::set_query_result(queries[0], Some(0));
+    render_context.end_frame();
+
+    // Growing the Vec moves the first instance, which keeps its
+    // queries (and is culled); each new instance has its own queries
+    for _ in 0..16 {
+        instances.push(instantiable.instantiate());
+    }
+    for (key, instance) in instances.iter().enumerate() {
+        shader_instantiable.gl_draw_keyed(instance, key);
+    }
+    let calls = mock_gl::take_calls();
+    assert_eq!(triangle_draws(&calls), 16);
+    let frame_queries = occlusion_queries(&calls);
+    assert_eq!(frame_queries.len(), 17);
+    assert!(!frame_queries.contains(&queries[0]));
+    let mut distinct = frame_queries.clone();
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(distinct.len(), 17);
+    let occlusion = shader_instantiable.occlusion_culling().unwrap();
+    assert!(!occlusion.is_visible(0));
+    assert!(occlusion.is_visible(1));
+}
+
+#[test]
+fn unkeyed_draws_are_not_culled() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    let program = program();
+    let (mut shader_instantiable, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    shader_instantiable.enable_occlusion_culling(&render_context).unwrap();
+    mock_gl::take_calls();
+    shader_instantiable.gl_draw(&instantiable.instantiate());
+    let calls = mock_gl::take_calls();
+    assert!(occlusion_queries(&calls).is_empty());
+    assert_eq!(triangle_draws(&calls), 1);
+}
+
+#[test]
+fn queries_of_instances_not_drawn_are_reused() {
+    mock_gl::install();
+    mock_gl::set_string(gl::VERSION, "OpenGL ES 3.0 Mock");
+    mock_gl::set_string(gl::SHADING_LANGUAGE_VERSION, "OpenGL ES GLSL ES 3.00 Mock");
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    let program = program();
+    let (mut shader_instantiable, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    shader_instantiable.enable_occlusion_culling(&render_context).unwrap();
+    let (first, second) = (instantiable.instantiate(), instantiable.instantiate());
+
+    shader_instantiable.gl_draw_keyed(&first, 0);
+    let queries = occlusion_queries(&mock_gl::take_calls());
+    mock_gl::set_query_result(queries[0], Some(0));
+    render_context.end_frame();
+    render_context.end_frame();
+
+    // The first instance was not drawn in the previous frame, so its
+    // queries are given to the second
+    shader_instantiable.gl_draw_keyed(&second, 1);
+    let calls = mock_gl::take_calls();
+    assert!(!calls.iter().any(|c| matches!(c, GlCall::GenQueries(_))));
+    assert_eq!(triangle_draws(&calls), 1);
+    let occlusion = shader_instantiable.occlusion_culling().unwrap();
+    assert!(occlusion.is_visible(0));
+    assert!(occlusion.is_visible(1));
+}
+
+#[test]
+fn conditional_rendering_uses_the_previous_query_of_the_instance() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    let program = program();
+    let (mut shader_instantiable, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    shader_instantiable.enable_occlusion_culling(&render_context).unwrap();
+    assert!(shader_instantiable.occlusion_culling().unwrap().is_conditional());
+    let (a, b) = (instantiable.instantiate(), instantiable.instantiate());
+
+    mock_gl::take_calls();
+    shader_instantiable.gl_draw_keyed(&a, 0);
+    shader_instantiable.gl_draw_keyed(&b, 1);
+    let calls = mock_gl::take_calls();
+    let queries = occlusion_queries(&calls);
+    assert!(!calls.iter().any(|c| matches!(c, GlCall::BeginConditionalRender(..))));
+    render_context.end_frame();
+
+    shader_instantiable.gl_draw_keyed(&b, 1);
+    shader_instantiable.gl_draw_keyed(&a, 0);
+    let conditions : Vec<_> = mock_gl::take_calls().into_iter().filter_map(|c| {
+        match c {
+            GlCall::BeginConditionalRender(q, gl::QUERY_NO_WAIT) => Some(q),
+            _ => None,
+        }
+    }).collect();
+    assert_eq!(conditions, vec![queries[1], queries[0]]);
+}
@@ -0,0 +1,54 @@
+//! Tests of GLSL source rewriting for the context's GLSL dialect
+
+use gl_model::rewrite_version;
+
+#[test]
+fn desktop_to_gles() {
+    let src = "#version 330 core\nvoid main() {}\n";
+    let rewritten = rewrite_version(src, true);
+    assert!(rewritten.starts_with("#version 300 es\n"));
+    assert!(rewritten.contains("precision highp float;\n"));
+    assert!(rewritten.contains("precision highp sampler2DArrayShadow;\n"));
+    assert!(rewritten.ends_with("void main() {}\n"));
+}
+
+#[test]
+fn old_desktop_versions_to_gles() {
+    for version in ["110", "140", "150 core", "150 compatibility"] {
+        let src = format!("#version {}\nvoid main() {{}}\n", version);
+        let rewritten = rewrite_version(&src, true);
+        assert!(rewritten.starts_with("#version 300 es\n"), "{}", rewritten);
+        assert!(rewritten.ends_with("void main() {}\n"));
+    }
+}
+
+#[test]
+fn declared_precisions_are_kept() {
+    let src = "#version 330 core\nprecision mediump int;\nvoid main() {}\n";
+    let rewritten = rewrite_version(src, true);
+    assert!(rewritten.contains("precision highp float;\n"));
+    assert!(!rewritten.contains("precision highp int;"));
+    assert!(rewritten.contains("precision mediump int;\n"));
+}
+
+#[test]
+fn unchanged_versions() {
+    let src = "#version 300 es\nvoid main() {}\n";
+    assert_eq!(rewrite_version(src, true), src);
+    assert_eq!(rewrite_version("void main() {}\n", true), "void main() {}\n");
+    assert_eq!(rewrite_version(src, false), "#version 330 core\nvoid main() {}\n");
+    let src = "#version 100\nvoid main() {}\n";
+    assert_eq!(rewrite_version(src, true), src);
+    let src = "#version 150\nvoid main() {}\n";
+    assert_eq!(rewrite_version(src, false), src);
+}
+
+#[test]
+fn precisions_follow_extensions() {
+    let src = "#version 330 core\n#extension GL_OES_texture_3D : enable\n\n// Comment\n#extension GL_EXT_shadow_samplers : require\nvoid main() {}\n";
+    let rewritten = rewrite_version(src, true);
+    let precision = rewritten.find("precision highp float;").unwrap();
+    assert!(rewritten.starts_with("#version 300 es\n#extension GL_OES_texture_3D : enable\n"));
+    assert!(rewritten.find("#extension GL_EXT_shadow_samplers").unwrap() < precision);
+    assert!(rewritten.ends_with("precision highp sampler2DArrayShadow;\nvoid main() {}\n"));
+}
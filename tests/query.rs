@@ -0,0 +1,166 @@
+//! Tests of [gl_model::GpuProfiler], with query results set through
+//! the mock GL
+#![cfg(all(feature="mock", not(feature="glow")))]
+
+mod common;
+
+use gl_model::mock_gl::{self, GlCall};
+use gl_model::{GpuProfiler, QueryKind, RenderContext};
+
+/// Find the queries begun for a target, and those used for timestamps,
+/// in a list of calls
+fn queries(calls:&[GlCall], target:u32) -> (Vec<u32>, Vec<u32>) {
+    let begun = calls.iter().filter_map(|c| {
+        match c {
+            GlCall::BeginQuery(t, q) if *t == target => Some(*q),
+            _ => None,
+        }
+    }).collect();
+    let timestamps = calls.iter().filter_map(|c| {
+        match c {
+            GlCall::QueryCounter(q, gl::TIMESTAMP) => Some(*q),
+            _ => None,
+        }
+    }).collect();
+    (begun, timestamps)
+}
+
+#[test]
+fn results_are_read_when_available() {
+    mock_gl::install();
+    let render_context = RenderContext::new();
+    let mut profiler = GpuProfiler::new(&render_context, &[QueryKind::TimeElapsed, QueryKind::Timestamp]).unwrap();
+    mock_gl::take_calls();
+    profiler.begin_frame().unwrap();
+    profiler.begin_object("triangle").unwrap();
+    let mut calls = mock_gl::take_calls();
+    let (begun, _) = queries(&calls, gl::TIME_ELAPSED);
+    assert_eq!(begun.len(), 1);
+    mock_gl::set_query_result(begun[0], None);
+    profiler.end_object(2, 10).unwrap();
+    profiler.end_frame().unwrap();
+    calls.extend(mock_gl::take_calls());
+    let (_, timestamps) = queries(&calls, gl::TIME_ELAPSED);
+    assert_eq!(timestamps.len(), 2);
+    assert!(profiler.take_report().is_none());
+
+    mock_gl::set_query_result(begun[0], Some(2500));
+    mock_gl::set_query_result(timestamps[0], Some(1000));
+    mock_gl::set_query_result(timestamps[1], Some(6000));
+    profiler.begin_frame().unwrap();
+    profiler.end_frame().unwrap();
+    let report = profiler.take_report().unwrap();
+    assert_eq!(report.frame, 0);
+    assert_eq!(report.gpu_frame_time_ns, Some(5000));
+    assert_eq!(report.objects.len(), 1);
+    assert_eq!(report.objects[0].name, "triangle");
+    assert_eq!(report.objects[0].gpu_time_ns, Some(2500));
+    assert_eq!(report.objects[0].primitives_generated, None);
+    assert_eq!((report.draw_calls(), report.triangles()), (2, 10));
+    let report = profiler.take_report().unwrap();
+    assert_eq!(report.frame, 1);
+    assert!(report.objects.is_empty());
+    assert!(profiler.take_report().is_none());
+}
+
+#[test]
+fn oldest_frames_are_waited_for() {
+    mock_gl::install();
+    let render_context = RenderContext::new();
+    let mut profiler = GpuProfiler::new(&render_context, &[QueryKind::PrimitivesGenerated]).unwrap();
+    profiler.set_max_pending(2);
+    for frame in 0..3 {
+        mock_gl::take_calls();
+        profiler.begin_frame().unwrap();
+        profiler.begin_object("object").unwrap();
+        let (begun, _) = queries(&mock_gl::take_calls(), gl::PRIMITIVES_GENERATED);
+        mock_gl::set_query_result(begun[0], None);
+        profiler.end_object(1, 1).unwrap();
+        profiler.end_frame().unwrap();
+        assert_eq!(profiler.take_report().map(|r| r.frame), if frame < 2 { None } else { Some(0) });
+    }
+}
+
+#[test]
+fn disjoint_timings_are_discarded() {
+    mock_gl::install();
+    mock_gl::set_extensions(&["GL_EXT_disjoint_timer_query"]);
+    let render_context = RenderContext::new();
+    let mut profiler = GpuProfiler::new(&render_context, &[QueryKind::TimeElapsed]).unwrap();
+    mock_gl::take_calls();
+    profiler.begin_frame().unwrap();
+    profiler.begin_object("triangle").unwrap();
+    let (begun, _) = queries(&mock_gl::take_calls(), gl::TIME_ELAPSED);
+    mock_gl::set_query_result(begun[0], None);
+    profiler.end_object(1, 1).unwrap();
+    profiler.end_frame().unwrap();
+
+    // The disjoint event is found while frames 0 and 1 are outstanding
+    mock_gl::set_integer(0x8FBB, 1);
+    profiler.begin_frame().unwrap();
+    profiler.end_frame().unwrap();
+    assert!(profiler.take_report().is_none());
+
+    mock_gl::set_integer(0x8FBB, 0);
+    mock_gl::set_query_result(begun[0], Some(2500));
+    profiler.begin_frame().unwrap();
+    profiler.end_frame().unwrap();
+    let report = profiler.take_report().unwrap();
+    assert!(report.disjoint);
+    assert_eq!(report.objects[0].gpu_time_ns, None);
+    assert_eq!(report.objects[0].draw_calls, 1);
+    assert!(report.to_string().contains("(disjoint)"));
+    assert!(profiler.take_report().unwrap().disjoint);
+    assert!(!profiler.take_report().unwrap().disjoint);
+}
+
+#[test]
+fn reports_are_limited() {
+    mock_gl::install();
+    let render_context = RenderContext::new();
+    let mut profiler = GpuProfiler::new(&render_context, &[QueryKind::TimeElapsed]).unwrap();
+    profiler.set_max_reports(2);
+    for _ in 0..5 {
+        profiler.begin_frame().unwrap();
+        profiler.end_frame().unwrap();
+    }
+    assert_eq!(profiler.take_report().map(|r| r.frame), Some(3));
+    assert_eq!(profiler.take_report().map(|r| r.frame), Some(4));
+    assert!(profiler.take_report().is_none());
+}
+
+#[test]
+fn misuse_is_an_error() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let mut profiler = GpuProfiler::new(&render_context, &[QueryKind::TimeElapsed]).unwrap();
+    assert!(profiler.end_frame().is_err());
+    assert!(profiler.begin_object("outside").is_err());
+    assert!(profiler.end_object(0, 0).is_err());
+    profiler.begin_frame().unwrap();
+    assert!(profiler.begin_frame().is_err());
+    assert!(profiler.end_object(0, 0).is_err());
+    profiler.begin_object("outer").unwrap();
+    let e = profiler.begin_object("inner").unwrap_err();
+    assert!(e.contains("inner") && e.contains("outer"), "{}", e);
+    assert!(profiler.end_frame().is_err());
+    profiler.end_object(1, 1).unwrap();
+    profiler.end_frame().unwrap();
+    let report = profiler.take_report().unwrap();
+    assert_eq!(report.objects.len(), 1);
+    assert_eq!(report.objects[0].name, "outer");
+
+    // Drawing with the profiler outside a frame draws nothing
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    mock_gl::set_attrib_location("position", 0);
+    let mut program = gl_model::GlProgram::compile_program(&[(gl::VERTEX_SHADER, "#version 330 core\nin vec3 position;\nvoid main() {}\n")]).unwrap();
+    program.add_attr_name("position", model3d::VertexAttr::Position).unwrap();
+    let (shader_instantiable, _) = gl_model::ShaderInstantiable::new(&program, &instantiable, &gl_model::MissingAttributes::new()).unwrap();
+    mock_gl::take_calls();
+    assert!(shader_instantiable.gl_draw_profiled(&instantiable.instantiate(), None, &mut profiler).is_err());
+    assert!(!mock_gl::take_calls().iter().any(|c| matches!(c, GlCall::DrawElements(..))));
+    profiler.begin_frame().unwrap();
+    shader_instantiable.gl_draw_profiled(&instantiable.instantiate(), None, &mut profiler).unwrap();
+    assert!(mock_gl::take_calls().iter().any(|c| matches!(c, GlCall::DrawElements(..))));
+    profiler.end_frame().unwrap();
+}
@@ -0,0 +1,97 @@
+//! Tests of the [gl_model::ResourceRegistry] - handles, release and
+//! generations, finding by name, and memory usage (with the mock GL)
+
+use std::rc::Rc;
+
+use gl_model::{Material, ResourceRegistry};
+
+#[test]
+fn handles_find_their_resources() {
+    let mut registry = ResourceRegistry::new();
+    let red = registry.add("red", Material::new().with_base_color([1., 0., 0., 1.]));
+    let blue = registry.add("blue", Material::new().with_base_color([0., 0., 1., 1.]));
+    assert_ne!(red, blue);
+    assert_eq!(registry.get(red).unwrap().base_color(), [1., 0., 0., 1.]);
+    assert_eq!(registry.name(blue), Some("blue"));
+    assert_eq!(registry.iter::<Material>().map(|(_, n, _)| n).collect::<Vec<_>>(), vec!["red", "blue"]);
+    assert_eq!(format!("{:?}", blue), "material#1.0");
+}
+
+#[test]
+fn find_by_name() {
+    let mut registry = ResourceRegistry::new();
+    assert_eq!(registry.find::<Material>("red"), None);
+    let red = registry.add("red", Material::new());
+    let _blue = registry.add("blue", Material::new());
+    let again = registry.add("red", Material::new());
+    // The first of a name is found
+    assert_eq!(registry.find::<Material>("red"), Some(red));
+    registry.release(red);
+    assert_eq!(registry.find::<Material>("red"), Some(again));
+    assert_eq!(registry.find::<Material>("green"), None);
+}
+
+#[test]
+fn released_resources_are_dropped_at_the_frame_boundary() {
+    let mut registry = ResourceRegistry::new();
+    let material = Rc::new(Material::new());
+    let weak = Rc::downgrade(&material);
+    let handle = registry.add_rc("material", material);
+    assert!(registry.release(handle));
+    assert!(registry.get(handle).is_none());
+    assert!(registry.name(handle).is_none());
+    assert_eq!(registry.iter::<Material>().count(), 0);
+    assert!(weak.upgrade().is_some());
+    registry.end_frame();
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn stale_handles_are_rejected() {
+    let mut registry = ResourceRegistry::new();
+    let first = registry.add("first", Material::new());
+    assert!(registry.release(first));
+    assert!(!registry.release(first));
+
+    // The slot is reused with a new generation
+    let second = registry.add("second", Material::new());
+    assert_eq!(format!("{:?}", first), "material#0.0");
+    assert_eq!(format!("{:?}", second), "material#0.1");
+    assert_ne!(first, second);
+    assert!(registry.get(first).is_none());
+    assert!(!registry.release(first));
+    assert_eq!(registry.name(second), Some("second"));
+
+    // Each release of the slot moves it to a new generation
+    registry.release(second);
+    let third = registry.add("third", Material::new());
+    assert_eq!(format!("{:?}", third), "material#0.2");
+    assert!(registry.get(second).is_none());
+    assert_eq!(registry.name(third), Some("third"));
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn memory_usage() {
+    use gl_model::mock_gl;
+    use gl_model::{GlBuffer, RenderContext, ResourceKind, Texture, TextureFormat};
+    mock_gl::install();
+    let render_context = RenderContext::new();
+    let mut registry = ResourceRegistry::new();
+    let texture = registry.add("texture", Texture::new_2d(16, 8, TextureFormat::Rgba8, None).unwrap());
+    let mut buffer = GlBuffer::default();
+    buffer.uniform_buffer(&[0f32; 16], &render_context).unwrap();
+    registry.add("buffer", buffer);
+    registry.add("material", Material::new());
+
+    let usage = registry.memory_usage();
+    assert_eq!(usage.entries.len(), 3);
+    assert_eq!(usage.total_of(ResourceKind::Texture), 16 * 8 * 4);
+    assert_eq!(usage.total_of(ResourceKind::Buffer), 64);
+    assert_eq!(usage.total_of(ResourceKind::Material), 0);
+    assert_eq!(usage.total(), 16 * 8 * 4 + 64);
+    assert!(usage.to_string().ends_with(&format!("{:53} {:>12}\n", "total", 16 * 8 * 4 + 64)));
+
+    registry.release(texture);
+    assert_eq!(registry.memory_usage().total(), 64);
+}
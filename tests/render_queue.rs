@@ -0,0 +1,137 @@
+//! Tests of the ordering of render queue sort keys, and of the state
+//! changes made when submitting a queue (with the mock GL)
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+mod common;
+
+use gl_model::SortKey;
+
+#[test]
+fn layers_are_most_significant() {
+    let last_of_layer_0 = SortKey::new(0, true, 0xfff, 0xfff, 0x7fff, 0.);
+    let first_of_layer_1 = SortKey::new(1, false, 0, 0, 0, 1000.);
+    assert!(last_of_layer_0 < first_of_layer_1);
+    assert_eq!(first_of_layer_1.layer(), 1);
+    assert_eq!(SortKey::new(15, false, 0, 0, 0, 0.).layer(), 15);
+    assert_eq!(SortKey::new(17, false, 0, 0, 0, 0.).layer(), 1);
+}
+
+#[test]
+fn opaque_before_transparent() {
+    let opaque = SortKey::new(0, false, 0xfff, 0xfff, 0x7fff, 1.0e30);
+    let transparent = SortKey::new(0, true, 0, 0, 0, 1.0e30);
+    assert!(opaque < transparent);
+    assert!(!opaque.is_transparent());
+    assert!(transparent.is_transparent());
+}
+
+#[test]
+fn opaque_front_to_back_within_state() {
+    let near = SortKey::new(0, false, 1, 1, 1, 1.0);
+    let far = SortKey::new(0, false, 1, 1, 1, 10.0);
+    assert!(near < far);
+    // State changes take precedence over depth for opaque primitives
+    assert!(far < SortKey::new(0, false, 1, 1, 2, 0.5));
+    assert!(far < SortKey::new(0, false, 1, 2, 0, 0.5));
+    assert!(far < SortKey::new(0, false, 2, 0, 0, 0.5));
+}
+
+#[test]
+fn opaque_by_program_then_material_then_vao() {
+    let key = SortKey::new(0, false, 1, 2, 3, 0.);
+    assert!(key < SortKey::new(0, false, 2, 0, 0, 0.));
+    assert!(key < SortKey::new(0, false, 1, 3, 0, 0.));
+    assert!(key < SortKey::new(0, false, 1, 2, 4, 0.));
+    assert!(SortKey::new(0, false, 1, 2, 2, 0.) < key);
+}
+
+#[test]
+fn transparent_back_to_front_before_state() {
+    let near = SortKey::new(0, true, 1, 1, 1, 1.0);
+    let far = SortKey::new(0, true, 1, 1, 1, 10.0);
+    assert!(far < near);
+    // Depth takes precedence over state changes for transparent primitives
+    assert!(SortKey::new(0, true, 2, 2, 2, 10.0) < near);
+    assert!(SortKey::new(0, true, 1, 1, 1, 5.0) < SortKey::new(0, true, 1, 1, 2, 5.0));
+    assert!(SortKey::new(0, true, 1, 1, 9, 5.0) < SortKey::new(0, true, 1, 2, 0, 5.0));
+    assert!(SortKey::new(0, true, 1, 9, 9, 5.0) < SortKey::new(0, true, 2, 0, 0, 5.0));
+}
+
+#[test]
+fn depth_quantization() {
+    // Depths are ordered over a wide range
+    let depths = [0.001, 0.01, 0.1, 0.5, 1.0, 2.0, 100.0, 1.0e4, 1.0e6];
+    for pair in depths.windows(2) {
+        assert!(SortKey::new(0, false, 0, 0, 0, pair[0]) < SortKey::new(0, false, 0, 0, 0, pair[1]),
+                "{} should sort before {}", pair[0], pair[1]);
+    }
+    // Depths closer than the quantization are equal
+    assert_eq!(SortKey::new(0, false, 0, 0, 0, 1.0),
+               SortKey::new(0, false, 0, 0, 0, 1.0 + 1.0e-6));
+    // Depths behind the camera are clamped to 0
+    assert_eq!(SortKey::new(0, false, 0, 0, 0, -5.0),
+               SortKey::new(0, false, 0, 0, 0, 0.0));
+    assert_eq!(SortKey::new(0, true, 0, 0, 0, -5.0),
+               SortKey::new(0, true, 0, 0, 0, 0.0));
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn shared_vaos_are_bound_once() {
+    use gl_model::mock_gl::{self, GlCall};
+    use gl_model::{GlProgram, MissingAttributes, RenderContext, RenderQueue, ShaderInstantiable};
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    mock_gl::set_attrib_location("position", 0);
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, "#version 330 core\nin vec3 position;\nvoid main() {}\n")]).unwrap();
+    program.add_attr_name("position", model3d::VertexAttr::Position).unwrap();
+    let (first, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    let (second, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    assert_eq!(first.gl_vao(0), second.gl_vao(0));
+    let instances = [instantiable.instantiate(), instantiable.instantiate()];
+    let mut queue = RenderQueue::new();
+    queue.add(&first, &instances[0], 0, false);
+    queue.add(&second, &instances[1], 0, false);
+    mock_gl::take_calls();
+    let stats = queue.submit(None);
+    assert_eq!(stats.draw_calls, 2);
+    assert_eq!(stats.vao_changes, 1);
+    assert_eq!(stats.program_changes, 1);
+    let binds = mock_gl::take_calls().into_iter().filter(|c| *c == GlCall::BindVertexArray(first.gl_vao(0))).count();
+    assert_eq!(binds, 1);
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn shared_vaos_set_the_missing_attributes_of_each_instantiable() {
+    use gl_model::mock_gl::{self, GlCall};
+    use gl_model::{GlProgram, MissingAttributes, RenderContext, RenderQueue, ShaderInstantiable};
+    use model3d::VertexAttr;
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    mock_gl::set_attrib_location("position", 0);
+    mock_gl::set_attrib_location("color", 1);
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, "#version 330 core\nin vec3 position;\nin vec4 color;\nvoid main() {}\n")]).unwrap();
+    program.add_attr_name("position", VertexAttr::Position).unwrap();
+    program.add_attr_name("color", VertexAttr::Color).unwrap();
+    let red = MissingAttributes::new().with_default(VertexAttr::Color, [1., 0., 0., 1.]);
+    let blue = MissingAttributes::new().with_default(VertexAttr::Color, [0., 0., 1., 1.]);
+    let (first, _) = ShaderInstantiable::new(&program, &instantiable, &red).unwrap();
+    let (second, _) = ShaderInstantiable::new(&program, &instantiable, &blue).unwrap();
+    assert_eq!(first.gl_vao(0), second.gl_vao(0));
+    let instance = instantiable.instantiate();
+    let mut queue = RenderQueue::new();
+    queue.add(&first, &instance, 0, false);
+    queue.add(&second, &instance, 0, false);
+    mock_gl::take_calls();
+    let stats = queue.submit(None);
+    assert_eq!(stats.vao_changes, 1);
+    let attribs : Vec<_> = mock_gl::take_calls().into_iter().filter(|c| matches!(c, GlCall::VertexAttrib4f(..))).collect();
+    assert_eq!(attribs,
+               vec![GlCall::VertexAttrib4f(1, 1., 0., 0., 1.),
+                    GlCall::VertexAttrib4f(1, 0., 0., 0., 1.),
+                    GlCall::VertexAttrib4f(1, 0., 0., 1., 1.),
+                    GlCall::VertexAttrib4f(1, 0., 0., 0., 1.)]);
+}
@@ -0,0 +1,143 @@
+//! Tests of sampler descriptions, and of the sampler objects created
+//! for them (with the GL calls recorded by the mock GL)
+
+use gl_model::{Filter, MipmapMode, SamplerDesc, WrapMode};
+
+#[test]
+fn descriptions_of_gl_enums() {
+    let desc = SamplerDesc::of_gl_enums(None, Some(gl::NEAREST_MIPMAP_LINEAR), gl::CLAMP_TO_EDGE, gl::MIRRORED_REPEAT).unwrap();
+    assert_eq!(desc.mag_filter, Filter::Linear);
+    assert_eq!((desc.min_filter, desc.mipmap), (Filter::Nearest, MipmapMode::Linear));
+    assert_eq!(desc.wrap, [WrapMode::ClampToEdge, WrapMode::MirroredRepeat, WrapMode::Repeat]);
+    assert_eq!(SamplerDesc::of_gl_enums(None, None, gl::REPEAT, gl::REPEAT).unwrap(), SamplerDesc::default());
+    assert!(SamplerDesc::of_gl_enums(Some(gl::LINEAR_MIPMAP_LINEAR), None, gl::REPEAT, gl::REPEAT).is_err());
+    assert!(SamplerDesc::of_gl_enums(None, None, gl::REPEAT, 0).is_err());
+}
+
+/// Get the sampler parameter calls made for a sampler, as (pname,
+/// values)
+#[cfg(all(feature="mock", not(feature="glow")))]
+fn parameters(calls:&[gl_model::mock_gl::GlCall], sampler:u32) -> Vec<(u32, Vec<f32>)> {
+    use gl_model::mock_gl::GlCall;
+    calls.iter().filter_map(|c| {
+        match c {
+            GlCall::SamplerParameteri(s, p, v) if *s == sampler => Some((*p, vec![*v as f32])),
+            GlCall::SamplerParameterf(s, p, v) if *s == sampler => Some((*p, vec![*v])),
+            GlCall::SamplerParameterfv(s, p, v) if *s == sampler => Some((*p, v.clone())),
+            _ => None,
+        }
+    }).collect()
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn samplers_are_shared() {
+    use std::rc::Rc;
+    use gl_model::mock_gl::{self, GlCall};
+    use gl_model::RenderContext;
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    mock_gl::take_calls();
+    let linear = render_context.sampler(&SamplerDesc::default());
+    let again = render_context.sampler(&SamplerDesc::default().with_mipmap(MipmapMode::None));
+    assert!(Rc::ptr_eq(&linear, &again));
+    let nearest = render_context.sampler(&SamplerDesc::new(Filter::Nearest, Filter::Nearest));
+    assert!(!Rc::ptr_eq(&linear, &nearest));
+    assert_ne!(linear.gl_sampler(), nearest.gl_sampler());
+    let generated = mock_gl::take_calls().iter().filter(|c| matches!(c, GlCall::GenSamplers(_))).count();
+    assert_eq!(generated, 2);
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn parameter_sequence() {
+    use gl_model::mock_gl;
+    use gl_model::{CompareFunction, RenderContext};
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    mock_gl::take_calls();
+    let desc = SamplerDesc::new(Filter::Linear, Filter::Nearest)
+        .with_mipmap(MipmapMode::Nearest)
+        .with_lod(0.5, 0., 8.)
+        .with_border_color([1., 0., 0., 1.])
+        .with_compare(CompareFunction::LessEqual);
+    let sampler = render_context.sampler(&desc);
+    let border = gl::CLAMP_TO_BORDER as f32;
+    assert_eq!(parameters(&mock_gl::take_calls(), sampler.gl_sampler()),
+               vec![(gl::TEXTURE_MIN_FILTER, vec![gl::NEAREST_MIPMAP_NEAREST as f32]),
+                    (gl::TEXTURE_MAG_FILTER, vec![gl::LINEAR as f32]),
+                    (gl::TEXTURE_WRAP_S, vec![border]),
+                    (gl::TEXTURE_WRAP_T, vec![border]),
+                    (gl::TEXTURE_WRAP_R, vec![border]),
+                    (gl::TEXTURE_MIN_LOD, vec![0.]),
+                    (gl::TEXTURE_MAX_LOD, vec![8.]),
+                    (gl::TEXTURE_LOD_BIAS, vec![0.5]),
+                    (gl::TEXTURE_BORDER_COLOR, vec![1., 0., 0., 1.]),
+                    (gl::TEXTURE_COMPARE_MODE, vec![gl::COMPARE_REF_TO_TEXTURE as f32]),
+                    (gl::TEXTURE_COMPARE_FUNC, vec![gl::LEQUAL as f32])]);
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn default_samplers_do_not_need_mipmaps() {
+    use gl_model::mock_gl::{self, GlCall};
+    use gl_model::{RenderContext, Texture, TextureFormat};
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    mock_gl::take_calls();
+    let desc = SamplerDesc::of_gl_enums(None, None, gl::REPEAT, gl::REPEAT).unwrap();
+    let sampler = render_context.sampler(&desc);
+    let texture = Texture::new_2d(4, 4, TextureFormat::Rgba8, None).unwrap();
+    texture.bind_unit(0, Some(&sampler)).unwrap();
+    let calls = mock_gl::take_calls();
+    let set = parameters(&calls, sampler.gl_sampler());
+    assert!(set.contains(&(gl::TEXTURE_MIN_FILTER, vec![gl::LINEAR as f32])));
+    assert!(calls.contains(&GlCall::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0)));
+    assert!(calls.contains(&GlCall::BindSampler(0, sampler.gl_sampler())));
+    assert_eq!(texture.levels(), 1);
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn anisotropy_is_clamped() {
+    use gl_model::mock_gl;
+    use gl_model::RenderContext;
+    const TEXTURE_MAX_ANISOTROPY : u32 = 0x84FE;
+    mock_gl::install();
+    mock_gl::set_integer(0x84FF, 8);
+    let mut render_context = RenderContext::new();
+    mock_gl::take_calls();
+    let sampler = render_context.sampler(&SamplerDesc::default().with_anisotropy(16.));
+    let anisotropy : Vec<_> = parameters(&mock_gl::take_calls(), sampler.gl_sampler()).into_iter()
+        .filter(|(p, _)| *p == TEXTURE_MAX_ANISOTROPY).collect();
+    assert_eq!(anisotropy, vec![(TEXTURE_MAX_ANISOTROPY, vec![8.])]);
+    let sampler = render_context.sampler(&SamplerDesc::default().with_anisotropy(4.));
+    assert!(parameters(&mock_gl::take_calls(), sampler.gl_sampler()).contains(&(TEXTURE_MAX_ANISOTROPY, vec![4.])));
+    let sampler = render_context.sampler(&SamplerDesc::default());
+    assert!(!parameters(&mock_gl::take_calls(), sampler.gl_sampler()).iter().any(|(p, _)| *p == TEXTURE_MAX_ANISOTROPY));
+}
+
+#[cfg(all(feature="mock", not(feature="glow")))]
+#[test]
+fn border_clamp_needs_support() {
+    use gl_model::mock_gl;
+    use gl_model::RenderContext;
+    mock_gl::install();
+    mock_gl::set_string(gl::VERSION, "OpenGL ES 3.0 Mock");
+    mock_gl::set_string(gl::SHADING_LANGUAGE_VERSION, "OpenGL ES GLSL ES 3.00 Mock");
+    let mut render_context = RenderContext::new();
+    mock_gl::take_calls();
+    let desc = SamplerDesc::default().with_lod(0.5, 0., 8.).with_border_color([1.; 4]);
+    let sampler = render_context.sampler(&desc);
+    let set = parameters(&mock_gl::take_calls(), sampler.gl_sampler());
+    assert!(set.contains(&(gl::TEXTURE_WRAP_S, vec![gl::CLAMP_TO_EDGE as f32])));
+    assert!(!set.iter().any(|(p, _)| *p == gl::TEXTURE_BORDER_COLOR || *p == gl::TEXTURE_LOD_BIAS));
+
+    mock_gl::set_extensions(&["GL_EXT_texture_border_clamp"]);
+    let mut render_context = RenderContext::new();
+    mock_gl::take_calls();
+    let sampler = render_context.sampler(&desc);
+    let set = parameters(&mock_gl::take_calls(), sampler.gl_sampler());
+    assert!(set.contains(&(gl::TEXTURE_WRAP_S, vec![gl::CLAMP_TO_BORDER as f32])));
+    assert!(set.contains(&(gl::TEXTURE_BORDER_COLOR, vec![1.; 4])));
+}
@@ -0,0 +1,143 @@
+//! Tests of [gl_model::ShaderInstantiable] - the sharing of VAOs
+//! between shader classes, the default material, and owned shader
+//! instantiables - with the GL calls recorded by the mock GL
+#![cfg(all(feature="mock", not(feature="glow")))]
+
+mod common;
+
+use gl_model::mock_gl::{self, GlCall};
+use gl_model::{GlProgram, Material, MissingAttributes, RenderContext, ShaderInstantiable, UniformId};
+use model3d::VertexAttr;
+
+/// Compile a program with a position attribute at a location
+fn program(location:i32) -> GlProgram {
+    mock_gl::set_attrib_location("position", location);
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, "#version 330 core\nin vec3 position;\nvoid main() {}\n")]).unwrap();
+    program.add_attr_name("position", VertexAttr::Position).unwrap();
+    program
+}
+
+/// Count the VAOs generated in a list of calls
+fn vaos_generated(calls:&[GlCall]) -> usize {
+    calls.iter().filter(|c| matches!(c, GlCall::GenVertexArrays(_))).count()
+}
+
+#[test]
+fn vaos_are_shared_by_identical_layouts() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    let (program_a, program_b, program_c) = (program(0), program(0), program(1));
+    mock_gl::take_calls();
+    let (a, _) = ShaderInstantiable::new(&program_a, &instantiable, &MissingAttributes::new()).unwrap();
+    let (b, _) = ShaderInstantiable::new(&program_b, &instantiable, &MissingAttributes::new()).unwrap();
+    assert_eq!(a.gl_vao(0), b.gl_vao(0));
+    assert_eq!(vaos_generated(&mock_gl::take_calls()), 1);
+
+    // A different attribute location is a different layout
+    let (c, _) = ShaderInstantiable::new(&program_c, &instantiable, &MissingAttributes::new()).unwrap();
+    assert_ne!(a.gl_vao(0), c.gl_vao(0));
+    let calls = mock_gl::take_calls();
+    assert_eq!(vaos_generated(&calls), 1);
+    assert!(calls.contains(&GlCall::EnableVertexAttribArray(1)));
+
+    // Different vertices have their own VAOs
+    let other = common::triangle(&mut render_context, 2.0);
+    mock_gl::take_calls();
+    let (d, _) = ShaderInstantiable::new(&program_a, &other, &MissingAttributes::new()).unwrap();
+    assert_ne!(a.gl_vao(0), d.gl_vao(0));
+    assert_eq!(vaos_generated(&mock_gl::take_calls()), 1);
+}
+
+#[test]
+fn shared_vaos_are_deleted_with_their_last_user() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    let program = program(0);
+    let (a, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    let (b, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    let vao = a.gl_vao(0);
+    drop(a);
+    render_context.flush_deletions();
+    assert!(!mock_gl::take_calls().contains(&GlCall::DeleteVertexArrays(vec![vao])));
+    drop(b);
+    render_context.flush_deletions();
+    assert!(mock_gl::take_calls().contains(&GlCall::DeleteVertexArrays(vec![vao])));
+
+    // The cache does not return the deleted VAO
+    let (c, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    assert_eq!(vaos_generated(&mock_gl::take_calls()), 1);
+    assert_ne!(c.gl_vao(0), 0);
+}
+
+#[test]
+fn shared_vaos_are_rebuilt_after_context_loss() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    render_context.enable_context_recovery();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    let program = program(0);
+    let (a, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    let (b, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    mock_gl::lose_context();
+    render_context.context_lost();
+    mock_gl::take_calls();
+    render_context.recover_context().unwrap();
+    // The shared VAO is rebuilt once, for both users
+    assert_eq!(vaos_generated(&mock_gl::take_calls()), 1);
+    assert_eq!(a.gl_vao(0), b.gl_vao(0));
+}
+
+#[test]
+fn primitives_without_materials_share_the_default() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    mock_gl::set_uniform_location("uBaseColorFactor", 1);
+    let mut program = program(0);
+    program.add_uniform_name("uBaseColorFactor", UniformId::BaseColorFactor).unwrap();
+    let (shader_instantiable, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    assert!(shader_instantiable.material(0).is_none());
+    assert_eq!(shader_instantiable.material_id(0), 0);
+    let default_id = Material::default_material().id();
+    assert_eq!(Material::default_material().id(), default_id);
+    mock_gl::take_calls();
+    shader_instantiable.bind_material(0);
+    shader_instantiable.bind_material(0);
+    assert_eq!(mock_gl::take_calls(), vec![GlCall::Uniform4f(1, [1.; 4]); 2]);
+    // Creating new materials does not change the default
+    let material = Material::new();
+    assert_ne!(material.id(), default_id);
+    assert_eq!(Material::default_material().id(), default_id);
+}
+
+#[test]
+fn owned_shader_instantiables_keep_their_program_and_instantiable() {
+    use std::rc::Rc;
+    use gl_model::{OwnedShaderInstantiable, ShaderClass};
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let instantiable = Rc::new(common::triangle(&mut render_context, 1.0));
+    let shader_class : Rc<dyn ShaderClass> = Rc::new(program(0));
+    let weak_program = Rc::downgrade(&shader_class);
+    let instance = instantiable.instantiate();
+    let (owned, report) = OwnedShaderInstantiable::new_owned(shader_class, instantiable.clone(), &MissingAttributes::new()).unwrap();
+    assert!(report.is_clean());
+    let (variant, _) = owned.variant_owned(Rc::new(program(1))).unwrap();
+
+    // The owned shader instantiables may be kept in the registry
+    let handle = render_context.registry_mut().add("triangle", owned);
+    assert!(weak_program.upgrade().is_some());
+    mock_gl::take_calls();
+    render_context.registry().get(handle).unwrap().gl_draw(&instance);
+    variant.gl_draw(&instance);
+    let calls = mock_gl::take_calls();
+    assert_eq!(calls.iter().filter(|c| matches!(c, GlCall::DrawElements(gl::TRIANGLES, 3, _, _))).count(), 2);
+    assert_eq!(calls.iter().filter(|c| matches!(c, GlCall::BindVertexArray(v) if *v != 0)).count(), 2);
+
+    // Dropping the last holder drops the program
+    render_context.registry_mut().release(handle);
+    render_context.end_frame();
+    assert!(weak_program.upgrade().is_none());
+}
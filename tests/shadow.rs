@@ -0,0 +1,96 @@
+//! Tests of rendering shadow maps, with the GL calls recorded by the
+//! mock GL
+#![cfg(all(feature="mock", not(feature="glow")))]
+
+mod common;
+
+use gl_model::mock_gl::{self, GlCall};
+use gl_model::{Backend, GlBackend};
+use gl_model::{Camera, MissingAttributes, RenderContext, ShaderInstantiable, ShadowKind, ShadowMap};
+
+/// Create a spot light shadow map, looking down -z at the origin
+fn spot_shadow_map(render_context:&mut RenderContext) -> ShadowMap {
+    let mut shadow_map = ShadowMap::spot(render_context, 64).unwrap();
+    shadow_map.update_spot([0., 0., 5.], [0., 0., -1.], 0.5, 20.).unwrap();
+    shadow_map.set_bias(2., 4.);
+    shadow_map
+}
+
+#[test]
+fn render_restores_disabled_state() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let shadow_map = spot_shadow_map(&mut render_context);
+    Backend::depth_mask(false);
+    Backend::polygon_offset(0.5, 1.);
+    mock_gl::take_calls();
+    shadow_map.render(&[]);
+    let calls = mock_gl::take_calls();
+    let render = calls.iter().position(|c| *c == GlCall::Clear(gl::DEPTH_BUFFER_BIT)).unwrap();
+    for call in [GlCall::Enable(gl::DEPTH_TEST), GlCall::Enable(gl::POLYGON_OFFSET_FILL),
+                 GlCall::DepthMask(gl::TRUE), GlCall::PolygonOffset(2., 4.)] {
+        assert!(calls[0..render].contains(&call), "{:?} should be made before rendering", call);
+    }
+    assert!(!Backend::is_enabled(gl::DEPTH_TEST));
+    assert!(!Backend::is_enabled(gl::POLYGON_OFFSET_FILL));
+    assert_eq!(Backend::get_integer(gl::DEPTH_WRITEMASK), 0);
+    assert_eq!(Backend::get_float(gl::POLYGON_OFFSET_FACTOR), 0.5);
+    assert_eq!(Backend::get_float(gl::POLYGON_OFFSET_UNITS), 1.);
+}
+
+#[test]
+fn render_keeps_enabled_state() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let shadow_map = spot_shadow_map(&mut render_context);
+    Backend::enable(gl::DEPTH_TEST);
+    Backend::enable(gl::POLYGON_OFFSET_FILL);
+    Backend::polygon_offset(1., 1.);
+    mock_gl::take_calls();
+    shadow_map.render(&[]);
+    let calls = mock_gl::take_calls();
+    assert!(!calls.iter().any(|c| matches!(c, GlCall::Disable(_))), "{:?}", calls);
+    assert!(Backend::is_enabled(gl::DEPTH_TEST));
+    assert!(Backend::is_enabled(gl::POLYGON_OFFSET_FILL));
+    assert_eq!(Backend::get_integer(gl::DEPTH_WRITEMASK), 1);
+    assert_eq!(calls.last(), Some(&GlCall::BindFramebuffer(gl::FRAMEBUFFER, 0)));
+    assert!(calls.contains(&GlCall::PolygonOffset(1., 1.)));
+}
+
+#[test]
+fn render_draws_casters_in_the_light_frustum() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let shadow_map = spot_shadow_map(&mut render_context);
+    let instantiable = common::triangle(&mut render_context, 1.0);
+    mock_gl::set_attrib_location("aPosition", 0);
+    mock_gl::set_uniform_location("uViewProjectionMatrix", 1);
+    mock_gl::set_uniform_location("uModelMatrix", 2);
+    mock_gl::set_uniform_location("uMeshMatrix", 3);
+    let program = ShadowMap::depth_program(ShadowKind::Spot).unwrap();
+    let (shader_instantiable, _) = ShaderInstantiable::new(&program, &instantiable, &MissingAttributes::new()).unwrap();
+    let inside = instantiable.instantiate();
+    let mut outside = instantiable.instantiate();
+    outside.transformation.set_translation([100., 0., 0.]);
+    mock_gl::take_calls();
+    let stats = shadow_map.render(&[(&shader_instantiable, &inside), (&shader_instantiable, &outside)]);
+    assert_eq!((stats.drawn, stats.culled), (1, 1));
+    let calls = mock_gl::take_calls();
+    assert!(calls.contains(&GlCall::UniformMatrix4fv(1, gl::FALSE, shadow_map.matrices()[0].to_vec())));
+    assert_eq!(calls.iter().filter(|c| matches!(c, GlCall::DrawElements(gl::TRIANGLES, 3, _, _))).count(), 1);
+}
+
+#[test]
+fn directional_cascades_of_large_orthographic_cameras() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let mut shadow_map = ShadowMap::directional(&mut render_context, 64, 1).unwrap();
+    // The view-projection determinant is about 8E-9, but not singular
+    let view = Camera::look_at([0.; 3], [0., 0., -1.], [0., 1., 0.]);
+    let camera = Camera::new(view, Camera::orthographic(-500., 500., -500., 500., 1., 1000.));
+    shadow_map.update_directional(&camera, [0., -1., 0.], 2000., 0.5).unwrap();
+    assert!((shadow_map.splits()[0] - 1000.).abs() < 0.01);
+
+    let camera = Camera::new(view, [0.; 16]);
+    assert!(shadow_map.update_directional(&camera, [0., -1., 0.], 2000., 0.5).is_err());
+}
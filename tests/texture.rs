@@ -0,0 +1,208 @@
+//! Tests of texture creation, image updates and mipmap generation,
+//! with the GL calls recorded by the mock GL
+#![cfg(all(feature="mock", not(feature="glow")))]
+
+/// Encode an image in a file format
+#[cfg(feature="image")]
+fn encode<I:Into<dep_image::DynamicImage>>(image:I, format:dep_image::ImageFormat) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image.into().write_to(&mut std::io::Cursor::new(&mut bytes), format).unwrap();
+    bytes
+}
+
+#[cfg(feature="image")]
+#[test]
+fn srgb_images_with_mipmaps_have_alpha() {
+    use gl_model::{mock_gl, RenderContext, Texture, TextureFormat, ImageOptions};
+    let image = dep_image::RgbImage::from_raw(2, 2, vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]).unwrap();
+    let png = encode(image, dep_image::ImageFormat::Png);
+    mock_gl::install();
+    let _render_context = RenderContext::new();
+    let texture = Texture::of_image(&png, &ImageOptions::default()).unwrap();
+    assert_eq!(texture.format(), TextureFormat::Srgb8Alpha8);
+    assert_eq!(texture.levels(), 2);
+    let texture = Texture::of_image(&png, &ImageOptions::default().with_mipmaps(false)).unwrap();
+    assert_eq!(texture.format(), TextureFormat::Srgb8);
+}
+
+#[cfg(feature="image")]
+#[test]
+fn linear_gray_alpha_images_are_rgba() {
+    use gl_model::{mock_gl, RenderContext, Texture, TextureFormat, ImageOptions};
+    let image = dep_image::GrayAlphaImage::from_raw(1, 1, vec![200, 100]).unwrap();
+    let png = encode(image, dep_image::ImageFormat::Png);
+    mock_gl::install();
+    let _render_context = RenderContext::new();
+    let options = ImageOptions::default().with_srgb(false);
+    assert_eq!(Texture::of_image(&png, &options).unwrap().format(), TextureFormat::Rgba8);
+    let image = dep_image::GrayImage::from_raw(1, 1, vec![200]).unwrap();
+    let png = encode(image, dep_image::ImageFormat::Png);
+    assert_eq!(Texture::of_image(&png, &options).unwrap().format(), TextureFormat::R8);
+    let image = dep_image::ImageBuffer::<dep_image::LumaA<u16>, _>::from_raw(1, 1, vec![200u16, 100]).unwrap();
+    let png = encode(image, dep_image::ImageFormat::Png);
+    assert_eq!(Texture::of_image(&png, &options).unwrap().format(), TextureFormat::Rgba16F);
+}
+
+#[cfg(feature="image")]
+#[test]
+fn rgb_half_float_images_with_mipmaps_have_alpha() {
+    use gl_model::{mock_gl, RenderContext, Texture, TextureFormat, ImageOptions};
+    let image = dep_image::Rgb32FImage::from_raw(2, 2, vec![0.5; 12]).unwrap();
+    let hdr = encode(image, dep_image::ImageFormat::Hdr);
+    let image = dep_image::ImageBuffer::<dep_image::Rgb<u16>, _>::from_raw(2, 2, vec![1000u16; 12]).unwrap();
+    let png = encode(image, dep_image::ImageFormat::Png);
+    mock_gl::install();
+    let _render_context = RenderContext::new();
+    let linear = ImageOptions::default().with_srgb(false);
+    for bytes in [&hdr, &png] {
+        let texture = Texture::of_image(bytes, &linear).unwrap();
+        assert_eq!(texture.format(), TextureFormat::Rgba16F);
+        assert_eq!(texture.levels(), 2);
+        let texture = Texture::of_image(bytes, &linear.with_mipmaps(false)).unwrap();
+        assert_eq!(texture.format(), TextureFormat::Rgb16F);
+        assert_eq!(texture.levels(), 1);
+    }
+}
+
+/// The calls made by [gl_model::Texture::create] for a new texture
+fn create_calls(target:u32, texture:u32, wrap:u32) -> Vec<gl_model::mock_gl::GlCall> {
+    use gl_model::mock_gl::GlCall;
+    let mut calls = vec![GlCall::GenTextures(vec![texture]),
+                         GlCall::BindTexture(target, texture),
+                         GlCall::PixelStorei(gl::UNPACK_ALIGNMENT, 1),
+                         GlCall::TexParameteri(target, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32),
+                         GlCall::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32),
+                         GlCall::TexParameteri(target, gl::TEXTURE_WRAP_S, wrap as i32),
+                         GlCall::TexParameteri(target, gl::TEXTURE_WRAP_T, wrap as i32)];
+    if target == gl::TEXTURE_CUBE_MAP {
+        calls.push(GlCall::TexParameteri(target, gl::TEXTURE_WRAP_R, wrap as i32));
+    }
+    calls.push(GlCall::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, 0));
+    calls
+}
+
+#[test]
+fn cube_map_creation() {
+    use gl_model::mock_gl::{self, GlCall};
+    use gl_model::{RenderContext, Texture, TextureFormat, TextureKind};
+    mock_gl::install();
+    let _render_context = RenderContext::new();
+    mock_gl::take_calls();
+    let faces = [vec![0u8; 4 * 4 * 4], vec![1; 4 * 4 * 4], vec![2; 4 * 4 * 4],
+                 vec![3; 4 * 4 * 4], vec![4; 4 * 4 * 4], vec![5; 4 * 4 * 4]];
+    let texture = Texture::new_cube_map(4, TextureFormat::Rgba8,
+                                        Some([&faces[0], &faces[1], &faces[2], &faces[3], &faces[4], &faces[5]])).unwrap();
+    assert_eq!((texture.kind(), texture.layers(), texture.levels()), (TextureKind::CubeMap, 6, 1));
+    let gl = texture.gl_texture();
+    let mut expected = create_calls(gl::TEXTURE_CUBE_MAP, gl, gl::CLAMP_TO_EDGE);
+    for face in 0..6 {
+        expected.push(GlCall::TexImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + face, 0, gl::RGBA8 as i32, 4, 4,
+                                         gl::RGBA, gl::UNSIGNED_BYTE, true));
+    }
+    expected.push(GlCall::BindTexture(gl::TEXTURE_CUBE_MAP, 0));
+    assert_eq!(mock_gl::take_calls(), expected);
+
+    // Faces must be large enough
+    assert!(Texture::new_cube_map(4, TextureFormat::Rgba8, Some([&faces[0], &faces[1], &faces[2], &faces[3], &faces[4], &[0; 4]])).is_err());
+}
+
+#[test]
+fn array_creation() {
+    use gl_model::mock_gl::{self, GlCall};
+    use gl_model::{RenderContext, Texture, TextureFormat, TextureKind};
+    mock_gl::install();
+    let _render_context = RenderContext::new();
+    mock_gl::take_calls();
+    let layers = [vec![0u8; 8 * 2], vec![1u8; 8 * 2], vec![2u8; 8 * 2]];
+    let texture = Texture::new_array(8, 2, 3, TextureFormat::R8, Some(&[&layers[0], &layers[1], &layers[2]])).unwrap();
+    assert_eq!((texture.kind(), texture.layers()), (TextureKind::Array2D, 3));
+    let gl = texture.gl_texture();
+    let mut expected = create_calls(gl::TEXTURE_2D_ARRAY, gl, gl::CLAMP_TO_EDGE);
+    expected.push(GlCall::TexImage3D(gl::TEXTURE_2D_ARRAY, 0, gl::R8 as i32, 8, 2, 3, gl::RED, gl::UNSIGNED_BYTE, false));
+    for layer in 0..3 {
+        expected.push(GlCall::TexSubImage3D(gl::TEXTURE_2D_ARRAY, 0, 0, 0, layer, 8, 2, 1, gl::RED, gl::UNSIGNED_BYTE));
+    }
+    expected.push(GlCall::BindTexture(gl::TEXTURE_2D_ARRAY, 0));
+    assert_eq!(mock_gl::take_calls(), expected);
+
+    // One image is required for each layer
+    assert!(Texture::new_array(8, 2, 3, TextureFormat::R8, Some(&[&layers[0], &layers[1]])).is_err());
+    // Without data the layers are only allocated
+    mock_gl::take_calls();
+    let texture = Texture::new_array(8, 2, 3, TextureFormat::R8, None).unwrap();
+    let calls = mock_gl::take_calls();
+    assert!(calls.contains(&GlCall::TexImage3D(gl::TEXTURE_2D_ARRAY, 0, gl::R8 as i32, 8, 2, 3, gl::RED, gl::UNSIGNED_BYTE, false)));
+    assert!(!calls.iter().any(|c| matches!(c, GlCall::TexSubImage3D(..))));
+    assert_eq!(texture.byte_size(), 8 * 2 * 3);
+}
+
+#[test]
+fn face_and_layer_updates() {
+    use gl_model::mock_gl::{self, GlCall};
+    use gl_model::{CubeFace, RenderContext, Texture, TextureFormat, TextureImage};
+    mock_gl::install();
+    let _render_context = RenderContext::new();
+    let cube_map = Texture::new_cube_map(4, TextureFormat::Rgba8, None).unwrap();
+    mock_gl::take_calls();
+    cube_map.set_face(CubeFace::NegativeY, &[0; 4 * 4 * 4]).unwrap();
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::BindTexture(gl::TEXTURE_CUBE_MAP, cube_map.gl_texture()),
+                    GlCall::PixelStorei(gl::UNPACK_ALIGNMENT, 1),
+                    GlCall::TexSubImage2D(gl::TEXTURE_CUBE_MAP_NEGATIVE_Y, 0, 0, 0, 4, 4, gl::RGBA, gl::UNSIGNED_BYTE),
+                    GlCall::BindTexture(gl::TEXTURE_CUBE_MAP, 0)]);
+    assert!(cube_map.set_face(CubeFace::PositiveX, &[0; 4]).is_err());
+    assert!(cube_map.sub_image(TextureImage::Layer(0), 0, (0, 0), (1, 1), &[0; 4]).is_err());
+
+    let array = Texture::new_array(8, 2, 3, TextureFormat::R8, None).unwrap();
+    mock_gl::take_calls();
+    array.set_layer(2, &[0; 8 * 2]).unwrap();
+    array.sub_image(TextureImage::Layer(1), 0, (4, 1), (2, 1), &[0; 2]).unwrap();
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::BindTexture(gl::TEXTURE_2D_ARRAY, array.gl_texture()),
+                    GlCall::PixelStorei(gl::UNPACK_ALIGNMENT, 1),
+                    GlCall::TexSubImage3D(gl::TEXTURE_2D_ARRAY, 0, 0, 0, 2, 8, 2, 1, gl::RED, gl::UNSIGNED_BYTE),
+                    GlCall::BindTexture(gl::TEXTURE_2D_ARRAY, 0),
+                    GlCall::BindTexture(gl::TEXTURE_2D_ARRAY, array.gl_texture()),
+                    GlCall::PixelStorei(gl::UNPACK_ALIGNMENT, 1),
+                    GlCall::TexSubImage3D(gl::TEXTURE_2D_ARRAY, 0, 4, 1, 1, 2, 1, 1, gl::RED, gl::UNSIGNED_BYTE),
+                    GlCall::BindTexture(gl::TEXTURE_2D_ARRAY, 0)]);
+    assert!(array.set_layer(3, &[0; 8 * 2]).is_err());
+    assert!(array.sub_image(TextureImage::Layer(0), 0, (7, 0), (2, 1), &[0; 2]).is_err());
+    assert!(mock_gl::take_calls().is_empty());
+}
+
+#[test]
+fn mipmap_generation() {
+    use gl_model::mock_gl::{self, GlCall};
+    use gl_model::{CubeFace, RenderContext, Texture, TextureFormat, TextureImage};
+    mock_gl::install();
+    let _render_context = RenderContext::new();
+    let mut cube_map = Texture::new_cube_map(16, TextureFormat::Rgba8, None).unwrap();
+    mock_gl::take_calls();
+    cube_map.generate_mipmaps().unwrap();
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::BindTexture(gl::TEXTURE_CUBE_MAP, cube_map.gl_texture()),
+                    GlCall::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAX_LEVEL, 4),
+                    GlCall::GenerateMipmap(gl::TEXTURE_CUBE_MAP),
+                    GlCall::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32),
+                    GlCall::BindTexture(gl::TEXTURE_CUBE_MAP, 0)]);
+    assert_eq!(cube_map.levels(), 5);
+    assert_eq!(cube_map.byte_size(), 6 * 4 * (256 + 64 + 16 + 4 + 1));
+    // Mip levels may then be updated
+    cube_map.sub_image(TextureImage::Face(CubeFace::PositiveZ), 4, (0, 0), (1, 1), &[0; 4]).unwrap();
+    assert!(mock_gl::take_calls().contains(&GlCall::TexSubImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_Z, 4, 0, 0, 1, 1, gl::RGBA, gl::UNSIGNED_BYTE)));
+    assert!(cube_map.sub_image(TextureImage::Face(CubeFace::PositiveZ), 5, (0, 0), (1, 1), &[0; 4]).is_err());
+
+    // A non-square array has levels down to 1x1
+    let mut array = Texture::new_array(8, 2, 3, TextureFormat::R8, None).unwrap();
+    array.generate_mipmaps().unwrap();
+    assert_eq!(array.levels(), 4);
+    assert!(mock_gl::take_calls().contains(&GlCall::GenerateMipmap(gl::TEXTURE_2D_ARRAY)));
+
+    // Depth textures cannot have mipmaps generated
+    let mut depth = Texture::new_2d(4, 4, TextureFormat::Depth24, None).unwrap();
+    mock_gl::take_calls();
+    assert!(depth.generate_mipmaps().is_err());
+    assert!(mock_gl::take_calls().is_empty());
+    assert_eq!(depth.levels(), 1);
+}
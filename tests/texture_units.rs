@@ -0,0 +1,95 @@
+//! Tests of the texture units allocated by each render context, and
+//! of the cache of the textures and samplers bound to them (with the
+//! GL calls recorded by the mock GL)
+#![cfg(all(feature="mock", not(feature="glow")))]
+
+use gl_model::mock_gl::{self, GlCall};
+use gl_model::{RenderContext, Sampler, SamplerDesc, Texture, TextureFormat, TextureSlot};
+
+#[test]
+fn units_are_allocated_per_context() {
+    mock_gl::install();
+    mock_gl::set_integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS, 3);
+    let first = RenderContext::new();
+    assert_eq!(first.texture_unit(TextureSlot::BaseColor), Ok(0));
+    assert_eq!(first.texture_unit(TextureSlot::Normal), Ok(1));
+    assert_eq!(first.texture_unit(TextureSlot::BaseColor), Ok(0));
+    assert_eq!(first.reserve_texture_unit(), Ok(2));
+    assert!(first.reserve_texture_unit().is_err());
+    assert!(first.texture_unit(TextureSlot::Emissive).is_err());
+    assert_eq!(first.texture_unit(TextureSlot::Normal), Ok(1));
+
+    let second = RenderContext::new();
+    assert_eq!(second.texture_unit(TextureSlot::Normal), Ok(0));
+    assert_eq!(second.reserve_texture_unit(), Ok(1));
+    assert_eq!(first.texture_unit(TextureSlot::Normal), Ok(1));
+}
+
+#[test]
+fn bound_textures_are_not_rebound() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let sampler = render_context.sampler(&SamplerDesc::default());
+    let texture = Texture::new_2d(1, 1, TextureFormat::Rgba8, None).unwrap();
+    let other = Texture::new_2d(1, 1, TextureFormat::Rgba8, None).unwrap();
+    mock_gl::take_calls();
+
+    texture.bind_unit(2, Some(&sampler)).unwrap();
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::ActiveTexture(gl::TEXTURE2),
+                    GlCall::BindTexture(gl::TEXTURE_2D, texture.gl_texture()),
+                    GlCall::BindSampler(2, sampler.gl_sampler())]);
+    texture.bind_unit(2, Some(&sampler)).unwrap();
+    assert_eq!(mock_gl::take_calls(), vec![]);
+
+    texture.bind_unit(2, None).unwrap();
+    assert_eq!(mock_gl::take_calls(), vec![GlCall::BindSampler(2, 0)]);
+    Sampler::unbind(2).unwrap();
+    sampler.bind(2).unwrap();
+    assert_eq!(mock_gl::take_calls(), vec![GlCall::BindSampler(2, sampler.gl_sampler())]);
+
+    other.bind_unit(2, Some(&sampler)).unwrap();
+    assert_eq!(mock_gl::take_calls(), vec![GlCall::BindTexture(gl::TEXTURE_2D, other.gl_texture())]);
+    texture.bind_unit(1, Some(&sampler)).unwrap();
+    other.bind_unit(2, Some(&sampler)).unwrap();
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::ActiveTexture(gl::TEXTURE1),
+                    GlCall::BindTexture(gl::TEXTURE_2D, texture.gl_texture()),
+                    GlCall::BindSampler(1, sampler.gl_sampler())]);
+}
+
+#[test]
+fn bindings_are_forgotten_on_context_loss() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let texture = Texture::new_2d(1, 1, TextureFormat::Rgba8, None).unwrap();
+    texture.bind_unit(1, None).unwrap();
+    let name = texture.gl_texture();
+
+    mock_gl::lose_context();
+    render_context.context_lost();
+    render_context.recover_context().unwrap();
+    drop(texture);
+    let texture = Texture::new_2d(1, 1, TextureFormat::Rgba8, None).unwrap();
+    assert_eq!(texture.gl_texture(), name);
+    assert!(mock_gl::take_calls().contains(&GlCall::BindTexture(gl::TEXTURE_2D, name)));
+    texture.bind_unit(1, None).unwrap();
+    assert_eq!(mock_gl::take_calls(),
+               vec![GlCall::ActiveTexture(gl::TEXTURE1),
+                    GlCall::BindTexture(gl::TEXTURE_2D, name)]);
+}
+
+#[test]
+fn units_need_a_render_context() {
+    mock_gl::install();
+    drop(RenderContext::new());
+    assert!(Sampler::unbind(0).is_err());
+    mock_gl::take_calls();
+    assert!(Texture::new_2d(1, 1, TextureFormat::Rgba8, None).is_err());
+    let calls = mock_gl::take_calls();
+    let generated = calls.iter().find_map(|c| match c {
+        GlCall::GenTextures(names) => Some(names.clone()),
+        _ => None,
+    });
+    assert_eq!(calls.last(), generated.map(GlCall::DeleteTextures).as_ref());
+}
@@ -0,0 +1,131 @@
+//! Tests of asynchronous texture uploads, with fences signaled (or
+//! not) by the mock GL
+#![cfg(all(feature="mock", not(feature="glow")))]
+
+use gl_model::mock_gl::{self, GlCall};
+use gl_model::{GlProgram, Material, RenderContext, TextureFormat, TextureSlot, UniformId};
+
+const VERTEX_SHADER : &str = "#version 330 core
+void main() { gl_Position = vec4(0.0); }
+";
+
+const FRAGMENT_SHADER : &str = "#version 330 core
+uniform sampler2D base_color;
+out vec4 fragColor;
+void main() { fragColor = texture(base_color, vec2(0.0)); }
+";
+
+/// The first fence created on a thread by the mock
+const FENCE : usize = 1;
+
+#[test]
+fn uploads_are_pending_until_their_fence_signals() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    mock_gl::set_fence_signaled(FENCE, false);
+    let texture = render_context.upload_texture_2d(4, 4, TextureFormat::Rgba8, &[0; 64], true).unwrap();
+    let calls = mock_gl::take_calls();
+    assert!(calls.contains(&GlCall::FenceSync(FENCE)));
+    assert!(!calls.contains(&GlCall::GenerateMipmap(gl::TEXTURE_2D)));
+    assert!(!texture.is_ready());
+    assert_eq!(texture.levels(), 1);
+    assert_eq!(render_context.pending_uploads(), (1, 64));
+
+    assert_eq!(render_context.poll_uploads(), 0);
+    assert!(!texture.is_ready());
+    assert_eq!(mock_gl::take_calls(), vec![]);
+
+    mock_gl::set_fence_signaled(FENCE, true);
+    assert_eq!(render_context.poll_uploads(), 1);
+    assert!(texture.is_ready());
+    assert_eq!(texture.levels(), 3);
+    assert_eq!(render_context.pending_uploads(), (0, 0));
+    let calls = mock_gl::take_calls();
+    let generate = calls.iter().position(|c| *c == GlCall::GenerateMipmap(gl::TEXTURE_2D)).unwrap();
+    let delete_fence = calls.iter().position(|c| *c == GlCall::DeleteSync(FENCE)).unwrap();
+    assert!(generate < delete_fence);
+    assert!(calls.contains(&GlCall::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 2)));
+    assert!(calls.contains(&GlCall::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32)));
+}
+
+#[test]
+fn uploads_without_mipmaps_do_not_generate_them() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    let texture = render_context.upload_texture_2d(4, 4, TextureFormat::Rgba8, &[0; 64], false).unwrap();
+    assert!(!texture.is_ready());
+    assert_eq!(render_context.poll_uploads(), 1);
+    assert!(texture.is_ready());
+    assert_eq!(texture.levels(), 1);
+    assert!(!mock_gl::take_calls().contains(&GlCall::GenerateMipmap(gl::TEXTURE_2D)));
+}
+
+#[test]
+fn uploads_are_abandoned_on_context_loss() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    mock_gl::set_fence_signaled(FENCE, false);
+    let texture = render_context.upload_texture_2d(4, 4, TextureFormat::Rgba8, &[0; 64], true).unwrap();
+    assert!(texture.is_current());
+    mock_gl::take_calls();
+
+    render_context.context_lost();
+    assert_eq!(render_context.pending_uploads(), (0, 0));
+    assert!(!texture.is_ready());
+    assert!(!texture.is_current());
+    render_context.recover_context().unwrap();
+
+    // The fence of the lost context is not deleted, and signaling it
+    // does not make the texture ready
+    mock_gl::set_fence_signaled(FENCE, true);
+    assert_eq!(render_context.poll_uploads(), 0);
+    assert!(!texture.is_ready());
+    let calls = mock_gl::take_calls();
+    assert!(!calls.contains(&GlCall::DeleteSync(FENCE)));
+    assert!(!calls.contains(&GlCall::GenerateMipmap(gl::TEXTURE_2D)));
+
+    // Uploading it again gives a texture that becomes ready
+    let texture = render_context.upload_texture_2d(4, 4, TextureFormat::Rgba8, &[0; 64], true).unwrap();
+    render_context.poll_uploads();
+    assert!(texture.is_ready());
+    assert!(texture.is_current());
+}
+
+#[test]
+fn invalid_uploads_are_rejected() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    assert!(render_context.upload_texture_2d(4, 4, TextureFormat::Rgba8, &[0; 63], false).is_err());
+    assert!(render_context.upload_texture_2d(4, 4, TextureFormat::Depth24, &[0; 64], true).is_err());
+    assert_eq!(render_context.pending_uploads(), (0, 0));
+}
+
+#[test]
+fn materials_bind_placeholders_while_pending() {
+    mock_gl::install();
+    let mut render_context = RenderContext::new();
+    mock_gl::set_uniform_location("base_color", 3);
+    let mut program = GlProgram::compile_program(&[(gl::VERTEX_SHADER, VERTEX_SHADER),
+                                                  (gl::FRAGMENT_SHADER, FRAGMENT_SHADER)]).unwrap();
+    program.add_uniform_name("base_color", UniformId::Texture(TextureSlot::BaseColor)).unwrap();
+    mock_gl::set_fence_signaled(FENCE, false);
+    let texture = render_context.upload_texture_2d(1, 1, TextureFormat::Rgba8, &[255, 0, 0, 255], false).unwrap();
+    let material = Material::new().with_texture(TextureSlot::BaseColor, texture.clone(), None);
+    let unit = render_context.texture_unit(TextureSlot::BaseColor).unwrap();
+    program.set_used();
+    mock_gl::take_calls();
+
+    material.bind(&program).unwrap();
+    let calls = mock_gl::take_calls();
+    // The placeholder is created and then bound, in place of the texture
+    let bound = calls.iter().rev().find(|c| matches!(c, GlCall::BindTexture(..)));
+    assert!(matches!(bound, Some(GlCall::BindTexture(gl::TEXTURE_2D, t)) if *t != 0 && *t != texture.gl_texture()));
+    assert!(!calls.contains(&GlCall::BindTexture(gl::TEXTURE_2D, texture.gl_texture())));
+    assert!(calls.contains(&GlCall::Uniform1i(3, unit as i32)));
+
+    mock_gl::set_fence_signaled(FENCE, true);
+    render_context.end_frame();
+    mock_gl::take_calls();
+    material.bind(&program).unwrap();
+    assert!(mock_gl::take_calls().contains(&GlCall::BindTexture(gl::TEXTURE_2D, texture.gl_texture())));
+}